@@ -0,0 +1,164 @@
+//! Throughput of encoding detection and of archive rewriting
+//!
+//! Detection is measured in names per second over the fixture corpus.
+//! Rewriting is measured in bytes per second, for a ZIP archive, whose
+//! entries are copied as stored, and for a gzip-compressed tar archive,
+//! which is decompressed and compressed again.
+//!
+//! Detected encodings are cached in the options for the rest of a run, so
+//! every pass starts with new options.
+//!
+//! Run with `cargo bench`, or `cargo bench -- detect` for one group.
+
+use criterion::measurement::WallTime;
+use criterion::{
+    BatchSize, BenchmarkGroup, Criterion, Throughput, criterion_group, criterion_main,
+};
+use std::hint::black_box;
+use std::io::Write;
+use tempfile::TempDir;
+
+#[path = "../tests/gen_corpus/corpus.rs"]
+mod corpus;
+
+/// Copies of every corpus file in an archive
+const COPIES: usize = 64;
+/// Size of the contents of each file in an archive
+const FILE_SIZE: usize = 16 * 1024;
+
+/// Every name of the fixture corpus, as stored
+fn corpus_names() -> Vec<Vec<u8>> {
+    corpus::corpus()
+        .iter()
+        .flat_map(|fixture| {
+            fixture
+                .names
+                .iter()
+                .map(|name| fixture.encoding.encode(name).0.into_owned())
+        })
+        .collect()
+}
+
+/// Names and contents of the files in a rewritten archive: the cp866 names
+/// of the corpus, numbered to keep them apart
+fn archive_files() -> Vec<(Vec<u8>, Vec<u8>)> {
+    let fixture = &corpus::corpus()[0];
+    // Random bytes do not compress, keeping the sizes of both archives close
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    let contents: Vec<u8> = (0..FILE_SIZE)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state.to_le_bytes()[0]
+        })
+        .collect();
+    (0..COPIES)
+        .flat_map(|copy| {
+            fixture
+                .names
+                .iter()
+                .filter(|name| !name.ends_with('/'))
+                .map(move |name| format!("{copy:03} {}", name.replace('/', " ")))
+        })
+        .map(|name| {
+            (
+                fixture.encoding.encode(&name).0.into_owned(),
+                contents.clone(),
+            )
+        })
+        .collect()
+}
+
+fn zip_archive(files: &[(Vec<u8>, Vec<u8>)]) -> Vec<u8> {
+    let entries: Vec<(&[u8], &[u8])> = files
+        .iter()
+        .map(|(name, contents)| (name.as_slice(), contents.as_slice()))
+        .collect();
+    corpus::raw_zip(&entries, corpus::Host::Dos)
+}
+
+fn tar_gz_archive(files: &[(Vec<u8>, Vec<u8>)]) -> Vec<u8> {
+    let encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    let mut builder = tar::Builder::new(encoder);
+    for (name, contents) in files {
+        let mut header = tar::Header::new_ustar();
+        header.as_old_mut().name[..name.len()].copy_from_slice(name);
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append(&header, contents.as_slice()).unwrap();
+    }
+    let mut encoder = builder.into_inner().unwrap();
+    encoder.flush().unwrap();
+    encoder.finish().unwrap()
+}
+
+fn detect(c: &mut Criterion) {
+    let names = corpus_names();
+    let mut group = c.benchmark_group("detect");
+    group.throughput(Throughput::Elements(names.len() as u64));
+    group.bench_function("detect_encoding", |b| {
+        b.iter(|| {
+            for name in &names {
+                black_box(runzip::detect_encoding(black_box(name)));
+            }
+        });
+    });
+    group.bench_function("convert_name", |b| {
+        b.iter(|| {
+            let options = runzip::FixOptions::default();
+            for name in &names {
+                black_box(runzip::convert_name(black_box(name), &options));
+            }
+        });
+    });
+    group.finish();
+}
+
+/// Fix a fresh copy of `data`, written to `file_name`, in every iteration
+fn bench_rewrite(group: &mut BenchmarkGroup<WallTime>, name: &str, file_name: &str, data: &[u8]) {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join(file_name);
+
+    group.throughput(Throughput::Bytes(data.len() as u64));
+    group.bench_function(name, |b| {
+        b.iter_batched(
+            || std::fs::write(&path, data).unwrap(),
+            |()| {
+                let options = runzip::FixOptions::builder()
+                    .write_rename_map(false)
+                    .build();
+                let report = runzip::fix_archive(&path, &options).unwrap();
+                assert!(report.renamed > 0);
+            },
+            BatchSize::PerIteration,
+        );
+    });
+}
+
+fn rewrite(c: &mut Criterion) {
+    let files = archive_files();
+    let zip = zip_archive(&files);
+    let mut group = c.benchmark_group("rewrite");
+    group.sample_size(20);
+    bench_rewrite(&mut group, "zip-raw-copy", "archive.zip", &zip);
+    bench_rewrite(
+        &mut group,
+        "tar-gz-recompress",
+        "archive.tar.gz",
+        &tar_gz_archive(&files),
+    );
+
+    group.throughput(Throughput::Bytes(zip.len() as u64));
+    group.bench_function("zip-in-memory", |b| {
+        b.iter(|| {
+            let options = runzip::FixOptions::default();
+            runzip::fix_archive_bytes(black_box(&zip), &options).unwrap()
+        });
+    });
+    group.finish();
+}
+
+criterion_group!(benches, detect, rewrite);
+criterion_main!(benches);
@@ -0,0 +1,11 @@
+//! Regenerate `include/runzip.h` from the exported functions
+
+fn main() {
+    let dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let config = cbindgen::Config::from_file(format!("{dir}/cbindgen.toml")).unwrap();
+    cbindgen::generate_with_config(&dir, config)
+        .expect("Failed to generate the C header")
+        .write_to_file(format!("{dir}/include/runzip.h"));
+    println!("cargo::rerun-if-changed=src/lib.rs");
+    println!("cargo::rerun-if-changed=cbindgen.toml");
+}
@@ -0,0 +1,81 @@
+/* Generated by cbindgen from ffi/src/lib.rs, do not edit */
+
+#ifndef RUNZIP_H
+#define RUNZIP_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Options of `runzip_fix_file`; a zeroed struct has the defaults of
+ * `runzip fix`
+ */
+typedef struct RunzipOptions {
+  /**
+   * Encoding of the names, such as "cp866", or NULL to detect it
+   */
+  const char *source;
+  /**
+   * Form of the new names, such as "utf-8" or "translit", or NULL for
+   * UTF-8
+   */
+  const char *target;
+  /**
+   * Report the renames without changing the archive
+   */
+  bool dry_run;
+  /**
+   * Do not write a rename map next to the archive for undoing the fix
+   */
+  bool no_rename_map;
+} RunzipOptions;
+
+/**
+ * Numbers of entries by what was (or would be) done with their names
+ */
+typedef struct RunzipReport {
+  /**
+   * Entries renamed, or to be renamed in a dry run
+   */
+  size_t renamed;
+  /**
+   * Entries whose names were fine already
+   */
+  size_t skipped;
+  /**
+   * Entries whose names could not be converted and were kept
+   */
+  size_t errors;
+} RunzipReport;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Fix the names in the archive at `path`, filling `out_report` with what
+ * was done unless it is NULL. `options` may be NULL for the defaults.
+ *
+ * # Safety
+ *
+ * `path` and the strings in `options` must be NUL-terminated, and
+ * `options` and `out_report` must be NULL or valid pointers.
+ */
+int runzip_fix_file(const char *path,
+                    const struct RunzipOptions *options,
+                    struct RunzipReport *out_report);
+
+/**
+ * Message of the last failure on the calling thread, or NULL. The string
+ * stays valid until the next call on the same thread.
+ */
+const char *runzip_last_error(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* RUNZIP_H */
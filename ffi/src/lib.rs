@@ -0,0 +1,134 @@
+//! C interface to runzip, for file managers and plugins written in C or C++
+//!
+//! Functions return 0 on success and -1 on failure, when
+//! `runzip_last_error` tells what went wrong. The build generates the
+//! declarations into `include/runzip.h`.
+
+#![warn(clippy::pedantic)]
+
+use anyhow::{Context, Result, anyhow};
+use std::cell::RefCell;
+use std::ffi::{CStr, CString, c_char, c_int};
+use std::path::PathBuf;
+
+use runzip::{FixOptions, FixReport, Target};
+
+/// Options of `runzip_fix_file`; a zeroed struct has the defaults of
+/// `runzip fix`
+#[repr(C)]
+pub struct RunzipOptions {
+    /// Encoding of the names, such as "cp866", or NULL to detect it
+    pub source: *const c_char,
+    /// Form of the new names, such as "utf-8" or "translit", or NULL for
+    /// UTF-8
+    pub target: *const c_char,
+    /// Report the renames without changing the archive
+    pub dry_run: bool,
+    /// Do not write a rename map next to the archive for undoing the fix
+    pub no_rename_map: bool,
+}
+
+/// Numbers of entries by what was (or would be) done with their names
+#[repr(C)]
+pub struct RunzipReport {
+    /// Entries renamed, or to be renamed in a dry run
+    pub renamed: usize,
+    /// Entries whose names were fine already
+    pub skipped: usize,
+    /// Entries whose names could not be converted and were kept
+    pub errors: usize,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Fix the names in the archive at `path`, filling `out_report` with what
+/// was done unless it is NULL. `options` may be NULL for the defaults.
+///
+/// # Safety
+///
+/// `path` and the strings in `options` must be NUL-terminated, and
+/// `options` and `out_report` must be NULL or valid pointers.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn runzip_fix_file(
+    path: *const c_char,
+    options: *const RunzipOptions,
+    out_report: *mut RunzipReport,
+) -> c_int {
+    let result = std::panic::catch_unwind(|| unsafe { fix_file(path, options) })
+        .unwrap_or_else(|_| Err(anyhow!("runzip panicked")));
+    match result {
+        Ok(report) => {
+            if !out_report.is_null() {
+                unsafe {
+                    out_report.write(RunzipReport {
+                        renamed: report.renamed,
+                        skipped: report.skipped,
+                        errors: report.errors,
+                    });
+                }
+            }
+            0
+        }
+        Err(e) => {
+            set_last_error(&format!("{e:#}"));
+            -1
+        }
+    }
+}
+
+/// Message of the last failure on the calling thread, or NULL. The string
+/// stays valid until the next call on the same thread.
+#[unsafe(no_mangle)]
+pub extern "C" fn runzip_last_error() -> *const c_char {
+    LAST_ERROR.with_borrow(|error| error.as_ref().map_or(std::ptr::null(), |e| e.as_ptr()))
+}
+
+unsafe fn fix_file(path: *const c_char, options: *const RunzipOptions) -> Result<FixReport> {
+    let path = unsafe { c_path(path) }.context("Invalid archive path")?;
+    let mut builder = FixOptions::builder();
+    if let Some(options) = unsafe { options.as_ref() } {
+        if let Some(source) = unsafe { c_str(options.source) }? {
+            builder = builder.source(Some(runzip::string_to_encoding(source)?));
+        }
+        if let Some(target) = unsafe { c_str(options.target) }? {
+            builder = builder.target(
+                target
+                    .parse::<Target>()
+                    .map_err(|_| anyhow!("Unsupported target: {target}"))?,
+            );
+        }
+        builder = builder
+            .dry_run(options.dry_run)
+            .write_rename_map(!options.no_rename_map);
+    }
+    Ok(runzip::fix_archive(&path, &builder.build())?)
+}
+
+/// Path from a NUL-terminated string, raw bytes on Unix and UTF-8 elsewhere
+unsafe fn c_path(path: *const c_char) -> Result<PathBuf> {
+    #[cfg(unix)]
+    if !path.is_null() {
+        use std::os::unix::ffi::OsStrExt;
+        let bytes = unsafe { CStr::from_ptr(path) }.to_bytes();
+        return Ok(PathBuf::from(std::ffi::OsStr::from_bytes(bytes)));
+    }
+    unsafe { c_str(path) }?
+        .map(PathBuf::from)
+        .ok_or_else(|| anyhow!("Path is NULL"))
+}
+
+/// UTF-8 string from a NUL-terminated string, or None for NULL
+unsafe fn c_str<'a>(text: *const c_char) -> Result<Option<&'a str>> {
+    if text.is_null() {
+        return Ok(None);
+    }
+    let text = unsafe { CStr::from_ptr(text) };
+    Ok(Some(text.to_str().context("String is not valid UTF-8")?))
+}
+
+fn set_last_error(message: &str) {
+    let message = CString::new(message.replace('\0', "?")).unwrap_or_default();
+    LAST_ERROR.set(Some(message));
+}
@@ -0,0 +1,61 @@
+use anyhow::Result;
+use std::ffi::{CStr, CString};
+use std::fs;
+use tempfile::TempDir;
+
+use runzip_ffi::{RunzipOptions, RunzipReport, runzip_fix_file, runzip_last_error};
+
+#[test]
+fn test_fix_file() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let archive = temp_dir.path().join("windows-archive.zip");
+    fs::copy("../tests/originals/windows-archive.zip", &archive)?;
+    let path = CString::new(archive.to_str().unwrap())?;
+    let target = CString::new("translit")?;
+    let mut report = RunzipReport {
+        renamed: 0,
+        skipped: 0,
+        errors: 0,
+    };
+
+    let options = RunzipOptions {
+        source: std::ptr::null(),
+        target: target.as_ptr(),
+        dry_run: true,
+        no_rename_map: false,
+    };
+    assert_eq!(
+        unsafe { runzip_fix_file(path.as_ptr(), &options, &mut report) },
+        0
+    );
+    assert_eq!(report.renamed, 2);
+    assert_eq!(
+        fs::read(&archive)?,
+        fs::read("../tests/originals/windows-archive.zip")?
+    );
+
+    assert_eq!(
+        unsafe { runzip_fix_file(path.as_ptr(), std::ptr::null(), &mut report) },
+        0
+    );
+    assert_eq!(report.renamed, 2);
+    assert!(
+        temp_dir
+            .path()
+            .join("windows-archive.zip.runzip.json")
+            .exists()
+    );
+    assert_eq!(
+        unsafe { runzip_fix_file(path.as_ptr(), std::ptr::null(), &mut report) },
+        0
+    );
+    assert_eq!(report.renamed, 0);
+
+    let missing = CString::new(temp_dir.path().join("missing.zip").to_str().unwrap())?;
+    let result = unsafe { runzip_fix_file(missing.as_ptr(), std::ptr::null(), &mut report) };
+    assert_eq!(result, -1);
+    let error = unsafe { CStr::from_ptr(runzip_last_error()) }.to_str()?;
+    assert!(error.contains("missing.zip"), "{error}");
+
+    Ok(())
+}
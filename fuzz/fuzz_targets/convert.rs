@@ -0,0 +1,41 @@
+//! Conversion of arbitrary names with every source encoding, target and
+//! handling of unencodable characters, picked by the first byte
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use runzip::{FixOptions, Target, Unencodable};
+
+const SOURCES: [Option<&str>; 5] = [
+    None,
+    Some("cp866"),
+    Some("windows-1251"),
+    Some("koi8-r"),
+    Some("koi8-u"),
+];
+const TARGETS: [Target; 4] = [Target::Utf8, Target::Translit, Target::Cp866, Target::Cp932];
+const UNENCODABLE: [Unencodable; 4] = [
+    Unencodable::Error,
+    Unencodable::Replace,
+    Unencodable::Translit,
+    Unencodable::Skip,
+];
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&choice, name)) = data.split_first() else {
+        return;
+    };
+    let choice = usize::from(choice);
+    let options = FixOptions::builder()
+        .source(
+            SOURCES[choice % SOURCES.len()]
+                .map(|source| runzip::string_to_encoding(source).unwrap()),
+        )
+        .target(TARGETS[choice / SOURCES.len() % TARGETS.len()])
+        .on_unencodable(UNENCODABLE[choice / SOURCES.len() / TARGETS.len() % UNENCODABLE.len()])
+        .sanitize_paths(choice & 1 != 0)
+        .sanitize_windows(choice & 2 != 0)
+        .truncate_long_names(choice & 4 != 0)
+        .build();
+    let _ = runzip::convert_name(name, &options);
+});
@@ -0,0 +1,9 @@
+//! Detection of arbitrary names, which must not panic
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|name: &[u8]| {
+    let _ = runzip::detect_encoding(name);
+});
@@ -0,0 +1,12 @@
+//! Fixing malformed ZIP archives, which must fail with an error instead of
+//! panicking
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use runzip::FixOptions;
+
+fuzz_target!(|data: &[u8]| {
+    let options = FixOptions::builder().recurse_archives(true).build();
+    let _ = runzip::fix_archive_bytes(data, &options);
+});
@@ -0,0 +1,186 @@
+//! The `runzip` Python module, for pipelines that would otherwise run the
+//! command line tool and parse its output
+//!
+//! Built with maturin: `maturin build --release` in this directory.
+
+#![warn(clippy::pedantic)]
+
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyValueError};
+use pyo3::prelude::*;
+use std::path::PathBuf;
+
+use runzip::{Action, FixOptions, FixOptionsBuilder, Target};
+
+create_exception!(runzip, Error, PyException, "An archive could not be fixed");
+
+/// What was (or would be) done with an entry name
+#[pyclass(module = "runzip", frozen, get_all, skip_from_py_object)]
+#[derive(Clone)]
+struct Entry {
+    /// Archive the entry belongs to, `outer.zip/inner.zip` for nested ones
+    archive: PathBuf,
+    /// Position of the entry in the archive
+    index: usize,
+    /// Name as stored in the archive
+    raw_name: Vec<u8>,
+    /// New name, or the original one decoded if it is kept
+    new_name: String,
+    /// Encoding the name was converted from, `None` for explicit renames
+    encoding: Option<&'static str>,
+    /// Confidence in the encoding, from 0 to 1; `None` for explicit renames
+    confidence: Option<f64>,
+    /// "already-utf8", "ok", "fixed" ("would-fix" in a dry run) or "failed"
+    action: &'static str,
+}
+
+#[pymethods]
+impl Entry {
+    fn __repr__(&self) -> String {
+        format!(
+            "Entry(index={}, new_name={:?}, action={:?})",
+            self.index, self.new_name, self.action
+        )
+    }
+}
+
+/// Outcome of `fix` and `fix_bytes`
+#[pyclass(module = "runzip", frozen, get_all)]
+struct FixReport {
+    /// Every entry, including those of nested archives
+    entries: Vec<Entry>,
+    /// Number of entries renamed (or to be renamed in a dry run)
+    renamed: usize,
+    /// Number of entries whose names were fine already
+    skipped: usize,
+    /// Number of entries whose names could not be converted and were kept
+    errors: usize,
+}
+
+#[pymethods]
+impl FixReport {
+    fn __repr__(&self) -> String {
+        format!(
+            "FixReport(renamed={}, skipped={}, errors={})",
+            self.renamed, self.skipped, self.errors
+        )
+    }
+}
+
+/// Name of the encoding auto-detection picks for `name`, "UTF-8" if it
+/// needs no conversion
+#[pyfunction]
+fn detect(name: &[u8]) -> &'static str {
+    runzip::detect_encoding(name).name()
+}
+
+/// Convert `name` as `fix` would, detecting its encoding unless `source`
+/// is given; raises `ValueError` if it cannot be converted
+#[pyfunction]
+#[pyo3(signature = (name, source=None, target="utf-8"))]
+fn convert(name: &[u8], source: Option<&str>, target: &str) -> PyResult<Vec<u8>> {
+    let options = options(false, source, target)?.build();
+    match runzip::convert_name(name, &options) {
+        (_, Action::Failed) => Err(PyValueError::new_err(format!(
+            "Cannot convert {} to {target}",
+            String::from_utf8_lossy(name)
+        ))),
+        (new_name, _) => Ok(new_name),
+    }
+}
+
+/// Fix the names in the archive at `path`, like `runzip fix`
+#[pyfunction]
+#[pyo3(signature = (path, *, dry_run=false, source=None, target="utf-8", rename_map=true))]
+fn fix(
+    py: Python<'_>,
+    path: PathBuf,
+    dry_run: bool,
+    source: Option<&str>,
+    target: &str,
+    rename_map: bool,
+) -> PyResult<FixReport> {
+    let (source, target) = (source.map(str::to_string), target.to_string());
+    let report = py.detach(move || {
+        let options = options(dry_run, source.as_deref(), &target)?
+            .write_rename_map(rename_map)
+            .build();
+        runzip::fix_archive(&path, &options).map_err(|e| Error::new_err(format!("{e:#}")))
+    })?;
+    Ok(FixReport::new(report, dry_run))
+}
+
+/// Fix the names in the ZIP archive `data`, returning the new archive, or
+/// `data` if nothing changes, with the report
+#[pyfunction]
+#[pyo3(signature = (data, *, dry_run=false, source=None, target="utf-8"))]
+fn fix_bytes(
+    py: Python<'_>,
+    data: Vec<u8>,
+    dry_run: bool,
+    source: Option<&str>,
+    target: &str,
+) -> PyResult<(Vec<u8>, FixReport)> {
+    let (source, target) = (source.map(str::to_string), target.to_string());
+    let (data, report) = py.detach(move || {
+        let options = options(dry_run, source.as_deref(), &target)?.build();
+        runzip::fix_archive_bytes(&data, &options).map_err(|e| Error::new_err(format!("{e:#}")))
+    })?;
+    Ok((data, FixReport::new(report, dry_run)))
+}
+
+impl FixReport {
+    fn new(report: runzip::FixReport, dry_run: bool) -> Self {
+        FixReport {
+            entries: report
+                .entries
+                .into_iter()
+                .map(|entry| Entry {
+                    archive: entry.archive,
+                    index: entry.index,
+                    raw_name: entry.raw_name,
+                    new_name: entry.new_name,
+                    encoding: entry.encoding.map(runzip::Encoding::name),
+                    confidence: entry.confidence,
+                    action: entry.action.label(dry_run),
+                })
+                .collect(),
+            renamed: report.renamed,
+            skipped: report.skipped,
+            errors: report.errors,
+        }
+    }
+}
+
+fn options(
+    dry_run: bool,
+    source: Option<&str>,
+    target: &str,
+) -> PyResult<FixOptionsBuilder<'static>> {
+    let source = source
+        .map(runzip::string_to_encoding)
+        .transpose()
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    let target = target
+        .parse::<Target>()
+        .map_err(|_| PyValueError::new_err(format!("Unsupported target: {target}")))?;
+    Ok(FixOptions::builder()
+        .dry_run(dry_run)
+        .source(source)
+        .target(target))
+}
+
+/// Fix the encoding of file names in ZIP archives
+// The docs are the docstring of the module
+#[allow(clippy::missing_errors_doc)]
+#[pymodule(name = "runzip")]
+pub fn runzip_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(detect, m)?)?;
+    m.add_function(wrap_pyfunction!(convert, m)?)?;
+    m.add_function(wrap_pyfunction!(fix, m)?)?;
+    m.add_function(wrap_pyfunction!(fix_bytes, m)?)?;
+    m.add_class::<Entry>()?;
+    m.add_class::<FixReport>()?;
+    m.add("Error", m.py().get_type::<Error>())?;
+    Ok(())
+}
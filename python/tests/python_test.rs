@@ -0,0 +1,64 @@
+use pyo3::prelude::*;
+use pyo3::types::IntoPyDict;
+use std::ffi::CString;
+use std::fs;
+use tempfile::TempDir;
+
+#[test]
+fn test_python_module() -> anyhow::Result<()> {
+    let temp_dir = TempDir::new()?;
+    let archive = temp_dir.path().join("windows-archive.zip");
+    fs::copy("../tests/originals/windows-archive.zip", &archive)?;
+
+    let code = CString::new(
+        r#"
+assert runzip.detect("Привет".encode("cp866")) == "IBM866"
+assert runzip.detect(b"readme.txt") == "UTF-8"
+assert runzip.convert("Привет".encode("cp866")) == "Привет".encode()
+assert runzip.convert("Привет".encode("cp1251"), source="windows-1251", target="translit") == b"Privet"
+
+with open(archive, "rb") as file:
+    data = file.read()
+fixed, report = runzip.fix_bytes(data)
+assert report.renamed == 2, report
+assert runzip.fix_bytes(fixed)[0] == fixed
+assert runzip.fix_bytes(data, dry_run=True)[0] == data
+
+report = runzip.fix(archive, dry_run=True)
+assert report.renamed == 2, report
+assert all(entry.action != "fixed" or entry.encoding == "IBM866" for entry in report.entries)
+assert runzip.fix(archive).renamed == 2
+assert runzip.fix(archive).renamed == 0
+
+try:
+    runzip.fix(archive + ".missing")
+    assert False
+except runzip.Error as e:
+    assert "missing" in str(e)
+try:
+    runzip.fix_bytes(b"not an archive")
+    assert False
+except runzip.Error:
+    pass
+try:
+    runzip.convert(b"name", target="ebcdic")
+    assert False
+except ValueError:
+    pass
+"#,
+    )?;
+    Python::attach(|py| {
+        let module = PyModule::new(py, "runzip")?;
+        runzip_python::runzip_module(&module)?;
+        let locals = [
+            ("runzip", module.into_any()),
+            (
+                "archive",
+                archive.to_str().unwrap().into_pyobject(py)?.into_any(),
+            ),
+        ]
+        .into_py_dict(py)?;
+        py.run(&code, None, Some(&locals))
+    })?;
+    Ok(())
+}
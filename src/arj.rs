@@ -0,0 +1,178 @@
+//! Reading ARJ archives
+//!
+//! Methods 0 (stored) to 4 are supported; encrypted ("garbled") entries and
+//! multi-volume archives are not. Names are in the OEM code page of the
+//! archiving system, usually cp866 for Russian archives.
+
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::error::{Context, Error, Result};
+use crate::extract::{Entry, ReadOnlyArchive};
+use crate::lzh;
+
+const HEADER_ID: [u8; 2] = [0x60, 0xea];
+const MAX_HEADER_SIZE: usize = 2600;
+
+const FLAG_GARBLED: u8 = 0x01;
+const FILE_TYPE_DIRECTORY: u8 = 3;
+
+/// Where the data of an entry is and how to read it
+struct Location {
+    offset: u64,
+    compressed_size: u64,
+    original_size: u64,
+    method: u8,
+    garbled: bool,
+    crc: u32,
+}
+
+pub struct Archive {
+    reader: BufReader<File>,
+    entries: Vec<Entry>,
+    locations: Vec<Location>,
+}
+
+/// Whether `path` starts with an ARJ main header
+pub fn is_arj(path: &Path) -> Result<bool> {
+    let file = File::open(path).context(format!("Failed to open {}", path.display()))?;
+    let mut header = Vec::with_capacity(2);
+    file.take(2).read_to_end(&mut header)?;
+    Ok(header == HEADER_ID)
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ])
+}
+
+fn truncated() -> Error {
+    Error::other("Truncated ARJ header")
+}
+
+/// Read a header, returning its basic part or `None` at the end of the archive
+fn read_header(reader: &mut impl Read) -> Result<Option<Vec<u8>>> {
+    let mut start = [0; 4];
+    reader.read_exact(&mut start).map_err(|_| truncated())?;
+    if start[..2] != HEADER_ID {
+        return Err(Error::other("Invalid ARJ header"));
+    }
+    let size = usize::from(u16::from_le_bytes([start[2], start[3]]));
+    if size == 0 {
+        return Ok(None);
+    }
+    if size > MAX_HEADER_SIZE {
+        return Err(Error::other("Invalid ARJ header"));
+    }
+    let mut header = vec![0; size];
+    reader.read_exact(&mut header).map_err(|_| truncated())?;
+    let mut crc = [0; 4];
+    reader.read_exact(&mut crc).map_err(|_| truncated())?;
+    if crc32fast::hash(&header) != u32::from_le_bytes(crc) {
+        return Err(Error::other("ARJ header CRC mismatch"));
+    }
+    // Extended headers, each followed by its CRC
+    loop {
+        let mut size = [0; 2];
+        reader.read_exact(&mut size).map_err(|_| truncated())?;
+        let size = u64::from(u16::from_le_bytes(size));
+        if size == 0 {
+            break;
+        }
+        std::io::copy(&mut reader.take(size + 4), &mut std::io::sink())?;
+    }
+    Ok(Some(header))
+}
+
+impl Archive {
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = File::open(path).context(format!("Failed to open {}", path.display()))?;
+        let mut reader = BufReader::new(file);
+        // The first header describes the archive itself
+        read_header(&mut reader)?.ok_or_else(|| Error::other("Empty ARJ archive"))?;
+
+        let mut entries = Vec::new();
+        let mut locations = Vec::new();
+        while let Some(header) = read_header(&mut reader)? {
+            let first_size = usize::from(header[0]);
+            if first_size < 28 || header.len() < first_size {
+                return Err(truncated());
+            }
+            let name = &header[first_size..];
+            let name = name
+                .iter()
+                .position(|&byte| byte == 0)
+                .map_or(name, |nul| &name[..nul]);
+            let is_dir = header[6] == FILE_TYPE_DIRECTORY;
+            let mut name: Vec<u8> = name
+                .iter()
+                .map(|&byte| if byte == b'\\' { b'/' } else { byte })
+                .collect();
+            if is_dir && !name.ends_with(b"/") {
+                name.push(b'/');
+            }
+
+            let compressed_size = u64::from(read_u32(&header, 12));
+            let original_size = u64::from(read_u32(&header, 16));
+            let offset = reader.stream_position()?;
+            reader.seek(SeekFrom::Current(i64::try_from(compressed_size)?))?;
+            entries.push(Entry {
+                name,
+                unicode: false,
+                is_dir,
+                host: None,
+                size: original_size,
+                compressed_size,
+            });
+            locations.push(Location {
+                offset,
+                compressed_size,
+                original_size,
+                method: header[5],
+                garbled: header[4] & FLAG_GARBLED != 0,
+                crc: read_u32(&header, 20),
+            });
+        }
+        Ok(Self {
+            reader,
+            entries,
+            locations,
+        })
+    }
+}
+
+impl ReadOnlyArchive for Archive {
+    fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    fn extract_entry(&mut self, index: usize, writer: &mut dyn Write) -> Result<()> {
+        let location = &self.locations[index];
+        if location.garbled {
+            return Err(Error::other("The entry is encrypted"));
+        }
+        self.reader.seek(SeekFrom::Start(location.offset))?;
+        let compressed = (&mut self.reader).take(location.compressed_size);
+        let size = usize::try_from(location.original_size)?;
+        let data = match location.method {
+            0 => {
+                let mut data = Vec::with_capacity(size);
+                compressed.take(size as u64).read_to_end(&mut data)?;
+                data
+            }
+            1..=3 => lzh::decode(compressed, lzh::ARJ, size)?,
+            4 => lzh::decode_arj_fastest(compressed, size)?,
+            method => return Err(Error::other(format!("Unsupported ARJ method {method}"))),
+        };
+        if data.len() != size || crc32fast::hash(&data) != location.crc {
+            return Err(Error::other("CRC mismatch, the entry is damaged"));
+        }
+        writer.write_all(&data)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,65 @@
+//! Fixing archives read from and written to Tokio streams
+//!
+//! The archive is read through the blocking core on a blocking thread, and
+//! the new archive is written to a temporary file there, which is then
+//! copied to the writer, so archives of any size take little memory.
+
+use std::io::BufReader;
+use std::path::Path;
+use tokio::io::{AsyncRead, AsyncSeek, AsyncWrite, AsyncWriteExt};
+use tokio_util::io::SyncIoBridge;
+
+use crate::error::{Context, Result};
+use crate::rawzip::RawArchive;
+use crate::{Error, FixOptions, FixReport, fix_zip_to_tempfile};
+
+/// Fix the names of the ZIP archive read from `reader`, writing the new
+/// archive to `writer`
+///
+/// Nothing is written when no name changes or `options.dry_run` is set; the
+/// original archive can be kept then. `name` is the archive name used in
+/// the report. Must be called from within a Tokio runtime.
+///
+/// The work is moved to Tokio's blocking thread pool along with `options`,
+/// which is why they must be `'static`: the callbacks, `events`, `cancel`
+/// and `tmpdir` they refer to have to be statics or leaked. The new archive
+/// is written to a temporary file in `tmpdir` (or the system's temporary
+/// directory) first, and takes up that much disk space until it is copied
+/// to `writer`.
+///
+/// # Errors
+///
+/// Fails if the archive cannot be read or is not a ZIP archive, the new
+/// archive cannot be written, or `cancel` is set.
+pub async fn fix_archive_async<R, W>(
+    reader: R,
+    mut writer: W,
+    name: &Path,
+    options: FixOptions<'static>,
+) -> Result<FixReport, Error>
+where
+    R: AsyncRead + AsyncSeek + Unpin + Send + 'static,
+    W: AsyncWrite + Unpin,
+{
+    let reader = BufReader::new(SyncIoBridge::new(reader));
+    let name = name.to_path_buf();
+    let (fixed, report) = tokio::task::spawn_blocking(move || {
+        let archive = RawArchive::new(reader).context("Failed to read ZIP archive")?;
+        let mut report = FixReport::default();
+        let fixed = fix_zip_to_tempfile(archive, &name, &options, &mut report)?;
+        Ok::<_, Error>((fixed, report))
+    })
+    .await
+    .context("Failed to fix archive")??;
+
+    if let Some(file) = fixed {
+        tokio::io::copy(&mut tokio::fs::File::from_std(file), &mut writer)
+            .await
+            .context("Failed to write new archive")?;
+        writer
+            .flush()
+            .await
+            .context("Failed to write new archive")?;
+    }
+    Ok(report)
+}
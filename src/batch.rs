@@ -0,0 +1,246 @@
+//! Batch job manifests (`runzip batch jobs.yaml`)
+//!
+//! A manifest lists jobs, each with the archives to fix, where to write
+//! them and the encodings to use, and per-archive overrides of those
+//! encodings. Paths are relative to the directory of the manifest.
+
+use crate::encoding::Encoding;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+use crate::config;
+use crate::error::{Context, Error, Result};
+use crate::stats;
+use crate::{Target, Unencodable, string_to_encoding};
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct File {
+    /// CSV report of the entries of every job
+    report: Option<PathBuf>,
+    jobs: Vec<JobFile>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct JobFile {
+    /// ZIP archive, or directory to search for .zip files recursively
+    input: PathBuf,
+    /// Directory to write the fixed archives to, instead of fixing them in
+    /// place
+    output: Option<PathBuf>,
+    source: Option<String>,
+    target: Option<String>,
+    on_unencodable: Option<String>,
+    #[serde(default)]
+    overrides: Vec<OverrideFile>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct OverrideFile {
+    archive: PathBuf,
+    source: Option<String>,
+    target: Option<String>,
+    on_unencodable: Option<String>,
+}
+
+/// Encodings of a job or override; unset ones fall back to the job, then
+/// to the configuration file
+#[derive(Clone, Copy, Default)]
+pub struct Settings {
+    source: Option<Source>,
+    pub target: Option<Target>,
+    pub on_unencodable: Option<Unencodable>,
+}
+
+/// Source encoding of a job or override
+#[derive(Clone, Copy)]
+enum Source {
+    Auto,
+    Encoding(&'static Encoding),
+}
+
+impl Settings {
+    fn parse(
+        source: Option<&str>,
+        target: Option<&str>,
+        on_unencodable: Option<&str>,
+    ) -> Result<Self> {
+        Ok(Settings {
+            source: source
+                .map(|name| match name {
+                    "auto" => Ok(Source::Auto),
+                    name => string_to_encoding(name).map(Source::Encoding),
+                })
+                .transpose()?,
+            target: config::value("target", target)?,
+            on_unencodable: config::value("on-unencodable", on_unencodable)?,
+        })
+    }
+
+    /// Source encoding to use, `None` for auto-detection; `default` if
+    /// unset
+    pub fn source_encoding(self, default: Option<&'static Encoding>) -> Option<&'static Encoding> {
+        match self.source {
+            None => default,
+            Some(Source::Auto) => None,
+            Some(Source::Encoding(encoding)) => Some(encoding),
+        }
+    }
+
+    /// These settings, with the unset ones taken from `fallback`
+    fn or(self, fallback: Settings) -> Settings {
+        Settings {
+            source: self.source.or(fallback.source),
+            target: self.target.or(fallback.target),
+            on_unencodable: self.on_unencodable.or(fallback.on_unencodable),
+        }
+    }
+}
+
+/// A parsed manifest
+pub struct Manifest {
+    pub report: Option<PathBuf>,
+    pub jobs: Vec<Job>,
+}
+
+pub struct Job {
+    pub input: PathBuf,
+    pub output: Option<PathBuf>,
+    pub settings: Settings,
+    overrides: Vec<(PathBuf, Settings)>,
+}
+
+/// An archive to fix
+pub struct Task {
+    /// Archive as found in the input
+    pub input: PathBuf,
+    /// Copy to fix instead of the input, if the job has an output directory
+    pub output: Option<PathBuf>,
+    pub settings: Settings,
+}
+
+impl Manifest {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let base = path.parent().unwrap_or_else(|| Path::new(""));
+        parse(&text, base).with_context(|| format!("Invalid manifest {}", path.display()))
+    }
+}
+
+fn parse(text: &str, base: &Path) -> Result<Manifest> {
+    let file: File = serde_yaml::from_str(text)?;
+    let jobs = file
+        .jobs
+        .into_iter()
+        .enumerate()
+        .map(|(index, job)| parse_job(job, base).context(format!("In job {}", index + 1)))
+        .collect::<Result<_>>()?;
+    Ok(Manifest {
+        report: file.report.map(|report| base.join(report)),
+        jobs,
+    })
+}
+
+fn parse_job(job: JobFile, base: &Path) -> Result<Job> {
+    let overrides = job
+        .overrides
+        .into_iter()
+        .map(|entry| {
+            let settings = Settings::parse(
+                entry.source.as_deref(),
+                entry.target.as_deref(),
+                entry.on_unencodable.as_deref(),
+            )?;
+            Ok((base.join(entry.archive), settings))
+        })
+        .collect::<Result<_>>()?;
+    Ok(Job {
+        input: base.join(job.input),
+        output: job.output.map(|output| base.join(output)),
+        settings: Settings::parse(
+            job.source.as_deref(),
+            job.target.as_deref(),
+            job.on_unencodable.as_deref(),
+        )?,
+        overrides,
+    })
+}
+
+impl Job {
+    /// The archives of the job with their settings
+    pub fn tasks(&self) -> Result<Vec<Task>> {
+        let archives = stats::collect_archives(std::slice::from_ref(&self.input))?;
+        if let Some((archive, _)) = self
+            .overrides
+            .iter()
+            .find(|(archive, _)| !archives.contains(archive))
+        {
+            return Err(Error::other(format!(
+                "Override for {}, which is not an archive of the job",
+                archive.display()
+            )));
+        }
+        archives
+            .into_iter()
+            .map(|archive| {
+                let settings = self
+                    .overrides
+                    .iter()
+                    .find(|(path, _)| *path == archive)
+                    .map_or(self.settings, |(_, settings)| settings.or(self.settings));
+                let output = self
+                    .output
+                    .as_ref()
+                    .map(|output| self.output_path(&archive, output))
+                    .transpose()?;
+                Ok(Task {
+                    input: archive,
+                    output,
+                    settings,
+                })
+            })
+            .collect()
+    }
+
+    /// Where the fixed copy of `archive` goes in `output`: at the same path
+    /// relative to the input directory, or under its own name for a single
+    /// input archive
+    fn output_path(&self, archive: &Path, output: &Path) -> Result<PathBuf> {
+        let relative = if archive == self.input {
+            archive.file_name().map(Path::new)
+        } else {
+            archive.strip_prefix(&self.input).ok()
+        };
+        relative
+            .map(|relative| output.join(relative))
+            .ok_or_else(|| Error::other(format!("No output path for {}", archive.display())))
+    }
+}
+
+impl Task {
+    /// Copy the input to the output, if there is one, and return the
+    /// archive to fix; a dry run reads the input instead
+    pub fn prepare(&self, dry_run: bool) -> Result<&Path> {
+        let Some(output) = self.output.as_ref().filter(|_| !dry_run) else {
+            return Ok(&self.input);
+        };
+        if output.exists() {
+            return Err(Error::other(format!("{} already exists", output.display())));
+        }
+        if let Some(dir) = output.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        std::fs::copy(&self.input, output).with_context(|| {
+            format!(
+                "Failed to copy {} to {}",
+                self.input.display(),
+                output.display()
+            )
+        })?;
+        Ok(output)
+    }
+}
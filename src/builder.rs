@@ -0,0 +1,363 @@
+//! Step-by-step construction of `FixOptions`, for the command line tool,
+//! the bindings and programs using the library alike
+
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+
+use crate::{
+    Backup, Case, ChooseEncoding, Confirm, ControlChars, Dedupe, Encoding, Events, FixOptions,
+    Lang, NameSource, Normalization, SortOrder, StripPrefix, Target, Unencodable,
+};
+
+/// Builder of [`FixOptions`], started by [`FixOptions::builder`] with the
+/// defaults of `runzip fix`
+///
+/// This is the only way to make options other than the defaults: the command
+/// line tool, the bindings and the fuzz targets all build theirs here.
+/// Methods take an `Option` for the settings that may be unset, so the
+/// command line tool can pass what it parsed as it is.
+#[must_use]
+pub struct FixOptionsBuilder<'a> {
+    options: FixOptions<'a>,
+}
+
+impl<'a> FixOptions<'a> {
+    /// Options built from the defaults, such as
+    /// `FixOptions::builder().source(cp866).target(Target::Utf8)
+    /// .preserve_timestamps(true).on_collision(Dedupe::Suffix).build()`
+    pub fn builder() -> FixOptionsBuilder<'a> {
+        FixOptionsBuilder {
+            options: FixOptions::default(),
+        }
+    }
+}
+
+impl<'a> FixOptionsBuilder<'a> {
+    /// The options, with the defaults for whatever was not set
+    pub fn build(self) -> FixOptions<'a> {
+        self.options
+    }
+
+    /// Report the renames without changing the archives; off by default
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.options.dry_run = dry_run;
+        self
+    }
+
+    /// Encoding of the names, `None` (the default) to detect it
+    pub fn source(mut self, encoding: Option<&'static Encoding>) -> Self {
+        self.options.source_encoding = encoding;
+        self
+    }
+
+    /// Encodings detection may pick besides UTF-8; by default those of
+    /// the default language
+    pub fn candidates(mut self, candidates: Vec<&'static Encoding>) -> Self {
+        self.options.candidates = candidates;
+        self
+    }
+
+    /// Print more about each entry, the more the higher; 0 by default
+    #[cfg(feature = "cli")]
+    pub(crate) fn verbose(mut self, verbose: u8) -> Self {
+        self.options.verbose = verbose;
+        self
+    }
+
+    /// Write a rename map next to each fixed archive for `runzip undo`;
+    /// on by default
+    pub fn write_rename_map(mut self, write_rename_map: bool) -> Self {
+        self.options.write_rename_map = write_rename_map;
+        self
+    }
+
+    /// Encoding to convert the names to; [`Target::Utf8`] by default
+    pub fn target(mut self, target: Target) -> Self {
+        self.options.target = target;
+        self
+    }
+
+    /// Mark entries as made on MS-DOS, as old Windows extractors expect of
+    /// names in the OEM code page; off by default
+    pub fn dos_host(mut self, dos_host: bool) -> Self {
+        self.options.dos_host = dos_host;
+        self
+    }
+
+    /// Also store the UTF-8 form of legacy names in Info-ZIP Unicode Path
+    /// extra fields; off by default
+    pub fn unicode_extra(mut self, unicode_extra: bool) -> Self {
+        self.options.unicode_extra = unicode_extra;
+        self
+    }
+
+    /// Handling of characters the target encoding cannot represent;
+    /// [`Unencodable::Error`], keeping such names, by default
+    pub fn on_unencodable(mut self, on_unencodable: Unencodable) -> Self {
+        self.options.on_unencodable = on_unencodable;
+        self
+    }
+
+    /// Fail, leaving the archive as it was, if a name cannot be converted;
+    /// off by default
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.options.strict = strict;
+        self
+    }
+
+    /// Handling of control characters in names; [`ControlChars::Keep`] by
+    /// default
+    pub fn control_chars(mut self, control_chars: ControlChars) -> Self {
+        self.options.control_chars = control_chars;
+        self
+    }
+
+    /// Replace backslashes separating directories with `/`; off by default
+    pub fn fix_separators(mut self, fix_separators: bool) -> Self {
+        self.options.fix_separators = fix_separators;
+        self
+    }
+
+    /// Remove leading separators, drive letters and UNC prefixes; off by
+    /// default
+    pub fn strip_absolute(mut self, strip_absolute: bool) -> Self {
+        self.options.strip_absolute = strip_absolute;
+        self
+    }
+
+    /// Leading directories to remove from the names; none by default
+    pub fn strip_prefix(mut self, strip_prefix: Option<StripPrefix>) -> Self {
+        self.options.strip_prefix = strip_prefix;
+        self
+    }
+
+    /// Unicode normalization form to convert the names to;
+    /// [`Normalization::None`] by default
+    pub fn normalize(mut self, normalize: Normalization) -> Self {
+        self.options.normalize = normalize;
+        self
+    }
+
+    /// Case to convert the names to; [`Case::Preserve`] by default
+    pub fn case(mut self, case: Case) -> Self {
+        self.options.case = case;
+        self
+    }
+
+    /// Directory to put every entry in; none by default
+    pub fn add_prefix(mut self, add_prefix: Option<String>) -> Self {
+        self.options.add_prefix = add_prefix;
+        self
+    }
+
+    /// Make absolute paths relative and drop `..` components; off by
+    /// default
+    pub fn sanitize_paths(mut self, sanitize_paths: bool) -> Self {
+        self.options.sanitize_paths = sanitize_paths;
+        self
+    }
+
+    /// Replace characters and device names invalid in Windows file names;
+    /// off by default
+    pub fn sanitize_windows(mut self, sanitize_windows: bool) -> Self {
+        self.options.sanitize_windows = sanitize_windows;
+        self
+    }
+
+    /// Shorten path components longer than 255 bytes; off by default
+    pub fn truncate_long_names(mut self, truncate_long_names: bool) -> Self {
+        self.options.truncate_long_names = truncate_long_names;
+        self
+    }
+
+    /// Name to use when the local header and central directory disagree;
+    /// [`NameSource::Central`] by default
+    pub fn prefer_name(mut self, prefer_name: NameSource) -> Self {
+        self.options.prefer_name = prefer_name;
+        self
+    }
+
+    /// Order of the entries in rewritten ZIP archives; [`SortOrder::None`],
+    /// the original order, by default
+    pub fn sort(mut self, sort: SortOrder) -> Self {
+        self.options.sort = sort;
+        self
+    }
+
+    /// Handling of entries with the same new name, `None` (the default) to
+    /// keep them all as they are
+    pub fn on_collision(mut self, dedupe: impl Into<Option<Dedupe>>) -> Self {
+        self.options.dedupe = dedupe.into();
+        self
+    }
+
+    /// Patterns of entries to leave out of rewritten ZIP archives; none by
+    /// default
+    pub fn delete(mut self, patterns: Vec<String>) -> Self {
+        self.options.delete = patterns;
+        self
+    }
+
+    /// Indices of the entries to fix, as `runzip list` numbers them; every
+    /// entry by default
+    pub fn entries(mut self, entries: Vec<usize>) -> Self {
+        self.options.entries = entries;
+        self
+    }
+
+    /// Patterns of the entries to fix, matched against their stored names;
+    /// every entry by default
+    pub fn entry_names(mut self, patterns: Vec<String>) -> Self {
+        self.options.entry_names = patterns;
+        self
+    }
+
+    /// Also fix ZIP archives stored inside the archives; off by default
+    pub fn recurse_archives(mut self, recurse_archives: bool) -> Self {
+        self.options.recurse_archives = recurse_archives;
+        self
+    }
+
+    /// Detect the encoding of ambiguous names from the text files in ZIP
+    /// archives; off by default
+    pub fn content_hints(mut self, content_hints: bool) -> Self {
+        self.options.content_hints = content_hints;
+        self
+    }
+
+    /// Language of the names, which decides the encodings detected;
+    /// [`Lang::Ru`] by default
+    pub fn lang(mut self, lang: Lang) -> Self {
+        self.options.lang = lang;
+        self
+    }
+
+    /// Repair UTF-8 names misread in a single-byte code page; on by default
+    pub fn repair_mojibake(mut self, repair_mojibake: bool) -> Self {
+        self.options.repair_mojibake = repair_mojibake;
+        self
+    }
+
+    /// Extensions, in lowercase without the dot, of the ZIP entries whose
+    /// contents are converted to UTF-8; none by default
+    pub fn fix_contents(mut self, extensions: Vec<String>) -> Self {
+        self.options.fix_contents = extensions;
+        self
+    }
+
+    /// Also process ZIP-based formats (docx, jar, ...) that renaming can
+    /// break; off by default
+    pub fn force(mut self, force: bool) -> Self {
+        self.options.force = force;
+        self
+    }
+
+    /// Keep the data of uncompressed entries 4-byte aligned; off by default
+    pub fn zipalign(mut self, zipalign: bool) -> Self {
+        self.options.zipalign = zipalign;
+        self
+    }
+
+    /// Rebuild archives with a damaged central directory from their local
+    /// headers; off by default
+    pub fn salvage(mut self, salvage: bool) -> Self {
+        self.options.salvage = salvage;
+        self
+    }
+
+    /// Read archives through a memory map; off by default
+    pub fn mmap(mut self, mmap: bool) -> Self {
+        self.options.mmap = mmap;
+        self
+    }
+
+    /// Capacity of the buffer in front of the new archive; 64 KiB by default
+    pub fn buffer_size(mut self, buffer_size: usize) -> Self {
+        self.options.buffer_size = buffer_size;
+        self
+    }
+
+    /// Fix read-only archives too, keeping them read-only, instead of
+    /// failing; off by default
+    pub fn force_readonly(mut self, force_readonly: bool) -> Self {
+        self.options.force_readonly = force_readonly;
+        self
+    }
+
+    /// Give fixed archives back the modification time they had before; off
+    /// by default
+    pub fn preserve_timestamps(mut self, preserve_mtime: bool) -> Self {
+        self.options.preserve_mtime = preserve_mtime;
+        self
+    }
+
+    /// Copy of the original archive to keep when it is replaced;
+    /// [`Backup::None`] by default
+    pub fn backup(mut self, backup: Backup) -> Self {
+        self.options.backup = backup;
+        self
+    }
+
+    /// Directory for new archives, `None` (the default) for the one of the
+    /// original
+    pub fn tmpdir(mut self, tmpdir: Option<&'a Path>) -> Self {
+        self.options.tmpdir = tmpdir;
+        self
+    }
+
+    /// Asked with the plan before each archive is rewritten; `None` (the
+    /// default) rewrites without asking
+    pub fn confirm(mut self, confirm: Option<&'a Confirm>) -> Self {
+        self.options.confirm = confirm;
+        self
+    }
+
+    /// Asked for the encoding of ambiguous names; `None` (the default)
+    /// keeps the detected one
+    pub fn choose_encoding(mut self, choose_encoding: Option<&'a ChooseEncoding>) -> Self {
+        self.options.choose_encoding = choose_encoding;
+        self
+    }
+
+    /// Told about every entry, warning and the bytes written; none by
+    /// default
+    pub fn events(mut self, events: Option<&'a dyn Events>) -> Self {
+        self.options.events = events;
+        self
+    }
+
+    /// Set to stop the fix, leaving the archive being rewritten as it was;
+    /// none by default
+    pub fn cancel(mut self, cancel: Option<&'a AtomicBool>) -> Self {
+        self.options.cancel = cancel;
+        self
+    }
+
+    /// Refuse archives with an entry that unpacks to more than `size` bytes;
+    /// no limit by default
+    pub fn max_entry_size(mut self, size: Option<u64>) -> Self {
+        self.options.limits.entry_size = size;
+        self
+    }
+
+    /// Refuse archives whose entries unpack to more than `size` bytes in
+    /// all; no limit by default
+    pub fn max_total_size(mut self, size: Option<u64>) -> Self {
+        self.options.limits.total_size = size;
+        self
+    }
+
+    /// Refuse archives with an entry that unpacks to more than `ratio` times
+    /// its compressed size; no limit by default
+    pub fn max_ratio(mut self, ratio: Option<u64>) -> Self {
+        self.options.limits.ratio = ratio;
+        self
+    }
+
+    /// Leave the outcome of every entry out of [`Events::on_message`]; on by
+    /// default
+    pub fn quiet(mut self, quiet: bool) -> Self {
+        self.options.quiet = quiet;
+        self
+    }
+}
@@ -0,0 +1,59 @@
+//! Checksums of archives for `--print-hash`
+
+use sha2::{Digest, Sha256, Sha512};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use crate::error::{Context, Result};
+use crate::hex;
+
+/// Hash algorithm of `--print-hash`
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum HashAlgorithm {
+    Sha256,
+    Sha512,
+}
+
+impl HashAlgorithm {
+    /// Name of the algorithm in the tagged format of `sha256sum --tag`
+    fn tag(self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha256 => "SHA256",
+            HashAlgorithm::Sha512 => "SHA512",
+        }
+    }
+
+    /// Line for the hash of the file at `path`, in the tagged format of
+    /// `sha256sum --tag`
+    pub fn line(self, path: &Path) -> Result<String> {
+        let digest = match self {
+            HashAlgorithm::Sha256 => file_digest::<Sha256>(path)?,
+            HashAlgorithm::Sha512 => file_digest::<Sha512>(path)?,
+        };
+        Ok(format!(
+            "{} ({}) = {}",
+            self.tag(),
+            path.display(),
+            hex::encode(&digest)
+        ))
+    }
+}
+
+fn file_digest<D: Digest>(path: &Path) -> Result<Vec<u8>> {
+    let mut file =
+        File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut hasher = D::new();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let read = file
+            .read(&mut buffer)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher.finalize().to_vec())
+}
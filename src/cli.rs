@@ -0,0 +1,1787 @@
+//! The `runzip` command line interface
+
+use crate::encoding::{Encoding, UTF_8};
+use anyhow::{Context, Result, anyhow};
+use clap::{CommandFactory, Parser, Subcommand};
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tempfile::NamedTempFile;
+
+use crate::batch::Manifest;
+use crate::checksum::HashAlgorithm;
+use crate::compare::{self, Change};
+use crate::config::{self, Config};
+use crate::console;
+use crate::diff::{self, DiffWriter};
+use crate::extract::{self, ReadOnlyArchive};
+#[cfg(unix)]
+use crate::fstree;
+use crate::journal::Journal;
+use crate::limits;
+#[cfg(all(feature = "mount", any(target_os = "linux", target_os = "macos")))]
+use crate::limits::Limits;
+#[cfg(all(feature = "mount", any(target_os = "linux", target_os = "macos")))]
+use crate::mount;
+use crate::porcelain;
+use crate::rawzip::RawWriter;
+use crate::renames::{self, RenameRules};
+use crate::report::{CsvReport, Reports};
+use crate::{
+    Action, Backup, Candidate, Case, ControlChars, Dedupe, Destination, Events, FixOptions,
+    FixOptionsBuilder, Hints, Host, Lang, NameSource, Normalization, Preflight, SortOrder,
+    StripPrefix, Target, Unencodable, WindowsLocale, apply_name_fixups, arj, create,
+    detection_name, display_name, encode_text, fix_archive, iso, lha, listing, open_archive,
+    parse_buffer_size, process_entry_name, rar, renamemap, sanitize, stats, string_to_encoding,
+    translit, undo, verify,
+};
+
+#[derive(Parser)]
+#[command(
+    name = "runzip",
+    version = "2.0.0",
+    about = "Russian filename encoding fix inside ZIP archives",
+    long_about = "Convert filenames inside ZIP archives from older Russian encodings\n(koi8-r, koi8-u, cp866, windows-1251) to UTF-8.",
+    args_conflicts_with_subcommands = true
+)]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    fix: FixArgs,
+
+    /// When to color the Error and Warning labels
+    #[arg(long = "color", value_name = "WHEN", global = true)]
+    color: Option<console::Color>,
+}
+
+#[derive(clap::Args)]
+#[allow(clippy::struct_excessive_bools)]
+struct FixArgs {
+    /// Dry run. Do not modify the <file.zip>
+    #[arg(short = 'n', long = "dry-run")]
+    dry_run: bool,
+
+    /// Rewrite archives without asking; runzip only asks when run in a
+    /// terminal
+    #[arg(short = 'y', long = "yes")]
+    yes: bool,
+
+    /// Ask which encoding to use for names that read about as well in
+    /// another one, showing their readings
+    #[arg(long = "interactive-on-ambiguous", conflicts_with = "porcelain")]
+    interactive_on_ambiguous: bool,
+
+    /// Verbose output (can be repeated)
+    #[arg(short = 'v', long = "verbose", action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Set source encoding. Auto-detect, if not set or "auto"
+    #[arg(short = 's', long = "source")]
+    source_encoding: Option<String>,
+
+    /// Language of the names, which decides the encodings detected
+    /// [default: ru]
+    #[arg(long = "lang", value_enum)]
+    lang: Option<Lang>,
+
+    /// Target form of the names [default: utf-8]
+    #[arg(short = 't', long = "target", value_enum)]
+    target: Option<Target>,
+
+    /// Produce a Windows-compatible archive: names in cp866 (or the code page
+    /// of --windows-locale), without the UTF-8 flag, made on MS-DOS
+    #[arg(short = 'w', long = "windows", conflicts_with = "target")]
+    windows: bool,
+
+    /// Windows system locale the archive is meant for with -w [default: ru]
+    #[arg(
+        long = "windows-locale",
+        value_enum,
+        value_name = "LOCALE",
+        requires = "windows"
+    )]
+    windows_locale: Option<WindowsLocale>,
+
+    /// Fix the archives for an extractor, choosing the form of the names and
+    /// what else is written for them
+    #[arg(
+        long = "for",
+        value_enum,
+        value_name = "EXTRACTOR",
+        conflicts_with_all = ["target", "windows"]
+    )]
+    destination: Option<Destination>,
+
+    /// Also store the Unicode form of legacy names in Info-ZIP Unicode Path
+    /// extra fields, which extractors with UTF-8 support use instead
+    #[arg(long = "unicode-extra")]
+    unicode_extra: bool,
+
+    /// What to do with characters the target encoding cannot represent [default: error]
+    #[arg(long = "on-unencodable", value_enum)]
+    on_unencodable: Option<Unencodable>,
+
+    /// Fail for an archive with a name that cannot be converted, or whose
+    /// encoding detection is unsure of, leaving it unchanged, instead of
+    /// rewriting it with that name kept
+    #[arg(long = "strict")]
+    strict: bool,
+
+    /// Continue with the remaining archives after a failure (default)
+    #[arg(long = "keep-going", overrides_with = "fail_fast")]
+    keep_going: bool,
+
+    /// Stop at the first archive that fails to process
+    #[arg(long = "fail-fast", overrides_with = "keep_going")]
+    fail_fast: bool,
+
+    /// Do not write the <file.zip>.runzip.json rename map after fixing
+    #[arg(long = "no-rename-map")]
+    no_rename_map: bool,
+
+    /// Rename entries as listed in a CSV file (old,new) instead of converting names
+    #[arg(long = "map", value_name = "RENAMES.CSV")]
+    map: Option<PathBuf>,
+
+    /// With --dry-run, list the renames in DIFF.TXT like a unified diff
+    #[arg(long = "diff", value_name = "DIFF.TXT", requires = "dry_run")]
+    diff: Option<PathBuf>,
+
+    /// Rename entries of the archives in DIFF.TXT as it lists, instead of
+    /// detecting encodings
+    #[arg(
+        long = "apply",
+        value_name = "DIFF.TXT",
+        conflicts_with_all = ["map", "diff", "files"]
+    )]
+    apply: Option<PathBuf>,
+
+    /// What to do with control characters (newlines, bell, ...) in names
+    #[arg(long = "control-chars", value_enum, value_name = "MODE", default_value_t = ControlChars::Keep)]
+    control_chars: ControlChars,
+
+    /// Replace backslashes separating directories (dir\file.txt) with slashes
+    #[arg(long = "fix-separators")]
+    fix_separators: bool,
+
+    /// Remove leading slashes, drive letters (C:\) and UNC prefixes
+    /// (\\server\share\) from names
+    #[arg(long = "strip-absolute")]
+    strip_absolute: bool,
+
+    /// Remove the first N directories, or the directory PATH, from the
+    /// names, e.g. a redundant top-level folder
+    #[arg(long = "strip-prefix", value_name = "N|PATH")]
+    strip_prefix: Option<StripPrefix>,
+
+    /// Convert the names to a Unicode normalization form: nfd for macOS
+    /// (HFS+), nfkc for search indexes
+    #[arg(long = "normalize", value_enum, value_name = "FORM", default_value_t = Normalization::None)]
+    normalize: Normalization,
+
+    /// Convert the names to lower or upper case, e.g. the ALL-CAPS names of
+    /// DOS archives
+    #[arg(long = "case", value_enum, value_name = "CASE", default_value_t = Case::Preserve)]
+    case: Case,
+
+    /// Put every entry in the directory PATH, after --strip-prefix
+    #[arg(long = "add-prefix", value_name = "PATH")]
+    add_prefix: Option<String>,
+
+    /// Make absolute paths relative and drop ".." components
+    #[arg(long = "sanitize-paths")]
+    sanitize_paths: bool,
+
+    /// Replace characters and device names (CON, NUL, ...) that are invalid in Windows file names
+    #[arg(long = "sanitize-windows")]
+    sanitize_windows: bool,
+
+    /// Shorten path components longer than 255 bytes, keeping names unique
+    #[arg(long = "truncate-long-names")]
+    truncate_long_names: bool,
+
+    /// Order of the entries in rewritten ZIP archives, by their new names
+    #[arg(long = "sort", value_enum, value_name = "ORDER", default_value_t = SortOrder::None)]
+    sort: SortOrder,
+
+    /// Drop entries stored more than once under the same name, keeping the
+    /// first or the last, rename the later ones, or refuse such archives
+    #[arg(long = "dedupe", value_enum, value_name = "MODE")]
+    dedupe: Option<Dedupe>,
+
+    /// Leave out the entries matching GLOB (e.g. Thumbs.db, __MACOSX/), as in
+    /// .gitignore; can be repeated
+    #[arg(long = "delete", value_name = "GLOB")]
+    delete: Vec<String>,
+
+    /// Fix only the entry with index N, as `runzip list` numbers them,
+    /// leaving the others as they are; can be repeated
+    #[arg(long = "entry", value_name = "N")]
+    entry: Vec<usize>,
+
+    /// Fix only the entries whose names as stored match GLOB, a pattern as in
+    /// --delete; can be repeated and combined with --entry
+    #[arg(long = "entry-name", value_name = "GLOB")]
+    entry_name: Vec<String>,
+
+    /// Also fix ZIP archives stored inside the archives
+    #[arg(long = "recurse-archives")]
+    recurse_archives: bool,
+
+    /// Detect the encoding of ambiguous names from the .txt, .nfo and .diz
+    /// files in ZIP archives
+    #[arg(long = "content-hints")]
+    content_hints: bool,
+
+    /// Keep UTF-8 names that were misread in another code page and encoded
+    /// again ("Ð¿Ñ€Ð¸Ð²ÐµÑ‚") instead of repairing them
+    #[arg(long = "keep-mojibake")]
+    keep_mojibake: bool,
+
+    /// Also convert the contents of the entries with these extensions (e.g.
+    /// txt,csv) in ZIP archives from a legacy encoding to UTF-8
+    #[arg(long = "fix-contents", value_name = "EXT,...", value_delimiter = ',')]
+    fix_contents: Vec<String>,
+
+    /// Also process ZIP-based formats (docx, jar, apk, epub, ...) that renaming can break
+    #[arg(long = "force")]
+    force: bool,
+
+    /// Keep the data of uncompressed entries 4-byte aligned, as Android's zipalign does
+    #[arg(long = "zipalign")]
+    zipalign: bool,
+
+    /// Name to use when the local header and central directory disagree
+    #[arg(long = "prefer-name", value_enum, default_value_t = NameSource::Central)]
+    prefer_name: NameSource,
+
+    /// Rebuild archives with a damaged central directory from their local headers
+    #[arg(long = "salvage")]
+    salvage: bool,
+
+    /// Read archives through a memory map, which is faster for archives with
+    /// many small entries; the archives must not be changed by others meanwhile
+    #[arg(long = "mmap")]
+    mmap: bool,
+
+    /// Fix read-only archives too, keeping them read-only (they are skipped
+    /// with an error otherwise)
+    #[arg(long = "force-readonly")]
+    force_readonly: bool,
+
+    /// Keep the modification time of the archives when fixing them
+    #[arg(long = "preserve-mtime")]
+    preserve_mtime: bool,
+
+    /// Keep the original archives when replacing them, as `<file>~` (simple)
+    /// or `<file>.~N~` (numbered)
+    #[arg(long = "backup", value_name = "POLICY")]
+    backup: Option<Backup>,
+
+    /// Directory to write the new archives to before they replace the
+    /// originals (next to the original by default)
+    #[arg(long = "tmpdir", value_name = "DIR")]
+    tmpdir: Option<PathBuf>,
+
+    /// Size of the write buffer, larger values mean fewer system calls on
+    /// network file systems (K, M suffixes allowed)
+    #[arg(long = "buffer-size", value_name = "SIZE", default_value = "64K", value_parser = parse_buffer_size)]
+    buffer_size: usize,
+
+    /// Refuse archives with an entry that unpacks to more than SIZE (K, M, G suffixes allowed)
+    #[arg(long = "max-entry-size", value_name = "SIZE", value_parser = limits::parse_size)]
+    max_entry_size: Option<u64>,
+
+    /// Refuse archives that unpack to more than SIZE in total
+    #[arg(long = "max-total-size", value_name = "SIZE", value_parser = limits::parse_size)]
+    max_total_size: Option<u64>,
+
+    /// Refuse archives with an entry compressed more than RATIO:1
+    #[arg(long = "max-ratio", value_name = "RATIO")]
+    max_ratio: Option<u64>,
+
+    /// Write a CSV report with one row per entry
+    #[arg(long = "report", value_name = "REPORT.CSV")]
+    report: Option<PathBuf>,
+
+    /// Print one tab-separated line per entry in a format that stays the
+    /// same between releases, instead of the progress
+    #[arg(
+        long = "porcelain",
+        value_name = "VERSION",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "1",
+        value_parser = clap::value_parser!(u32).range(1..=i64::from(porcelain::VERSION))
+    )]
+    porcelain: Option<u32>,
+
+    /// Print the hash of every archive once it is fixed, as `sha256sum --tag`
+    /// does
+    #[arg(
+        long = "print-hash",
+        value_enum,
+        value_name = "ALGORITHM",
+        conflicts_with_all = ["dry_run", "porcelain"]
+    )]
+    print_hash: Option<HashAlgorithm>,
+
+    /// With --print-hash, also print the hash of every archive before it is
+    /// fixed
+    #[arg(long = "print-hash-original", requires = "print_hash")]
+    print_hash_original: bool,
+
+    /// Record the archives fixed so far in FILE, and skip the archives it
+    /// lists, to resume an interrupted batch; removed once all succeed
+    #[arg(long = "journal", value_name = "FILE", conflicts_with = "dry_run")]
+    journal: Option<PathBuf>,
+
+    /// ZIP, tar or gzip files to process
+    files: Vec<PathBuf>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Fix the names in ZIP archives, tar archives and gzip files (the default command)
+    Fix(Box<FixArgs>),
+    /// Restore the original entry names recorded in the rename map
+    Undo(UndoArgs),
+    /// List the entries of RAR, ARJ and LHA archives and ISO images with their detected encodings
+    Detect(DetectArgs),
+    /// Extract ZIP, ARJ and LHA archives and ISO images, converting the names to UTF-8
+    Extract(ExtractArgs),
+    /// Pack files and directories into a new ZIP archive with the names in a chosen encoding
+    Create(CreateArgs),
+    /// Rename files and directories on disk whose names are in a legacy encoding to UTF-8
+    Fs(FsArgs),
+    /// Mount a ZIP, ARJ or LHA archive or an ISO image as a read-only filesystem with the names converted to UTF-8
+    Mount(MountArgs),
+    /// Print a table of the entries of ZIP archives with their detected encodings
+    List(ListArgs),
+    /// Compare the entries of two ZIP archives by their decoded names
+    Diff(DiffArgs),
+    /// Fix ZIP archives as listed in a YAML manifest of jobs, with one report for all of them
+    Batch(BatchArgs),
+    /// Count ZIP archives by detected encoding, creator system and decade, to survey a collection
+    Stats(StatsArgs),
+    /// Print a shell completion script
+    Completions(CompletionsArgs),
+    /// Print a man page in roff format
+    #[command(hide = true)]
+    Man(ManArgs),
+}
+
+#[derive(clap::Args)]
+struct UndoArgs {
+    /// Dry run. Do not modify the <file.zip>
+    #[arg(short = 'n', long = "dry-run")]
+    dry_run: bool,
+
+    /// Rename map to use instead of <file.zip>.runzip.json
+    #[arg(long = "rename-map", value_name = "MAP.JSON")]
+    rename_map: Option<PathBuf>,
+
+    /// ZIP files to restore
+    files: Vec<PathBuf>,
+}
+
+#[derive(clap::Args)]
+struct DetectArgs {
+    /// Verbose output (can be repeated)
+    #[arg(short = 'v', long = "verbose", action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Set source encoding. Auto-detect, if not set or "auto"
+    #[arg(short = 's', long = "source")]
+    source_encoding: Option<String>,
+
+    /// Language of the names, which decides the encodings detected
+    /// [default: ru]
+    #[arg(long = "lang", value_enum)]
+    lang: Option<Lang>,
+
+    /// Target form of the names [default: utf-8]
+    #[arg(short = 't', long = "target", value_enum)]
+    target: Option<Target>,
+
+    /// Write the proposed renames as a CSV file in the --map format, to fix
+    /// the names after extraction
+    #[arg(long = "emit-map", value_name = "RENAMES.CSV")]
+    emit_map: Option<PathBuf>,
+
+    /// Print the bytes of every name in hex and read as Latin-1
+    #[arg(long = "show-raw")]
+    show_raw: bool,
+
+    /// RAR, ARJ and LHA archives and ISO images to inspect
+    files: Vec<PathBuf>,
+}
+
+#[derive(clap::Args)]
+struct ExtractArgs {
+    /// Dry run. Only list the names the entries would be extracted under
+    #[arg(short = 'n', long = "dry-run")]
+    dry_run: bool,
+
+    /// Verbose output (can be repeated)
+    #[arg(short = 'v', long = "verbose", action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Set source encoding. Auto-detect, if not set or "auto"
+    #[arg(short = 's', long = "source")]
+    source_encoding: Option<String>,
+
+    /// Language of the names, which decides the encodings detected
+    /// [default: ru]
+    #[arg(long = "lang", value_enum)]
+    lang: Option<Lang>,
+
+    /// Directory to extract to
+    #[arg(
+        short = 'd',
+        long = "directory",
+        value_name = "DIR",
+        default_value = "."
+    )]
+    directory: PathBuf,
+
+    /// Refuse archives with an entry that unpacks to more than SIZE (K, M, G suffixes allowed)
+    #[arg(long = "max-entry-size", value_name = "SIZE", value_parser = limits::parse_size)]
+    max_entry_size: Option<u64>,
+
+    /// Refuse archives that unpack to more than SIZE in total
+    #[arg(long = "max-total-size", value_name = "SIZE", value_parser = limits::parse_size)]
+    max_total_size: Option<u64>,
+
+    /// Refuse archives with an entry compressed more than RATIO:1
+    #[arg(long = "max-ratio", value_name = "RATIO")]
+    max_ratio: Option<u64>,
+
+    /// ZIP, ARJ and LHA archives and ISO images to extract
+    files: Vec<PathBuf>,
+}
+
+#[derive(clap::Args)]
+struct CreateArgs {
+    /// Verbose output (can be repeated)
+    #[arg(short = 'v', long = "verbose", action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Form of the names in the new archive
+    #[arg(short = 't', long = "target", value_enum, default_value_t = Target::Cp866)]
+    target: Target,
+
+    /// What to do with characters the target encoding cannot represent
+    #[arg(long = "on-unencodable", value_enum, default_value_t = Unencodable::Error)]
+    on_unencodable: Unencodable,
+
+    /// Also store the Unicode names in Info-ZIP Unicode Path extra fields,
+    /// which extractors with UTF-8 support use instead
+    #[arg(long = "unicode-extra")]
+    unicode_extra: bool,
+
+    /// Store the files without compression
+    #[arg(short = '0', long = "store")]
+    store: bool,
+
+    /// ZIP archive to create
+    archive: PathBuf,
+
+    /// Files and directories to pack
+    files: Vec<PathBuf>,
+}
+
+#[derive(clap::Args)]
+struct FsArgs {
+    /// Dry run. Only list the names that would be changed
+    #[arg(short = 'n', long = "dry-run")]
+    dry_run: bool,
+
+    /// Verbose output (can be repeated)
+    #[arg(short = 'v', long = "verbose", action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Set source encoding. Auto-detect, if not set or "auto"
+    #[arg(short = 's', long = "source")]
+    source_encoding: Option<String>,
+
+    /// Language of the names, which decides the encodings detected
+    /// [default: ru]
+    #[arg(long = "lang", value_enum)]
+    lang: Option<Lang>,
+
+    /// Directories to process, with everything under them
+    dirs: Vec<PathBuf>,
+}
+
+#[derive(clap::Args)]
+struct MountArgs {
+    /// Set source encoding. Auto-detect, if not set or "auto"
+    #[arg(short = 's', long = "source")]
+    source_encoding: Option<String>,
+
+    /// Language of the names, which decides the encodings detected
+    /// [default: ru]
+    #[arg(long = "lang", value_enum)]
+    lang: Option<Lang>,
+
+    /// Refuse to open a file that unpacks to more than SIZE (K, M, G suffixes allowed)
+    #[arg(long = "max-entry-size", value_name = "SIZE", value_parser = limits::parse_size)]
+    max_entry_size: Option<u64>,
+
+    /// Refuse to open more files once they unpack to more than SIZE in total
+    #[arg(long = "max-total-size", value_name = "SIZE", value_parser = limits::parse_size)]
+    max_total_size: Option<u64>,
+
+    /// Refuse to open a file compressed more than RATIO:1
+    #[arg(long = "max-ratio", value_name = "RATIO")]
+    max_ratio: Option<u64>,
+
+    /// ZIP, ARJ or LHA archive or ISO image to mount
+    archive: PathBuf,
+
+    /// Directory to mount it on
+    mountpoint: PathBuf,
+}
+
+#[derive(clap::Args)]
+struct ListArgs {
+    /// Verbose output (can be repeated)
+    #[arg(short = 'v', long = "verbose", action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Set source encoding. Auto-detect, if not set or "auto"
+    #[arg(short = 's', long = "source")]
+    source_encoding: Option<String>,
+
+    /// Language of the names, which decides the encodings detected
+    /// [default: ru]
+    #[arg(long = "lang", value_enum)]
+    lang: Option<Lang>,
+
+    /// Target form of the proposed names [default: utf-8]
+    #[arg(short = 't', long = "target", value_enum)]
+    target: Option<Target>,
+
+    /// Print zipinfo-style lines (permissions, version made by, size, method,
+    /// date) with the converted names instead of the table
+    #[arg(long = "zipinfo")]
+    zipinfo: bool,
+
+    /// Add columns with the bytes of every name in hex and read as Latin-1
+    #[arg(long = "show-raw", conflicts_with = "zipinfo")]
+    show_raw: bool,
+
+    /// ZIP files to list
+    files: Vec<PathBuf>,
+}
+
+#[derive(clap::Args)]
+struct DiffArgs {
+    /// Set source encoding of the names in both archives. Auto-detect, if
+    /// not set or "auto"
+    #[arg(short = 's', long = "source")]
+    source_encoding: Option<String>,
+
+    /// Language of the names, which decides the encodings detected
+    /// [default: ru]
+    #[arg(long = "lang", value_enum)]
+    lang: Option<Lang>,
+
+    /// Archive to compare from, e.g. the original
+    old: PathBuf,
+
+    /// Archive to compare to, e.g. the fixed one
+    new: PathBuf,
+}
+
+#[derive(clap::Args)]
+struct BatchArgs {
+    /// Dry run. Do not modify or copy any archive
+    #[arg(short = 'n', long = "dry-run")]
+    dry_run: bool,
+
+    /// Write the CSV report of every job to REPORT.CSV, instead of the
+    /// report the manifest names
+    #[arg(long = "report", value_name = "REPORT.CSV")]
+    report: Option<PathBuf>,
+
+    /// Manifest listing the jobs
+    #[arg(value_name = "JOBS.YAML")]
+    manifest: PathBuf,
+}
+
+#[derive(clap::Args)]
+struct StatsArgs {
+    /// Detect the encoding of ambiguous names from the .txt, .nfo and .diz
+    /// files in the archives
+    #[arg(long = "content-hints")]
+    content_hints: bool,
+
+    /// Language of the names, which decides the encodings detected
+    /// [default: ru]
+    #[arg(long = "lang", value_enum)]
+    lang: Option<Lang>,
+
+    /// ZIP files, and directories to search for .zip files recursively
+    paths: Vec<PathBuf>,
+}
+
+#[derive(clap::Args)]
+struct CompletionsArgs {
+    /// Shell to complete the command line of
+    shell: clap_complete::Shell,
+}
+
+#[derive(clap::Args)]
+struct ManArgs {
+    /// Subcommand to print the page of (`runzip-<COMMAND>`), the main page if
+    /// not set
+    command: Option<String>,
+}
+
+/// Print the list of archives that failed and exit if there are any
+fn exit_on_failures(failed: &[&PathBuf], total: usize) {
+    if failed.is_empty() {
+        return;
+    }
+    eprintln!(
+        "Failed to process {} of {total} archive{}:",
+        failed.len(),
+        if total == 1 { "" } else { "s" }
+    );
+    for zipfile in failed {
+        eprintln!("  {}", zipfile.display());
+    }
+    std::process::exit(1);
+}
+
+fn undo(args: &UndoArgs) {
+    if args.files.is_empty() {
+        eprintln!("{}: No ZIP files specified", console::error());
+        std::process::exit(1);
+    }
+    if args.rename_map.is_some() && args.files.len() > 1 {
+        eprintln!(
+            "{}: --rename-map can only be used with a single ZIP file",
+            console::error()
+        );
+        std::process::exit(1);
+    }
+
+    let mut failed = Vec::new();
+    for zipfile in &args.files {
+        let map_path = args
+            .rename_map
+            .clone()
+            .unwrap_or_else(|| renamemap::sidecar_path(zipfile));
+        // Only the sidecar is removed, an explicitly given map is the user's
+        let remove_map = args.rename_map.is_none();
+        if let Err(e) = undo::undo_archive(zipfile, &map_path, args.dry_run, remove_map) {
+            eprintln!("{} processing {}: {e}", console::error(), zipfile.display());
+            failed.push(zipfile);
+        }
+    }
+
+    exit_on_failures(&failed, args.files.len());
+}
+
+/// Open an archive that can only be listed and extracted, if `path` is one
+fn open_read_only(path: &Path) -> Result<Option<Box<dyn ReadOnlyArchive>>> {
+    Ok(if iso::is_iso(path)? {
+        let image = iso::Image::open(path)?;
+        println!("{}: using {} names", path.display(), image.names.label());
+        Some(Box::new(image))
+    } else if arj::is_arj(path)? {
+        Some(Box::new(arj::Archive::open(path)?))
+    } else if lha::is_lha(path)? {
+        Some(Box::new(lha::Archive::open(path)?))
+    } else {
+        None
+    })
+}
+
+/// List the entries of a RAR, ARJ or LHA archive or an ISO image with the
+/// names they would be fixed to, returning the raw and new names of the
+/// entries that need renaming; `show_raw` prints the bytes of every name too
+fn detect_archive(
+    path: &Path,
+    options: &FixOptions,
+    show_raw: bool,
+) -> Result<Vec<(Vec<u8>, String)>> {
+    let entries: Vec<_> = if rar::is_rar(path)? {
+        rar::list_entries(path)?
+            .into_iter()
+            .map(|entry| (entry.name, entry.unicode))
+            .collect()
+    } else if let Some(archive) = open_read_only(path)? {
+        archive
+            .entries()
+            .iter()
+            .map(|entry| (entry.name.clone(), entry.unicode))
+            .collect()
+    } else {
+        return Err(anyhow!(
+            "not a RAR, ARJ or LHA archive or an ISO image \
+             (use runzip fix --dry-run for other formats)"
+        ));
+    };
+    if !options.quiet {
+        println!(
+            "{} contains {} file{}",
+            path.display(),
+            entries.len(),
+            if entries.len() == 1 { "" } else { "s" }
+        );
+    }
+
+    let mut used_names = HashSet::new();
+    let mut renames = Vec::new();
+    for (name, unicode) in entries {
+        let mut outcome = process_entry_name(&name, unicode, Hints::default(), options);
+        apply_name_fixups(&mut outcome, options, &used_names);
+        used_names.insert(outcome.new_name.clone());
+        if show_raw {
+            println!(
+                "    Raw: {}  {}",
+                listing::hex_name(&name),
+                listing::latin1_name(&name)
+            );
+        }
+        if outcome.action == Action::Fixed {
+            renames.push((name, outcome.display_name()));
+        }
+    }
+    Ok(renames)
+}
+
+fn detect(args: &DetectArgs) {
+    if args.files.is_empty() {
+        eprintln!("{}: No archives specified", console::error());
+        std::process::exit(1);
+    }
+    if args.emit_map.is_some() && args.files.len() > 1 {
+        eprintln!(
+            "{}: --emit-map can only be used with a single archive",
+            console::error()
+        );
+        std::process::exit(1);
+    }
+
+    // Archives are never modified, so this is always a dry run
+    let config = load_config();
+    let options = with_lang(
+        FixOptions::read_only(
+            true,
+            parse_source_encoding(args.source_encoding.as_deref(), &config),
+            args.verbose,
+            args.target.or(config.target).unwrap_or(Target::Utf8),
+        ),
+        args.lang,
+        &config,
+    )
+    .build();
+
+    let mut failed = Vec::new();
+    for path in &args.files {
+        match detect_archive(path, &options, args.show_raw) {
+            Ok(renames) => {
+                if let Some(map_path) = &args.emit_map
+                    && let Err(e) = renames::write_rules(map_path, &renames)
+                {
+                    eprintln!("{}: {:#}", console::error(), anyhow::Error::from(e));
+                    std::process::exit(1);
+                }
+            }
+            Err(e) => {
+                eprintln!("{} processing {}: {e}", console::error(), path.display());
+                failed.push(path);
+            }
+        }
+    }
+
+    exit_on_failures(&failed, args.files.len());
+}
+
+/// Open an archive for `runzip extract`: one of the read-only formats or a
+/// ZIP archive
+fn open_extractable(path: &Path) -> Result<Box<dyn ReadOnlyArchive>> {
+    if let Some(archive) = open_read_only(path)? {
+        return Ok(archive);
+    }
+    let archive =
+        open_archive(path, None).context("not a ZIP, ARJ or LHA archive or an ISO image")?;
+    Ok(Box::new(extract::Zip::new(archive)))
+}
+
+/// Name an entry is extracted under: converted to UTF-8, relative and
+/// unique among `used_names`
+fn extraction_name(
+    raw_name: &[u8],
+    unicode: bool,
+    host: Option<Host>,
+    options: &FixOptions,
+    used_names: &mut HashSet<Vec<u8>>,
+) -> String {
+    let mut outcome = process_entry_name(raw_name, unicode, Hints::from_host(host), options);
+    apply_name_fixups(&mut outcome, options, used_names);
+    let name = sanitize::safe_path(outcome.display_name().as_bytes());
+    used_names.insert(name.clone());
+    String::from_utf8_lossy(&name).into_owned()
+}
+
+/// Extract a read-only archive into `dest`
+fn extract_archive(
+    path: &Path,
+    archive: &mut dyn ReadOnlyArchive,
+    options: &FixOptions,
+    dest: &Path,
+) -> Result<()> {
+    let count = archive.entries().len();
+    if !options.quiet {
+        println!(
+            "{} contains {} file{}",
+            path.display(),
+            count,
+            if count == 1 { "" } else { "s" }
+        );
+    }
+
+    let mut unpacked = 0;
+    for (index, entry) in archive.entries().iter().enumerate() {
+        options.limits.check_entry(
+            index,
+            &entry.name,
+            entry.size,
+            entry.compressed_size,
+            &mut unpacked,
+        )?;
+    }
+
+    let mut used_names = HashSet::new();
+    for index in 0..count {
+        let entry = &archive.entries()[index];
+        let is_dir = entry.is_dir;
+        let name = extraction_name(
+            &entry.name,
+            entry.unicode,
+            entry.host,
+            options,
+            &mut used_names,
+        );
+        if options.dry_run {
+            continue;
+        }
+        if is_dir {
+            extract::create_dir(dest, &name)?;
+            continue;
+        }
+        let mut file = extract::create_file(dest, &name)?;
+        if let Err(e) = archive.extract_entry(index, &mut file) {
+            drop(file);
+            // Best effort, the error below is what matters
+            let _ = std::fs::remove_file(dest.join(&name));
+            return Err(e).context(format!("Failed to extract {name}"));
+        }
+    }
+    Ok(())
+}
+
+fn extract(args: &ExtractArgs) {
+    if args.files.is_empty() {
+        eprintln!("{}: No archives specified", console::error());
+        std::process::exit(1);
+    }
+
+    let config = load_config();
+    let options = with_lang(
+        FixOptions::read_only(
+            args.dry_run,
+            parse_source_encoding(args.source_encoding.as_deref(), &config),
+            args.verbose,
+            Target::Utf8,
+        ),
+        args.lang,
+        &config,
+    )
+    // Nothing may be written outside the target directory
+    .sanitize_paths(true)
+    .max_entry_size(args.max_entry_size)
+    .max_total_size(args.max_total_size)
+    .max_ratio(args.max_ratio)
+    .build();
+
+    let mut failed = Vec::new();
+    for path in &args.files {
+        let result = open_extractable(path).and_then(|mut archive| {
+            extract_archive(path, archive.as_mut(), &options, &args.directory)
+        });
+        if let Err(e) = result {
+            eprintln!("{} processing {}: {e:#}", console::error(), path.display());
+            failed.push(path);
+        }
+    }
+
+    exit_on_failures(&failed, args.files.len());
+}
+
+/// Name of a packed file or directory in the new archive
+fn created_name(source: &create::Source, args: &CreateArgs) -> Result<Vec<u8>> {
+    let name = if args.target == Target::Translit {
+        translit::transliterate(&source.name)
+    } else {
+        source.name.clone()
+    };
+    encode_text(&name, args.target.encoding(), args.on_unencodable).context(format!(
+        "Cannot name \"{}\" in the new archive (see --on-unencodable)",
+        source.name
+    ))
+}
+
+fn create_archive(args: &CreateArgs) -> Result<()> {
+    if args.archive.exists() {
+        return Err(anyhow!("{} already exists", args.archive.display()));
+    }
+    let sources = create::collect(&args.files)?;
+    // Legacy names are what DOS and Windows extractors expect
+    let dos_host = args.target.encoding() != UTF_8;
+
+    let temp_file = NamedTempFile::new_in(args.archive.parent().unwrap_or_else(|| Path::new(".")))
+        .context("Failed to create temporary file")?;
+    let mut writer = RawWriter::new(BufWriter::new(temp_file.as_file()));
+    let mut used_names = HashSet::new();
+    for source in &sources {
+        let name = created_name(source, args)?;
+        if !used_names.insert(name.clone()) {
+            return Err(anyhow!(
+                "\"{}\" gets the same name as another entry",
+                source.name
+            ));
+        }
+        let utf8 = args.target.encoding() == UTF_8 && !name.is_ascii();
+        let mut template = create::entry_template(source, dos_host);
+        template.name.clone_from(&name);
+        let unicode_name = (args.unicode_extra && !utf8 && name != source.name.as_bytes())
+            .then_some(source.name.as_str());
+        let data = if source.is_dir() {
+            Vec::new()
+        } else {
+            std::fs::read(&source.path)
+                .context(format!("Failed to read {}", source.path.display()))?
+        };
+        writer.add_entry(&template, &name, utf8, unicode_name, &data, !args.store)?;
+        if args.verbose >= 1 {
+            println!(
+                "  {} -> {}",
+                source.name,
+                display_name(&name, args.target.encoding())
+            );
+        }
+    }
+    let written = writer.entries().to_vec();
+    writer
+        .finish(b"")
+        .context("Failed to finalize new archive")?;
+
+    let new_file = File::open(temp_file.path()).context("Failed to reopen the new archive")?;
+    verify::verify_archive(BufReader::new(new_file), &written)?;
+    temp_file
+        .persist_noclobber(&args.archive)
+        .map_err(|e| e.error)
+        .context(format!("Failed to create {}", args.archive.display()))?;
+    println!(
+        "{} created with {} entr{} named in {}",
+        args.archive.display(),
+        written.len(),
+        if written.len() == 1 { "y" } else { "ies" },
+        args.target.encoding().name()
+    );
+    Ok(())
+}
+
+fn create(args: &CreateArgs) {
+    if args.files.is_empty() {
+        eprintln!("{}: No files specified", console::error());
+        std::process::exit(1);
+    }
+    if let Err(e) = create_archive(args) {
+        eprintln!("{}: {e:#}", console::error());
+        std::process::exit(1);
+    }
+}
+
+/// Print `zipinfo` output for a ZIP archive with the converted names
+fn zipinfo_archive(path: &Path, options: &FixOptions) -> Result<()> {
+    let archive = open_archive(path, None)?;
+    let entries = archive.entries();
+    println!("Archive:  {}", path.display());
+    println!(
+        "Zip file size: {} bytes, number of entries: {}",
+        std::fs::metadata(path)?.len(),
+        entries.len()
+    );
+    let mut used_names = HashSet::new();
+    for entry in entries {
+        let mut outcome = process_entry_name(
+            &entry.name,
+            entry.is_utf8(),
+            Hints::from_host(Host::from_version_made_by(entry.version_made_by)),
+            options,
+        );
+        apply_name_fixups(&mut outcome, options, &used_names);
+        used_names.insert(outcome.new_name.clone());
+        println!("{}", listing::zipinfo_line(entry, &outcome.display_name()));
+    }
+    println!("{}", listing::zipinfo_summary(entries));
+    Ok(())
+}
+
+/// Print the detection table of a ZIP archive, with the raw bytes of the
+/// names if `show_raw`
+fn list_archive(path: &Path, options: &FixOptions, show_raw: bool) -> Result<()> {
+    let archive = open_archive(path, None)?;
+    let mut used_names = HashSet::new();
+    let rows: Vec<Vec<String>> = archive
+        .entries()
+        .iter()
+        .enumerate()
+        .map(|(index, entry)| {
+            let mut outcome = process_entry_name(
+                &entry.name,
+                entry.is_utf8(),
+                Hints::from_host(Host::from_version_made_by(entry.version_made_by)),
+                options,
+            );
+            apply_name_fixups(&mut outcome, options, &used_names);
+            used_names.insert(outcome.new_name.clone());
+            let proposed = match outcome.action {
+                Action::Fixed => outcome.display_name(),
+                Action::Failed => "(cannot convert)".to_string(),
+                Action::AlreadyUtf8 | Action::Unchanged => "-".to_string(),
+            };
+            let mut row = vec![
+                index.to_string(),
+                entry.uncompressed_size.to_string(),
+                listing::method_name(entry.compression_method),
+                if entry.is_utf8() { "yes" } else { "no" }.to_string(),
+                listing::escape_name(&entry.name),
+            ];
+            if show_raw {
+                row.push(listing::hex_name(&entry.name));
+                row.push(listing::latin1_name(&entry.name));
+            }
+            row.extend([
+                detection_name(outcome.encoding, outcome.misread_as)
+                    .unwrap_or_else(|| "-".to_string()),
+                outcome
+                    .confidence
+                    .map_or_else(|| "-".to_string(), |confidence| format!("{confidence:.2}")),
+                proposed,
+            ]);
+            row
+        })
+        .collect();
+
+    let mut header = vec!["#", "Size", "Method", "UTF-8", "Raw name"];
+    let mut numeric = vec![true, true, false, false, false];
+    if show_raw {
+        header.extend(["Hex", "Latin-1"]);
+        numeric.extend([false, false]);
+    }
+    header.extend(["Encoding", "Confidence", "Proposed name"]);
+    numeric.extend([false, true, false]);
+    println!("{}:", path.display());
+    listing::print_table(&header, &numeric, &rows);
+    Ok(())
+}
+
+fn list(args: &ListArgs) {
+    if args.files.is_empty() {
+        eprintln!("{}: No ZIP files specified", console::error());
+        std::process::exit(1);
+    }
+
+    let config = load_config();
+    let options = with_lang(
+        FixOptions::read_only(
+            true,
+            parse_source_encoding(args.source_encoding.as_deref(), &config),
+            args.verbose,
+            args.target.or(config.target).unwrap_or(Target::Utf8),
+        ),
+        args.lang,
+        &config,
+    )
+    .quiet(true)
+    .build();
+
+    let mut failed = Vec::new();
+    for path in &args.files {
+        let result = if args.zipinfo {
+            zipinfo_archive(path, &options)
+        } else {
+            list_archive(path, &options, args.show_raw)
+        };
+        if let Err(e) = result {
+            eprintln!("{} processing {}: {e:#}", console::error(), path.display());
+            failed.push(path);
+        }
+    }
+
+    exit_on_failures(&failed, args.files.len());
+}
+
+fn compare_archives(args: &DiffArgs) {
+    let config = load_config();
+    let options = with_lang(
+        FixOptions::read_only(
+            true,
+            parse_source_encoding(args.source_encoding.as_deref(), &config),
+            0,
+            Target::Utf8,
+        ),
+        args.lang,
+        &config,
+    )
+    .quiet(true)
+    .build();
+
+    let changes = or_exit(compare::compare(&args.old, &args.new, &options));
+    for change in &changes {
+        println!("{}", change.line());
+    }
+    let count = |kind: fn(&Change) -> bool| changes.iter().filter(|change| kind(change)).count();
+    println!(
+        "{} added, {} removed, {} renamed, {} modified, {} re-encoded",
+        count(|change| matches!(change, Change::Added(_))),
+        count(|change| matches!(change, Change::Removed(_))),
+        count(|change| matches!(change, Change::Renamed(..))),
+        count(|change| matches!(change, Change::Modified(_))),
+        count(|change| matches!(change, Change::Reencoded(..))),
+    );
+}
+
+fn batch(args: &BatchArgs) {
+    let manifest = or_exit(Manifest::load(&args.manifest));
+    let config = load_config();
+    let report_path = args.report.as_deref().or(manifest.report.as_deref());
+    let mut report = Reports {
+        csv: or_exit(report_path.map(CsvReport::create).transpose()),
+        ..Reports::default()
+    };
+
+    handle_ctrl_c();
+
+    let mut total = 0;
+    let mut renamed = 0;
+    let mut failed = Vec::new();
+    for (index, job) in manifest.jobs.iter().enumerate() {
+        if CANCELLED.load(Ordering::Relaxed) {
+            break;
+        }
+        let tasks = match job.tasks() {
+            Ok(tasks) => tasks,
+            Err(e) => {
+                eprintln!(
+                    "{} in job {}: {:#}",
+                    console::error(),
+                    index + 1,
+                    anyhow::Error::from(e)
+                );
+                total += 1;
+                failed.push(job.input.clone());
+                continue;
+            }
+        };
+        for task in tasks {
+            if CANCELLED.load(Ordering::Relaxed) {
+                break;
+            }
+            total += 1;
+            let options = FixOptions::builder()
+                .dry_run(args.dry_run)
+                .source(task.settings.source_encoding(config.source))
+                .lang(config.lang.unwrap_or_default())
+                .candidates(candidates(config.lang.unwrap_or_default(), &config))
+                .target(
+                    task.settings
+                        .target
+                        .or(config.target)
+                        .unwrap_or(Target::Utf8),
+                )
+                .on_unencodable(
+                    task.settings
+                        .on_unencodable
+                        .or(config.on_unencodable)
+                        .unwrap_or(Unencodable::Error),
+                )
+                .backup(config.backup.unwrap_or_default())
+                .quiet(false)
+                .events(Some(&Printer))
+                .cancel(Some(&CANCELLED))
+                .build();
+            let result = task
+                .prepare(args.dry_run)
+                .and_then(|zipfile| fix_archive(zipfile, &options))
+                .and_then(|fixed| {
+                    renamed += fixed.renamed;
+                    report.add(&fixed, args.dry_run)
+                });
+            if let Err(e) = result {
+                eprintln!(
+                    "{} processing {}: {:#}",
+                    console::error(),
+                    task.input.display(),
+                    anyhow::Error::from(e)
+                );
+                failed.push(task.input);
+            }
+        }
+    }
+
+    or_exit(report.finish());
+    exit_if_cancelled();
+    println!(
+        "{} job{}: {total} archive{}, {renamed} entr{} {}renamed, {} failed",
+        manifest.jobs.len(),
+        if manifest.jobs.len() == 1 { "" } else { "s" },
+        if total == 1 { "" } else { "s" },
+        if renamed == 1 { "y" } else { "ies" },
+        if args.dry_run { "to be " } else { "" },
+        failed.len()
+    );
+
+    exit_on_failures(&failed.iter().collect::<Vec<_>>(), total);
+}
+
+fn stats(args: &StatsArgs) {
+    if args.paths.is_empty() {
+        eprintln!(
+            "{}: No ZIP files or directories specified",
+            console::error()
+        );
+        std::process::exit(1);
+    }
+    let archives = match stats::collect_archives(&args.paths) {
+        Ok(archives) => archives,
+        Err(e) => {
+            eprintln!("{}: {:#}", console::error(), anyhow::Error::from(e));
+            std::process::exit(1);
+        }
+    };
+
+    let config = load_config();
+    let options = with_lang(
+        FixOptions::read_only(true, None, 0, Target::Utf8),
+        args.lang,
+        &config,
+    )
+    .content_hints(args.content_hints)
+    .quiet(true)
+    .build();
+
+    let mut survey = stats::Survey::default();
+    let mut failed = Vec::new();
+    for path in &archives {
+        if let Err(e) = survey.add_archive(path, &options) {
+            eprintln!(
+                "{} processing {}: {:#}",
+                console::error(),
+                path.display(),
+                anyhow::Error::from(e)
+            );
+            failed.push(path);
+        }
+    }
+    survey.print();
+
+    exit_on_failures(&failed, archives.len());
+}
+
+#[cfg(unix)]
+fn fix_directories(args: &FsArgs) {
+    if args.dirs.is_empty() {
+        eprintln!("{}: No directories specified", console::error());
+        std::process::exit(1);
+    }
+
+    let config = load_config();
+    let options = with_lang(
+        FixOptions::read_only(
+            args.dry_run,
+            parse_source_encoding(args.source_encoding.as_deref(), &config),
+            args.verbose,
+            Target::Utf8,
+        ),
+        args.lang,
+        &config,
+    )
+    .build();
+
+    let mut failed = Vec::new();
+    for dir in &args.dirs {
+        let mut summary = fstree::Summary::default();
+        match fstree::fix_tree(dir, &options, &mut summary) {
+            Ok(()) => {
+                println!(
+                    "{}: {} {} of {} name{}",
+                    dir.display(),
+                    if args.dry_run {
+                        "would rename"
+                    } else {
+                        "renamed"
+                    },
+                    summary.renamed,
+                    summary.names,
+                    if summary.names == 1 { "" } else { "s" }
+                );
+                if summary.failed > 0 {
+                    eprintln!(
+                        "{} processing {}: {} name{} could not be fixed",
+                        console::error(),
+                        dir.display(),
+                        summary.failed,
+                        if summary.failed == 1 { "" } else { "s" }
+                    );
+                    failed.push(dir);
+                }
+            }
+            Err(e) => {
+                eprintln!(
+                    "{} processing {}: {:#}",
+                    console::error(),
+                    dir.display(),
+                    anyhow::Error::from(e)
+                );
+                failed.push(dir);
+            }
+        }
+    }
+
+    if !failed.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+#[cfg(all(feature = "mount", any(target_os = "linux", target_os = "macos")))]
+fn mount(args: &MountArgs) {
+    let config = load_config();
+    let options = with_lang(
+        FixOptions::read_only(
+            true,
+            parse_source_encoding(args.source_encoding.as_deref(), &config),
+            0,
+            Target::Utf8,
+        ),
+        args.lang,
+        &config,
+    )
+    .sanitize_paths(true)
+    .quiet(true)
+    .build();
+
+    let archive = or_exit(open_extractable(&args.archive));
+    let metadata = or_exit(
+        std::fs::metadata(&args.archive)
+            .context(format!("Failed to read {}", args.archive.display())),
+    );
+    let mut used_names = HashSet::new();
+    let names: Vec<String> = archive
+        .entries()
+        .iter()
+        .map(|entry| {
+            extraction_name(
+                &entry.name,
+                entry.unicode,
+                entry.host,
+                &options,
+                &mut used_names,
+            )
+        })
+        .collect();
+    let limits = Limits {
+        entry_size: args.max_entry_size,
+        total_size: args.max_total_size,
+        ratio: args.max_ratio,
+    };
+    let view = mount::View::new(archive, &names, &metadata, limits);
+    or_exit(mount::mount(view, &args.archive, &args.mountpoint));
+}
+
+#[cfg(not(all(feature = "mount", any(target_os = "linux", target_os = "macos"))))]
+fn mount(_args: &MountArgs) {
+    eprintln!(
+        "{}: runzip mount needs the mount feature, on Linux or macOS",
+        console::error()
+    );
+    std::process::exit(1);
+}
+
+#[cfg(not(unix))]
+fn fix_directories(_args: &FsArgs) {
+    eprintln!(
+        "{}: runzip fs is only supported on Unix, where names on disk are raw bytes",
+        console::error()
+    );
+    std::process::exit(1);
+}
+
+fn man(args: &ManArgs) {
+    let mut command = Args::command();
+    // Fills in the "runzip <COMMAND>" names used in the synopsis
+    command.build();
+    let page = match &args.command {
+        Some(name) => {
+            let Some(subcommand) = command.find_subcommand(name) else {
+                eprintln!("{}: Unknown command \"{name}\"", console::error());
+                std::process::exit(1);
+            };
+            let subcommand = subcommand.clone().version(env!("CARGO_PKG_VERSION"));
+            clap_mangen::Man::new(subcommand).title(format!("runzip-{name}"))
+        }
+        None => clap_mangen::Man::new(command),
+    };
+    if let Err(e) = page.render(&mut std::io::stdout()) {
+        eprintln!("{}: {e}", console::error());
+        std::process::exit(1);
+    }
+}
+
+pub fn main() {
+    console::use_utf8();
+    let args = Args::parse();
+    // A broken configuration file is reported by the command itself
+    console::set_color(
+        args.color
+            .or_else(|| config::load().ok()?.color)
+            .unwrap_or(console::Color::Auto),
+    );
+
+    match &args.command {
+        Some(Command::Fix(fix_args)) => fix(fix_args),
+        Some(Command::Undo(undo_args)) => undo(undo_args),
+        Some(Command::Detect(detect_args)) => detect(detect_args),
+        Some(Command::Extract(extract_args)) => extract(extract_args),
+        Some(Command::Create(create_args)) => create(create_args),
+        Some(Command::Fs(fs_args)) => fix_directories(fs_args),
+        Some(Command::Mount(mount_args)) => mount(mount_args),
+        Some(Command::List(list_args)) => list(list_args),
+        Some(Command::Diff(diff_args)) => compare_archives(diff_args),
+        Some(Command::Batch(batch_args)) => batch(batch_args),
+        Some(Command::Stats(stats_args)) => stats(stats_args),
+        Some(Command::Completions(completions_args)) => clap_complete::generate(
+            completions_args.shell,
+            &mut Args::command(),
+            "runzip",
+            &mut std::io::stdout(),
+        ),
+        Some(Command::Man(man_args)) => man(man_args),
+        None => fix(&args.fix),
+    }
+}
+
+/// The value of `result`, exiting with its error otherwise
+fn or_exit<T, E: Into<anyhow::Error>>(result: Result<T, E>) -> T {
+    result.unwrap_or_else(|e| {
+        eprintln!("{}: {:#}", console::error(), e.into());
+        std::process::exit(1);
+    })
+}
+
+/// Settings from the configuration file, exiting if it cannot be read
+fn load_config() -> Config {
+    or_exit(config::load())
+}
+
+/// Set the language of the options `builder` makes from `--lang`, or else
+/// the configuration, with the encodings detection picks from for it
+fn with_lang<'a>(
+    builder: FixOptionsBuilder<'a>,
+    lang: Option<Lang>,
+    config: &Config,
+) -> FixOptionsBuilder<'a> {
+    let lang = lang.or(config.lang).unwrap_or_default();
+    builder.lang(lang).candidates(candidates(lang, config))
+}
+
+/// Legacy encodings detection picks from for `lang`: the configured ones,
+/// or else those of the language
+fn candidates(lang: Lang, config: &Config) -> Vec<&'static Encoding> {
+    config
+        .candidates
+        .clone()
+        .unwrap_or_else(|| lang.candidates())
+}
+
+/// The encoding given with --source, or else in the configuration file,
+/// exiting if it is not supported
+fn parse_source_encoding(source: Option<&str>, config: &Config) -> Option<&'static Encoding> {
+    let Some(source) = source else {
+        return config.source;
+    };
+    if source == "auto" {
+        None
+    } else if let Ok(encoding) = string_to_encoding(source) {
+        Some(encoding)
+    } else {
+        eprintln!("{}: Invalid source encoding: {source}", console::error());
+        std::process::exit(1);
+    }
+}
+
+/// Ask on the terminal whether to rewrite the archive at `path`
+fn ask_to_rewrite(path: &Path, _: &Preflight) -> bool {
+    print!("Rewrite {}? [y/N] ", path.display());
+    let _ = std::io::stdout().flush();
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer).is_ok()
+        && matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+/// Prints the messages and warnings of fixes; `quiet` options, as for the
+/// porcelain output, leave the messages out
+pub(crate) struct Printer;
+
+impl Events for Printer {
+    fn on_warning(&self, _path: &Path, message: &str) {
+        eprintln!("{}: {message}", console::warning());
+    }
+
+    fn on_message(&self, message: &str) {
+        println!("{message}");
+    }
+}
+
+/// Ask on the terminal which of the readings of an ambiguous `name` to use
+fn ask_encoding(name: &[u8], candidates: &[Candidate]) -> Option<&'static Encoding> {
+    println!("{} is ambiguous:", listing::escape_name(name));
+    for (number, candidate) in candidates.iter().enumerate() {
+        println!(
+            "  {}. {} ({}, {:.2})",
+            number + 1,
+            candidate.decoded,
+            candidate.encoding.name(),
+            candidate.score
+        );
+    }
+    print!("Encoding [1]: ");
+    let _ = std::io::stdout().flush();
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer).ok()?;
+    let number = match answer.trim() {
+        "" => 1,
+        answer => answer.parse().ok()?,
+    };
+    candidates
+        .get(usize::checked_sub(number, 1)?)
+        .map(|candidate| candidate.encoding)
+}
+
+/// Options of `runzip fix`, from the command line or else the configuration
+fn fix_options<'a>(args: &'a FixArgs, config: &Config) -> FixOptions<'a> {
+    with_lang(FixOptions::builder(), args.lang, config)
+        .dry_run(args.dry_run)
+        .source(parse_source_encoding(
+            args.source_encoding.as_deref(),
+            config,
+        ))
+        .verbose(args.verbose)
+        .write_rename_map(!args.no_rename_map)
+        .target(if let Some(destination) = args.destination {
+            destination.target()
+        } else if args.windows {
+            args.windows_locale
+                .map_or(Target::Cp866, WindowsLocale::target)
+        } else {
+            args.target.or(config.target).unwrap_or(Target::Utf8)
+        })
+        .dos_host(args.windows || args.destination.is_some_and(Destination::dos_host))
+        .unicode_extra(
+            args.unicode_extra || args.destination.is_some_and(Destination::unicode_extra),
+        )
+        .on_unencodable(
+            args.on_unencodable
+                .or(config.on_unencodable)
+                .unwrap_or(Unencodable::Error),
+        )
+        .strict(args.strict)
+        .control_chars(args.control_chars)
+        .fix_separators(args.fix_separators)
+        .strip_absolute(args.strip_absolute)
+        .strip_prefix(args.strip_prefix.clone())
+        .normalize(args.normalize)
+        .case(args.case)
+        .add_prefix(args.add_prefix.clone())
+        .sanitize_paths(args.sanitize_paths)
+        .sanitize_windows(
+            args.sanitize_windows || args.destination.is_some_and(Destination::sanitize_windows),
+        )
+        .truncate_long_names(args.truncate_long_names)
+        .prefer_name(args.prefer_name)
+        .sort(args.sort)
+        .on_collision(args.dedupe)
+        .delete(args.delete.clone())
+        .entries(args.entry.clone())
+        .entry_names(args.entry_name.clone())
+        .recurse_archives(args.recurse_archives)
+        .content_hints(args.content_hints)
+        .repair_mojibake(!args.keep_mojibake)
+        .fix_contents(
+            args.fix_contents
+                .iter()
+                .map(|extension| extension.trim_start_matches('.').to_lowercase())
+                .collect(),
+        )
+        .force(args.force)
+        .zipalign(args.zipalign)
+        .salvage(args.salvage)
+        .mmap(args.mmap)
+        .buffer_size(args.buffer_size)
+        .force_readonly(args.force_readonly)
+        .preserve_timestamps(args.preserve_mtime)
+        .backup(args.backup.or(config.backup).unwrap_or_default())
+        .tmpdir(args.tmpdir.as_deref())
+        .confirm(
+            (!args.yes && args.porcelain.is_none() && std::io::stdin().is_terminal())
+                .then_some(&ask_to_rewrite),
+        )
+        .choose_encoding(args.interactive_on_ambiguous.then_some(&ask_encoding))
+        .events(Some(&Printer))
+        .cancel(Some(&CANCELLED))
+        .max_entry_size(args.max_entry_size)
+        .max_total_size(args.max_total_size)
+        .max_ratio(args.max_ratio)
+        .quiet(args.porcelain.is_some())
+        .build()
+}
+
+/// Hash line of `zipfile` before it is fixed, with --print-hash-original
+fn original_hash(args: &FixArgs, zipfile: &Path) -> Result<Option<String>> {
+    args.print_hash
+        .filter(|_| args.print_hash_original)
+        .map(|algorithm| algorithm.line(zipfile))
+        .transpose()
+        .map_err(Into::into)
+}
+
+/// Set by Ctrl-C to stop fixing
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+
+/// Make Ctrl-C stop the fix after cleaning up instead of killing runzip
+/// with temporary files left behind; a second Ctrl-C quits at once
+fn handle_ctrl_c() {
+    #[cfg(not(target_family = "wasm"))]
+    if let Err(e) = ctrlc::set_handler(|| {
+        if CANCELLED.swap(true, Ordering::Relaxed) {
+            std::process::exit(130);
+        }
+        eprintln!("Cancelling, press Ctrl-C again to quit at once");
+    }) {
+        eprintln!("{}: cannot handle Ctrl-C: {e}", console::warning());
+    }
+}
+
+/// Exit with the status of a process stopped by Ctrl-C if it was pressed
+fn exit_if_cancelled() {
+    if CANCELLED.load(Ordering::Relaxed) {
+        eprintln!("Cancelled");
+        std::process::exit(130);
+    }
+}
+
+fn fix(args: &FixArgs) {
+    let rename_rules = or_exit(args.map.as_deref().map(RenameRules::load).transpose());
+    let applied = or_exit(args.apply.as_deref().map(diff::load_rules).transpose());
+    let files: Vec<(&PathBuf, Option<&RenameRules>)> = match &applied {
+        Some(applied) => applied
+            .iter()
+            .map(|(path, rules)| (path, Some(rules)))
+            .collect(),
+        None => args
+            .files
+            .iter()
+            .map(|path| (path, rename_rules.as_ref()))
+            .collect(),
+    };
+    if files.is_empty() {
+        eprintln!("{}: No ZIP files specified", console::error());
+        std::process::exit(1);
+    }
+
+    let config = load_config();
+    let mut report = Reports {
+        csv: or_exit(args.report.as_deref().map(CsvReport::create).transpose()),
+        diff: or_exit(args.diff.as_deref().map(DiffWriter::create).transpose()),
+        porcelain: args.porcelain.is_some(),
+    };
+
+    let mut journal = or_exit(args.journal.as_deref().map(Journal::open).transpose());
+
+    let mut options = fix_options(args, &config);
+    handle_ctrl_c();
+
+    let fail_fast = args.fail_fast || (!args.keep_going && config.fail_fast == Some(true));
+    let mut failed = Vec::new();
+    for &(zipfile, rules) in &files {
+        if CANCELLED.load(Ordering::Relaxed) {
+            break;
+        }
+        options.rename_rules = rules;
+        if journal
+            .as_ref()
+            .is_some_and(|journal| journal.is_completed(zipfile))
+        {
+            if !options.quiet {
+                println!(
+                    "{}: already fixed according to the journal",
+                    zipfile.display()
+                );
+            }
+            continue;
+        }
+        let result = original_hash(args, zipfile).and_then(|original| {
+            let fixed = fix_archive(zipfile, &options)?;
+            report.add(&fixed, options.dry_run)?;
+            if let Some(algorithm) = args.print_hash {
+                if let Some(line) = original {
+                    println!("Original {line}");
+                }
+                println!("{}", algorithm.line(zipfile)?);
+            }
+            journal.as_mut().map_or(Ok(()), |journal| {
+                journal.complete(zipfile).map_err(Into::into)
+            })
+        });
+        if result.is_err() {
+            or_exit(report.add_failure(zipfile));
+        }
+        if let Err(e) = result {
+            eprintln!(
+                "{} processing {}: {e:#}",
+                console::error(),
+                zipfile.display()
+            );
+            failed.push(zipfile);
+            if fail_fast {
+                break;
+            }
+        }
+    }
+
+    or_exit(report.finish());
+    exit_if_cancelled();
+
+    if let Some(journal) = journal
+        && failed.is_empty()
+        && let Err(e) = journal.remove()
+    {
+        eprintln!("{}: {:#}", console::error(), anyhow::Error::from(e));
+        std::process::exit(1);
+    }
+
+    exit_on_failures(&failed, files.len());
+}
@@ -0,0 +1,126 @@
+//! Comparison of the entry names of two archives (`runzip diff`)
+//!
+//! Entries are matched by their decoded names first, so an archive and its
+//! fixed version differ only in how the names are stored. Entries left over
+//! on both sides are matched by CRC-32 and size, which finds renames;
+//! whatever is left after that was removed or added.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::error::Result;
+use crate::{FixOptions, Hints, Host, open_archive, process_entry_name};
+
+/// An entry as compared
+struct Named {
+    /// Name decoded to UTF-8
+    name: String,
+    raw_name: Vec<u8>,
+    /// Encoding the name is stored in
+    encoding: &'static str,
+    crc32: u32,
+    size: u64,
+}
+
+/// A difference between two archives
+pub enum Change {
+    Removed(String),
+    Added(String),
+    /// The same data under another name
+    Renamed(String, String),
+    /// The same name with other data
+    Modified(String),
+    /// The same name stored in other bytes, in the encodings given
+    Reencoded(String, &'static str, &'static str),
+}
+
+impl Change {
+    /// Line of `runzip diff` output for the change
+    pub fn line(&self) -> String {
+        match self {
+            Change::Removed(name) => format!("removed     {name}"),
+            Change::Added(name) => format!("added       {name}"),
+            Change::Renamed(old, new) => format!("renamed     {old} -> {new}"),
+            Change::Modified(name) => format!("modified    {name}"),
+            Change::Reencoded(name, old, new) => format!("re-encoded  {name} ({old} -> {new})"),
+        }
+    }
+}
+
+/// Changes from the archive at `old` to the one at `new`: those of the
+/// entries of `old` in their order, then the entries added in `new`
+pub fn compare(old: &Path, new: &Path, options: &FixOptions) -> Result<Vec<Change>> {
+    let old = decoded_entries(old, options)?;
+    let new = decoded_entries(new, options)?;
+
+    let mut unmatched_new: HashMap<&str, usize> = new
+        .iter()
+        .enumerate()
+        .map(|(index, entry)| (entry.name.as_str(), index))
+        .collect();
+    let mut changes = Vec::new();
+    let mut unmatched_old = Vec::new();
+    for entry in &old {
+        let Some(index) = unmatched_new.remove(entry.name.as_str()) else {
+            unmatched_old.push(entry);
+            continue;
+        };
+        let other = &new[index];
+        if (entry.crc32, entry.size) != (other.crc32, other.size) {
+            changes.push(Change::Modified(entry.name.clone()));
+        } else if entry.raw_name != other.raw_name {
+            changes.push(Change::Reencoded(
+                entry.name.clone(),
+                entry.encoding,
+                other.encoding,
+            ));
+        }
+    }
+
+    let mut added: Vec<usize> = unmatched_new.into_values().collect();
+    added.sort_unstable();
+    for entry in unmatched_old {
+        let renamed = added.iter().position(|&index| {
+            let other = &new[index];
+            (entry.crc32, entry.size) == (other.crc32, other.size)
+                && entry.name.ends_with('/') == other.name.ends_with('/')
+        });
+        match renamed {
+            Some(position) => {
+                let index = added.remove(position);
+                changes.push(Change::Renamed(entry.name.clone(), new[index].name.clone()));
+            }
+            None => changes.push(Change::Removed(entry.name.clone())),
+        }
+    }
+    changes.extend(
+        added
+            .into_iter()
+            .map(|index| Change::Added(new[index].name.clone())),
+    );
+    Ok(changes)
+}
+
+/// The entries of the archive at `path` with their names decoded
+fn decoded_entries(path: &Path, options: &FixOptions) -> Result<Vec<Named>> {
+    let archive = open_archive(path, None)?;
+    Ok(archive
+        .entries()
+        .iter()
+        .map(|entry| {
+            let outcome = process_entry_name(
+                &entry.name,
+                entry.is_utf8(),
+                Hints::from_host(Host::from_version_made_by(entry.version_made_by)),
+                options,
+            );
+            Named {
+                name: outcome.display_name(),
+                raw_name: entry.name.clone(),
+                encoding: outcome.encoding.map_or("UTF-8", |encoding| encoding.name()),
+                crc32: entry.crc32,
+                size: entry.uncompressed_size,
+            }
+        })
+        .collect())
+}
@@ -0,0 +1,169 @@
+//! Transparent decompression and recompression of compressed tar archives
+//!
+//! The new stream mirrors what the original one records about its settings:
+//! the gzip header and level, the xz integrity check and the zstd checksum
+//! flag. Levels that are not recorded fall back to the compressor defaults.
+//!
+//! xz and zstd are C libraries, left out of WebAssembly builds.
+
+use std::fs::File;
+use std::io::{self, BufReader, Read, Write};
+use std::path::Path;
+#[cfg(not(target_family = "wasm"))]
+use xz2::stream::{Check, Stream};
+
+use crate::error::{Context, Result};
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const XZ_MAGIC: &[u8] = &[0xfd, b'7', b'z', b'X', b'Z', 0x00];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// Offsets of the xz check type and the zstd frame header descriptor
+#[cfg(not(target_family = "wasm"))]
+const XZ_CHECK_OFFSET: usize = 7;
+#[cfg(not(target_family = "wasm"))]
+const ZSTD_DESCRIPTOR_OFFSET: usize = 4;
+#[cfg(not(target_family = "wasm"))]
+const ZSTD_CHECKSUM_FLAG: u8 = 1 << 2;
+
+#[cfg(not(target_family = "wasm"))]
+const XZ_DEFAULT_PRESET: u32 = 6;
+
+/// Compressor of the outer stream of a file
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Xz,
+    Zstd,
+}
+
+/// The first bytes of `path`, enough to tell the compressor and its settings
+fn read_magic(path: &Path) -> Result<Vec<u8>> {
+    let file = File::open(path).context(format!("Failed to open {}", path.display()))?;
+    let mut magic = Vec::new();
+    file.take(16).read_to_end(&mut magic)?;
+    Ok(magic)
+}
+
+/// The compressor of `path`, if it is compressed with a supported one
+pub fn detect(path: &Path) -> Result<Option<Compression>> {
+    let magic = read_magic(path)?;
+    Ok(if magic.starts_with(GZIP_MAGIC) {
+        Some(Compression::Gzip)
+    } else if magic.starts_with(XZ_MAGIC) {
+        Some(Compression::Xz)
+    } else if magic.starts_with(ZSTD_MAGIC) {
+        Some(Compression::Zstd)
+    } else {
+        None
+    })
+}
+
+/// Open `path` for reading, decompressing it if needed
+pub fn open(path: &Path) -> Result<Box<dyn Read>> {
+    let compression = detect(path)?;
+    let file = File::open(path).context(format!("Failed to open {}", path.display()))?;
+    let reader = BufReader::new(file);
+    Ok(match compression {
+        None => Box::new(reader),
+        Some(Compression::Gzip) => Box::new(flate2::bufread::MultiGzDecoder::new(reader)),
+        #[cfg(not(target_family = "wasm"))]
+        Some(Compression::Xz) => Box::new(xz2::bufread::XzDecoder::new_multi_decoder(reader)),
+        #[cfg(not(target_family = "wasm"))]
+        Some(Compression::Zstd) => Box::new(zstd::Decoder::with_buffer(reader)?),
+        #[cfg(target_family = "wasm")]
+        Some(Compression::Xz | Compression::Zstd) => return Err(unsupported(path)),
+    })
+}
+
+/// Error for a compressor this build cannot handle
+#[cfg(target_family = "wasm")]
+fn unsupported(path: &Path) -> Error {
+    Error::other(format!(
+        "{} is compressed with xz or zstd, unsupported in this build",
+        path.display()
+    ))
+}
+
+/// Writer compressing like the original file did
+pub enum Encoder<W: Write> {
+    Plain(W),
+    Gzip(crate::gzip::MemberWriter<W>),
+    #[cfg(not(target_family = "wasm"))]
+    Xz(xz2::write::XzEncoder<W>),
+    #[cfg(not(target_family = "wasm"))]
+    Zstd(zstd::Encoder<'static, W>),
+}
+
+impl<W: Write> Encoder<W> {
+    /// Compress into `writer` with the compressor and settings of `path`
+    pub fn like(path: &Path, writer: W) -> Result<Self> {
+        Ok(match detect(path)? {
+            None => Encoder::Plain(writer),
+            Some(Compression::Gzip) => {
+                Encoder::Gzip(crate::gzip::MemberWriter::like(path, writer)?)
+            }
+            #[cfg(not(target_family = "wasm"))]
+            Some(Compression::Xz) => {
+                let magic = read_magic(path)?;
+                let check = match magic.get(XZ_CHECK_OFFSET).map(|flags| flags & 0x0f) {
+                    Some(0x00) => Check::None,
+                    Some(0x01) => Check::Crc32,
+                    Some(0x0a) => Check::Sha256,
+                    _ => Check::Crc64,
+                };
+                let stream = Stream::new_easy_encoder(XZ_DEFAULT_PRESET, check)
+                    .context("Failed to set up xz compression")?;
+                Encoder::Xz(xz2::write::XzEncoder::new_stream(writer, stream))
+            }
+            #[cfg(not(target_family = "wasm"))]
+            Some(Compression::Zstd) => {
+                let magic = read_magic(path)?;
+                let checksum = magic
+                    .get(ZSTD_DESCRIPTOR_OFFSET)
+                    .is_some_and(|descriptor| descriptor & ZSTD_CHECKSUM_FLAG != 0);
+                let mut encoder = zstd::Encoder::new(writer, zstd::DEFAULT_COMPRESSION_LEVEL)?;
+                encoder.include_checksum(checksum)?;
+                Encoder::Zstd(encoder)
+            }
+            #[cfg(target_family = "wasm")]
+            Some(Compression::Xz | Compression::Zstd) => return Err(unsupported(path)),
+        })
+    }
+
+    /// Finish the compressed stream, returning the underlying writer
+    pub fn finish(self) -> io::Result<W> {
+        match self {
+            Encoder::Plain(writer) => Ok(writer),
+            Encoder::Gzip(encoder) => encoder.finish(),
+            #[cfg(not(target_family = "wasm"))]
+            Encoder::Xz(encoder) => encoder.finish(),
+            #[cfg(not(target_family = "wasm"))]
+            Encoder::Zstd(encoder) => encoder.finish(),
+        }
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Encoder::Plain(writer) => writer.write(buf),
+            Encoder::Gzip(encoder) => encoder.write(buf),
+            #[cfg(not(target_family = "wasm"))]
+            Encoder::Xz(encoder) => encoder.write(buf),
+            #[cfg(not(target_family = "wasm"))]
+            Encoder::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Encoder::Plain(writer) => writer.flush(),
+            Encoder::Gzip(encoder) => encoder.flush(),
+            #[cfg(not(target_family = "wasm"))]
+            Encoder::Xz(encoder) => encoder.flush(),
+            #[cfg(not(target_family = "wasm"))]
+            Encoder::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
@@ -0,0 +1,108 @@
+//! Defaults read from the configuration file, overridden by command line
+//! options
+
+use crate::encoding::Encoding;
+use serde::Deserialize;
+use std::path::PathBuf;
+
+use crate::console::Color;
+use crate::error::{Context, Error, Result};
+use crate::{Backup, Lang, Target, Unencodable, string_to_encoding};
+
+/// Contents of `config.toml`, every setting optional
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct File {
+    source: Option<String>,
+    target: Option<String>,
+    on_unencodable: Option<String>,
+    lang: Option<String>,
+    candidates: Option<Vec<String>>,
+    fail_fast: Option<bool>,
+    backup: Option<String>,
+    color: Option<String>,
+}
+
+/// Settings from the configuration file
+#[derive(Default)]
+pub struct Config {
+    /// Source encoding instead of auto-detection (`--source`)
+    pub source: Option<&'static Encoding>,
+    /// Target form of the names (`--target`)
+    pub target: Option<Target>,
+    /// Handling of unencodable characters (`--on-unencodable`)
+    pub on_unencodable: Option<Unencodable>,
+    /// Language of the names (`--lang`)
+    pub lang: Option<Lang>,
+    /// Legacy encodings auto-detection may pick, in place of those of the
+    /// language; names detected as anything else are left alone
+    pub candidates: Option<Vec<&'static Encoding>>,
+    /// Stop at the first archive that fails (`--fail-fast`)
+    pub fail_fast: Option<bool>,
+    /// Copy of the original archives to keep (`--backup`)
+    pub backup: Option<Backup>,
+    /// When to color the labels of errors and warnings (`--color`)
+    pub color: Option<Color>,
+}
+
+/// Location of the configuration file: `$RUNZIP_CONFIG` if set, otherwise
+/// `runzip/config.toml` in the user configuration directory
+/// (`$XDG_CONFIG_HOME`, `~/.config` or `%APPDATA%`)
+pub fn path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("RUNZIP_CONFIG") {
+        return Some(PathBuf::from(path));
+    }
+    let dir = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))?;
+    Some(dir.join("runzip").join("config.toml"))
+}
+
+/// Read the configuration file; a missing file means no settings
+pub fn load() -> Result<Config> {
+    let Some(path) = path() else {
+        return Ok(Config::default());
+    };
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Config::default()),
+        Err(e) => return Err(e).context(format!("Failed to read {}", path.display())),
+    };
+    parse(&text).context(format!("Invalid configuration in {}", path.display()))
+}
+
+fn parse(text: &str) -> Result<Config> {
+    let file: File = toml::from_str(text)?;
+    Ok(Config {
+        source: match file.source.as_deref() {
+            None | Some("auto") => None,
+            Some(name) => Some(string_to_encoding(name)?),
+        },
+        target: value("target", file.target.as_deref())?,
+        on_unencodable: value("on-unencodable", file.on_unencodable.as_deref())?,
+        lang: value("lang", file.lang.as_deref())?,
+        candidates: file
+            .candidates
+            .map(|names| {
+                names
+                    .iter()
+                    .map(|name| string_to_encoding(name))
+                    .collect::<Result<_, _>>()
+            })
+            .transpose()?,
+        fail_fast: file.fail_fast,
+        backup: value("backup", file.backup.as_deref())?,
+        color: value("color", file.color.as_deref())?,
+    })
+}
+
+/// Parse `value` as one of the values of the command line option `key`
+pub fn value<T: clap::ValueEnum>(key: &str, value: Option<&str>) -> Result<Option<T>> {
+    value
+        .map(|value| {
+            T::from_str(value, false).map_err(|_| Error::other(format!("Invalid {key}: {value}")))
+        })
+        .transpose()
+}
@@ -0,0 +1,96 @@
+//! UTF-8 output on Windows consoles, and colored labels of errors and
+//! warnings
+//!
+//! Rust writes to a console window in UTF-16, so names print correctly there
+//! whatever the code page. Output piped to another console program
+//! (`runzip -n archive.zip | more`) stays UTF-8, though, and is decoded with
+//! the console output code page, the OEM one (CP866 on Russian systems) by
+//! default. runzip switches the console to UTF-8 while it runs.
+
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Switch the console output code page to UTF-8 until the process exits
+#[cfg(windows)]
+pub fn use_utf8() {
+    use std::sync::atomic::AtomicU32;
+
+    const CP_UTF8: u32 = 65001;
+    static PREVIOUS: AtomicU32 = AtomicU32::new(0);
+
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        fn GetConsoleOutputCP() -> u32;
+        fn SetConsoleOutputCP(code_page: u32) -> i32;
+    }
+    unsafe extern "C" {
+        fn atexit(callback: extern "C" fn()) -> i32;
+    }
+
+    // The code page outlives the process in the console, so it is switched
+    // back on exit, std::process::exit included
+    extern "C" fn restore() {
+        // SAFETY: plain Win32 call with a code page the console had
+        unsafe {
+            SetConsoleOutputCP(PREVIOUS.load(Ordering::Relaxed));
+        }
+    }
+
+    // SAFETY: plain Win32 and C runtime calls; GetConsoleOutputCP returns 0
+    // without a console
+    unsafe {
+        let previous = GetConsoleOutputCP();
+        if previous == 0 || previous == CP_UTF8 || SetConsoleOutputCP(CP_UTF8) == 0 {
+            return;
+        }
+        PREVIOUS.store(previous, Ordering::Relaxed);
+        atexit(restore);
+    }
+}
+
+/// Terminals elsewhere take the locale's encoding, UTF-8 nearly everywhere
+#[cfg(not(windows))]
+pub fn use_utf8() {}
+
+/// When to color the "Error" and "Warning" labels on stderr (`--color`)
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Color {
+    /// When stderr is a terminal on Unix and `NO_COLOR` is not set
+    Auto,
+    Always,
+    Never,
+}
+
+static COLORED: AtomicBool = AtomicBool::new(false);
+
+/// Color the labels from now on as `color` asks
+pub fn set_color(color: Color) {
+    let colored = match color {
+        Color::Always => true,
+        Color::Never => false,
+        Color::Auto => {
+            cfg!(unix)
+                && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+                && std::io::stderr().is_terminal()
+        }
+    };
+    COLORED.store(colored, Ordering::Relaxed);
+}
+
+/// Label of error messages, bold red when colored
+pub fn error() -> &'static str {
+    if COLORED.load(Ordering::Relaxed) {
+        "\x1b[1;31mError\x1b[0m"
+    } else {
+        "Error"
+    }
+}
+
+/// Label of warnings, bold yellow when colored
+pub fn warning() -> &'static str {
+    if COLORED.load(Ordering::Relaxed) {
+        "\x1b[1;33mWarning\x1b[0m"
+    } else {
+        "Warning"
+    }
+}
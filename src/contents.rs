@@ -0,0 +1,50 @@
+//! Conversion of the contents of text entries to UTF-8 (`--fix-contents`)
+
+use crate::encoding::Encoding;
+use chardetng::EncodingDetector;
+
+/// Whether an entry named `name` is a text file with one of `extensions`,
+/// given in lowercase without the dot
+pub fn is_text_entry(name: &[u8], extensions: &[String]) -> bool {
+    let name = name.to_ascii_lowercase();
+    extensions.iter().any(|extension| {
+        name.strip_suffix(extension.as_bytes())
+            .is_some_and(|stem| stem.ends_with(b"."))
+    })
+}
+
+/// Whether `text` is ASCII or UTF-8, possibly cut in the middle of a
+/// character
+pub fn is_utf8(text: &[u8]) -> bool {
+    match std::str::from_utf8(text) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none(),
+    }
+}
+
+/// Legacy encoding of `text`: `source` if given, otherwise the one detected
+/// if it is among the `candidates`; `None` for ASCII and UTF-8 text
+pub fn text_encoding(
+    text: &[u8],
+    source: Option<&'static Encoding>,
+    candidates: &[&'static Encoding],
+) -> Option<&'static Encoding> {
+    if is_utf8(text) {
+        return None;
+    }
+    if source.is_some() {
+        return source;
+    }
+    let mut detector = EncodingDetector::new();
+    detector.feed(text, true);
+    Encoding::for_standard(detector.guess(None, true))
+        .filter(|encoding| candidates.contains(encoding))
+}
+
+/// `text` in `encoding` converted to UTF-8, or `None` if it has bytes the
+/// encoding does not define
+pub fn to_utf8(text: &[u8], encoding: &'static Encoding) -> Option<Vec<u8>> {
+    encoding
+        .decode_without_bom_handling_and_without_replacement(text)
+        .map(|text| text.into_owned().into_bytes())
+}
@@ -0,0 +1,171 @@
+//! Collecting files and directories to pack into a new ZIP archive
+
+use std::fs::{self, Metadata};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::{Context, Error, Result};
+use crate::rawzip::Entry;
+
+/// Version 2.0, enough for deflate and directories
+const VERSION: u16 = 20;
+const HOST_MSDOS: u16 = 0;
+const HOST_UNIX: u16 = 3;
+
+const DOS_READ_ONLY: u32 = 0x01;
+const DOS_DIRECTORY: u32 = 0x10;
+
+/// A file or directory to pack
+pub struct Source {
+    pub path: PathBuf,
+    /// Path inside the archive, with `/` separators and a trailing `/` for
+    /// directories
+    pub name: String,
+    pub metadata: Metadata,
+}
+
+impl Source {
+    pub fn is_dir(&self) -> bool {
+        self.metadata.is_dir()
+    }
+}
+
+/// Collect `paths` and everything under them, each named after its last path
+/// component (the contents for `.`), directories before their contents
+pub fn collect(paths: &[PathBuf]) -> Result<Vec<Source>> {
+    let mut sources = Vec::new();
+    for path in paths {
+        let prefix = match path.file_name() {
+            Some(name) => utf8_name(path, name)?.to_string(),
+            None => String::new(),
+        };
+        let metadata = fs::metadata(path).context(format!("Failed to read {}", path.display()))?;
+        if prefix.is_empty() && metadata.is_dir() {
+            walk(path, "", &mut sources)?;
+        } else {
+            add(path, prefix, metadata, &mut sources)?;
+        }
+    }
+    Ok(sources)
+}
+
+fn add(path: &Path, name: String, metadata: Metadata, sources: &mut Vec<Source>) -> Result<()> {
+    let is_dir = metadata.is_dir();
+    let name = if is_dir { format!("{name}/") } else { name };
+    sources.push(Source {
+        path: path.to_path_buf(),
+        name: name.clone(),
+        metadata,
+    });
+    if is_dir {
+        walk(path, &name, sources)?;
+    }
+    Ok(())
+}
+
+/// Add the contents of `dir` in name order, prefixing their names with `prefix`
+fn walk(dir: &Path, prefix: &str, sources: &mut Vec<Source>) -> Result<()> {
+    let mut children = fs::read_dir(dir)
+        .context(format!("Failed to read {}", dir.display()))?
+        .map(|child| child.map(|child| child.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .context(format!("Failed to read {}", dir.display()))?;
+    children.sort();
+    for path in children {
+        let name = path
+            .file_name()
+            .map(|name| utf8_name(&path, name))
+            .transpose()?
+            .unwrap_or_default();
+        let metadata = fs::metadata(&path).context(format!("Failed to read {}", path.display()))?;
+        add(&path, format!("{prefix}{name}"), metadata, sources)?;
+    }
+    Ok(())
+}
+
+fn utf8_name<'a>(path: &Path, name: &'a std::ffi::OsStr) -> Result<&'a str> {
+    name.to_str()
+        .ok_or_else(|| Error::other(format!("The name of {} is not valid UTF-8", path.display())))
+}
+
+/// Central directory record for `source`, without name, sizes or CRC
+///
+/// Archives for DOS and Windows extractors (`dos_host`) get MS-DOS
+/// attributes, others Unix permissions where available.
+pub fn entry_template(source: &Source, dos_host: bool) -> Entry {
+    let (last_mod_time, last_mod_date) =
+        dos_date_time(source.metadata.modified().unwrap_or(UNIX_EPOCH));
+    let mut attributes = if source.is_dir() { DOS_DIRECTORY } else { 0 };
+    if source.metadata.permissions().readonly() {
+        attributes |= DOS_READ_ONLY;
+    }
+    let host = if cfg!(unix) && !dos_host {
+        HOST_UNIX
+    } else {
+        HOST_MSDOS
+    };
+    #[cfg(unix)]
+    if host == HOST_UNIX {
+        use std::os::unix::fs::PermissionsExt;
+        attributes |= source.metadata.permissions().mode() << 16;
+    }
+
+    Entry {
+        version_made_by: host << 8 | VERSION,
+        version_needed: if source.is_dir() { VERSION } else { 10 },
+        flags: 0,
+        compression_method: 0,
+        last_mod_time,
+        last_mod_date,
+        crc32: 0,
+        compressed_size: 0,
+        uncompressed_size: 0,
+        internal_attributes: 0,
+        external_attributes: attributes,
+        header_offset: 0,
+        name: Vec::new(),
+        extra: Vec::new(),
+        comment: Vec::new(),
+    }
+}
+
+/// MS-DOS time and date fields for `time` in UTC, clamped to the range they
+/// can hold (1980 to 2107)
+fn dos_date_time(time: SystemTime) -> (u16, u16) {
+    const DOS_EPOCH: u64 = 315_532_800; // 1980-01-01
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+        .max(DOS_EPOCH);
+    let days = seconds / 86_400;
+    let (year, month, day) = civil_from_days(days);
+    if year > 2107 {
+        return (0xBF7D, 0xFF9F); // 2107-12-31 23:59:58
+    }
+    let of_day = seconds % 86_400;
+    // Every value below fits its bit field
+    let field = |value: u64| u16::try_from(value).unwrap_or(0);
+    let time = field(of_day / 3600) << 11 | field(of_day % 3600 / 60) << 5 | field(of_day % 60 / 2);
+    let date = field(year - 1980) << 9 | field(month) << 5 | field(day);
+    (time, date)
+}
+
+/// Gregorian year, month and day of the day `days` after 1970-01-01
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    // Howard Hinnant's algorithm, with eras starting on March 1st
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
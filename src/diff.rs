@@ -0,0 +1,176 @@
+//! Rename listings in the style of a unified diff
+//!
+//! Every archive with renamed entries gets a `--- archive` / `+++ archive`
+//! header, followed by a `- old` and `+ new` line per renamed entry. The
+//! old name is the raw name as stored, so the listing can be applied to the
+//! same archive later exactly as reviewed. Names that are not valid UTF-8,
+//! or contain control characters or backslashes, have those bytes written
+//! as `\xNN` and backslashes as `\\`.
+
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::EntryResult;
+use crate::error::{Context, Error, Result};
+use crate::hex;
+use crate::renames::RenameRules;
+
+pub struct DiffWriter {
+    writer: BufWriter<File>,
+    /// Archive whose header was written last
+    archive: Option<PathBuf>,
+}
+
+impl DiffWriter {
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create diff {}", path.display()))?;
+        Ok(Self {
+            writer: BufWriter::new(file),
+            archive: None,
+        })
+    }
+
+    /// List the rename of `entry`, if it is renamed
+    pub fn add_entry(&mut self, entry: &EntryResult) -> Result<()> {
+        if entry.action != crate::Action::Fixed {
+            return Ok(());
+        }
+        if self.archive.as_ref() != Some(&entry.archive) {
+            let name = entry.archive.display();
+            writeln!(self.writer, "--- {name}\n+++ {name}").context("Failed to write diff")?;
+            self.archive = Some(entry.archive.clone());
+        }
+        writeln!(
+            self.writer,
+            "- {}\n+ {}",
+            escape(&entry.raw_name),
+            escape(entry.new_name.as_bytes())
+        )
+        .context("Failed to write diff")
+    }
+
+    /// Flush buffered lines to disk
+    pub fn finish(mut self) -> Result<()> {
+        self.writer.flush().context("Failed to write diff")
+    }
+}
+
+/// Renames listed for one archive: raw old names and new names
+pub struct ArchiveDiff {
+    pub archive: PathBuf,
+    pub renames: Vec<(Vec<u8>, String)>,
+}
+
+/// Read a listing written by [`DiffWriter`], in the order of the archives
+pub fn read(path: &Path) -> Result<Vec<ArchiveDiff>> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read diff {}", path.display()))?;
+    parse(&text).with_context(|| format!("Failed to parse diff {}", path.display()))
+}
+
+/// Rename rules for every archive in the listing at `path`, the renames of
+/// nested archives (`outer.zip/inner.zip`) merged into their outer archive
+pub fn load_rules(path: &Path) -> Result<Vec<(PathBuf, RenameRules)>> {
+    let mut archives: Vec<ArchiveDiff> = Vec::new();
+    for diff in read(path)? {
+        match archives
+            .iter_mut()
+            .find(|outer| diff.archive.starts_with(&outer.archive))
+        {
+            Some(outer) => outer.renames.extend(diff.renames),
+            None => archives.push(diff),
+        }
+    }
+    archives
+        .into_iter()
+        .map(|diff| {
+            let rules = RenameRules::from_renames(diff.renames)
+                .with_context(|| format!("Invalid renames for {}", diff.archive.display()))?;
+            Ok((diff.archive, rules))
+        })
+        .collect()
+}
+
+fn parse(text: &str) -> Result<Vec<ArchiveDiff>> {
+    let mut diffs: Vec<ArchiveDiff> = Vec::new();
+    let mut old_name = None;
+    for (number, line) in text.lines().enumerate().map(|(i, line)| (i + 1, line)) {
+        if let Some(archive) = line.strip_prefix("--- ") {
+            diffs.push(ArchiveDiff {
+                archive: PathBuf::from(archive),
+                renames: Vec::new(),
+            });
+        } else if line.starts_with("+++ ") || line.is_empty() {
+        } else if let Some(old) = line.strip_prefix("- ") {
+            if diffs.is_empty() {
+                return Err(Error::other(format!(
+                    "Line {number}: rename before the first archive"
+                )));
+            }
+            old_name = Some(unescape(old).with_context(|| format!("Line {number}"))?);
+        } else if let Some(new) = line.strip_prefix("+ ") {
+            let (Some(old), Some(diff)) = (old_name.take(), diffs.last_mut()) else {
+                return Err(Error::other(format!(
+                    "Line {number}: new name without an old one"
+                )));
+            };
+            let new = String::from_utf8(unescape(new).with_context(|| format!("Line {number}"))?)
+                .map_err(|_| {
+                Error::other(format!("Line {number}: new name is not valid UTF-8"))
+            })?;
+            diff.renames.push((old, new));
+        } else {
+            return Err(Error::other(format!(
+                "Line {number}: unexpected \"{line}\""
+            )));
+        }
+    }
+    Ok(diffs)
+}
+
+/// Printable, reversible form of a name
+pub fn escape(name: &[u8]) -> String {
+    if let Ok(text) = std::str::from_utf8(name)
+        && !text.chars().any(|c| c.is_control() || c == '\\')
+    {
+        return text.to_string();
+    }
+    name.iter().fold(String::new(), |mut out, &byte| {
+        match byte {
+            b'\\' => out.push_str("\\\\"),
+            b' ' => out.push(' '),
+            _ if byte.is_ascii_graphic() => out.push(char::from(byte)),
+            _ => {
+                let _ = write!(out, "\\x{byte:02x}");
+            }
+        }
+        out
+    })
+}
+
+/// Raw bytes of a name printed by [`escape`]
+fn unescape(text: &str) -> Result<Vec<u8>> {
+    if !text.contains('\\') {
+        return Ok(text.as_bytes().to_vec());
+    }
+    let mut name = Vec::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(backslash) = rest.find('\\') {
+        name.extend_from_slice(&rest.as_bytes()[..backslash]);
+        rest = &rest[backslash + 1..];
+        if let Some(after) = rest.strip_prefix('\\') {
+            name.push(b'\\');
+            rest = after;
+        } else if let Some(digits) = rest.strip_prefix('x').and_then(|r| r.get(..2)) {
+            name.extend(hex::decode(digits)?);
+            rest = &rest[3..];
+        } else {
+            return Err(Error::other(format!("Invalid escape in \"{text}\"")));
+        }
+    }
+    name.extend_from_slice(rest.as_bytes());
+    Ok(name)
+}
@@ -0,0 +1,360 @@
+//! Encodings of entry names
+//!
+//! `encoding_rs` has the encodings of the Web, which leaves out the DOS code
+//! pages archives were made with outside Russia, such as CP437, the one
+//! APPNOTE assigns to names without the EFS flag. [`Encoding`] wraps the
+//! `encoding_rs` ones and adds those as tables of their upper halves, behind
+//! the same methods.
+
+use std::borrow::Cow;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+/// A character encoding names can be in
+pub struct Encoding {
+    name: &'static str,
+    kind: Kind,
+}
+
+enum Kind {
+    Standard(&'static encoding_rs::Encoding),
+    /// A single-byte code page with ASCII in its lower half and these
+    /// characters in its upper half, `U+FFFD` for bytes it leaves undefined
+    Table(&'static [char; 128]),
+}
+
+pub static UTF_8: &Encoding = &UTF_8_INIT;
+pub static IBM866: &Encoding = &IBM866_INIT;
+pub static WINDOWS_1251: &Encoding = &WINDOWS_1251_INIT;
+pub static KOI8_R: &Encoding = &KOI8_R_INIT;
+pub static KOI8_U: &Encoding = &KOI8_U_INIT;
+pub static SHIFT_JIS: &Encoding = &SHIFT_JIS_INIT;
+pub static GBK: &Encoding = &GBK_INIT;
+pub static EUC_KR: &Encoding = &EUC_KR_INIT;
+pub static BIG5: &Encoding = &BIG5_INIT;
+/// The original IBM PC code page, with Western European letters
+pub static IBM437: &Encoding = &IBM437_INIT;
+pub static WINDOWS_1253: &Encoding = &WINDOWS_1253_INIT;
+pub static ISO_8859_7: &Encoding = &ISO_8859_7_INIT;
+pub static WINDOWS_1255: &Encoding = &WINDOWS_1255_INIT;
+pub static ISO_8859_8: &Encoding = &ISO_8859_8_INIT;
+pub static EUC_JP: &Encoding = &EUC_JP_INIT;
+/// The Hebrew DOS code page, CP437 with the Hebrew letters in place of the
+/// accented ones
+pub static IBM862: &Encoding = &IBM862_INIT;
+/// The Central European DOS code page (Latin-2), for Polish, Czech,
+/// Hungarian and the other Latin-script languages of the region
+pub static IBM852: &Encoding = &IBM852_INIT;
+/// The Western European DOS code page (Latin-1), CP437 with more accented
+/// letters in place of some box drawing and Greek characters
+pub static IBM850: &Encoding = &IBM850_INIT;
+/// The Ukrainian DOS code page (RUSCII), CP866 with the Ukrainian letters in
+/// place of `Є`, `Ї` and `Ў`
+pub static IBM1125: &Encoding = &IBM1125_INIT;
+/// The Cyrillic-Asian code page of Kazakh and other Central Asian languages,
+/// Windows-1251 with their letters in place of the Serbian and Macedonian
+/// ones and of some punctuation
+pub static PTCP154: &Encoding = &PTCP154_INIT;
+/// The Kazakh standard code page, Windows-1251 with the Kazakh letters in
+/// place of a few Serbian and Macedonian ones
+pub static KZ_1048: &Encoding = &KZ_1048_INIT;
+/// The Western European Windows code page, which UTF-8 names are misread in;
+/// names are not detected in it
+pub static WINDOWS_1252: &Encoding = &WINDOWS_1252_INIT;
+
+static UTF_8_INIT: Encoding = Encoding::standard("UTF-8", &encoding_rs::UTF_8_INIT);
+static IBM866_INIT: Encoding = Encoding::standard("IBM866", &encoding_rs::IBM866_INIT);
+static WINDOWS_1251_INIT: Encoding =
+    Encoding::standard("windows-1251", &encoding_rs::WINDOWS_1251_INIT);
+static KOI8_R_INIT: Encoding = Encoding::standard("KOI8-R", &encoding_rs::KOI8_R_INIT);
+static KOI8_U_INIT: Encoding = Encoding::standard("KOI8-U", &encoding_rs::KOI8_U_INIT);
+static SHIFT_JIS_INIT: Encoding = Encoding::standard("Shift_JIS", &encoding_rs::SHIFT_JIS_INIT);
+static GBK_INIT: Encoding = Encoding::standard("GBK", &encoding_rs::GBK_INIT);
+static EUC_KR_INIT: Encoding = Encoding::standard("EUC-KR", &encoding_rs::EUC_KR_INIT);
+static BIG5_INIT: Encoding = Encoding::standard("Big5", &encoding_rs::BIG5_INIT);
+static WINDOWS_1253_INIT: Encoding =
+    Encoding::standard("windows-1253", &encoding_rs::WINDOWS_1253_INIT);
+static ISO_8859_7_INIT: Encoding = Encoding::standard("ISO-8859-7", &encoding_rs::ISO_8859_7_INIT);
+static WINDOWS_1255_INIT: Encoding =
+    Encoding::standard("windows-1255", &encoding_rs::WINDOWS_1255_INIT);
+static ISO_8859_8_INIT: Encoding = Encoding::standard("ISO-8859-8", &encoding_rs::ISO_8859_8_INIT);
+static EUC_JP_INIT: Encoding = Encoding::standard("EUC-JP", &encoding_rs::EUC_JP_INIT);
+static WINDOWS_1252_INIT: Encoding =
+    Encoding::standard("windows-1252", &encoding_rs::WINDOWS_1252_INIT);
+static IBM437_INIT: Encoding = Encoding {
+    name: "IBM437",
+    kind: Kind::Table(&CP437),
+};
+static IBM862_INIT: Encoding = Encoding {
+    name: "IBM862",
+    kind: Kind::Table(&CP862),
+};
+static IBM852_INIT: Encoding = Encoding {
+    name: "IBM852",
+    kind: Kind::Table(&CP852),
+};
+static IBM850_INIT: Encoding = Encoding {
+    name: "IBM850",
+    kind: Kind::Table(&CP850),
+};
+static IBM1125_INIT: Encoding = Encoding {
+    name: "IBM1125",
+    kind: Kind::Table(&CP1125),
+};
+static PTCP154_INIT: Encoding = Encoding {
+    name: "PTCP154",
+    kind: Kind::Table(&PT154),
+};
+static KZ_1048_INIT: Encoding = Encoding {
+    name: "KZ-1048",
+    kind: Kind::Table(&KZ1048),
+};
+
+/// Every encoding wrapping an `encoding_rs` one names are detected in
+static STANDARD: [&Encoding; 14] = [
+    &UTF_8_INIT,
+    &IBM866_INIT,
+    &WINDOWS_1251_INIT,
+    &KOI8_R_INIT,
+    &KOI8_U_INIT,
+    &SHIFT_JIS_INIT,
+    &GBK_INIT,
+    &EUC_KR_INIT,
+    &BIG5_INIT,
+    &WINDOWS_1253_INIT,
+    &ISO_8859_7_INIT,
+    &WINDOWS_1255_INIT,
+    &ISO_8859_8_INIT,
+    &EUC_JP_INIT,
+];
+
+#[rustfmt::skip]
+static CP437: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å',
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ',
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»',
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐',
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧',
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀',
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩',
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{a0}',
+];
+
+#[rustfmt::skip]
+static CP862: [char; 128] = [
+    'א', 'ב', 'ג', 'ד', 'ה', 'ו', 'ז', 'ח', 'ט', 'י', 'ך', 'כ', 'ל', 'ם', 'מ', 'ן',
+    'נ', 'ס', 'ע', 'ף', 'פ', 'ץ', 'צ', 'ק', 'ר', 'ש', 'ת', '¢', '£', '¥', '₧', 'ƒ',
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»',
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐',
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧',
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀',
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩',
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{a0}',
+];
+
+#[rustfmt::skip]
+static CP852: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'ů', 'ć', 'ç', 'ł', 'ë', 'Ő', 'ő', 'î', 'Ź', 'Ä', 'Ć',
+    'É', 'Ĺ', 'ĺ', 'ô', 'ö', 'Ľ', 'ľ', 'Ś', 'ś', 'Ö', 'Ü', 'Ť', 'ť', 'Ł', '×', 'č',
+    'á', 'í', 'ó', 'ú', 'Ą', 'ą', 'Ž', 'ž', 'Ę', 'ę', '¬', 'ź', 'Č', 'ş', '«', '»',
+    '░', '▒', '▓', '│', '┤', 'Á', 'Â', 'Ě', 'Ş', '╣', '║', '╗', '╝', 'Ż', 'ż', '┐',
+    '└', '┴', '┬', '├', '─', '┼', 'Ă', 'ă', '╚', '╔', '╩', '╦', '╠', '═', '╬', '¤',
+    'đ', 'Đ', 'Ď', 'Ë', 'ď', 'Ň', 'Í', 'Î', 'ě', '┘', '┌', '█', '▄', 'Ţ', 'Ů', '▀',
+    'Ó', 'ß', 'Ô', 'Ń', 'ń', 'ň', 'Š', 'š', 'Ŕ', 'Ú', 'ŕ', 'Ű', 'ý', 'Ý', 'ţ', '´',
+    '\u{ad}', '˝', '˛', 'ˇ', '˘', '§', '÷', '¸', '°', '¨', '˙', 'ű', 'Ř', 'ř', '■', '\u{a0}',
+];
+
+#[rustfmt::skip]
+static CP850: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å',
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', 'ø', '£', 'Ø', '×', 'ƒ',
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '®', '¬', '½', '¼', '¡', '«', '»',
+    '░', '▒', '▓', '│', '┤', 'Á', 'Â', 'À', '©', '╣', '║', '╗', '╝', '¢', '¥', '┐',
+    '└', '┴', '┬', '├', '─', '┼', 'ã', 'Ã', '╚', '╔', '╩', '╦', '╠', '═', '╬', '¤',
+    'ð', 'Ð', 'Ê', 'Ë', 'È', 'ı', 'Í', 'Î', 'Ï', '┘', '┌', '█', '▄', '¦', 'Ì', '▀',
+    'Ó', 'ß', 'Ô', 'Ò', 'õ', 'Õ', 'µ', 'þ', 'Þ', 'Ú', 'Û', 'Ù', 'ý', 'Ý', '¯', '´',
+    '\u{ad}', '±', '‗', '¾', '¶', '§', '÷', '¸', '°', '¨', '·', '¹', '³', '²', '■', '\u{a0}',
+];
+
+#[rustfmt::skip]
+static CP1125: [char; 128] = [
+    'А', 'Б', 'В', 'Г', 'Д', 'Е', 'Ж', 'З', 'И', 'Й', 'К', 'Л', 'М', 'Н', 'О', 'П',
+    'Р', 'С', 'Т', 'У', 'Ф', 'Х', 'Ц', 'Ч', 'Ш', 'Щ', 'Ъ', 'Ы', 'Ь', 'Э', 'Ю', 'Я',
+    'а', 'б', 'в', 'г', 'д', 'е', 'ж', 'з', 'и', 'й', 'к', 'л', 'м', 'н', 'о', 'п',
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐',
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧',
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀',
+    'р', 'с', 'т', 'у', 'ф', 'х', 'ц', 'ч', 'ш', 'щ', 'ъ', 'ы', 'ь', 'э', 'ю', 'я',
+    'Ё', 'ё', 'Ґ', 'ґ', 'Є', 'є', 'І', 'і', 'Ї', 'ї', '·', '√', '№', '¤', '■', '\u{a0}',
+];
+
+#[rustfmt::skip]
+static PT154: [char; 128] = [
+    'Җ', 'Ғ', 'Ӯ', 'ғ', '„', '…', 'Ҷ', 'Ү', 'Ҳ', 'ү', 'Ҡ', 'Ӣ', 'Ң', 'Қ', 'Һ', 'Ҹ',
+    'җ', '‘', '’', '“', '”', '•', '–', '—', 'ҳ', 'ҷ', 'ҡ', 'ӣ', 'ң', 'қ', 'һ', 'ҹ',
+    '\u{a0}', 'Ў', 'ў', 'Ј', 'Ө', 'Ҙ', 'Ұ', '§', 'Ё', '©', 'Ә', '«', '¬', 'ӯ', '®', 'Ҝ',
+    '°', 'ұ', 'І', 'і', 'ҙ', 'ө', '¶', '·', 'ё', '№', 'ә', '»', 'ј', 'Ҫ', 'ҫ', 'ҝ',
+    'А', 'Б', 'В', 'Г', 'Д', 'Е', 'Ж', 'З', 'И', 'Й', 'К', 'Л', 'М', 'Н', 'О', 'П',
+    'Р', 'С', 'Т', 'У', 'Ф', 'Х', 'Ц', 'Ч', 'Ш', 'Щ', 'Ъ', 'Ы', 'Ь', 'Э', 'Ю', 'Я',
+    'а', 'б', 'в', 'г', 'д', 'е', 'ж', 'з', 'и', 'й', 'к', 'л', 'м', 'н', 'о', 'п',
+    'р', 'с', 'т', 'у', 'ф', 'х', 'ц', 'ч', 'ш', 'щ', 'ъ', 'ы', 'ь', 'э', 'ю', 'я',
+];
+
+#[rustfmt::skip]
+static KZ1048: [char; 128] = [
+    'Ђ', 'Ѓ', '‚', 'ѓ', '„', '…', '†', '‡', '€', '‰', 'Љ', '‹', 'Њ', 'Қ', 'Һ', 'Џ',
+    'ђ', '‘', '’', '“', '”', '•', '–', '—', '\u{fffd}', '™', 'љ', '›', 'њ', 'қ', 'һ', 'џ',
+    '\u{a0}', 'Ұ', 'ұ', 'Ә', '¤', 'Ө', '¦', '§', 'Ё', '©', 'Ғ', '«', '¬', '\u{ad}', '®', 'Ү',
+    '°', '±', 'І', 'і', 'ө', 'µ', '¶', '·', 'ё', '№', 'ғ', '»', 'ә', 'Ң', 'ң', 'ү',
+    'А', 'Б', 'В', 'Г', 'Д', 'Е', 'Ж', 'З', 'И', 'Й', 'К', 'Л', 'М', 'Н', 'О', 'П',
+    'Р', 'С', 'Т', 'У', 'Ф', 'Х', 'Ц', 'Ч', 'Ш', 'Щ', 'Ъ', 'Ы', 'Ь', 'Э', 'Ю', 'Я',
+    'а', 'б', 'в', 'г', 'д', 'е', 'ж', 'з', 'и', 'й', 'к', 'л', 'м', 'н', 'о', 'п',
+    'р', 'с', 'т', 'у', 'ф', 'х', 'ц', 'ч', 'ш', 'щ', 'ъ', 'ы', 'ь', 'э', 'ю', 'я',
+];
+
+impl Encoding {
+    const fn standard(name: &'static str, encoding: &'static encoding_rs::Encoding) -> Self {
+        Encoding {
+            name,
+            kind: Kind::Standard(encoding),
+        }
+    }
+
+    /// The encoding wrapping `encoding`, if names can be in it
+    #[must_use]
+    pub fn for_standard(encoding: &'static encoding_rs::Encoding) -> Option<&'static Encoding> {
+        STANDARD
+            .into_iter()
+            .find(|candidate| matches!(candidate.kind, Kind::Standard(e) if e == encoding))
+    }
+
+    /// Whether the encoding wraps an `encoding_rs` one, which chardetng can
+    /// guess, rather than being a code page it knows nothing of
+    #[must_use]
+    pub fn is_standard(&self) -> bool {
+        matches!(self.kind, Kind::Standard(_))
+    }
+
+    /// Name of the encoding, as `encoding_rs` spells it for its encodings
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Whether every character is a single byte
+    #[must_use]
+    pub fn is_single_byte(&self) -> bool {
+        match self.kind {
+            Kind::Standard(encoding) => encoding.is_single_byte(),
+            Kind::Table(_) => true,
+        }
+    }
+
+    /// Decode `bytes`, sniffing a BOM, with replacement characters for
+    /// malformed sequences; the flag tells whether there were any
+    #[must_use]
+    pub fn decode<'a>(&self, bytes: &'a [u8]) -> (Cow<'a, str>, bool) {
+        match self.kind {
+            Kind::Standard(encoding) => {
+                let (text, _, had_errors) = encoding.decode(bytes);
+                (text, had_errors)
+            }
+            Kind::Table(_) => self.decode_without_bom_handling(bytes),
+        }
+    }
+
+    /// Decode `bytes` with replacement characters for malformed sequences;
+    /// the flag tells whether there were any
+    #[must_use]
+    pub fn decode_without_bom_handling<'a>(&self, bytes: &'a [u8]) -> (Cow<'a, str>, bool) {
+        match self.kind {
+            Kind::Standard(encoding) => encoding.decode_without_bom_handling(bytes),
+            Kind::Table(table) => match std::str::from_utf8(bytes) {
+                Ok(text) if bytes.is_ascii() => (Cow::Borrowed(text), false),
+                _ => {
+                    let text: String = bytes
+                        .iter()
+                        .map(|&byte| match byte {
+                            0..0x80 => char::from(byte),
+                            _ => table[usize::from(byte - 0x80)],
+                        })
+                        .collect();
+                    let had_errors = text.contains(char::REPLACEMENT_CHARACTER);
+                    (Cow::Owned(text), had_errors)
+                }
+            },
+        }
+    }
+
+    /// Decode `bytes`, or `None` if they are malformed
+    #[must_use]
+    pub fn decode_without_bom_handling_and_without_replacement<'a>(
+        &self,
+        bytes: &'a [u8],
+    ) -> Option<Cow<'a, str>> {
+        match self.kind {
+            Kind::Standard(encoding) => {
+                encoding.decode_without_bom_handling_and_without_replacement(bytes)
+            }
+            Kind::Table(_) => match self.decode_without_bom_handling(bytes) {
+                (text, false) => Some(text),
+                (_, true) => None,
+            },
+        }
+    }
+
+    /// Encode `text`, with HTML numeric character references for the
+    /// characters the encoding cannot represent; the flag tells whether
+    /// there were any
+    #[must_use]
+    pub fn encode<'a>(&self, text: &'a str) -> (Cow<'a, [u8]>, bool) {
+        match self.kind {
+            Kind::Standard(encoding) => {
+                let (bytes, _, had_errors) = encoding.encode(text);
+                (bytes, had_errors)
+            }
+            Kind::Table(table) => {
+                if text.is_ascii() {
+                    return (Cow::Borrowed(text.as_bytes()), false);
+                }
+                let mut bytes = Vec::with_capacity(text.len());
+                let mut had_errors = false;
+                for c in text.chars() {
+                    let byte = u8::try_from(c).ok().filter(u8::is_ascii).or_else(|| {
+                        (0x80..=0xFF)
+                            .zip(table)
+                            .find(|(_, entry)| **entry == c && c != char::REPLACEMENT_CHARACTER)
+                            .map(|(byte, _)| byte)
+                    });
+                    if let Some(byte) = byte {
+                        bytes.push(byte);
+                    } else {
+                        bytes.extend_from_slice(format!("&#{};", u32::from(c)).as_bytes());
+                        had_errors = true;
+                    }
+                }
+                (Cow::Owned(bytes), had_errors)
+            }
+        }
+    }
+}
+
+impl PartialEq for Encoding {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+impl Eq for Encoding {}
+
+impl Hash for Encoding {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+    }
+}
+
+impl fmt::Debug for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Encoding {{ {} }}", self.name)
+    }
+}
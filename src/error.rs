@@ -0,0 +1,276 @@
+//! Errors of the library, by kind of failure
+//!
+//! A failure gets its kind where it happens: a file that cannot be read or
+//! written makes an [`Error::Io`], a damaged ZIP archive an
+//! [`Error::ZipFormat`]. What it was doing at the time is added on the way
+//! up with [`Context`], keeping the kind and the error it wraps as its
+//! `source()`.
+
+use std::fmt;
+
+use crate::encoding::Encoding;
+
+/// What went wrong underneath an [`Error`], with its own causes as its
+/// `source()`
+pub type Cause = Box<dyn std::error::Error + Send + Sync>;
+
+pub(crate) type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Why an archive could not be fixed
+///
+/// An error displays as the message runzip prints; the errors that caused it
+/// are its `source()`, so `{:#}` of an `anyhow::Error` made from it, or
+/// walking the sources, gives the whole story.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// Reading or writing a file failed
+    #[error("{message}")]
+    Io {
+        kind: std::io::ErrorKind,
+        message: String,
+        #[source]
+        source: Option<Cause>,
+    },
+    /// The archive is damaged or not a ZIP archive
+    #[error("{message}")]
+    ZipFormat {
+        message: String,
+        #[source]
+        source: Option<Cause>,
+    },
+    /// An encoding name runzip does not know
+    #[error("Unsupported encoding: {0}")]
+    UnsupportedEncoding(String),
+    /// With `FixOptionsBuilder::strict`, detection could not tell the encoding of
+    /// a name for sure: `confidence` (see [`crate::confidence`]) is below
+    /// [`crate::AMBIGUITY_MARGIN`]
+    #[error("\"{name}\" may not be {} (confidence {confidence:.2})", encoding.name())]
+    DetectionAmbiguous {
+        /// The name as converted from the detected encoding
+        name: String,
+        encoding: &'static Encoding,
+        confidence: f64,
+    },
+    /// With `FixOptionsBuilder::strict`, the target encoding cannot represent a
+    /// name
+    #[error("Cannot encode \"{name}\" in {}", encoding.name())]
+    UnencodableName {
+        /// The name decoded from the archive
+        name: String,
+        encoding: &'static Encoding,
+    },
+    /// With `FixOptionsBuilder::strict`, a name could not be converted
+    #[error("{0}")]
+    Unconverted(String),
+    /// The `AtomicBool` given to `FixOptionsBuilder::cancel` was set
+    #[error("Cancelled")]
+    Cancelled,
+    /// Any other failure, such as a new archive that does not verify
+    #[error("{message}")]
+    Other {
+        message: String,
+        #[source]
+        source: Option<Cause>,
+    },
+}
+
+impl Error {
+    /// A failure of no particular kind
+    pub(crate) fn other(message: impl Into<String>) -> Self {
+        Error::Other {
+            message: message.into(),
+            source: None,
+        }
+    }
+
+    /// A damaged ZIP archive
+    pub(crate) fn format(message: impl Into<String>) -> Self {
+        Error::ZipFormat {
+            message: message.into(),
+            source: None,
+        }
+    }
+
+    /// This error as the cause of `message`, which keeps its kind if it is
+    /// an I/O or format error; a cancelled fix stays just that
+    pub(crate) fn context(self, message: impl fmt::Display) -> Self {
+        let message = message.to_string();
+        match self {
+            Error::Io { kind, .. } => Error::Io {
+                kind,
+                message,
+                source: Some(Box::new(self)),
+            },
+            Error::ZipFormat { .. } => Error::ZipFormat {
+                message,
+                source: Some(Box::new(self)),
+            },
+            Error::Cancelled => self,
+            _ => Error::Other {
+                message,
+                source: Some(Box::new(self)),
+            },
+        }
+    }
+
+    /// This error and the errors behind it, separated like `{:#}` of an
+    /// `anyhow::Error`
+    pub(crate) fn chain(&self) -> String {
+        let mut text = self.to_string();
+        let mut source = std::error::Error::source(self);
+        while let Some(cause) = source {
+            text.push_str(": ");
+            text.push_str(&cause.to_string());
+            source = cause.source();
+        }
+        text
+    }
+}
+
+/// An error of another crate, which becomes an [`Error`] of its kind
+pub(crate) trait IntoError: Sized {
+    /// The error as the cause of `message`
+    fn context(self, message: String) -> Error;
+}
+
+impl IntoError for Error {
+    fn context(self, message: String) -> Error {
+        Error::context(self, message)
+    }
+}
+
+impl IntoError for std::io::Error {
+    fn context(self, message: String) -> Error {
+        // Errors such as `Cancelled` get out of a writer inside an I/O error
+        match self.downcast::<Error>() {
+            Ok(error) => error.context(message),
+            Err(error) => Error::Io {
+                kind: error.kind(),
+                message,
+                source: Some(Box::new(error)),
+            },
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        match error.downcast::<Error>() {
+            Ok(error) => error,
+            Err(error) => Error::Io {
+                kind: error.kind(),
+                message: error.to_string(),
+                source: None,
+            },
+        }
+    }
+}
+
+impl IntoError for zip::result::ZipError {
+    fn context(self, message: String) -> Error {
+        match self {
+            zip::result::ZipError::Io(error) => IntoError::context(error, message),
+            _ => Error::ZipFormat {
+                message,
+                source: Some(Box::new(self)),
+            },
+        }
+    }
+}
+
+impl From<zip::result::ZipError> for Error {
+    fn from(error: zip::result::ZipError) -> Self {
+        match error {
+            zip::result::ZipError::Io(error) => error.into(),
+            _ => Error::format(error.to_string()),
+        }
+    }
+}
+
+/// Errors of other crates that are no failure of a particular kind
+macro_rules! other_errors {
+    ($($error:ty),* $(,)?) => {$(
+        impl IntoError for $error {
+            fn context(self, message: String) -> Error {
+                Error::Other {
+                    message,
+                    source: Some(Box::new(self)),
+                }
+            }
+        }
+
+        impl From<$error> for Error {
+            fn from(error: $error) -> Self {
+                Error::other(error.to_string())
+            }
+        }
+    )*};
+}
+
+other_errors!(
+    fmt::Error,
+    serde_json::Error,
+    serde_yaml::Error,
+    std::array::TryFromSliceError,
+    std::num::TryFromIntError,
+    std::str::Utf8Error,
+    std::string::FromUtf8Error,
+    tempfile::PersistError,
+    toml::de::Error,
+);
+
+#[cfg(not(target_family = "wasm"))]
+other_errors!(xz2::stream::Error);
+
+#[cfg(feature = "async")]
+other_errors!(tokio::task::JoinError);
+
+#[cfg(all(feature = "cli", not(target_family = "wasm")))]
+other_errors!(ctrlc::Error);
+
+#[cfg(feature = "sevenz")]
+impl IntoError for sevenz_rust::Error {
+    fn context(self, message: String) -> Error {
+        match &self {
+            sevenz_rust::Error::Io(error, _) | sevenz_rust::Error::FileOpen(error, _) => {
+                Error::Io {
+                    kind: error.kind(),
+                    message,
+                    source: Some(Box::new(self)),
+                }
+            }
+            _ => Error::Other {
+                message,
+                source: Some(Box::new(self)),
+            },
+        }
+    }
+}
+
+/// Adding what was being done to a failure, like `anyhow::Context`
+pub(crate) trait Context<T> {
+    fn context(self, message: impl fmt::Display) -> Result<T>;
+
+    fn with_context<M: fmt::Display>(self, message: impl FnOnce() -> M) -> Result<T>;
+}
+
+impl<T, E: IntoError> Context<T> for Result<T, E> {
+    fn context(self, message: impl fmt::Display) -> Result<T> {
+        self.map_err(|error| error.context(message.to_string()))
+    }
+
+    fn with_context<M: fmt::Display>(self, message: impl FnOnce() -> M) -> Result<T> {
+        self.map_err(|error| error.context(message().to_string()))
+    }
+}
+
+impl<T> Context<T> for Option<T> {
+    fn context(self, message: impl fmt::Display) -> Result<T> {
+        self.ok_or_else(|| Error::other(message.to_string()))
+    }
+
+    fn with_context<M: fmt::Display>(self, message: impl FnOnce() -> M) -> Result<T> {
+        self.ok_or_else(|| Error::other(message().to_string()))
+    }
+}
@@ -0,0 +1,79 @@
+//! Hooks for programs following a fix (`FixOptionsBuilder::events`)
+
+use std::io::{Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::Error;
+
+/// Told about the progress of a fix, for GUIs and services that show it
+/// without parsing the output; every method does nothing by default
+pub trait Events: Sync {
+    /// The entry at `index` of the archive at `path`, named `name` as
+    /// stored, is about to be looked at
+    fn on_entry_start(&self, _path: &Path, _index: usize, _name: &[u8]) {}
+
+    /// The entry at `index` of the archive at `path` gets the name
+    /// `new_name`; called when the names are planned, so in dry runs too
+    fn on_entry_renamed(&self, _path: &Path, _index: usize, _old_name: &[u8], _new_name: &str) {}
+
+    /// `bytes` bytes of the new archive have been written so far
+    fn on_progress(&self, _bytes: u64) {}
+
+    /// A problem with the archive at `path` that does not stop the fix,
+    /// such as entries with unsafe paths or names left unchanged; `message`
+    /// may span several lines
+    fn on_warning(&self, _path: &Path, _message: &str) {}
+
+    /// A line of what the fix does, such as the outcome of an entry, or with
+    /// verbose output how a name was detected; the command line tool prints
+    /// these, unless `quiet` leaves them out
+    fn on_message(&self, _message: &str) {}
+}
+
+/// Writer telling [`Events::on_progress`] how much went through it, and
+/// failing with [`Error::Cancelled`] once `cancel` is set
+pub struct ProgressWriter<'a, W> {
+    inner: W,
+    written: u64,
+    events: Option<&'a dyn Events>,
+    cancel: Option<&'a AtomicBool>,
+}
+
+impl<'a, W> ProgressWriter<'a, W> {
+    pub fn new(inner: W, events: Option<&'a dyn Events>, cancel: Option<&'a AtomicBool>) -> Self {
+        ProgressWriter {
+            inner,
+            written: 0,
+            events,
+            cancel,
+        }
+    }
+}
+
+impl<W: Write> Write for ProgressWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self
+            .cancel
+            .is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+        {
+            return Err(std::io::Error::other(Error::Cancelled));
+        }
+        let written = self.inner.write(buf)?;
+        self.written += written as u64;
+        if let Some(events) = self.events {
+            events.on_progress(self.written);
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Seek> Seek for ProgressWriter<'_, W> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
@@ -0,0 +1,89 @@
+//! Read-only archive formats and writing their entries to disk
+
+use std::fs::{self, File};
+use std::io::{Read, Seek, Write};
+use std::path::Path;
+
+use crate::Host;
+use crate::error::{Context, Error, Result};
+use crate::rawzip::RawArchive;
+
+/// An entry of an archive that can be listed and extracted but not rewritten
+pub struct Entry {
+    /// Full path, with `/` separators and a trailing `/` for directories
+    pub name: Vec<u8>,
+    /// Whether the name is known to be UTF-8
+    pub unicode: bool,
+    pub is_dir: bool,
+    /// System the archive was made on, where recorded
+    pub host: Option<Host>,
+    /// Size of the contents once unpacked
+    pub size: u64,
+    /// Size of the contents as stored in the archive
+    pub compressed_size: u64,
+}
+
+/// An archive format that can be listed and extracted but not rewritten,
+/// and read from another thread by `runzip mount`
+pub trait ReadOnlyArchive: Send {
+    fn entries(&self) -> &[Entry];
+
+    /// Write the contents of entry `index` to `writer`
+    fn extract_entry(&mut self, index: usize, writer: &mut dyn Write) -> Result<()>;
+}
+
+/// A ZIP archive opened for extraction only
+pub struct Zip<R> {
+    archive: RawArchive<R>,
+    entries: Vec<Entry>,
+}
+
+impl<R: Read + Seek> Zip<R> {
+    pub fn new(archive: RawArchive<R>) -> Self {
+        let entries = archive
+            .entries()
+            .iter()
+            .map(|entry| Entry {
+                name: entry.name.clone(),
+                unicode: entry.is_utf8(),
+                is_dir: entry.name.ends_with(b"/"),
+                host: Host::from_version_made_by(entry.version_made_by),
+                size: entry.uncompressed_size,
+                compressed_size: entry.compressed_size,
+            })
+            .collect();
+        Zip { archive, entries }
+    }
+}
+
+impl<R: Read + Seek + Send> ReadOnlyArchive for Zip<R> {
+    fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    fn extract_entry(&mut self, index: usize, writer: &mut dyn Write) -> Result<()> {
+        if self.archive.write_entry_data(index, writer)? {
+            Ok(())
+        } else {
+            Err(Error::other(
+                "Encrypted entries and compression methods other than store and deflate are not supported",
+            ))
+        }
+    }
+}
+
+/// Create the directory `name` (relative, with `/` separators) under `dest`
+pub fn create_dir(dest: &Path, name: &str) -> Result<()> {
+    let path = dest.join(name);
+    fs::create_dir_all(&path).context(format!("Failed to create {}", path.display()))
+}
+
+/// Create the file `name` (relative, with `/` separators) under `dest`,
+/// refusing to overwrite existing files
+pub fn create_file(dest: &Path, name: &str) -> Result<File> {
+    let path = dest.join(name);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).context(format!("Failed to create {}", parent.display()))?;
+    }
+    File::create_new(&path).context(format!("Failed to create {}", path.display()))
+}
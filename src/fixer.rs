@@ -0,0 +1,63 @@
+//! A configured fixer for programs fixing many archives, such as servers
+
+use std::path::Path;
+
+use crate::{
+    Candidate, Error, FixOptions, FixReport, Hints, detect_name_encoding, fix_archive,
+    fix_archive_bytes, rank_candidates, unexplained,
+};
+
+/// Options fixed once and shared by every archive fixed with them
+///
+/// The fixer is `Send + Sync`: one instance behind an `Arc` or a `static`
+/// serves every request-handling thread. Its options cannot change, and
+/// only the encodings detected for names are shared, so a name is
+/// detected once for all the archives it appears in.
+pub struct ArchiveFixer {
+    options: FixOptions<'static>,
+}
+
+impl ArchiveFixer {
+    #[must_use]
+    pub fn new(options: FixOptions<'static>) -> Self {
+        ArchiveFixer { options }
+    }
+
+    #[must_use]
+    pub fn options(&self) -> &FixOptions<'static> {
+        &self.options
+    }
+
+    /// Fix the archive at `path` like [`fix_archive`]
+    ///
+    /// # Errors
+    ///
+    /// Fails as [`fix_archive`] does, changing nothing then.
+    pub fn fix(&self, path: &Path) -> Result<FixReport, Error> {
+        fix_archive(path, &self.options)
+    }
+
+    /// Fix a ZIP archive held in memory like [`fix_archive_bytes`]
+    ///
+    /// # Errors
+    ///
+    /// Fails as [`fix_archive_bytes`] does.
+    pub fn fix_bytes(&self, data: &[u8]) -> Result<(Vec<u8>, FixReport), Error> {
+        fix_archive_bytes(data, &self.options)
+    }
+
+    /// The readings of a single `name` like [`crate::detect`], among the
+    /// candidates and for the language of the options
+    #[must_use]
+    pub fn detect(&self, name: &[u8]) -> Vec<Candidate> {
+        let options = &self.options;
+        let picked = detect_name_encoding(
+            name,
+            &unexplained,
+            &options.candidates,
+            Hints::default(),
+            options.lang,
+        );
+        rank_candidates(name, picked, &options.candidates, options.lang)
+    }
+}
@@ -0,0 +1,49 @@
+//! Recognition of file formats built on ZIP whose entry names must not change
+
+use std::path::Path;
+
+use crate::rawzip::Entry;
+
+/// Extensions of ZIP-based formats, with a description
+const EXTENSIONS: &[(&str, &str)] = &[
+    ("docx", "an Office Open XML document"),
+    ("xlsx", "an Office Open XML document"),
+    ("pptx", "an Office Open XML document"),
+    ("odt", "an OpenDocument file"),
+    ("ods", "an OpenDocument file"),
+    ("odp", "an OpenDocument file"),
+    ("epub", "an EPUB book"),
+    ("jar", "a Java archive"),
+    ("war", "a Java archive"),
+    ("ear", "a Java archive"),
+    ("apk", "an Android package"),
+    ("aab", "an Android package"),
+    ("xpi", "a browser extension"),
+];
+
+/// Entries that mark a ZIP-based format regardless of the file name
+const MARKER_ENTRIES: &[(&[u8], &str)] = &[
+    (b"[Content_Types].xml", "an Office Open XML document"),
+    (b"AndroidManifest.xml", "an Android package"),
+    (b"META-INF/MANIFEST.MF", "a Java archive"),
+    (b"mimetype", "an OpenDocument file or EPUB book"),
+];
+
+/// Describe the ZIP-based format `zipfile` looks like, if any
+pub fn structured_format(zipfile: &Path, entries: &[Entry]) -> Option<&'static str> {
+    let extension = zipfile
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase());
+    EXTENSIONS
+        .iter()
+        .find(|(known, _)| extension.as_deref() == Some(*known))
+        .map(|(_, description)| *description)
+        .or_else(|| {
+            MARKER_ENTRIES.iter().find_map(|(marker, description)| {
+                entries
+                    .iter()
+                    .any(|entry| entry.name == *marker)
+                    .then_some(*description)
+            })
+        })
+}
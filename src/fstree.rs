@@ -0,0 +1,74 @@
+//! Renaming files and directories whose names on disk are in a legacy encoding
+
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+use crate::error::{Context, Result};
+use crate::{Action, FixOptions, Hints, display_name, process_entry_name};
+
+/// Counts of the names seen in a directory tree
+#[derive(Default)]
+pub struct Summary {
+    pub names: usize,
+    pub renamed: usize,
+    /// Names that could not be converted or whose new name is taken
+    pub failed: usize,
+}
+
+/// Fix the names of everything under `dir`, descending into renamed
+/// directories under their new names
+///
+/// Names that are valid UTF-8 already are left alone, and symbolic links are
+/// renamed but not followed.
+pub fn fix_tree(dir: &Path, options: &FixOptions, summary: &mut Summary) -> Result<()> {
+    let mut children = fs::read_dir(dir)
+        .context(format!("Failed to read {}", dir.display()))?
+        .map(|child| child.map(|child| child.file_name()))
+        .collect::<Result<Vec<OsString>, _>>()
+        .context(format!("Failed to read {}", dir.display()))?;
+    children.sort();
+    if children.iter().any(|name| name.to_str().is_none()) {
+        println!("{}:", dir.display());
+    }
+
+    for name in children {
+        summary.names += 1;
+        let path = dir.join(&name);
+        let is_dir = fs::symlink_metadata(&path)
+            .context(format!("Failed to read {}", path.display()))?
+            .is_dir();
+        let mut new_path = path.clone();
+
+        if name.to_str().is_none() {
+            let outcome = process_entry_name(name.as_bytes(), false, Hints::default(), options);
+            match outcome.action {
+                Action::Fixed => {
+                    let target = dir.join(OsStr::from_bytes(&outcome.new_name));
+                    if outcome.new_name.contains(&b'/') || fs::symlink_metadata(&target).is_ok() {
+                        println!(
+                            "    Skipped: \"{}\" already exists or is not a valid name",
+                            display_name(&outcome.new_name, outcome.name_encoding)
+                        );
+                        summary.failed += 1;
+                    } else {
+                        if !options.dry_run {
+                            fs::rename(&path, &target)
+                                .context(format!("Failed to rename {}", path.display()))?;
+                            new_path = target;
+                        }
+                        summary.renamed += 1;
+                    }
+                }
+                Action::Failed => summary.failed += 1,
+                Action::AlreadyUtf8 | Action::Unchanged => {}
+            }
+        }
+
+        if is_dir {
+            fix_tree(&new_path, options, summary)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,52 @@
+//! Patterns of `--delete`, matched against entry names like `.gitignore`
+//! patterns are against paths
+//!
+//! `*` matches any characters but `/`, `**` any characters, `?` a single
+//! character but `/`. A pattern without a slash matches a file or directory
+//! name at any depth; one with a slash in it matches a path from the root of
+//! the archive. A pattern ending with a slash matches directories only.
+//! Everything inside a matched directory matches too.
+
+/// Whether the entry `name` matches `pattern` or is inside a directory
+/// that does
+pub fn matches(pattern: &str, name: &str) -> bool {
+    let directory_only = pattern.ends_with('/');
+    let pattern = pattern.trim_end_matches('/');
+    let anchored = pattern.contains('/');
+    let pattern: Vec<char> = pattern.trim_start_matches('/').chars().collect();
+
+    let is_directory = name.ends_with('/');
+    let path = name.trim_end_matches('/');
+    let mut start = 0;
+    for (end, _) in path.match_indices('/').chain([(path.len(), "")]) {
+        let is_last = end == path.len();
+        if !directory_only || !is_last || is_directory {
+            let text = if anchored {
+                &path[..end]
+            } else {
+                &path[start..end]
+            };
+            if wildcard(&pattern, &text.chars().collect::<Vec<_>>()) {
+                return true;
+            }
+        }
+        start = end + 1;
+    }
+    false
+}
+
+fn wildcard(pattern: &[char], text: &[char]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        ['*', '*', rest @ ..] => (0..=text.len()).any(|skip| wildcard(rest, &text[skip..])),
+        ['*', rest @ ..] => (0..=text.len())
+            .take_while(|&skip| skip == 0 || text[skip - 1] != '/')
+            .any(|skip| wildcard(rest, &text[skip..])),
+        ['?', rest @ ..] => text
+            .split_first()
+            .is_some_and(|(&c, text)| c != '/' && wildcard(rest, text)),
+        [c, rest @ ..] => text
+            .split_first()
+            .is_some_and(|(t, text)| t == c && wildcard(rest, text)),
+    }
+}
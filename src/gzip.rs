@@ -0,0 +1,192 @@
+//! Fixing the original file name (FNAME) stored in gzip headers
+//!
+//! Only the header is rewritten, the compressed stream is copied as is.
+//! Compressed tar archives are recompressed by [`MemberWriter`] instead.
+
+use flate2::Compression;
+use flate2::write::DeflateEncoder;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Write};
+use std::path::Path;
+
+use crate::error::{Context, Error, Result};
+
+const MAGIC: [u8; 2] = [0x1f, 0x8b];
+const FIXED_HEADER_SIZE: usize = 10;
+
+const FLAG_HCRC: u8 = 1 << 1;
+const FLAG_EXTRA: u8 = 1 << 2;
+const FLAG_NAME: u8 = 1 << 3;
+const FLAG_COMMENT: u8 = 1 << 4;
+
+/// XFL values set by compressors using the best and the fastest level
+const XFL_SLOWEST: u8 = 2;
+const XFL_FASTEST: u8 = 4;
+
+/// The parts of a gzip member header up to the compressed data
+struct Header {
+    fixed: [u8; FIXED_HEADER_SIZE],
+    extra: Option<Vec<u8>>,
+    name: Option<Vec<u8>>,
+    comment: Option<Vec<u8>>,
+}
+
+impl Header {
+    fn read(reader: &mut impl Read) -> Result<Self> {
+        let mut fixed = [0; FIXED_HEADER_SIZE];
+        reader
+            .read_exact(&mut fixed)
+            .context("Failed to read gzip header")?;
+        if fixed[..2] != MAGIC {
+            return Err(Error::other("Not a gzip file"));
+        }
+        let flags = fixed[3];
+
+        let extra = if flags & FLAG_EXTRA == 0 {
+            None
+        } else {
+            let mut len = [0; 2];
+            reader.read_exact(&mut len)?;
+            let mut extra = vec![0; usize::from(u16::from_le_bytes(len))];
+            reader.read_exact(&mut extra)?;
+            Some(extra)
+        };
+        let name = if flags & FLAG_NAME == 0 {
+            None
+        } else {
+            Some(read_zero_terminated(reader)?)
+        };
+        let comment = if flags & FLAG_COMMENT == 0 {
+            None
+        } else {
+            Some(read_zero_terminated(reader)?)
+        };
+        if flags & FLAG_HCRC != 0 {
+            // Recomputed on write
+            reader.read_exact(&mut [0; 2])?;
+        }
+
+        Ok(Self {
+            fixed,
+            extra,
+            name,
+            comment,
+        })
+    }
+
+    fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut header = self.fixed.to_vec();
+        if let Some(extra) = &self.extra {
+            header.extend_from_slice(&u16::try_from(extra.len())?.to_le_bytes());
+            header.extend_from_slice(extra);
+        }
+        for field in [&self.name, &self.comment].into_iter().flatten() {
+            if field.contains(&0) {
+                return Err(Error::other("gzip header fields cannot contain NUL bytes"));
+            }
+            header.extend_from_slice(field);
+            header.push(0);
+        }
+        if self.fixed[3] & FLAG_HCRC != 0 {
+            // CRC16 is the low half of the CRC-32 of the header so far
+            let crc32 = crc32fast::hash(&header).to_le_bytes();
+            header.extend_from_slice(&crc32[..2]);
+        }
+        Ok(header)
+    }
+}
+
+fn read_zero_terminated(reader: &mut impl Read) -> Result<Vec<u8>> {
+    let mut field = Vec::new();
+    let mut byte = [0; 1];
+    loop {
+        reader
+            .read_exact(&mut byte)
+            .context("Truncated gzip header")?;
+        if byte[0] == 0 {
+            return Ok(field);
+        }
+        field.push(byte[0]);
+    }
+}
+
+/// Whether `path` starts with the gzip magic bytes
+pub fn is_gzip(path: &Path) -> Result<bool> {
+    let mut file = File::open(path).context(format!("Failed to open {}", path.display()))?;
+    let mut magic = [0; 2];
+    match file.read_exact(&mut magic) {
+        Ok(()) => Ok(magic == MAGIC),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// The original file name stored in the header of the first member
+pub fn read_name(path: &Path) -> Result<Option<Vec<u8>>> {
+    let file = File::open(path).context(format!("Failed to open {}", path.display()))?;
+    Ok(Header::read(&mut BufReader::new(file))?.name)
+}
+
+/// Copy the gzip file at `path` to `writer`, with `name` as the original
+/// file name in the header of the first member
+pub fn copy_with_name(path: &Path, name: &[u8], mut writer: impl Write) -> Result<()> {
+    let file = File::open(path).context(format!("Failed to open {}", path.display()))?;
+    let mut reader = BufReader::new(file);
+    let mut header = Header::read(&mut reader)?;
+    header.fixed[3] |= FLAG_NAME;
+    header.name = Some(name.to_vec());
+
+    writer.write_all(&header.to_bytes()?)?;
+    io::copy(&mut reader, &mut writer).context("Failed to copy compressed data")?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// A gzip member with the header of an existing file, compressed at the
+/// level its XFL field suggests
+pub struct MemberWriter<W: Write> {
+    encoder: DeflateEncoder<W>,
+    crc: crc32fast::Hasher,
+    size: u64,
+}
+
+impl<W: Write> MemberWriter<W> {
+    /// Start a member in `writer` with the header of the first member of `path`
+    pub fn like(path: &Path, mut writer: W) -> Result<Self> {
+        let file = File::open(path).context(format!("Failed to open {}", path.display()))?;
+        let header = Header::read(&mut BufReader::new(file))?;
+        writer.write_all(&header.to_bytes()?)?;
+        let level = match header.fixed[8] {
+            XFL_SLOWEST => Compression::best(),
+            XFL_FASTEST => Compression::fast(),
+            _ => Compression::default(),
+        };
+        Ok(Self {
+            encoder: DeflateEncoder::new(writer, level),
+            crc: crc32fast::Hasher::new(),
+            size: 0,
+        })
+    }
+
+    /// Finish the compressed stream and write the trailer
+    pub fn finish(self) -> io::Result<W> {
+        let mut writer = self.encoder.finish()?;
+        writer.write_all(&self.crc.finalize().to_le_bytes())?;
+        // ISIZE is the uncompressed size modulo 2^32
+        writer.write_all(&self.size.to_le_bytes()[..4])?;
+        Ok(writer)
+    }
+}
+
+impl<W: Write> Write for MemberWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.encoder.write(buf)?;
+        self.crc.update(&buf[..written]);
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.encoder.flush()
+    }
+}
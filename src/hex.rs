@@ -0,0 +1,32 @@
+//! Hex encoding of raw entry names for reports and rename maps
+
+use std::fmt::Write as _;
+
+#[cfg(feature = "cli")]
+use crate::error::{Error, Result};
+
+/// Encode bytes as lowercase hex without separators
+pub fn encode(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut out, byte| {
+        let _ = write!(out, "{byte:02x}");
+        out
+    })
+}
+
+/// Decode a string produced by [`encode`]
+#[cfg(feature = "cli")]
+pub fn decode(text: &str) -> Result<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return Err(Error::other(format!(
+            "Odd number of hex digits in \"{text}\""
+        )));
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| {
+            text.get(i..i + 2)
+                .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+                .ok_or_else(|| Error::other(format!("Invalid hex digits in \"{text}\"")))
+        })
+        .collect()
+}
@@ -0,0 +1,63 @@
+//! Encoding hints from the text files inside an archive
+//!
+//! A file name has a dozen letters at most, too few to tell apart code
+//! pages that all decode it to plausible Cyrillic. The `.txt`, `.nfo` and
+//! `.diz` files packed with it were usually written on the same system, and
+//! their first kilobytes give the frequency analysis far more to go on.
+
+use crate::encoding::Encoding;
+use chardetng::EncodingDetector;
+use std::io::{Read, Seek};
+
+use crate::contents;
+use crate::rawzip::RawArchive;
+
+/// Extensions of the entries sampled
+const TEXT_EXTENSIONS: [&[u8]; 3] = [b".txt", b".nfo", b".diz"];
+/// Bytes read from the start of each sampled entry
+const SAMPLE_SIZE: u64 = 4096;
+/// Entries sampled at most, so large archives are not read through
+const MAX_SAMPLES: usize = 16;
+
+/// Legacy encoding of the text entries of `archive`, if they have non-ASCII
+/// text and it is detected as one of the `candidates`
+pub fn content_encoding<R: Read + Seek>(
+    archive: &mut RawArchive<R>,
+    candidates: &[&'static Encoding],
+) -> Option<&'static Encoding> {
+    let text_entries: Vec<usize> = archive
+        .entries()
+        .iter()
+        .enumerate()
+        .filter(|(_, entry)| {
+            let name = entry.name.to_ascii_lowercase();
+            TEXT_EXTENSIONS
+                .iter()
+                .any(|extension| name.ends_with(extension))
+        })
+        .map(|(index, _)| index)
+        .collect();
+
+    let mut detector = EncodingDetector::new();
+    let mut sampled = 0;
+    for index in text_entries {
+        // Damaged text entries are left to fail where their data is needed
+        let Ok(Some(sample)) = archive.entry_prefix(index, SAMPLE_SIZE) else {
+            continue;
+        };
+        if contents::is_utf8(&sample) {
+            continue;
+        }
+        detector.feed(&sample, false);
+        sampled += 1;
+        if sampled == MAX_SAMPLES {
+            break;
+        }
+    }
+    if sampled == 0 {
+        return None;
+    }
+    detector.feed(&[], true);
+    Encoding::for_standard(detector.guess(None, true))
+        .filter(|encoding| candidates.contains(encoding))
+}
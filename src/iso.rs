@@ -0,0 +1,314 @@
+//! Reading ISO 9660 images with Joliet and Rock Ridge extensions
+//!
+//! An image can carry three sets of names for the same files: the primary
+//! volume descriptor (short names, often raw code page bytes from careless
+//! mastering tools), Rock Ridge `NM` entries (raw bytes as they were on the
+//! Unix system that made the image) and a Joliet tree (UCS-2, already
+//! Unicode). The Joliet tree is preferred, then Rock Ridge.
+
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::error::{Context, Error, Result};
+use crate::extract::{Entry, ReadOnlyArchive};
+
+const SECTOR_SIZE: u64 = 2048;
+const FIRST_DESCRIPTOR_SECTOR: u64 = 16;
+const STANDARD_ID: &[u8] = b"CD001";
+
+const DESCRIPTOR_PRIMARY: u8 = 1;
+const DESCRIPTOR_SUPPLEMENTARY: u8 = 2;
+const DESCRIPTOR_TERMINATOR: u8 = 255;
+/// Escape sequences of the three Joliet UCS-2 levels
+const JOLIET_ESCAPES: &[&[u8]] = &[b"%/@", b"%/C", b"%/E"];
+const ESCAPES_OFFSET: usize = 88;
+const ROOT_RECORD_OFFSET: usize = 156;
+
+const FLAG_DIRECTORY: u8 = 0x02;
+/// Directory record length up to the name
+const RECORD_FIXED_SIZE: usize = 33;
+
+/// Which set of names an image is read with
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Names {
+    Joliet,
+    RockRidge,
+    Primary,
+}
+
+impl Names {
+    pub fn label(self) -> &'static str {
+        match self {
+            Names::Joliet => "Joliet",
+            Names::RockRidge => "Rock Ridge",
+            Names::Primary => "ISO 9660",
+        }
+    }
+}
+
+/// Where the data of an entry is
+struct Location {
+    extent: u32,
+    size: u32,
+}
+
+pub struct Image {
+    reader: BufReader<File>,
+    pub names: Names,
+    entries: Vec<Entry>,
+    locations: Vec<Location>,
+}
+
+/// Whether `path` has an ISO 9660 volume descriptor
+pub fn is_iso(path: &Path) -> Result<bool> {
+    let mut file = File::open(path).context(format!("Failed to open {}", path.display()))?;
+    let mut descriptor = [0; 6];
+    file.seek(SeekFrom::Start(FIRST_DESCRIPTOR_SECTOR * SECTOR_SIZE))?;
+    match file.read_exact(&mut descriptor) {
+        Ok(()) => Ok(&descriptor[1..] == STANDARD_ID),
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn read_sectors(reader: &mut (impl Read + Seek), sector: u32, size: u32) -> Result<Vec<u8>> {
+    reader.seek(SeekFrom::Start(u64::from(sector) * SECTOR_SIZE))?;
+    let mut data = vec![0; usize::try_from(size)?];
+    reader
+        .read_exact(&mut data)
+        .context("Truncated ISO image")?;
+    Ok(data)
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ])
+}
+
+/// A directory record, with its name and location
+struct Record<'a> {
+    extent: u32,
+    size: u32,
+    flags: u8,
+    name: &'a [u8],
+    system_use: &'a [u8],
+}
+
+/// Split a directory extent into its records
+fn parse_records(data: &[u8]) -> Vec<Record<'_>> {
+    let mut records = Vec::new();
+    let mut offset = 0;
+    while offset < data.len() {
+        let len = usize::from(data[offset]);
+        if len == 0 {
+            // Records do not cross sectors, the rest of this one is padding
+            let sector = usize::try_from(SECTOR_SIZE).unwrap_or(usize::MAX);
+            offset = (offset / sector + 1) * sector;
+            continue;
+        }
+        let Some(record) = data.get(offset..offset + len) else {
+            break;
+        };
+        if len < RECORD_FIXED_SIZE {
+            break;
+        }
+        let name_len = usize::from(record[32]);
+        let Some(name) = record.get(RECORD_FIXED_SIZE..RECORD_FIXED_SIZE + name_len) else {
+            break;
+        };
+        // Names of even length are followed by a padding byte
+        let system_use_start = RECORD_FIXED_SIZE + name_len + (1 - name_len % 2);
+        records.push(Record {
+            extent: read_u32(record, 2),
+            size: read_u32(record, 10),
+            flags: record[25],
+            name,
+            system_use: record.get(system_use_start..).unwrap_or_default(),
+        });
+        offset += len;
+    }
+    records
+}
+
+/// System Use Sharing Protocol entries as (signature, data)
+fn susp_entries(mut area: &[u8]) -> impl Iterator<Item = (&[u8], &[u8])> {
+    std::iter::from_fn(move || {
+        if area.len() < 4 {
+            return None;
+        }
+        let len = usize::from(area[2]);
+        if len < 4 || len > area.len() {
+            return None;
+        }
+        let (entry, rest) = area.split_at(len);
+        area = rest;
+        Some((&entry[..2], &entry[4..]))
+    })
+}
+
+/// The Rock Ridge name of a record, joined from its `NM` entries
+fn rock_ridge_name(system_use: &[u8]) -> Option<Vec<u8>> {
+    let mut name = Vec::new();
+    let mut found = false;
+    for (signature, data) in susp_entries(system_use) {
+        // The first data byte holds flags (continuation, ".", "..")
+        if signature == b"NM"
+            && let Some((&flags, part)) = data.split_first()
+            && flags & 0b110 == 0
+        {
+            name.extend_from_slice(part);
+            found = true;
+        }
+    }
+    found.then_some(name)
+}
+
+/// The name of a record in the primary tree, without the `;1` version
+fn primary_name(name: &[u8]) -> Vec<u8> {
+    let name = name
+        .iter()
+        .position(|&byte| byte == b';')
+        .map_or(name, |semicolon| &name[..semicolon]);
+    name.strip_suffix(b".").unwrap_or(name).to_vec()
+}
+
+/// Decode a Joliet name, UCS-2 big endian
+fn joliet_name(name: &[u8]) -> Vec<u8> {
+    let units: Vec<u16> = name
+        .chunks_exact(2)
+        .map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+        .collect();
+    let name = String::from_utf16_lossy(&units);
+    let name = name.split(';').next().unwrap_or_default();
+    name.strip_suffix('.').unwrap_or(name).as_bytes().to_vec()
+}
+
+impl Image {
+    /// Open the image at `path` and list its files
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = File::open(path).context(format!("Failed to open {}", path.display()))?;
+        let mut reader = BufReader::new(file);
+
+        let mut primary_root = None;
+        let mut joliet_root = None;
+        for sector in FIRST_DESCRIPTOR_SECTOR.. {
+            let descriptor = read_sectors(&mut reader, u32::try_from(sector)?, 2048)?;
+            if &descriptor[1..6] != STANDARD_ID || descriptor[0] == DESCRIPTOR_TERMINATOR {
+                break;
+            }
+            let root = &descriptor[ROOT_RECORD_OFFSET..ROOT_RECORD_OFFSET + 34];
+            let root = (read_u32(root, 2), read_u32(root, 10));
+            match descriptor[0] {
+                DESCRIPTOR_PRIMARY => primary_root = Some(root),
+                DESCRIPTOR_SUPPLEMENTARY
+                    if JOLIET_ESCAPES
+                        .iter()
+                        .any(|escape| descriptor[ESCAPES_OFFSET..].starts_with(escape)) =>
+                {
+                    joliet_root = Some(root);
+                }
+                _ => {}
+            }
+        }
+
+        let (names, (extent, size)) = if let Some(root) = joliet_root {
+            (Names::Joliet, root)
+        } else {
+            let root = primary_root.ok_or_else(|| Error::other("No primary volume descriptor"))?;
+            // Rock Ridge announces itself in the "." record of the root
+            let data = read_sectors(&mut reader, root.0, root.1)?;
+            let rock_ridge = parse_records(&data).first().is_some_and(|dot| {
+                susp_entries(dot.system_use).any(|(signature, _)| signature == b"SP")
+            });
+            (
+                if rock_ridge {
+                    Names::RockRidge
+                } else {
+                    Names::Primary
+                },
+                root,
+            )
+        };
+
+        let mut image = Self {
+            reader,
+            names,
+            entries: Vec::new(),
+            locations: Vec::new(),
+        };
+        let mut visited = HashSet::new();
+        image.read_directory(extent, size, &[], &mut visited)?;
+        Ok(image)
+    }
+
+    fn read_directory(
+        &mut self,
+        extent: u32,
+        size: u32,
+        parent: &[u8],
+        visited: &mut HashSet<u32>,
+    ) -> Result<()> {
+        if !visited.insert(extent) {
+            return Err(Error::other("Directory loop in ISO image"));
+        }
+        let data = read_sectors(&mut self.reader, extent, size)?;
+        for record in parse_records(&data) {
+            // "." and ".." have the single byte names 0 and 1
+            if matches!(record.name, [0 | 1]) {
+                continue;
+            }
+            let name = match self.names {
+                Names::Joliet => joliet_name(record.name),
+                Names::RockRidge => {
+                    rock_ridge_name(record.system_use).unwrap_or_else(|| primary_name(record.name))
+                }
+                Names::Primary => primary_name(record.name),
+            };
+            let is_dir = record.flags & FLAG_DIRECTORY != 0;
+            let mut path = [parent, &name].concat();
+            if is_dir {
+                path.push(b'/');
+            }
+            self.entries.push(Entry {
+                name: path.clone(),
+                unicode: self.names == Names::Joliet,
+                is_dir,
+                host: None,
+                size: if is_dir { 0 } else { u64::from(record.size) },
+                compressed_size: if is_dir { 0 } else { u64::from(record.size) },
+            });
+            self.locations.push(Location {
+                extent: record.extent,
+                size: record.size,
+            });
+            if is_dir {
+                self.read_directory(record.extent, record.size, &path, visited)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ReadOnlyArchive for Image {
+    fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    fn extract_entry(&mut self, index: usize, writer: &mut dyn Write) -> Result<()> {
+        let location = &self.locations[index];
+        self.reader
+            .seek(SeekFrom::Start(u64::from(location.extent) * SECTOR_SIZE))?;
+        let mut data = (&mut self.reader).take(u64::from(location.size));
+        let copied = std::io::copy(&mut data, writer)?;
+        if copied != u64::from(location.size) {
+            return Err(Error::other("Truncated ISO image"));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,99 @@
+//! Journal of the archives a batch has finished, for resuming it
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use tempfile::NamedTempFile;
+
+use crate::error::{Context, Error, Result};
+
+const FORMAT_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct State {
+    version: u32,
+    /// Absolute paths of the archives fixed successfully
+    completed: BTreeSet<PathBuf>,
+}
+
+pub struct Journal {
+    path: PathBuf,
+    state: State,
+}
+
+impl Journal {
+    /// Open the journal at `path`, starting an empty one if it does not exist
+    pub fn open(path: &Path) -> Result<Self> {
+        let state = match std::fs::read(path) {
+            Ok(data) => {
+                let state: State = serde_json::from_slice(&data)
+                    .with_context(|| format!("Failed to parse journal {}", path.display()))?;
+                if state.version != FORMAT_VERSION {
+                    return Err(Error::other(format!(
+                        "Unsupported journal version {} in {}",
+                        state.version,
+                        path.display()
+                    )));
+                }
+                state
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => State {
+                version: FORMAT_VERSION,
+                completed: BTreeSet::new(),
+            },
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("Failed to read journal {}", path.display()));
+            }
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            state,
+        })
+    }
+
+    /// Whether `archive` was finished by an earlier run
+    pub fn is_completed(&self, archive: &Path) -> bool {
+        std::path::absolute(archive).is_ok_and(|path| self.state.completed.contains(&path))
+    }
+
+    /// Record `archive` as finished, saving the journal right away
+    pub fn complete(&mut self, archive: &Path) -> Result<()> {
+        let path = std::path::absolute(archive)
+            .with_context(|| format!("Failed to resolve {}", archive.display()))?;
+        self.state.completed.insert(path);
+        self.save()
+    }
+
+    /// Remove the journal once the whole batch is done
+    pub fn remove(self) -> Result<()> {
+        match std::fs::remove_file(&self.path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(e).with_context(|| format!("Failed to remove journal {}", self.path.display()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Replace the journal file atomically, so an interrupted run leaves
+    /// either the old or the new version
+    fn save(&self) -> Result<()> {
+        let context = || format!("Failed to write journal {}", self.path.display());
+        let dir = match self.path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let temp_file = NamedTempFile::new_in(dir).with_context(context)?;
+        let mut writer = BufWriter::new(temp_file.as_file());
+        serde_json::to_writer_pretty(&mut writer, &self.state).with_context(context)?;
+        writeln!(writer).with_context(context)?;
+        writer.flush().with_context(context)?;
+        drop(writer);
+        temp_file
+            .persist(&self.path)
+            .map_err(|e| e.error)
+            .with_context(context)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,351 @@
+//! Languages names are detected in (`--lang`)
+//!
+//! A language has a profile: the letters of its script, the legacy
+//! encodings its names are found in, and its most frequent letters with the
+//! share of running text they make up. A name decoded in the right encoding
+//! has about that share of frequent letters, one decoded in a wrong encoding
+//! far fewer, so the share relative to the language's is a score comparable
+//! across languages. Cyrillic languages are detected as Cyrillic names
+//! always were, weighing the system an archive was made on too, and only
+//! scored against the other scripts.
+
+use unicode_normalization::UnicodeNormalization;
+
+use crate::encoding::{
+    BIG5, EUC_JP, EUC_KR, Encoding, GBK, IBM437, IBM862, IBM866, IBM1125, ISO_8859_7, ISO_8859_8,
+    KOI8_R, KOI8_U, KZ_1048, PTCP154, SHIFT_JIS, WINDOWS_1251, WINDOWS_1253, WINDOWS_1255,
+};
+
+/// Language of the names to detect
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Lang {
+    /// Every language below, picking the one that fits each name best
+    Auto,
+    /// Russian
+    #[default]
+    Ru,
+    /// Ukrainian
+    Uk,
+    /// Belarusian
+    Be,
+    /// Kazakh
+    Kk,
+    /// Serbian
+    Sr,
+    /// Bulgarian
+    Bg,
+    /// Macedonian
+    Mk,
+    /// Greek
+    El,
+    /// Hebrew
+    He,
+    /// Japanese
+    Ja,
+    /// Chinese
+    Zh,
+    /// Korean
+    Ko,
+}
+
+/// Script of a language
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Script {
+    Cyrillic,
+    Greek,
+    Hebrew,
+    Cjk,
+}
+
+/// What detection knows about a language
+pub struct Profile {
+    pub lang: Lang,
+    pub script: Script,
+    /// Legacy encodings names in the language are found in, most common
+    /// first
+    pub encodings: &'static [&'static Encoding],
+    /// Whether a non-ASCII character can be in a name in the language
+    letters: fn(char) -> bool,
+    /// Whether a letter, lowercase and without diacritics, is among the most
+    /// frequent ones
+    frequent: fn(char) -> bool,
+    /// Share of the letters of running text that are frequent ones
+    coverage: f64,
+}
+
+static RU: Profile = Profile {
+    lang: Lang::Ru,
+    script: Script::Cyrillic,
+    encodings: &[WINDOWS_1251, IBM866, KOI8_R, KOI8_U],
+    letters: is_cyrillic,
+    frequent: |c| "оеаинтсрвлкмдпу".contains(c),
+    coverage: 0.82,
+};
+
+static UK: Profile = Profile {
+    lang: Lang::Uk,
+    script: Script::Cyrillic,
+    encodings: &[WINDOWS_1251, IBM1125, IBM866, KOI8_U],
+    letters: is_ukrainian,
+    // With `є`, the most frequent letter Russian lacks after `і`, to tell
+    // apart CP1125 and CP866, which swap it with `ї`
+    frequent: |c| "оаніивтерсклудмпє".contains(c),
+    coverage: 0.81,
+};
+
+// Belarusian names are not found in the KOI8 code pages, which lack `ў`
+static BE: Profile = Profile {
+    lang: Lang::Be,
+    script: Script::Cyrillic,
+    encodings: &[WINDOWS_1251, IBM866],
+    letters: is_belarusian,
+    frequent: |c| "аоніеырвстлкдумяў".contains(c),
+    coverage: 0.82,
+};
+
+// Kazakh letters are missing from Windows-1251, which names without them
+// are found in
+static KK: Profile = Profile {
+    lang: Lang::Kk,
+    script: Script::Cyrillic,
+    encodings: &[KZ_1048, PTCP154, WINDOWS_1251],
+    letters: is_kazakh,
+    frequent: |c| "аеынрлтікдсумоқбзә".contains(c),
+    coverage: 0.86,
+};
+
+// Serbian and Macedonian letters are missing from CP866 and the KOI8 code
+// pages, so their names are only found in Windows-1251
+static SR: Profile = Profile {
+    lang: Lang::Sr,
+    script: Script::Cyrillic,
+    encodings: &[WINDOWS_1251],
+    letters: is_cyrillic,
+    frequent: |c| "аиоенсрјтукдвлмп".contains(c),
+    coverage: 0.85,
+};
+
+static BG: Profile = Profile {
+    lang: Lang::Bg,
+    script: Script::Cyrillic,
+    encodings: &[WINDOWS_1251, IBM866],
+    letters: is_cyrillic,
+    frequent: |c| "аоиентрсвлкдпмъ".contains(c),
+    coverage: 0.85,
+};
+
+static MK: Profile = Profile {
+    lang: Lang::Mk,
+    script: Script::Cyrillic,
+    encodings: &[WINDOWS_1251],
+    letters: is_cyrillic,
+    frequent: |c| "аоеинтрскдвлпмј".contains(c),
+    coverage: 0.85,
+};
+
+static EL: Profile = Profile {
+    lang: Lang::El,
+    script: Script::Greek,
+    encodings: &[WINDOWS_1253, ISO_8859_7],
+    letters: |c| matches!(c, '\u{0370}'..='\u{03FF}' | '«' | '»'),
+    frequent: |c| "αοιετσςνηυρπκμλ".contains(c),
+    coverage: 0.88,
+};
+
+static HE: Profile = Profile {
+    lang: Lang::He,
+    script: Script::Hebrew,
+    encodings: &[WINDOWS_1255, IBM862, ISO_8859_8],
+    letters: |c| matches!(c, '\u{0591}'..='\u{05F4}'),
+    frequent: |c| "יוהלארמםבתנןשעדכךק".contains(c),
+    coverage: 0.85,
+};
+
+static JA: Profile = Profile {
+    lang: Lang::Ja,
+    script: Script::Cjk,
+    encodings: &[SHIFT_JIS, EUC_JP],
+    letters: is_cjk,
+    // Kana, which Chinese names lack, and the most common kanji; the
+    // half-width katakana other encodings often decode to are not frequent
+    frequent: |c| matches!(c, '\u{3041}'..='\u{30FA}') || "日本人大年中一出月会社生学".contains(c),
+    coverage: 0.5,
+};
+
+static ZH: Profile = Profile {
+    lang: Lang::Zh,
+    script: Script::Cjk,
+    encodings: &[GBK, BIG5],
+    letters: is_cjk,
+    frequent: |c| COMMON_HAN.contains(c),
+    coverage: 0.35,
+};
+
+static KO: Profile = Profile {
+    lang: Lang::Ko,
+    script: Script::Cjk,
+    encodings: &[EUC_KR],
+    letters: is_cjk,
+    frequent: |c| COMMON_HANGUL.contains(c),
+    coverage: 0.45,
+};
+
+/// The most common Chinese characters, simplified and traditional
+const COMMON_HAN: &str = "的一是不了人在有中大上个國国到们們为為子和地出也时時年得就要下以\
+                          生会會自着去之过過家学學对對可里后後小心多天能好都然没日于起还发成\
+                          事只作当想看文无开手十用主行方又如前所本见经头面公同三已老从动两长\
+                          知民样现分将外但身些与高意进把法此实回二理美点月明其种声全工己话";
+
+/// The most common Korean syllables
+const COMMON_HANGUL: &str = "이의다는에하고을가를지기사서리로한자대으정수도인시아일나어구부전상보해주\
+                             국그제경조성비우동연들적문화장신공";
+
+/// Cyrillic letters, with those of the languages of Central Asia, and `№`
+pub fn is_cyrillic(c: char) -> bool {
+    matches!(c, '\u{0400}'..='\u{04FF}' | '№')
+}
+
+/// The Ukrainian alphabet, which has `Ґ`, `Є`, `І` and `Ї` but not `Ё`,
+/// `Ъ`, `Ы` and `Э`
+fn is_ukrainian(c: char) -> bool {
+    matches!(
+        c,
+        'А'..='я' | 'Ґ' | 'ґ' | 'Є' | 'є' | 'І' | 'і' | 'Ї' | 'ї' | '№'
+    ) && !"ЪЫЭъыэ".contains(c)
+}
+
+/// The Belarusian alphabet, which has `І` and `Ў` but not `И`, `Щ` and `Ъ`
+fn is_belarusian(c: char) -> bool {
+    matches!(c, 'А'..='я' | 'Ё' | 'ё' | 'І' | 'і' | 'Ў' | 'ў' | '№') && !"ИЩЪищъ".contains(c)
+}
+
+/// The Kazakh alphabet, the Russian one with `Ә`, `Ғ`, `Қ`, `Ң`, `Ө`, `Ұ`,
+/// `Ү`, `Һ` and `І`
+fn is_kazakh(c: char) -> bool {
+    matches!(
+        c,
+        'А'..='я'
+            | 'Ё'
+            | 'ё'
+            | 'Ә'
+            | 'ә'
+            | 'Ғ'
+            | 'ғ'
+            | 'Қ'
+            | 'қ'
+            | 'Ң'
+            | 'ң'
+            | 'Ө'
+            | 'ө'
+            | 'Ұ'
+            | 'ұ'
+            | 'Ү'
+            | 'ү'
+            | 'Һ'
+            | 'һ'
+            | 'І'
+            | 'і'
+            | '№'
+    )
+}
+
+/// Kana, hangul, CJK ideographs and the punctuation and full-width forms
+/// used with them
+fn is_cjk(c: char) -> bool {
+    matches!(
+        c,
+        '\u{3000}'..='\u{30FF}'
+            | '\u{3130}'..='\u{318F}'
+            | '\u{4E00}'..='\u{9FFF}'
+            | '\u{AC00}'..='\u{D7A3}'
+            | '\u{F900}'..='\u{FAFF}'
+            | '\u{FF01}'..='\u{FF9F}'
+    )
+}
+
+static PROFILES: [&Profile; 12] = [&RU, &UK, &BE, &KK, &SR, &BG, &MK, &EL, &HE, &JA, &ZH, &KO];
+
+impl Lang {
+    /// Profiles of the languages taking part in detection
+    #[must_use]
+    pub fn profiles(self) -> Vec<&'static Profile> {
+        PROFILES
+            .into_iter()
+            .filter(|profile| self == Lang::Auto || profile.lang == self)
+            .collect()
+    }
+
+    /// Legacy encodings detection picks from: those of the languages, and
+    /// CP437 for names in none of them
+    #[must_use]
+    pub fn candidates(self) -> Vec<&'static Encoding> {
+        let mut candidates: Vec<&'static Encoding> = Vec::new();
+        for &encoding in self.profiles().iter().flat_map(|profile| profile.encodings) {
+            if !candidates.contains(&encoding) {
+                candidates.push(encoding);
+            }
+        }
+        candidates.push(IBM437);
+        candidates
+    }
+}
+
+impl Profile {
+    /// Whether `c`, lowercased and without diacritics, is a frequent letter
+    pub fn is_frequent(&self, c: char) -> bool {
+        let lowercase = c.to_lowercase().next().unwrap_or(c);
+        // Cyrillic letters with diacritics, like `й` and `ї`, are letters of
+        // their own, and Hangul syllables decompose to their letters
+        let base = match lowercase {
+            '\u{0400}'..='\u{04FF}' | '\u{AC00}'..='\u{D7A3}' => lowercase,
+            _ => lowercase.nfd().next().unwrap_or(lowercase),
+        };
+        (self.frequent)(base)
+    }
+
+    /// Whether every non-ASCII character of `text` can be in a name in the
+    /// language
+    pub fn spells(&self, text: &str) -> bool {
+        text.chars().all(|c| c.is_ascii() || (self.letters)(c))
+    }
+
+    /// How well `name` decoded in `encoding` fits the language: `None` if
+    /// it cannot be decoded or has non-ASCII characters the language does
+    /// not use, otherwise the share of frequent letters relative to the
+    /// share in running text, less the share of capitals following
+    /// lowercase letters, directly or after other such capitals, which wrong
+    /// decodings are full of
+    pub fn score(&self, name: &[u8], encoding: &'static Encoding) -> Option<f64> {
+        let text = encoding.decode_without_bom_handling_and_without_replacement(name)?;
+        if !self.spells(&text) {
+            return None;
+        }
+        let mut letters = 0_u32;
+        let mut frequent = 0_u32;
+        let mut odd = 0_u32;
+        let mut previous_lowercase = false;
+        let mut capitals_after_lowercase = false;
+        for c in text.chars() {
+            if !c.is_ascii() && c.is_alphabetic() {
+                letters += 1;
+                if self.is_frequent(c) {
+                    frequent += 1;
+                }
+            }
+            capitals_after_lowercase =
+                c.is_uppercase() && (previous_lowercase || capitals_after_lowercase);
+            if capitals_after_lowercase {
+                odd += 1;
+            }
+            previous_lowercase = c.is_lowercase();
+        }
+        if letters == 0 {
+            return None;
+        }
+        Some(
+            f64::from(frequent) / f64::from(letters) / self.coverage
+                - f64::from(odd) / f64::from(letters),
+        )
+    }
+}
@@ -0,0 +1,274 @@
+//! Reading LHA (LZH) archives
+//!
+//! Header levels 0 to 2 and the `-lh0-`, `-lh5-`, `-lh6-` and `-lh7-`
+//! methods are supported, which covers archives made by LHA on DOS and
+//! Windows. Names are in the code page of the archiving system.
+
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::error::{Context, Error, Result};
+use crate::extract::{Entry, ReadOnlyArchive};
+use crate::lzh;
+
+const EXT_FILENAME: u8 = 0x01;
+const EXT_DIRECTORY: u8 = 0x02;
+/// Directory separator in the directory name extended header
+const DIRECTORY_SEPARATOR: u8 = 0xff;
+
+/// Where the data of an entry is and how to read it
+struct Location {
+    offset: u64,
+    compressed_size: u64,
+    original_size: u64,
+    method: [u8; 5],
+    crc: u16,
+}
+
+pub struct Archive {
+    reader: BufReader<File>,
+    entries: Vec<Entry>,
+    locations: Vec<Location>,
+}
+
+/// Whether `path` starts with an LHA header (`-lh?-` or `-lz?-` method)
+pub fn is_lha(path: &Path) -> Result<bool> {
+    let file = File::open(path).context(format!("Failed to open {}", path.display()))?;
+    let mut header = Vec::with_capacity(7);
+    file.take(7).read_to_end(&mut header)?;
+    Ok(header.len() == 7
+        && header[2] == b'-'
+        && header[3] == b'l'
+        && matches!(header[4], b'h' | b'z')
+        && header[6] == b'-')
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ])
+}
+
+fn truncated() -> Error {
+    Error::other("Truncated LHA header")
+}
+
+/// CRC-16 used by LHA (polynomial 0xA001, as in ARC)
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0u16;
+    for &byte in data {
+        crc ^= u16::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 0 {
+                crc >> 1
+            } else {
+                crc >> 1 ^ 0xa001
+            };
+        }
+    }
+    crc
+}
+
+/// What the extended headers of an entry hold
+struct ExtendedHeaders {
+    file_name: Option<Vec<u8>>,
+    directory: Option<Vec<u8>>,
+    /// Total size of the headers
+    size: u64,
+}
+
+/// Read extended headers starting with one of `size` bytes
+fn read_extended_headers(reader: &mut impl Read, mut size: usize) -> Result<ExtendedHeaders> {
+    let (mut file_name, mut directory) = (None, None);
+    let mut total = 0;
+    while size > 0 {
+        // Type, data and the size of the next header
+        if size < 3 {
+            return Err(truncated());
+        }
+        let mut header = vec![0; size];
+        reader.read_exact(&mut header).map_err(|_| truncated())?;
+        total += size as u64;
+        let data = &header[1..size - 2];
+        match header[0] {
+            EXT_FILENAME => file_name = Some(data.to_vec()),
+            EXT_DIRECTORY => directory = Some(data.to_vec()),
+            _ => {}
+        }
+        size = usize::from(read_u16(&header, size - 2));
+    }
+    Ok(ExtendedHeaders {
+        file_name,
+        directory,
+        size: total,
+    })
+}
+
+/// Join a directory from the extended header and a file name
+fn join_name(directory: Option<Vec<u8>>, name: Vec<u8>) -> Vec<u8> {
+    let Some(mut path) = directory else {
+        return name;
+    };
+    for byte in &mut path {
+        if *byte == DIRECTORY_SEPARATOR {
+            *byte = b'/';
+        }
+    }
+    if !path.is_empty() && !path.ends_with(b"/") {
+        path.push(b'/');
+    }
+    path.extend_from_slice(&name);
+    path
+}
+
+impl Archive {
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = File::open(path).context(format!("Failed to open {}", path.display()))?;
+        let mut archive = Self {
+            reader: BufReader::new(file),
+            entries: Vec::new(),
+            locations: Vec::new(),
+        };
+        while archive.read_header()? {}
+        Ok(archive)
+    }
+
+    /// Read the next entry header, returning false at the end of the archive
+    fn read_header(&mut self) -> Result<bool> {
+        let mut start = [0; 22];
+        match self.reader.read_exact(&mut start[..1]) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(false),
+            Err(e) => return Err(e.into()),
+        }
+        // A zero byte marks the end of the archive
+        if start[0] == 0 {
+            return Ok(false);
+        }
+        self.reader
+            .read_exact(&mut start[1..])
+            .map_err(|_| truncated())?;
+        let method: [u8; 5] = start[2..7].try_into()?;
+        let mut compressed_size = u64::from(read_u32(&start, 7));
+        let original_size = u64::from(read_u32(&start, 11));
+        let level = start[20];
+
+        let (name, crc) = match level {
+            0 | 1 => {
+                // Level 0 and 1 base headers: size, checksum, fields, name
+                let header_size = usize::from(start[0]) + 2;
+                let mut rest = vec![0; header_size.checked_sub(22).ok_or_else(truncated)?];
+                self.reader.read_exact(&mut rest).map_err(|_| truncated())?;
+                let name_length = usize::from(start[21]);
+                let name = rest.get(..name_length).ok_or_else(truncated)?.to_vec();
+                let crc = rest
+                    .get(name_length..name_length + 2)
+                    .map(|crc| read_u16(crc, 0))
+                    .ok_or_else(truncated)?;
+                if level == 0 {
+                    // Level 0 names use DOS separators
+                    let name = name
+                        .into_iter()
+                        .map(|byte| if byte == b'\\' { b'/' } else { byte })
+                        .collect();
+                    (name, crc)
+                } else {
+                    let next_size = usize::from(read_u16(&rest, rest.len() - 2));
+                    let extended = read_extended_headers(&mut self.reader, next_size)?;
+                    // The skip size of level 1 includes the extended headers
+                    compressed_size = compressed_size
+                        .checked_sub(extended.size)
+                        .ok_or_else(truncated)?;
+                    let name = extended.file_name.unwrap_or(name);
+                    (join_name(extended.directory, name), crc)
+                }
+            }
+            2 => {
+                let header_size = usize::from(read_u16(&start, 0));
+                let mut rest = [0; 4];
+                self.reader.read_exact(&mut rest).map_err(|_| truncated())?;
+                let crc = u16::from_le_bytes([start[21], rest[0]]);
+                let next_size = usize::from(read_u16(&rest, 2));
+                let extended = read_extended_headers(&mut self.reader, next_size)?;
+                // Level 2 headers are padded to their declared size
+                let padding = (header_size as u64).saturating_sub(26 + extended.size);
+                self.reader
+                    .seek(SeekFrom::Current(i64::try_from(padding)?))?;
+                let name = extended.file_name.unwrap_or_default();
+                (join_name(extended.directory, name), crc)
+            }
+            _ => {
+                return Err(Error::other(format!(
+                    "Unsupported LHA header level {level}"
+                )));
+            }
+        };
+
+        let is_dir = &method == b"-lhd-";
+        let mut name = name;
+        if is_dir && !name.ends_with(b"/") {
+            name.push(b'/');
+        }
+        let offset = self.reader.stream_position()?;
+        self.reader
+            .seek(SeekFrom::Current(i64::try_from(compressed_size)?))?;
+        self.entries.push(Entry {
+            name,
+            unicode: false,
+            is_dir,
+            host: None,
+            size: original_size,
+            compressed_size,
+        });
+        self.locations.push(Location {
+            offset,
+            compressed_size,
+            original_size,
+            method,
+            crc,
+        });
+        Ok(true)
+    }
+}
+
+impl ReadOnlyArchive for Archive {
+    fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    fn extract_entry(&mut self, index: usize, writer: &mut dyn Write) -> Result<()> {
+        let location = &self.locations[index];
+        self.reader.seek(SeekFrom::Start(location.offset))?;
+        let compressed = (&mut self.reader).take(location.compressed_size);
+        let size = usize::try_from(location.original_size)?;
+        let data = match &location.method {
+            b"-lh0-" | b"-lz4-" => {
+                let mut data = Vec::with_capacity(size);
+                compressed.take(size as u64).read_to_end(&mut data)?;
+                data
+            }
+            b"-lh5-" => lzh::decode(compressed, lzh::LH5, size)?,
+            b"-lh6-" => lzh::decode(compressed, lzh::LH6, size)?,
+            b"-lh7-" => lzh::decode(compressed, lzh::LH7, size)?,
+            method => {
+                return Err(Error::other(format!(
+                    "Unsupported LHA method {}",
+                    String::from_utf8_lossy(method)
+                )));
+            }
+        };
+        if data.len() != size || crc16(&data) != location.crc {
+            return Err(Error::other("CRC mismatch, the entry is damaged"));
+        }
+        writer.write_all(&data)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,3466 @@
+//! Fixing the encoding of file names inside ZIP and other archives
+//!
+//! [`fix_archive`] converts the names of one archive in place and returns a
+//! [`FixReport`] of what was done to each entry. The `runzip` command line
+//! tool is built on top of it.
+
+#![warn(clippy::pedantic)]
+
+use chardetng::EncodingDetector;
+use encoding::{
+    BIG5, EUC_JP, EUC_KR, GBK, IBM437, IBM850, IBM852, IBM862, IBM866, IBM1125, ISO_8859_7,
+    ISO_8859_8, KOI8_R, KOI8_U, KZ_1048, PTCP154, SHIFT_JIS, UTF_8, WINDOWS_1251, WINDOWS_1253,
+    WINDOWS_1255,
+};
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::SystemTime;
+use tempfile::NamedTempFile;
+use unicode_normalization::UnicodeNormalization;
+
+#[cfg(feature = "cli")]
+mod arj;
+#[cfg(feature = "async")]
+mod async_fix;
+#[cfg(feature = "cli")]
+mod batch;
+mod builder;
+#[cfg(feature = "cli")]
+mod checksum;
+#[doc(hidden)]
+#[cfg(feature = "cli")]
+pub mod cli;
+#[cfg(feature = "cli")]
+mod compare;
+mod compression;
+#[cfg(feature = "cli")]
+mod config;
+#[cfg(feature = "cli")]
+mod console;
+mod contents;
+#[cfg(feature = "cli")]
+mod create;
+#[cfg(feature = "cli")]
+#[cfg(feature = "cli")]
+mod csv;
+#[cfg(feature = "cli")]
+mod diff;
+mod encoding;
+mod error;
+mod events;
+#[cfg(feature = "cli")]
+mod extract;
+mod fixer;
+mod formats;
+#[cfg(unix)]
+#[cfg(feature = "cli")]
+mod fstree;
+mod glob;
+mod gzip;
+mod hex;
+mod hints;
+#[cfg(feature = "cli")]
+mod iso;
+#[cfg(feature = "cli")]
+mod journal;
+mod lang;
+#[cfg(feature = "cli")]
+mod lha;
+mod limits;
+#[cfg(feature = "cli")]
+mod listing;
+mod lock;
+#[cfg(feature = "cli")]
+mod lzh;
+mod mojibake;
+#[cfg(all(feature = "mount", any(target_os = "linux", target_os = "macos")))]
+mod mount;
+#[cfg(feature = "cli")]
+mod porcelain;
+#[cfg(feature = "cli")]
+mod rar;
+mod rawzip;
+mod renamemap;
+mod renames;
+#[cfg(feature = "cli")]
+mod report;
+mod sanitize;
+mod sevenz;
+#[cfg(feature = "cli")]
+mod stats;
+mod tarball;
+mod translit;
+#[cfg(feature = "cli")]
+mod undo;
+mod verify;
+
+#[cfg(feature = "async")]
+pub use async_fix::fix_archive_async;
+pub use builder::FixOptionsBuilder;
+use compression::Encoder;
+pub use encoding::Encoding;
+pub use error::{Cause, Error};
+use error::{Context, Result};
+pub use events::Events;
+use events::ProgressWriter;
+pub use fixer::ArchiveFixer;
+pub use lang::Lang;
+use lang::{Profile, Script};
+use limits::Limits;
+use lock::ArchiveLock;
+use memmap2::Mmap;
+use rawzip::{RawArchive, RawWriter};
+use renamemap::RenameMap;
+use renames::RenameRules;
+
+/// Form the fixed names are written in
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Target {
+    /// Unicode names
+    #[cfg_attr(feature = "cli", value(name = "utf-8"))]
+    Utf8,
+    /// ASCII transliteration of Cyrillic (Привет.txt -> Privet.txt)
+    Translit,
+    /// DOS/Windows console code page, for unzip tools without UTF-8 support
+    Cp866,
+    /// Windows ANSI code page
+    #[cfg_attr(feature = "cli", value(name = "windows-1251"))]
+    Windows1251,
+    /// Russian Unix code page
+    #[cfg_attr(feature = "cli", value(name = "koi8-r"))]
+    Koi8R,
+    /// Ukrainian Unix code page
+    #[cfg_attr(feature = "cli", value(name = "koi8-u"))]
+    Koi8U,
+    /// Japanese Windows code page (Shift JIS)
+    Cp932,
+    /// Simplified Chinese Windows code page (GBK)
+    Cp936,
+    /// Korean Windows code page (Unified Hangul Code)
+    Cp949,
+    /// Traditional Chinese Windows code page (Big5)
+    Cp950,
+    /// Central European DOS code page (Latin-2)
+    Cp852,
+    /// Western European DOS code page (Latin-1)
+    Cp850,
+}
+
+impl std::str::FromStr for Target {
+    type Err = Error;
+
+    /// Target by the name `--target` takes, such as `utf-8` or `cp866`,
+    /// ignoring case
+    fn from_str(name: &str) -> Result<Self, Error> {
+        match name.to_lowercase().as_str() {
+            "utf-8" => Ok(Target::Utf8),
+            "translit" => Ok(Target::Translit),
+            "cp866" => Ok(Target::Cp866),
+            "windows-1251" => Ok(Target::Windows1251),
+            "koi8-r" => Ok(Target::Koi8R),
+            "koi8-u" => Ok(Target::Koi8U),
+            "cp932" => Ok(Target::Cp932),
+            "cp936" => Ok(Target::Cp936),
+            "cp949" => Ok(Target::Cp949),
+            "cp950" => Ok(Target::Cp950),
+            "cp852" => Ok(Target::Cp852),
+            "cp850" => Ok(Target::Cp850),
+            _ => Err(Error::UnsupportedEncoding(name.to_string())),
+        }
+    }
+}
+
+impl Target {
+    /// Encoding the new names are written in
+    fn encoding(self) -> &'static Encoding {
+        match self {
+            Target::Utf8 | Target::Translit => UTF_8,
+            Target::Cp866 => IBM866,
+            Target::Windows1251 => WINDOWS_1251,
+            Target::Koi8R => KOI8_R,
+            Target::Koi8U => KOI8_U,
+            Target::Cp932 => SHIFT_JIS,
+            Target::Cp936 => GBK,
+            Target::Cp949 => EUC_KR,
+            Target::Cp950 => BIG5,
+            Target::Cp852 => IBM852,
+            Target::Cp850 => IBM850,
+        }
+    }
+}
+
+/// Windows system locale, which decides the code page that Windows tools
+/// without UTF-8 support read names in
+#[cfg(feature = "cli")]
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum WindowsLocale {
+    /// Russian (cp866)
+    Ru,
+    /// Ukrainian (cp866)
+    Uk,
+    /// Belarusian (cp866)
+    Be,
+    /// Japanese (cp932)
+    Ja,
+    /// Korean (cp949)
+    Ko,
+    /// Chinese, PRC (cp936)
+    #[value(name = "zh-cn")]
+    ZhCn,
+    /// Chinese, Taiwan (cp950)
+    #[value(name = "zh-tw")]
+    ZhTw,
+    /// Polish (cp852)
+    Pl,
+    /// Czech (cp852)
+    Cs,
+    /// Hungarian (cp852)
+    Hu,
+    /// German (cp850)
+    De,
+    /// French (cp850)
+    Fr,
+    /// Spanish (cp850)
+    Es,
+    /// Italian (cp850)
+    It,
+}
+
+#[cfg(feature = "cli")]
+impl WindowsLocale {
+    /// OEM code page of the locale, used by Explorer and the console for
+    /// names in ZIP archives
+    fn target(self) -> Target {
+        match self {
+            WindowsLocale::Ru | WindowsLocale::Uk | WindowsLocale::Be => Target::Cp866,
+            WindowsLocale::Ja => Target::Cp932,
+            WindowsLocale::Ko => Target::Cp949,
+            WindowsLocale::ZhCn => Target::Cp936,
+            WindowsLocale::ZhTw => Target::Cp950,
+            WindowsLocale::Pl | WindowsLocale::Cs | WindowsLocale::Hu => Target::Cp852,
+            WindowsLocale::De | WindowsLocale::Fr | WindowsLocale::Es | WindowsLocale::It => {
+                Target::Cp850
+            }
+        }
+    }
+}
+
+/// Extractor an archive is fixed for with --for, which decides the form of
+/// the names and what else is written for them
+#[cfg(feature = "cli")]
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Destination {
+    /// Russian Windows: cp866 names made on MS-DOS for Explorer, with Unicode
+    /// Path extra fields for the extractors that read them
+    WindowsRu,
+    /// Windows XP and older: cp866 names made on MS-DOS only, renaming what
+    /// Windows cannot create
+    WindowsOld,
+    /// Total Commander: cp866 names with Unicode Path extra fields, which it
+    /// reads since version 7.5
+    TotalCommander,
+    /// The RAR archiver for Windows, version 5 and later: UTF-8 names,
+    /// renaming what Windows cannot create
+    Winrar5,
+}
+
+#[cfg(feature = "cli")]
+impl Destination {
+    fn target(self) -> Target {
+        match self {
+            Destination::WindowsRu | Destination::WindowsOld | Destination::TotalCommander => {
+                Target::Cp866
+            }
+            Destination::Winrar5 => Target::Utf8,
+        }
+    }
+
+    fn dos_host(self) -> bool {
+        self.target() != Target::Utf8
+    }
+
+    fn unicode_extra(self) -> bool {
+        matches!(self, Destination::WindowsRu | Destination::TotalCommander)
+    }
+
+    fn sanitize_windows(self) -> bool {
+        matches!(self, Destination::WindowsOld | Destination::Winrar5)
+    }
+}
+
+/// Where an entry name is taken from
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum NameSource {
+    /// The central directory, as read by most extractors
+    Central,
+    /// The local file header in front of the entry data
+    Local,
+}
+
+/// Unicode normalization form of the converted names
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Normalization {
+    /// Keep the names as decoded
+    None,
+    /// Composed, as most systems write names
+    Nfc,
+    /// Decomposed, as HFS+ on macOS stores names
+    Nfd,
+    /// Composed, with compatibility characters (ﬁ, ², full-width letters)
+    /// replaced, for search indexes
+    Nfkc,
+    /// Decomposed, with compatibility characters replaced
+    Nfkd,
+}
+
+/// Case of the converted names
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Case {
+    /// Keep the case of the names
+    Preserve,
+    Lower,
+    Upper,
+}
+
+/// Order of the entries in a rewritten ZIP archive
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum SortOrder {
+    /// The order of the original archive
+    None,
+    /// By file name, then by path
+    Name,
+    /// By path, keeping the contents of every directory together
+    Path,
+}
+
+/// Handling of entries stored more than once under the same name
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Dedupe {
+    /// Keep the first entry with a name, the one most extractors overwrite
+    KeepFirst,
+    /// Keep the last entry with a name, the one extracted last
+    KeepLast,
+    /// Refuse to rewrite the archive
+    Error,
+    /// Keep every entry, adding `~N` before the extension of the later
+    /// names
+    Suffix,
+}
+
+/// Handling of characters the target encoding cannot represent
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Unencodable {
+    /// Keep the original name of the entry
+    Error,
+    /// Replace each such character with '_'
+    Replace,
+    /// Transliterate such characters to ASCII
+    Translit,
+    /// Drop such characters
+    Skip,
+}
+
+/// Copy of the original archive kept when a fixed one replaces it
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Backup {
+    /// Keep no copy
+    #[default]
+    None,
+    /// Keep it as `<file>~`, replacing an older backup
+    Simple,
+    /// Keep it as `<file>.~1~`, `<file>.~2~` and so on, the first number
+    /// not taken
+    Numbered,
+}
+
+/// Handling of control characters (newlines, bell, ...) in names
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum ControlChars {
+    /// Keep them, warning about each such name
+    Keep,
+    /// Replace each one with '_'
+    Replace,
+    /// Replace each one with the %XX escapes of its bytes
+    Escape,
+}
+
+fn convert_encoding(
+    text: &[u8],
+    from_encoding: &'static Encoding,
+    to_encoding: &'static Encoding,
+    on_unencodable: Unencodable,
+) -> Result<Vec<u8>> {
+    // First, decode from source encoding
+    let (decoded, had_errors) = from_encoding.decode(text);
+    if had_errors {
+        return Err(Error::other(format!(
+            "Failed to decode from {}",
+            from_encoding.name()
+        )));
+    }
+
+    // Then encode to target encoding
+    encode_text(&decoded, to_encoding, on_unencodable)
+}
+
+/// Encode `text`, handling characters `encoding` cannot represent as
+/// requested; fails with [`Error::UnencodableName`] if that is an error
+fn encode_text(
+    text: &str,
+    encoding: &'static Encoding,
+    on_unencodable: Unencodable,
+) -> Result<Vec<u8>, Error> {
+    // Legacy code pages only have precomposed letters (й, not и + U+0306)
+    let composed;
+    let text = if encoding == UTF_8 {
+        text
+    } else {
+        composed = text.nfc().collect::<String>();
+        &composed
+    };
+
+    let (encoded, had_errors) = encoding.encode(text);
+    if !had_errors {
+        return Ok(encoded.into_owned());
+    }
+    if on_unencodable == Unencodable::Error {
+        return Err(Error::UnencodableName {
+            name: text.to_string(),
+            encoding,
+        });
+    }
+
+    // encoding_rs substitutes HTML numeric character references, so go
+    // character by character instead
+    let mut result = Vec::with_capacity(text.len());
+    let mut buffer = [0; 4];
+    for c in text.chars() {
+        let c = c.encode_utf8(&mut buffer);
+        let (encoded, had_errors) = encoding.encode(c);
+        if !had_errors {
+            result.extend_from_slice(&encoded);
+            continue;
+        }
+        match on_unencodable {
+            Unencodable::Replace => result.push(b'_'),
+            Unencodable::Translit => {
+                result.extend_from_slice(translit::transliterate(c).as_bytes());
+            }
+            Unencodable::Skip | Unencodable::Error => {}
+        }
+    }
+    Ok(result)
+}
+
+/// Decode a name for display
+fn display_name(name: &[u8], encoding: &'static Encoding) -> String {
+    encoding.decode_without_bom_handling(name).0.into_owned()
+}
+
+/// Check if filename is valid UTF-8 with Cyrillic content
+fn is_valid_utf8_cyrillic(filename: &[u8]) -> bool {
+    if let Ok(utf8_str) = std::str::from_utf8(filename) {
+        // Only consider it valid UTF-8 if it contains Cyrillic characters
+        utf8_str
+            .chars()
+            .any(|c| matches!(c, '\u{0400}'..='\u{04FF}' | '\u{0500}'..='\u{052F}'))
+    } else {
+        false
+    }
+}
+
+/// Legacy encodings auto-detection picks from for the default language
+/// unless the configuration file lists other `candidates`
+fn default_candidates() -> Vec<&'static Encoding> {
+    Lang::default().candidates()
+}
+
+/// Operating system an archive was made on, a hint for detection
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Host {
+    /// MS-DOS and Windows, whose names are in CP866 or Windows-1251, or
+    /// CP437 outside Russia
+    Dos,
+    /// Unix, whose names are in KOI8 (or UTF-8)
+    Unix,
+}
+
+impl Host {
+    /// Host of a ZIP entry from its "version made by" field
+    fn from_version_made_by(version_made_by: u16) -> Option<Host> {
+        match version_made_by >> 8 {
+            0 | 6 | 11 | 14 => Some(Host::Dos), // FAT, HPFS, NTFS, VFAT
+            3 | 19 => Some(Host::Unix),         // Unix, OS X
+            _ => None,
+        }
+    }
+
+    /// Legacy encodings names made on this host are usually in
+    fn encodings(self) -> [&'static Encoding; 2] {
+        match self {
+            Host::Dos => [IBM866, WINDOWS_1251],
+            Host::Unix => [KOI8_R, KOI8_U],
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Host::Dos => "MS-DOS/Windows",
+            Host::Unix => "Unix",
+        }
+    }
+}
+
+/// What is known about an archive besides a name, to detect its encoding
+/// where the name alone is ambiguous
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+struct Hints {
+    /// System the archive was made on
+    host: Option<Host>,
+    /// Encoding of the text files in the archive (`--content-hints`)
+    content: Option<&'static Encoding>,
+}
+
+impl Hints {
+    #[cfg(feature = "cli")]
+    fn from_host(host: Option<Host>) -> Self {
+        Hints {
+            host,
+            content: None,
+        }
+    }
+}
+
+/// Whether `encoding` decodes `filename` to Cyrillic letters as plausibly
+/// as any of the `candidates`, so that the name alone cannot rule it out
+fn fits_name(
+    filename: &[u8],
+    encoding: &'static Encoding,
+    candidates: &[&'static Encoding],
+) -> bool {
+    let Some(oddness) = cyrillic_oddness(filename, encoding) else {
+        return false;
+    };
+    candidates
+        .iter()
+        .filter_map(|&candidate| cyrillic_oddness(filename, candidate))
+        .all(|other| oddness <= other)
+}
+
+/// How odd `name` decodes in `encoding` for a Cyrillic name: `None` if it
+/// cannot be decoded or has non-ASCII characters other than Cyrillic
+/// letters, otherwise the number of capitals following lowercase letters,
+/// which names decoded in the wrong code page are full of
+fn cyrillic_oddness(name: &[u8], encoding: &'static Encoding) -> Option<usize> {
+    let text = encoding.decode_without_bom_handling_and_without_replacement(name)?;
+    if text.chars().any(|c| !c.is_ascii() && !lang::is_cyrillic(c)) {
+        return None;
+    }
+    let mut oddness = 0;
+    let mut previous_lowercase = false;
+    for c in text.chars() {
+        if previous_lowercase && c.is_uppercase() {
+            oddness += 1;
+        }
+        previous_lowercase = c.is_lowercase();
+    }
+    Some(oddness)
+}
+
+/// Whether `text` has a word mixing Latin and Cyrillic letters, which a name
+/// decoded in the wrong code page is more likely to have than a real one
+fn mixes_scripts(text: &str) -> bool {
+    text.split(|c: char| !c.is_alphabetic()).any(|word| {
+        word.chars().any(|c| c.is_ascii_alphabetic()) && word.chars().any(lang::is_cyrillic)
+    })
+}
+
+/// Whether `filename` reads as a Western name in CP437, the code page
+/// APPNOTE assigns to names without the EFS flag: every non-ASCII character
+/// is an accented Latin letter, while the Cyrillic `candidates` decode it
+/// to no Cyrillic name or to words mixing Latin and Cyrillic letters
+fn is_cp437_name(filename: &[u8], candidates: &[&'static Encoding]) -> bool {
+    let text = IBM437.decode_without_bom_handling(filename).0;
+    text.chars()
+        .all(|c| c.is_ascii() || (c.is_alphabetic() && matches!(c, 'À'..='ÿ' | 'ƒ')))
+        && candidates
+            .iter()
+            .filter(|&&encoding| encoding != IBM437)
+            .all(|&encoding| {
+                cyrillic_oddness(filename, encoding).is_none()
+                    || mixes_scripts(&encoding.decode_without_bom_handling(filename).0)
+            })
+}
+
+/// How many letters of `name` decoded in `encoding` are among the most
+/// frequent in one of the languages of `profiles`, which tells apart code
+/// pages that decode a short name to equally plausible Cyrillic letters
+fn frequent_letters(name: &[u8], encoding: &'static Encoding, profiles: &[&Profile]) -> usize {
+    let text = encoding.decode_without_bom_handling(name).0;
+    text.chars()
+        .filter(|&c| profiles.iter().any(|profile| profile.is_frequent(c)))
+        .count()
+}
+
+/// Encoding the `host` that made the archive suggests for `filename` when
+/// the `detected` one is not a candidate or does not decode it to Cyrillic
+/// letters, as happens with short names
+fn host_encoding(
+    filename: &[u8],
+    detected: &'static Encoding,
+    host: Host,
+    candidates: &[&'static Encoding],
+    profiles: &[&Profile],
+) -> Option<&'static Encoding> {
+    if candidates.contains(&detected) && cyrillic_oddness(filename, detected).is_some() {
+        return None;
+    }
+    host.encodings()
+        .into_iter()
+        .filter(|encoding| candidates.contains(encoding))
+        .filter_map(|encoding| Some((encoding, cyrillic_oddness(filename, encoding)?)))
+        .min_by_key(|&(encoding, oddness)| {
+            (
+                oddness,
+                Reverse(frequent_letters(filename, encoding, profiles)),
+            )
+        })
+        .map(|(encoding, _)| encoding)
+}
+
+/// Candidate code page chardetng knows nothing of that fits `filename`
+/// better than the `detected` one, such as CP1125 for a Ukrainian name it
+/// reads as CP866: one decoding it to letters of a language of `profiles`
+/// where `detected` does not, or to fewer capitals following lowercase
+/// letters, or to more frequent letters
+fn unguessed_encoding(
+    filename: &[u8],
+    detected: &'static Encoding,
+    candidates: &[&'static Encoding],
+    profiles: &[&Profile],
+) -> Option<&'static Encoding> {
+    let fit = |encoding: &'static Encoding| {
+        let oddness = cyrillic_oddness(filename, encoding)?;
+        let text = encoding.decode_without_bom_handling(filename).0;
+        let spelled = profiles.iter().any(|profile| profile.spells(&text));
+        Some((
+            Reverse(spelled),
+            oddness,
+            Reverse(frequent_letters(filename, encoding, profiles)),
+        ))
+    };
+    let detected_fit = fit(detected);
+    candidates
+        .iter()
+        .filter(|encoding| !encoding.is_standard())
+        .filter_map(|&encoding| Some((encoding, fit(encoding)?)))
+        .filter(|(_, fit)| detected_fit.is_none_or(|detected| *fit < detected))
+        .min_by_key(|&(_, fit)| fit)
+        .map(|(encoding, _)| encoding)
+}
+
+/// Bonus to the score of the encoding chardetng guesses, which weighs the
+/// pairs of characters that letter counts miss
+const GUESS_BONUS: f64 = 0.5;
+
+/// Detect the encoding of `filename` among the languages of `lang`: the
+/// Cyrillic ones with `detect_cyrillic_encoding`, the others by scoring the
+/// decodings in their encodings, picking the decoding that fits best;
+/// `explain` is told how, line by line
+fn detect_name_encoding(
+    filename: &[u8],
+    explain: &dyn Fn(std::fmt::Arguments<'_>),
+    candidates: &[&'static Encoding],
+    hints: Hints,
+    lang: Lang,
+) -> &'static Encoding {
+    let (cyrillic, others): (Vec<&Profile>, Vec<&Profile>) = lang
+        .profiles()
+        .into_iter()
+        .partition(|profile| profile.script == Script::Cyrillic);
+    if others.is_empty() {
+        return detect_cyrillic_encoding(filename, explain, candidates, hints, &cyrillic);
+    }
+    // Legacy names are hardly ever valid UTF-8 in the scripts with
+    // multi-byte encodings
+    if std::str::from_utf8(filename).is_ok() {
+        return UTF_8;
+    }
+
+    let mut detector = EncodingDetector::new();
+    detector.feed(filename, true);
+    let guess = Encoding::for_standard(detector.guess(None, true));
+
+    let mut fallback = UTF_8;
+    let mut best: Option<(&'static Encoding, f64)> = None;
+    let mut consider = |profile: &Profile, encoding: &'static Encoding| {
+        let Some(mut score) = profile.score(filename, encoding) else {
+            return;
+        };
+        if guess == Some(encoding) {
+            score += GUESS_BONUS;
+        }
+        explain(format_args!(
+            "\t{:?} in {}: {score:.2}",
+            profile.lang,
+            encoding.name()
+        ));
+        if best.is_none_or(|(_, best)| score > best) {
+            best = Some((encoding, score));
+        }
+    };
+    if !cyrillic.is_empty() {
+        fallback = detect_cyrillic_encoding(filename, explain, candidates, hints, &cyrillic);
+        for &profile in &cyrillic {
+            consider(profile, fallback);
+        }
+    }
+    for &profile in &others {
+        for &encoding in profile.encodings {
+            if candidates.contains(&encoding) {
+                consider(profile, encoding);
+            }
+        }
+    }
+    best.filter(|&(_, score)| score > 0.0)
+        .map_or(fallback, |(encoding, _)| encoding)
+}
+
+/// `explain` for detection whose reasons are not shown
+fn unexplained(_line: std::fmt::Arguments<'_>) {}
+
+/// Detect the encoding of `filename`, taking the encoding of the text files
+/// in the archive and the system it was made on as `hints` where the name
+/// is ambiguous or chardetng is unsure; `profiles` are the Cyrillic
+/// languages detected
+fn detect_cyrillic_encoding(
+    filename: &[u8],
+    explain: &dyn Fn(std::fmt::Arguments<'_>),
+    candidates: &[&'static Encoding],
+    hints: Hints,
+    profiles: &[&Profile],
+) -> &'static Encoding {
+    // First, check if the filename is already valid UTF-8 with Cyrillic content
+    if is_valid_utf8_cyrillic(filename) {
+        explain(format_args!("For filename detection:"));
+        explain(format_args!("\tAlready valid UTF-8 with Cyrillic content"));
+        return UTF_8;
+    }
+
+    // Check for pure ASCII (which is also valid UTF-8)
+    if let Ok(utf8_str) = std::str::from_utf8(filename)
+        && !utf8_str.chars().any(|c| c as u32 > 127)
+    {
+        explain(format_args!("For filename detection:"));
+        explain(format_args!("\tPure ASCII, treating as UTF-8"));
+        return UTF_8;
+    }
+
+    // Use chardetng for encoding detection
+    let mut detector = EncodingDetector::new();
+    detector.feed(filename, true);
+    let guess = detector.guess(None, true);
+
+    explain(format_args!("For filename detection:"));
+    explain(format_args!("\tchardetng detected: {}", guess.name()));
+    // Encodings names cannot be in are no candidates, like UTF-8
+    let detected_encoding = Encoding::for_standard(guess).unwrap_or(UTF_8);
+
+    if let Some(encoding) = hints.content
+        && fits_name(filename, encoding, candidates)
+    {
+        explain(format_args!(
+            "\tText files in the archive suggest {}",
+            encoding.name()
+        ));
+        return encoding;
+    }
+
+    // Names made on Unix are in the charset of the locale, not in CP437
+    if hints.host != Some(Host::Unix)
+        && candidates.contains(&IBM437)
+        && is_cp437_name(filename, candidates)
+    {
+        explain(format_args!(
+            "\tNo Cyrillic name, reading it as CP437 like APPNOTE says"
+        ));
+        return IBM437;
+    }
+
+    if let Some(encoding) = unguessed_encoding(filename, detected_encoding, candidates, profiles) {
+        explain(format_args!(
+            "\t{} fits better, which chardetng cannot guess",
+            encoding.name()
+        ));
+        return encoding;
+    }
+
+    if let Some(host) = hints.host
+        && let Some(encoding) =
+            host_encoding(filename, detected_encoding, host, candidates, profiles)
+    {
+        explain(format_args!(
+            "\tMade on {}, which suggests {}",
+            host.name(),
+            encoding.name()
+        ));
+        return encoding;
+    }
+
+    // Check if the detected encoding is one of the candidates
+    if detected_encoding == UTF_8 || candidates.contains(&detected_encoding) {
+        detected_encoding
+    } else {
+        // For other encodings, default to UTF-8 (maintains original behavior)
+        explain(format_args!(
+            "\tNot a candidate encoding, defaulting to UTF-8"
+        ));
+        UTF_8
+    }
+}
+
+/// How well `name` decoded in `encoding` fits the language of `lang` it
+/// fits best, from 0 to 1: 1 for valid UTF-8, [`Profile::score`] capped for
+/// legacy encodings; `None` if no language has such a decoding
+fn fit(name: &[u8], encoding: &'static Encoding, lang: Lang) -> Option<f64> {
+    if encoding == UTF_8 {
+        return std::str::from_utf8(name).is_ok().then_some(1.0);
+    }
+    lang.profiles()
+        .into_iter()
+        .filter_map(|profile| profile.score(name, encoding))
+        .reduce(f64::max)
+        .map(|score| score.clamp(0.0, 1.0))
+}
+
+/// Confidence, from 0 to 1, that `name` is in `encoding`: how well the
+/// decoding fits a language of `lang`, less how well the best of UTF-8 and
+/// the other `candidates` fits where it decodes the name to other text.
+/// Encodings of no language, like CP437, fit fully where they decode the
+/// name, so only the other readings lower their confidence.
+#[must_use]
+pub fn confidence(
+    name: &[u8],
+    encoding: &'static Encoding,
+    candidates: &[&'static Encoding],
+    lang: Lang,
+) -> f64 {
+    let Some(text) = encoding.decode_without_bom_handling_and_without_replacement(name) else {
+        return 0.0;
+    };
+    let known = encoding == UTF_8
+        || Lang::Auto
+            .profiles()
+            .iter()
+            .any(|profile| profile.encodings.contains(&encoding));
+    let own = if known {
+        fit(name, encoding, lang).unwrap_or(0.0)
+    } else {
+        1.0
+    };
+    let rival = candidates
+        .iter()
+        .chain([&UTF_8])
+        .filter(|&&other| {
+            other != encoding
+                && other
+                    .decode_without_bom_handling_and_without_replacement(name)
+                    .is_some_and(|other_text| other_text != text)
+        })
+        .filter_map(|&other| fit(name, other, lang))
+        .fold(0.0, f64::max);
+    (own - rival).max(0.0)
+}
+
+/// Encoding auto-detection picks for a single `name`, or UTF-8 for names
+/// that need no conversion
+#[must_use]
+pub fn detect_encoding(name: &[u8]) -> &'static Encoding {
+    detect_name_encoding(
+        name,
+        &unexplained,
+        &default_candidates(),
+        Hints::default(),
+        Lang::default(),
+    )
+}
+
+/// A reading of a name in one encoding, as [`detect`] ranks them
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub encoding: &'static Encoding,
+    /// The name decoded in `encoding`
+    pub decoded: String,
+    /// Confidence in `encoding`, from 0 to 1 (see [`confidence`])
+    pub score: f64,
+}
+
+/// Every reading of a single `name` in UTF-8 and the encodings
+/// auto-detection picks from, best first: the one [`detect_encoding`]
+/// picks, then the others by score. Encodings that cannot decode the name
+/// are left out, as are those decoding it to the same text as a better one.
+#[must_use]
+pub fn detect(name: &[u8]) -> Vec<Candidate> {
+    let candidates = default_candidates();
+    let lang = Lang::default();
+    let picked = detect_name_encoding(name, &unexplained, &candidates, Hints::default(), lang);
+    rank_candidates(name, picked, &candidates, lang)
+}
+
+/// The readings of `name` in UTF-8 and the `candidates` as [`detect`] ranks
+/// them, `picked` first
+fn rank_candidates(
+    name: &[u8],
+    picked: &'static Encoding,
+    candidates: &[&'static Encoding],
+    lang: Lang,
+) -> Vec<Candidate> {
+    let mut ranked: Vec<Candidate> = Vec::new();
+    for &encoding in [&picked, &UTF_8].into_iter().chain(candidates) {
+        let Some(decoded) = encoding.decode_without_bom_handling_and_without_replacement(name)
+        else {
+            continue;
+        };
+        if ranked.iter().any(|candidate| candidate.decoded == decoded) {
+            continue;
+        }
+        ranked.push(Candidate {
+            encoding,
+            decoded: decoded.into_owned(),
+            score: confidence(name, encoding, candidates, lang),
+        });
+    }
+    ranked.sort_by(|a, b| {
+        (b.encoding == picked)
+            .cmp(&(a.encoding == picked))
+            .then(b.score.total_cmp(&a.score))
+    });
+    ranked
+}
+
+/// Convert a single `name` as [`fix_archive`] would, returning the new name
+/// and what was done
+#[must_use]
+pub fn convert_name(name: &[u8], options: &FixOptions) -> (Vec<u8>, Action) {
+    let mut outcome = process_entry_name(name, false, Hints::default(), options);
+    apply_name_fixups(&mut outcome, options, &HashSet::new());
+    (outcome.new_name, outcome.action)
+}
+
+/// Convert a string encoding name to the corresponding `encoding_rs` Encoding
+///
+/// # Errors
+///
+/// Fails with [`Error::UnsupportedEncoding`] for names of encodings runzip
+/// does not support.
+pub fn string_to_encoding(encoding_name: &str) -> Result<&'static Encoding, Error> {
+    match encoding_name.to_lowercase().as_str() {
+        "utf-8" | "utf-8-mac" => Ok(UTF_8), // Treat UTF-8-MAC as UTF-8 for simplicity
+        "windows-1251" => Ok(WINDOWS_1251),
+        "cp866" => Ok(IBM866),
+        "koi8-r" => Ok(KOI8_R),
+        "koi8-u" => Ok(KOI8_U),
+        "cp437" | "ibm437" => Ok(IBM437),
+        "windows-1253" => Ok(WINDOWS_1253),
+        "iso-8859-7" => Ok(ISO_8859_7),
+        "windows-1255" => Ok(WINDOWS_1255),
+        "iso-8859-8" => Ok(ISO_8859_8),
+        "cp862" | "ibm862" => Ok(IBM862),
+        "cp852" | "ibm852" => Ok(IBM852),
+        "cp850" | "ibm850" => Ok(IBM850),
+        "cp1125" | "ibm1125" | "ruscii" => Ok(IBM1125),
+        "ptcp154" | "pt154" | "cp154" => Ok(PTCP154),
+        "kz-1048" | "kz1048" | "rk1048" => Ok(KZ_1048),
+        "shift_jis" | "cp932" => Ok(SHIFT_JIS),
+        "euc-jp" => Ok(EUC_JP),
+        "gbk" | "cp936" => Ok(GBK),
+        "big5" | "cp950" => Ok(BIG5),
+        "euc-kr" | "cp949" => Ok(EUC_KR),
+        _ => Err(Error::UnsupportedEncoding(encoding_name.to_string())),
+    }
+}
+
+/// What was (or would be) done with a single entry name
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    /// The EFS flag already marks the name as UTF-8
+    AlreadyUtf8,
+    /// The name does not need to be converted
+    Unchanged,
+    /// The name was (or would be) converted to the target encoding
+    Fixed,
+    /// Conversion failed, the original name is kept
+    Failed,
+}
+
+impl Action {
+    /// Name of the action in reports: `already-utf8`, `ok`, `fixed` (or
+    /// `would-fix` in a dry run) or `failed`
+    #[must_use]
+    pub fn label(self, dry_run: bool) -> &'static str {
+        match self {
+            Action::AlreadyUtf8 => "already-utf8",
+            Action::Unchanged => "ok",
+            Action::Fixed if dry_run => "would-fix",
+            Action::Fixed => "fixed",
+            Action::Failed => "failed",
+        }
+    }
+}
+
+#[cfg(feature = "cli")]
+/// Name of what detection found for a name, "UTF-8 misread as ..." for
+/// repaired mojibake; `None` if detection was bypassed
+fn detection_name(
+    encoding: Option<&'static Encoding>,
+    misread_as: Option<&'static Encoding>,
+) -> Option<String> {
+    match misread_as {
+        Some(misread_as) => Some(format!("UTF-8 misread as {}", misread_as.name())),
+        None => encoding.map(|encoding| encoding.name().to_string()),
+    }
+}
+
+/// What was (or would be) done with one entry of an archive
+#[derive(Debug)]
+pub struct EntryResult {
+    /// Archive the entry is in, `outer.zip/inner.zip` for nested archives
+    pub archive: PathBuf,
+    /// Position of the entry in the archive
+    pub index: usize,
+    /// Name as stored in the original archive
+    pub raw_name: Vec<u8>,
+    /// Name after fixing, decoded
+    pub new_name: String,
+    /// Source encoding of the name, `None` if detection was bypassed
+    pub encoding: Option<&'static Encoding>,
+    /// Code page the UTF-8 name had been misread in, if it was repaired
+    /// from mojibake ("Ð¿Ñ€Ð¸Ð²ÐµÑ‚" for "привет")
+    pub misread_as: Option<&'static Encoding>,
+    /// Confidence in `encoding`, from 0 to 1 (see [`confidence`]); `None`
+    /// if detection was bypassed
+    pub confidence: Option<f64>,
+    pub action: Action,
+    /// Whether the entry was left out of the new archive, deleted
+    /// (`--delete`) or as a duplicate (`--dedupe`)
+    pub removed: bool,
+}
+
+/// What rewriting an archive involves, worked out before anything is written
+#[derive(Debug, Clone, Copy)]
+pub struct Preflight {
+    /// Number of entries in the archive
+    pub entries: usize,
+    /// Number of entries whose names change
+    pub renamed: usize,
+    /// Number of entries left out, deleted (`--delete`) or duplicates
+    /// (`--dedupe`)
+    pub removed: usize,
+    /// Bytes of entry data to copy into the new archive
+    pub bytes_to_copy: u64,
+    /// Whether the entry data is copied as stored, without recompressing it
+    pub raw_copy: bool,
+    /// Estimated size of the new archive, which is written to a temporary
+    /// file before it replaces the original
+    pub temp_space: u64,
+}
+
+impl Preflight {
+    /// Print the plan, then ask `options.confirm` whether to go ahead
+    fn confirm(&self, path: &Path, options: &FixOptions) -> bool {
+        options.outcome(format_args!(
+            "{}: rename {} of {} entr{}, {}copy {} bytes of entry data {}, \
+             using about {} bytes of temporary space",
+            path.display(),
+            self.renamed,
+            self.entries,
+            if self.entries == 1 { "y" } else { "ies" },
+            match self.removed {
+                0 => String::new(),
+                1 => "remove 1 entry, ".to_string(),
+                removed => format!("remove {removed} entries, "),
+            },
+            self.bytes_to_copy,
+            if self.raw_copy {
+                "as stored"
+            } else {
+                "recompressing it"
+            },
+            self.temp_space
+        ));
+        if options.dry_run {
+            return false;
+        }
+        let confirmed = options.confirm.is_none_or(|confirm| confirm(path, self));
+        if !confirmed {
+            options.outcome(format_args!("{}: skipped", path.display()));
+        }
+        confirmed
+    }
+}
+
+/// Outcome of [`fix_archive`]
+#[derive(Debug, Default)]
+pub struct FixReport {
+    /// Every entry, including those of nested archives
+    pub entries: Vec<EntryResult>,
+    /// Number of entries renamed (or to be renamed in a dry run)
+    pub renamed: usize,
+    /// Number of entries whose names were fine already
+    pub skipped: usize,
+    /// Number of entries whose names could not be converted and were kept
+    pub errors: usize,
+}
+
+impl FixReport {
+    fn add(
+        &mut self,
+        archive: &Path,
+        index: usize,
+        raw_name: &[u8],
+        outcome: &EntryOutcome,
+        removed: bool,
+    ) {
+        match outcome.action {
+            Action::Fixed => self.renamed += 1,
+            Action::AlreadyUtf8 | Action::Unchanged => self.skipped += 1,
+            Action::Failed => self.errors += 1,
+        }
+        self.entries.push(EntryResult {
+            archive: archive.to_path_buf(),
+            index,
+            raw_name: raw_name.to_vec(),
+            new_name: outcome.display_name(),
+            encoding: outcome.encoding,
+            misread_as: outcome.misread_as,
+            confidence: outcome.confidence,
+            action: outcome.action,
+            removed,
+        });
+    }
+}
+
+/// The outcome of processing a single entry name
+struct EntryOutcome {
+    /// Source encoding of the name, `None` if detection was bypassed
+    encoding: Option<&'static Encoding>,
+    /// Code page the UTF-8 name was misread in, for repaired mojibake
+    misread_as: Option<&'static Encoding>,
+    /// Confidence in `encoding`, see [`EntryResult::confidence`]
+    confidence: Option<f64>,
+    new_name: Vec<u8>,
+    /// Encoding `new_name` is in
+    name_encoding: &'static Encoding,
+    action: Action,
+    /// New name a rename rule gives, kept for a name the target encoding
+    /// cannot represent
+    rule: Option<String>,
+}
+
+impl EntryOutcome {
+    fn new(
+        encoding: Option<&'static Encoding>,
+        new_name: Vec<u8>,
+        name_encoding: &'static Encoding,
+        action: Action,
+    ) -> Self {
+        EntryOutcome {
+            encoding,
+            misread_as: None,
+            confidence: None,
+            new_name,
+            name_encoding,
+            action,
+            rule: None,
+        }
+    }
+
+    /// The new name decoded for display
+    fn display_name(&self) -> String {
+        display_name(&self.new_name, self.name_encoding)
+    }
+
+    /// Tell `options.events` about the new name of the entry at `index` of
+    /// the archive at `path`, if it has one
+    fn announce_rename(&self, path: &Path, index: usize, old_name: &[u8], options: &FixOptions) {
+        if let Some(events) = options.events
+            && self.new_name != old_name
+        {
+            events.on_entry_renamed(path, index, old_name, &self.display_name());
+        }
+    }
+
+    /// Whether the new name should be flagged as UTF-8
+    ///
+    /// ASCII names are left unflagged unless they were flagged originally.
+    fn utf8_flag(&self, entry: &rawzip::Entry) -> bool {
+        self.name_encoding == UTF_8
+            && std::str::from_utf8(&self.new_name).is_ok()
+            && (entry.is_utf8() || !self.new_name.is_ascii())
+    }
+
+    /// The new name for an Info-ZIP Unicode Path extra field, if it is
+    /// legacy and `--unicode-extra` asks for one
+    fn unicode_name(&self, entry: &rawzip::Entry, options: &FixOptions) -> Option<String> {
+        (options.unicode_extra && !self.utf8_flag(entry) && !self.new_name.is_ascii())
+            .then(|| self.display_name())
+    }
+}
+
+/// Write buffer capacity unless --buffer-size is given
+const DEFAULT_BUFFER_SIZE: usize = 64 << 10;
+
+/// Leading directories to remove from entry names (`--strip-prefix`)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StripPrefix {
+    /// This many directories, whatever their names
+    Components(usize),
+    /// This directory, for the entries inside it
+    Path(String),
+}
+
+impl std::str::FromStr for StripPrefix {
+    type Err = String;
+
+    /// A number of directories or a path
+    fn from_str(text: &str) -> Result<Self, String> {
+        if let Ok(count) = text.parse() {
+            return Ok(StripPrefix::Components(count));
+        }
+        let path = text.trim_matches('/');
+        if path.is_empty() {
+            return Err("expected a number or a path".to_string());
+        }
+        Ok(StripPrefix::Path(path.to_string()))
+    }
+}
+
+#[cfg(feature = "cli")]
+/// Parse --buffer-size, which must be at least one byte
+fn parse_buffer_size(text: &str) -> Result<usize, String> {
+    limits::parse_size(text)
+        .ok()
+        .and_then(|size| usize::try_from(size).ok())
+        .filter(|&size| size > 0)
+        .ok_or_else(|| format!("invalid buffer size \"{text}\""))
+}
+
+/// How many levels of archives inside archives --recurse-archives descends
+const MAX_NESTING_DEPTH: usize = 8;
+
+/// Asked whether to rewrite the archive at a path with a plan
+pub type Confirm = dyn Fn(&Path, &Preflight) -> bool + Sync;
+
+/// Asked to choose the encoding of an ambiguous name among its readings,
+/// ranked as by [`detect`]; `None` keeps the detected one
+pub type ChooseEncoding = dyn Fn(&[u8], &[Candidate]) -> Option<&'static Encoding> + Sync;
+
+/// How close the score of another reading of a name has to come to that of
+/// the detected one for the name to be ambiguous
+pub const AMBIGUITY_MARGIN: f64 = 0.2;
+
+/// Detected encodings by `detection_key` and hints
+type DetectionCache = HashMap<(Vec<u8>, Hints), &'static Encoding>;
+
+/// How many detected encodings are remembered before they are forgotten,
+/// which bounds the memory of a fixer shared by a long-running server
+const MAX_CACHED_DETECTIONS: usize = 1 << 16;
+
+/// Settings shared by every archive processed in a run, made with
+/// [`FixOptions::builder`]
+#[allow(clippy::struct_excessive_bools)]
+pub struct FixOptions<'a> {
+    /// Report the renames without changing the archive
+    pub(crate) dry_run: bool,
+    /// Encoding of the names instead of auto-detection
+    pub(crate) source_encoding: Option<&'static Encoding>,
+    /// Encodings auto-detection may pick besides UTF-8
+    pub(crate) candidates: Vec<&'static Encoding>,
+    verbose: u8,
+    /// Write a rename map next to the archive for undoing the fix
+    pub(crate) write_rename_map: bool,
+    rename_rules: Option<&'a RenameRules>,
+    pub(crate) target: Target,
+    /// Mark entries as made on MS-DOS, as old Windows extractors expect of
+    /// names in the OEM code page (`-w`)
+    pub(crate) dos_host: bool,
+    /// Give legacy names Info-ZIP Unicode Path extra fields with their UTF-8
+    /// form, which Unicode-aware extractors prefer
+    pub(crate) unicode_extra: bool,
+    pub(crate) on_unencodable: Unencodable,
+    /// Fail, leaving the archive as it was, if any name cannot be converted
+    /// instead of keeping those names
+    pub(crate) strict: bool,
+    pub(crate) control_chars: ControlChars,
+    /// Replace backslashes separating directories with `/`
+    pub(crate) fix_separators: bool,
+    /// Remove leading separators, drive letters and UNC prefixes
+    pub(crate) strip_absolute: bool,
+    /// Leading directories to remove from the names
+    pub(crate) strip_prefix: Option<StripPrefix>,
+    /// Unicode normalization form to convert the names to
+    pub(crate) normalize: Normalization,
+    /// Case to convert the names to, after `strip_prefix` is removed and
+    /// before `add_prefix` is added
+    pub(crate) case: Case,
+    /// Directory to put every entry in, added after `strip_prefix` is
+    /// removed
+    pub(crate) add_prefix: Option<String>,
+    pub(crate) sanitize_paths: bool,
+    pub(crate) sanitize_windows: bool,
+    pub(crate) truncate_long_names: bool,
+    pub(crate) prefer_name: NameSource,
+    /// Order of the entries in rewritten ZIP archives, by their new names
+    pub(crate) sort: SortOrder,
+    /// Handling of entries with the same new name, `None` to keep them all
+    pub(crate) dedupe: Option<Dedupe>,
+    /// Patterns of entries to leave out of rewritten ZIP archives, matched
+    /// against their new names like `.gitignore` patterns
+    pub(crate) delete: Vec<String>,
+    /// Indices of the entries to fix, as `runzip list` numbers them; the
+    /// other entries are left as they are unless `entry_names` selects them.
+    /// With neither, every entry is fixed
+    pub(crate) entries: Vec<usize>,
+    /// Patterns of the entries to fix, matched against their names as
+    /// stored like `delete` patterns
+    pub(crate) entry_names: Vec<String>,
+    pub(crate) recurse_archives: bool,
+    /// Detect the encoding of ambiguous names in ZIP archives from the
+    /// `.txt`, `.nfo` and `.diz` files in them
+    pub(crate) content_hints: bool,
+    /// Language of the names, which decides the encodings detection picks
+    /// from along with `candidates`
+    pub(crate) lang: Lang,
+    /// Repair UTF-8 names misread in a single-byte code page and encoded
+    /// again ("Ð¿Ñ€Ð¸Ð²ÐµÑ‚" for "привет")
+    pub(crate) repair_mojibake: bool,
+    /// Extensions, in lowercase without the dot, of the ZIP entries whose
+    /// contents are converted to UTF-8 from a legacy encoding
+    pub(crate) fix_contents: Vec<String>,
+    pub(crate) force: bool,
+    pub(crate) zipalign: bool,
+    pub(crate) salvage: bool,
+    /// Read archives through a memory map
+    pub(crate) mmap: bool,
+    /// Capacity of the buffer in front of the new archive
+    pub(crate) buffer_size: usize,
+    /// Fix read-only archives too, keeping them read-only, instead of
+    /// failing
+    pub(crate) force_readonly: bool,
+    /// Give fixed archives back the modification time they had before
+    pub(crate) preserve_mtime: bool,
+    /// Copy of the original archive to keep when it is replaced
+    pub(crate) backup: Backup,
+    /// Directory for new archives instead of the one of the original
+    pub(crate) tmpdir: Option<&'a Path>,
+    /// Asked with the plan before an archive is rewritten, which is skipped
+    /// unless it returns true
+    pub(crate) confirm: Option<&'a Confirm>,
+    /// Asked for the encoding of names another reading of which scores
+    /// within [`AMBIGUITY_MARGIN`] of the detected one
+    pub(crate) choose_encoding: Option<&'a ChooseEncoding>,
+    /// Told about every entry and the bytes written
+    pub(crate) events: Option<&'a dyn Events>,
+    /// Set, from another thread or a signal handler, to stop the fix; the
+    /// archive being rewritten is left as it was
+    pub(crate) cancel: Option<&'a AtomicBool>,
+    limits: Limits,
+    /// Leave the outcome of every entry out of `events`, for commands with
+    /// their own output
+    quiet: bool,
+    /// Encodings detected for the names seen so far, shared by the threads
+    /// fixing archives with the same options
+    detected: Mutex<DetectionCache>,
+}
+
+/// The defaults of `runzip fix`: names are detected, converted to UTF-8
+/// and recorded in a rename map, and nothing is printed
+impl Default for FixOptions<'_> {
+    fn default() -> Self {
+        FixOptions {
+            dry_run: false,
+            source_encoding: None,
+            candidates: default_candidates(),
+            verbose: 0,
+            write_rename_map: true,
+            rename_rules: None,
+            target: Target::Utf8,
+            dos_host: false,
+            unicode_extra: false,
+            on_unencodable: Unencodable::Error,
+            strict: false,
+            control_chars: ControlChars::Keep,
+            fix_separators: false,
+            strip_absolute: false,
+            strip_prefix: None,
+            normalize: Normalization::None,
+            case: Case::Preserve,
+            add_prefix: None,
+            sanitize_paths: false,
+            sanitize_windows: false,
+            truncate_long_names: false,
+            prefer_name: NameSource::Central,
+            sort: SortOrder::None,
+            dedupe: None,
+            delete: Vec::new(),
+            entries: Vec::new(),
+            entry_names: Vec::new(),
+            recurse_archives: false,
+            content_hints: false,
+            lang: Lang::default(),
+            repair_mojibake: true,
+            fix_contents: Vec::new(),
+            force: false,
+            zipalign: false,
+            salvage: false,
+            mmap: false,
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            force_readonly: false,
+            preserve_mtime: false,
+            backup: Backup::None,
+            tmpdir: None,
+            confirm: None,
+            choose_encoding: None,
+            events: None,
+            cancel: None,
+            limits: Limits::default(),
+            quiet: true,
+            detected: Mutex::default(),
+        }
+    }
+}
+
+impl<'a> FixOptions<'a> {
+    /// Whether `entries` or `entry_names` select the entry at `index` named
+    /// `name`
+    fn selects(&self, index: usize, name: &[u8]) -> bool {
+        (self.entries.is_empty() && self.entry_names.is_empty())
+            || self.entries.contains(&index)
+            || self
+                .entry_names
+                .iter()
+                .any(|pattern| glob::matches(pattern, &String::from_utf8_lossy(name)))
+    }
+
+    #[cfg(feature = "cli")]
+    /// Builder of the options of commands that only read archives, with
+    /// every fixup off
+    fn read_only(
+        dry_run: bool,
+        source_encoding: Option<&'static Encoding>,
+        verbose: u8,
+        target: Target,
+    ) -> FixOptionsBuilder<'a> {
+        FixOptions::builder()
+            .dry_run(dry_run)
+            .source(source_encoding)
+            .verbose(verbose)
+            .write_rename_map(false)
+            .target(target)
+            .events(Some(&cli::Printer))
+            .quiet(false)
+    }
+
+    /// Temporary file to write the new version of `path` to
+    fn temp_file_for(&self, path: &Path) -> Result<NamedTempFile> {
+        let dir = self
+            .tmpdir
+            .unwrap_or_else(|| path.parent().unwrap_or_else(|| Path::new(".")));
+        NamedTempFile::new_in(dir).context(format!(
+            "Failed to create temporary file in {}",
+            dir.display()
+        ))
+    }
+
+    /// Buffered writer of a new archive to `file`, telling `events` how much
+    /// has been written
+    fn archive_writer<'f>(&self, file: &'f File) -> BufWriter<ProgressWriter<'a, &'f File>> {
+        BufWriter::with_capacity(
+            self.buffer_size,
+            ProgressWriter::new(file, self.events, self.cancel),
+        )
+    }
+
+    /// Tell `events` about a problem with the archive at `path` that does not
+    /// stop the fix
+    fn warn(&self, path: &Path, message: &str) {
+        if let Some(events) = self.events {
+            events.on_warning(path, message);
+        }
+    }
+
+    /// Tell `events` about a line of the progress of the fix
+    fn message(&self, message: std::fmt::Arguments<'_>) {
+        if let Some(events) = self.events {
+            events.on_message(&message.to_string());
+        }
+    }
+
+    /// Tell `events` about a line of the outcome of the fix, which `quiet`
+    /// leaves out
+    fn outcome(&self, message: std::fmt::Arguments<'_>) {
+        if !self.quiet {
+            self.message(message);
+        }
+    }
+
+    /// Whether `cancel` is set
+    fn cancelled(&self) -> bool {
+        self.cancel
+            .is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+    }
+
+    /// The encoding `choose_encoding` picks for `name` if it is ambiguous,
+    /// otherwise the `detected` one
+    fn choose_encoding(&self, name: &[u8], detected: &'static Encoding) -> &'static Encoding {
+        let Some(choose) = self.choose_encoding else {
+            return detected;
+        };
+        let ranked = rank_candidates(name, detected, &self.candidates, self.lang);
+        // The detected encoding is missing if it cannot decode the name
+        let ambiguous = match ranked.as_slice() {
+            [] => false,
+            [first, rest @ ..] => {
+                first.encoding != detected
+                    || rest
+                        .first()
+                        .is_some_and(|second| second.score >= first.score - AMBIGUITY_MARGIN)
+            }
+        };
+        if ambiguous {
+            choose(name, &ranked).unwrap_or(detected)
+        } else {
+            detected
+        }
+    }
+
+    /// Detect the encoding of `name`, once for all the names sharing its
+    /// `detection_key`
+    fn detect_encoding(&self, name: &[u8], hints: Hints) -> &'static Encoding {
+        let key = (detection_key(name).to_vec(), hints);
+        if let Some(&encoding) = self.detection_cache().get(&key) {
+            if self.verbose >= 1 {
+                self.message(format_args!("For filename detection:"));
+                self.message(format_args!(
+                    "\tSame as \"{}\"",
+                    String::from_utf8_lossy(&key.0)
+                ));
+            }
+            return encoding;
+        }
+        let explain = |line: std::fmt::Arguments<'_>| {
+            if self.verbose >= 1 {
+                self.message(line);
+            }
+        };
+        let encoding = detect_name_encoding(&key.0, &explain, &self.candidates, hints, self.lang);
+        if self.verbose >= 1 {
+            self.message(format_args!(
+                "\tConfidence: {:.2}",
+                confidence(&key.0, encoding, &self.candidates, self.lang)
+            ));
+        }
+        let encoding = self.choose_encoding(&key.0, encoding);
+        let mut cache = self.detection_cache();
+        if cache.len() >= MAX_CACHED_DETECTIONS {
+            cache.clear();
+        }
+        cache.insert(key, encoding);
+        encoding
+    }
+
+    /// The cache of detected encodings, locked
+    fn detection_cache(&self) -> std::sync::MutexGuard<'_, DetectionCache> {
+        // The cache stays consistent if a thread panics while holding it
+        self.detected
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+/// The part of `name` its encoding is detected from: up to the end of the
+/// last component with non-ASCII bytes, as the ASCII components after it
+/// tell nothing about the encoding
+///
+/// Entries of a directory with a legacy name and ASCII file names thus share
+/// one detection.
+fn detection_key(name: &[u8]) -> &[u8] {
+    let Some(last) = name.iter().rposition(|byte| !byte.is_ascii()) else {
+        return name;
+    };
+    match name[last..].iter().position(|&byte| byte == b'/') {
+        Some(slash) => &name[..=last + slash],
+        None => name,
+    }
+}
+
+/// The new name of the entry at `index` of the archive at `path`: renamed
+/// by `--map` or converted, then fixed up; kept as it is if not selected
+/// with `--entry`
+fn plan_entry(
+    path: &Path,
+    index: usize,
+    filename_bytes: &[u8],
+    utf8_flag: bool,
+    hints: Hints,
+    options: &FixOptions,
+    used_names: &HashSet<Vec<u8>>,
+) -> EntryOutcome {
+    if let Some(events) = options.events {
+        events.on_entry_start(path, index, filename_bytes);
+    }
+    if !options.selects(index, filename_bytes) {
+        return unselected_entry(filename_bytes, options);
+    }
+    let mut outcome = match options.rename_rules {
+        Some(rules) => apply_rename_rules(
+            filename_bytes,
+            (!utf8_flag).then_some(filename_bytes),
+            hints,
+            rules,
+            options,
+        ),
+        None => process_entry_name(filename_bytes, utf8_flag, hints, options),
+    };
+    apply_name_fixups(&mut outcome, options, used_names);
+    outcome.announce_rename(path, index, filename_bytes, options);
+    outcome
+}
+
+/// An entry `--entry` and `--entry-name` leave as it is
+fn unselected_entry(filename_bytes: &[u8], options: &FixOptions) -> EntryOutcome {
+    options.outcome(format_args!(
+        "  {}: OK (not selected)",
+        String::from_utf8_lossy(filename_bytes)
+    ));
+    EntryOutcome::new(None, filename_bytes.to_vec(), UTF_8, Action::Unchanged)
+}
+
+/// Rename an entry according to explicit rules, matched by its raw name or
+/// by `legacy_bytes`, its name in a legacy encoding, decoded in the source or
+/// detected encoding; the entry is otherwise kept as it is
+fn apply_rename_rules(
+    filename_bytes: &[u8],
+    legacy_bytes: Option<&[u8]>,
+    hints: Hints,
+    rules: &RenameRules,
+    options: &FixOptions,
+) -> EntryOutcome {
+    let filename_display = String::from_utf8_lossy(filename_bytes);
+    let target = options.target.encoding();
+
+    let (encoding, new_name) = match rules.lookup(filename_bytes) {
+        Some(new_name) => (None, Some(new_name)),
+        None => match legacy_bytes.filter(|_| rules.has_names()) {
+            Some(bytes) => {
+                let encoding = options
+                    .source_encoding
+                    .unwrap_or_else(|| options.detect_encoding(bytes, hints));
+                let new_name = encoding
+                    .decode_without_bom_handling_and_without_replacement(bytes)
+                    .and_then(|name| rules.lookup_decoded(&name));
+                (Some(encoding), new_name)
+            }
+            None => (None, None),
+        },
+    };
+    let Some(new_name) = new_name else {
+        options.outcome(format_args!("  {filename_display}: OK (not in map)"));
+        return EntryOutcome::new(None, filename_bytes.to_vec(), UTF_8, Action::Unchanged);
+    };
+
+    match encode_text(new_name, target, options.on_unencodable) {
+        Ok(new_name_bytes) if new_name_bytes != filename_bytes => {
+            options.outcome(format_args!(
+                "  {new_name}: {} (mapped from \"{filename_display}\")",
+                if options.dry_run {
+                    "WOULD FIX"
+                } else {
+                    "FIXED"
+                }
+            ));
+            EntryOutcome::new(encoding, new_name_bytes, target, Action::Fixed)
+        }
+        Ok(_) => {
+            options.outcome(format_args!("  {filename_display}: OK"));
+            EntryOutcome::new(encoding, filename_bytes.to_vec(), target, Action::Unchanged)
+        }
+        Err(e) => {
+            options.outcome(format_args!(
+                "  Failed to rename \"{filename_display}\" to \"{new_name}\": {e}"
+            ));
+            let mut outcome =
+                EntryOutcome::new(encoding, filename_bytes.to_vec(), UTF_8, Action::Failed);
+            outcome.rule = Some(new_name.to_string());
+            outcome
+        }
+    }
+}
+
+/// Replace the proposed name with `fixed`, reporting the change under `reason`
+fn apply_fixup(outcome: &mut EntryOutcome, fixed: Vec<u8>, reason: &str, options: &FixOptions) {
+    if fixed == outcome.new_name {
+        return;
+    }
+    options.outcome(format_args!(
+        "    {reason}: \"{}\" -> \"{}\"",
+        printable(&outcome.display_name()),
+        printable(&display_name(&fixed, outcome.name_encoding))
+    ));
+    outcome.new_name = fixed;
+    outcome.action = Action::Fixed;
+}
+
+/// Remove the leading directories `strip` gives from the new name; the
+/// directories themselves get empty names, which leave them out
+fn strip_prefix(outcome: &mut EntryOutcome, strip: &StripPrefix, options: &FixOptions) {
+    let fixed = match strip {
+        StripPrefix::Components(count) => sanitize::strip_components(&outcome.new_name, *count),
+        StripPrefix::Path(path) => encode_text(path, outcome.name_encoding, options.on_unencodable)
+            .ok()
+            .and_then(|prefix| sanitize::strip_path_prefix(&outcome.new_name, &prefix)),
+    };
+    match fixed {
+        Some(fixed) => apply_fixup(outcome, fixed, "Stripped prefix", options),
+        None if matches!(strip, StripPrefix::Components(_)) => options.outcome(format_args!(
+            "    Warning: \"{}\" is not inside enough directories to strip them",
+            outcome.display_name()
+        )),
+        None => {}
+    }
+}
+
+/// Rewrite the text of the new name with `change`; names that cannot be
+/// decoded, or whose new text the target encoding lacks, are kept
+fn change_text(
+    outcome: &mut EntryOutcome,
+    change: impl FnOnce(&str) -> String,
+    reason: &str,
+    options: &FixOptions,
+) {
+    let (text, had_errors) = outcome
+        .name_encoding
+        .decode_without_bom_handling(&outcome.new_name);
+    if had_errors {
+        return;
+    }
+    if let Ok(fixed) = encode_text(&change(&text), outcome.name_encoding, Unencodable::Error) {
+        apply_fixup(outcome, fixed, reason, options);
+    }
+}
+
+/// Convert the new name to the normalization `form`; legacy code pages
+/// keep their names composed
+fn normalize(outcome: &mut EntryOutcome, form: Normalization, options: &FixOptions) {
+    let change = |text: &str| match form {
+        Normalization::None => text.to_string(),
+        Normalization::Nfc => text.nfc().collect(),
+        Normalization::Nfd => text.nfd().collect(),
+        Normalization::Nfkc => text.nfkc().collect(),
+        Normalization::Nfkd => text.nfkd().collect(),
+    };
+    change_text(outcome, change, "Normalized", options);
+}
+
+/// Convert the new name to `case`
+fn change_case(outcome: &mut EntryOutcome, case: Case, options: &FixOptions) {
+    let change = |text: &str| match case {
+        Case::Preserve => text.to_string(),
+        Case::Lower => text.to_lowercase(),
+        Case::Upper => text.to_uppercase(),
+    };
+    change_text(outcome, change, "Changed case", options);
+}
+
+/// Put the new name in the directory `prefix`
+fn add_prefix(outcome: &mut EntryOutcome, prefix: &str, options: &FixOptions) {
+    match encode_text(
+        prefix.trim_matches('/'),
+        outcome.name_encoding,
+        options.on_unencodable,
+    ) {
+        Ok(mut fixed) => {
+            fixed.push(b'/');
+            fixed.extend_from_slice(&outcome.new_name);
+            apply_fixup(outcome, fixed, "Added prefix", options);
+        }
+        Err(e) => options.outcome(format_args!(
+            "    Warning: cannot add the prefix to \"{}\": {e}",
+            outcome.display_name()
+        )),
+    }
+}
+
+/// `name` with control characters escaped, for messages
+fn printable(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_control() {
+                c.escape_default().to_string()
+            } else {
+                c.to_string()
+            }
+        })
+        .collect()
+}
+
+/// Clean up the proposed name according to the enabled sanitization options
+///
+/// `used_names` holds the final names of the entries processed so far.
+fn apply_name_fixups(
+    outcome: &mut EntryOutcome,
+    options: &FixOptions,
+    used_names: &HashSet<Vec<u8>>,
+) {
+    if options.target == Target::Translit {
+        let fixed = translit::transliterate(&String::from_utf8_lossy(&outcome.new_name));
+        apply_fixup(outcome, fixed.into_bytes(), "Transliterated", options);
+    }
+
+    if sanitize::has_control_chars(&outcome.new_name, outcome.name_encoding) {
+        match options.control_chars {
+            ControlChars::Keep => options.outcome(format_args!(
+                "    Warning: \"{}\" contains control characters \
+                 (use --control-chars to replace them)",
+                printable(&outcome.display_name())
+            )),
+            ControlChars::Replace | ControlChars::Escape => {
+                let fixed = sanitize::replace_control_chars(
+                    &outcome.new_name,
+                    outcome.name_encoding,
+                    options.control_chars == ControlChars::Escape,
+                );
+                apply_fixup(outcome, fixed, "Replaced control characters", options);
+            }
+        }
+    }
+
+    let fixed = sanitize::forward_slashes(&outcome.new_name, outcome.name_encoding);
+    if options.fix_separators {
+        apply_fixup(outcome, fixed, "Fixed separators", options);
+    } else if fixed != outcome.new_name {
+        options.outcome(format_args!(
+            "    Warning: \"{}\" separates directories with backslashes \
+             (use --fix-separators to fix it)",
+            outcome.display_name()
+        ));
+    }
+
+    if options.strip_absolute {
+        let fixed = sanitize::strip_absolute(&outcome.new_name);
+        apply_fixup(outcome, fixed, "Stripped absolute path", options);
+    }
+
+    if let Some(strip) = &options.strip_prefix {
+        strip_prefix(outcome, strip, options);
+    }
+    if options.normalize != Normalization::None {
+        normalize(outcome, options.normalize, options);
+    }
+    if options.case != Case::Preserve {
+        change_case(outcome, options.case, options);
+    }
+    if let Some(prefix) = &options.add_prefix
+        && !outcome.new_name.is_empty()
+    {
+        add_prefix(outcome, prefix, options);
+    }
+
+    if sanitize::is_unsafe_path(&outcome.new_name) {
+        if options.sanitize_paths {
+            let fixed = sanitize::safe_path(&outcome.new_name);
+            apply_fixup(outcome, fixed, "Sanitized path", options);
+        } else {
+            options.outcome(format_args!(
+                "    WARNING: \"{}\" would be extracted outside the target directory \
+                 (use --sanitize-paths to fix it)",
+                outcome.display_name()
+            ));
+        }
+    }
+
+    if options.sanitize_windows {
+        let fixed = sanitize::windows_names(&outcome.new_name);
+        apply_fixup(outcome, fixed, "Sanitized for Windows", options);
+    } else if let Some(device) = sanitize::windows_reserved_component(&outcome.new_name) {
+        options.outcome(format_args!(
+            "    Warning: \"{device}\" is a reserved device name on Windows \
+             (use --sanitize-windows to rename it)"
+        ));
+    }
+
+    // Converting to UTF-8 can double the length of a name
+    if sanitize::longest_component(&outcome.new_name) > sanitize::MAX_COMPONENT_BYTES {
+        if options.truncate_long_names {
+            let max = sanitize::MAX_COMPONENT_BYTES;
+            let mut fixed = sanitize::truncate_components(&outcome.new_name, max);
+            let truncated = fixed.clone();
+            let mut n = 1;
+            while used_names.contains(&fixed) {
+                fixed = sanitize::with_unique_suffix(&truncated, n, max);
+                n += 1;
+            }
+            apply_fixup(outcome, fixed, "Truncated long name", options);
+        } else {
+            options.outcome(format_args!(
+                "    Warning: a path component is longer than {} bytes \
+                 (use --truncate-long-names to shorten it)",
+                sanitize::MAX_COMPONENT_BYTES
+            ));
+        }
+    }
+    if sanitize::windows_path_len(&outcome.new_name) >= sanitize::WINDOWS_MAX_PATH {
+        options.outcome(format_args!(
+            "    Warning: the path is longer than Windows MAX_PATH ({} characters)",
+            sanitize::WINDOWS_MAX_PATH
+        ));
+    }
+}
+
+/// Convert a name to the target encoding; `utf8_flag` marks names already
+/// known to be UTF-8, and `hints` is what is known about the archive
+fn process_entry_name(
+    filename_bytes: &[u8],
+    utf8_flag: bool,
+    hints: Hints,
+    options: &FixOptions,
+) -> EntryOutcome {
+    let mut outcome = read_entry_name(filename_bytes, utf8_flag, hints, options);
+    outcome.confidence = if outcome.misread_as.is_some() || outcome.action == Action::AlreadyUtf8 {
+        Some(1.0)
+    } else {
+        outcome
+            .encoding
+            .map(|encoding| confidence(filename_bytes, encoding, &options.candidates, options.lang))
+    };
+    outcome
+}
+
+fn read_entry_name(
+    filename_bytes: &[u8],
+    utf8_flag: bool,
+    hints: Hints,
+    options: &FixOptions,
+) -> EntryOutcome {
+    let filename_display = String::from_utf8_lossy(filename_bytes);
+    let target = options.target.encoding();
+
+    if options.verbose >= 2 {
+        options.message(format_args!(
+            "Raw bytes for '{filename_display}': {filename_bytes:02x?}"
+        ));
+    }
+
+    // A name flagged as UTF-8 is what the archiver was given
+    if !utf8_flag && let Some(outcome) = repair_mojibake(filename_bytes, options) {
+        return outcome;
+    }
+
+    // Names flagged as UTF-8 only need converting for a legacy target
+    let detected_encoding = if utf8_flag {
+        if target == UTF_8 {
+            options.outcome(format_args!("  {filename_display}: OK (already UTF-8)"));
+            return EntryOutcome::new(
+                Some(UTF_8),
+                filename_bytes.to_vec(),
+                UTF_8,
+                Action::AlreadyUtf8,
+            );
+        }
+        UTF_8
+    } else {
+        options
+            .source_encoding
+            .unwrap_or_else(|| options.detect_encoding(filename_bytes, hints))
+    };
+
+    if detected_encoding == target {
+        options.outcome(format_args!(
+            "  {}: OK",
+            display_name(filename_bytes, target)
+        ));
+        return EntryOutcome::new(
+            Some(detected_encoding),
+            filename_bytes.to_vec(),
+            target,
+            Action::Unchanged,
+        );
+    }
+
+    if options.verbose >= 1 {
+        options.message(format_args!(
+            "  Converting \"{filename_display}\" ({} -> {})",
+            detected_encoding.name(),
+            target.name()
+        ));
+    }
+
+    match convert_encoding(
+        filename_bytes,
+        detected_encoding,
+        target,
+        options.on_unencodable,
+    ) {
+        Ok(new_name_bytes) => {
+            if filename_bytes == new_name_bytes {
+                options.outcome(format_args!("  {filename_display}: OK"));
+                EntryOutcome::new(
+                    Some(detected_encoding),
+                    new_name_bytes,
+                    target,
+                    Action::Unchanged,
+                )
+            } else {
+                options.outcome(format_args!(
+                    "  {}: {} ({} -> {})",
+                    display_name(&new_name_bytes, target),
+                    if options.dry_run {
+                        "WOULD FIX"
+                    } else {
+                        "FIXED"
+                    },
+                    detected_encoding.name(),
+                    target.name()
+                ));
+                EntryOutcome::new(
+                    Some(detected_encoding),
+                    new_name_bytes,
+                    target,
+                    Action::Fixed,
+                )
+            }
+        }
+        Err(e) => {
+            options.outcome(format_args!(
+                "  Failed to recode \"{filename_display}\": {e}"
+            ));
+            EntryOutcome::new(
+                Some(detected_encoding),
+                filename_bytes.to_vec(),
+                detected_encoding,
+                Action::Failed,
+            )
+        }
+    }
+}
+
+/// Convert what the UTF-8 name `filename_bytes` was before it was misread
+/// in a single-byte code page to the target encoding, or `None` if it does
+/// not look misread
+fn repair_mojibake(filename_bytes: &[u8], options: &FixOptions) -> Option<EntryOutcome> {
+    if !options.repair_mojibake
+        || options
+            .source_encoding
+            .is_some_and(|source| source != UTF_8)
+    {
+        return None;
+    }
+    let (text, misread_as) = mojibake::repair(filename_bytes)?;
+    let filename_display = String::from_utf8_lossy(filename_bytes);
+    let target = options.target.encoding();
+    Some(match encode_text(&text, target, options.on_unencodable) {
+        Ok(new_name) => {
+            options.outcome(format_args!(
+                "  {}: {} (UTF-8 misread as {} -> {})",
+                display_name(&new_name, target),
+                if options.dry_run {
+                    "WOULD FIX"
+                } else {
+                    "FIXED"
+                },
+                misread_as.name(),
+                target.name()
+            ));
+            EntryOutcome {
+                encoding: Some(UTF_8),
+                misread_as: Some(misread_as),
+                confidence: None,
+                new_name,
+                name_encoding: target,
+                action: Action::Fixed,
+                rule: None,
+            }
+        }
+        Err(e) => {
+            options.outcome(format_args!(
+                "  Failed to recode \"{filename_display}\": {e}"
+            ));
+            EntryOutcome {
+                encoding: Some(UTF_8),
+                misread_as: Some(misread_as),
+                confidence: None,
+                new_name: filename_bytes.to_vec(),
+                name_encoding: UTF_8,
+                action: Action::Failed,
+                rule: None,
+            }
+        }
+    })
+}
+
+/// Open an archive, rebuilding it from its local headers if `salvage` is set
+/// and the central directory cannot be read
+fn open_archive(
+    zipfile: &Path,
+    options: Option<&FixOptions>,
+) -> Result<RawArchive<BufReader<File>>> {
+    open_archive_with(zipfile, options, || {
+        let file = File::open(zipfile).context(format!("Failed to open {}", zipfile.display()))?;
+        Ok(BufReader::new(file))
+    })
+}
+
+/// Open an archive like `open_archive`, reading it through a memory map
+fn map_archive(zipfile: &Path, options: &FixOptions) -> Result<RawArchive<Cursor<Mmap>>> {
+    open_archive_with(zipfile, Some(options), || {
+        let file = File::open(zipfile).context(format!("Failed to open {}", zipfile.display()))?;
+        // SAFETY: the file is only read, and replaced by renaming once the
+        // map is gone; another process truncating it meanwhile is not
+        // guarded against, which is why mapping is opt-in
+        let map =
+            unsafe { Mmap::map(&file) }.context(format!("Failed to map {}", zipfile.display()))?;
+        Ok(Cursor::new(map))
+    })
+}
+
+/// Open the ZIP archive at `zipfile`, salvaging the entries of a damaged one
+/// from its local headers if fixed with `options` that have `salvage` set
+fn open_archive_with<R: Read + Seek>(
+    zipfile: &Path,
+    options: Option<&FixOptions>,
+    open: impl Fn() -> Result<R>,
+) -> Result<RawArchive<R>> {
+    match (
+        RawArchive::new(open()?),
+        options.filter(|options| options.salvage),
+    ) {
+        (Ok(archive), _) => Ok(archive),
+        (Err(e), Some(options)) => {
+            options.warn(
+                zipfile,
+                &format!(
+                    "{}: {e}, salvaging entries from local headers",
+                    zipfile.display()
+                ),
+            );
+            RawArchive::salvage(open()?).context("Failed to salvage ZIP archive")
+        }
+        (Err(e), None) => Err(e.context("Failed to read ZIP archive")),
+    }
+}
+
+/// Report entries whose local header and central directory names differ,
+/// settling on the preferred one; both get the same name when rewriting
+///
+/// Returns whether any names differed.
+fn reconcile_local_names<R: Read + Seek>(
+    archive: &mut RawArchive<R>,
+    options: &FixOptions,
+) -> Result<bool> {
+    let mut mismatch = false;
+    for index in 0..archive.entries().len() {
+        let local_name = archive.local_name(index)?;
+        let entry = &mut archive.entries_mut()[index];
+        if local_name == entry.name {
+            continue;
+        }
+        mismatch = true;
+        if options.quiet {
+            continue;
+        }
+        options.message(format_args!(
+            "  Warning: entry {index} is named \"{}\" in its local header and \"{}\" in the \
+             central directory, using the {} one",
+            String::from_utf8_lossy(&local_name),
+            String::from_utf8_lossy(&entry.name),
+            match options.prefer_name {
+                NameSource::Central => "central",
+                NameSource::Local => "local",
+            }
+        ));
+        if options.prefer_name == NameSource::Local {
+            entry.name = local_name;
+        }
+    }
+    Ok(mismatch)
+}
+
+/// New names of the entries of an archive
+struct Plan {
+    outcomes: Vec<EntryOutcome>,
+    /// Indices of the entries left out, deleted (`--delete`) or duplicates
+    /// (`--dedupe`)
+    removed: HashSet<usize>,
+    /// Whether local headers and the central directory disagree on any name
+    name_mismatch: bool,
+}
+
+impl Plan {
+    /// Whether rewriting the archive would leave it as it is: every name and
+    /// EFS flag stays, and nothing else asks for a rewrite
+    fn is_noop<R: Read + Seek>(&self, archive: &RawArchive<R>, options: &FixOptions) -> bool {
+        !self.name_mismatch
+            && !options.zipalign
+            && !archive.is_salvaged()
+            && (!options.dos_host || archive.entries().iter().all(rawzip::Entry::is_dos_host))
+            && self.removed.is_empty()
+            && entry_order(&self.outcomes, &self.removed, options.sort).is_sorted()
+            && self
+                .outcomes
+                .iter()
+                .zip(archive.entries())
+                .all(|(outcome, entry)| {
+                    outcome.new_name == entry.name
+                        && outcome.utf8_flag(entry) == entry.is_utf8()
+                        && outcome.unicode_name(entry, options).is_none()
+                })
+    }
+}
+
+/// Plan the new name of every entry, or return `None` if the archive is skipped
+fn plan_names<R: Read + Seek>(
+    archive: &mut RawArchive<R>,
+    zipfile: &Path,
+    options: &FixOptions,
+    report: &mut FixReport,
+) -> Result<Option<Plan>> {
+    if !options.force
+        && let Some(format) = formats::structured_format(zipfile, archive.entries())
+    {
+        options.warn(
+            zipfile,
+            &format!(
+                "skipping {}, it looks like {format} that renaming would break \
+                 (use --force to process it anyway)",
+                zipfile.display()
+            ),
+        );
+        return Ok(None);
+    }
+
+    let file_count = archive.entries().len();
+    options.outcome(format_args!(
+        "{} contains {} file{}",
+        zipfile.display(),
+        file_count,
+        if file_count == 1 { "" } else { "s" }
+    ));
+
+    let name_mismatch = reconcile_local_names(archive, options)?;
+
+    // Sizes are checked up front so nothing is written for a refused archive
+    options.limits.check(archive.entries())?;
+
+    let content = if options.content_hints
+        && options.source_encoding.is_none()
+        && options.rename_rules.is_none()
+    {
+        hints::content_encoding(archive, &options.candidates)
+    } else {
+        None
+    };
+
+    let mut used_names = HashSet::new();
+    let mut outcomes = Vec::with_capacity(file_count);
+    for (i, entry) in archive.entries().iter().enumerate() {
+        let hints = Hints {
+            host: Host::from_version_made_by(entry.version_made_by),
+            content,
+        };
+        let outcome = plan_entry(
+            zipfile,
+            i,
+            &entry.name,
+            entry.is_utf8(),
+            hints,
+            options,
+            &used_names,
+        );
+        used_names.insert(outcome.new_name.clone());
+        outcomes.push(outcome);
+    }
+
+    let mut removed = deleted(zipfile, &outcomes, options);
+    // The directories --strip-prefix removes are left out with their names
+    removed.extend(
+        outcomes
+            .iter()
+            .enumerate()
+            .filter(|(_, outcome)| outcome.new_name.is_empty())
+            .map(|(i, _)| i),
+    );
+    match options.dedupe {
+        Some(Dedupe::Suffix) => suffix_duplicates(&mut outcomes, &removed, options),
+        Some(dedupe) => {
+            let duplicates = duplicates(zipfile, &outcomes, &removed, dedupe, options)?;
+            removed.extend(duplicates);
+        }
+        None => {}
+    }
+    for (i, (entry, outcome)) in archive.entries().iter().zip(&outcomes).enumerate() {
+        report.add(zipfile, i, &entry.name, outcome, removed.contains(&i));
+    }
+
+    warn_unsafe_paths(zipfile, &outcomes, options);
+    report_unencodable(zipfile, &outcomes, options);
+    check_strict(zipfile, &outcomes, options)?;
+    Ok(Some(Plan {
+        outcomes,
+        removed,
+        name_mismatch,
+    }))
+}
+
+/// Indices of the entries whose new names match a `--delete` pattern,
+/// printing them
+fn deleted(path: &Path, outcomes: &[EntryOutcome], options: &FixOptions) -> HashSet<usize> {
+    let mut deleted = HashSet::new();
+    for (i, outcome) in outcomes.iter().enumerate() {
+        let name = outcome.display_name();
+        if options
+            .delete
+            .iter()
+            .any(|pattern| glob::matches(pattern, &name))
+        {
+            if !options.quiet {
+                if deleted.is_empty() {
+                    options.message(format_args!("{}: deleting entries:", path.display()));
+                }
+                options.message(format_args!("  {i} \"{name}\""));
+            }
+            deleted.insert(i);
+        }
+    }
+    deleted
+}
+
+/// Indices of the entries to leave out because an entry kept has the same
+/// new name, not counting the `deleted` ones, printing them; fails if
+/// `dedupe` says so and there are any
+fn duplicates(
+    path: &Path,
+    outcomes: &[EntryOutcome],
+    deleted: &HashSet<usize>,
+    dedupe: Dedupe,
+    options: &FixOptions,
+) -> Result<HashSet<usize>> {
+    let mut kept: HashMap<&[u8], usize> = HashMap::new();
+    let mut removed = HashSet::new();
+    let mut indices: Vec<usize> = (0..outcomes.len())
+        .filter(|i| !deleted.contains(i))
+        .collect();
+    if dedupe == Dedupe::KeepLast {
+        indices.reverse();
+    }
+    for i in indices {
+        let name = outcomes[i].new_name.as_slice();
+        let Some(&other) = kept.get(name) else {
+            kept.insert(name, i);
+            continue;
+        };
+        if dedupe == Dedupe::Error {
+            return Err(Error::other(format!(
+                "Entries {} and {} are both named \"{}\" (use --dedupe keep-first or \
+                 keep-last to drop one, or suffix to rename it)",
+                other.min(i),
+                other.max(i),
+                outcomes[i].display_name()
+            )));
+        }
+        removed.insert(i);
+    }
+    if !options.quiet && !removed.is_empty() {
+        let mut sorted: Vec<&usize> = removed.iter().collect();
+        sorted.sort_unstable();
+        options.message(format_args!(
+            "{}: removing duplicate entries:",
+            path.display()
+        ));
+        for &i in sorted {
+            let name = outcomes[i].new_name.as_slice();
+            options.message(format_args!(
+                "  {i} \"{}\" (keeping entry {})",
+                outcomes[i].display_name(),
+                kept[name]
+            ));
+        }
+    }
+    Ok(removed)
+}
+
+/// Add a `~N` suffix to the names of the entries with the new name of an
+/// earlier one, not counting the `deleted` ones, printing the renames;
+/// duplicate directories are kept as they are, as they unpack to one
+fn suffix_duplicates(
+    outcomes: &mut [EntryOutcome],
+    deleted: &HashSet<usize>,
+    options: &FixOptions,
+) {
+    let mut used: HashSet<Vec<u8>> = outcomes
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !deleted.contains(i))
+        .map(|(_, outcome)| outcome.new_name.clone())
+        .collect();
+    let mut seen = HashSet::new();
+    for (i, outcome) in outcomes.iter_mut().enumerate() {
+        if deleted.contains(&i)
+            || outcome.new_name.ends_with(b"/")
+            || seen.insert(outcome.new_name.clone())
+        {
+            continue;
+        }
+        let name = outcome.new_name.clone();
+        let mut fixed = name.clone();
+        let mut n = 1;
+        while used.contains(&fixed) {
+            fixed = sanitize::with_unique_suffix(&name, n, sanitize::MAX_COMPONENT_BYTES);
+            n += 1;
+        }
+        used.insert(fixed.clone());
+        apply_fixup(outcome, fixed, "Renamed duplicate", options);
+    }
+}
+
+/// Warn about the entries that would still be extracted outside the target
+/// directory after renaming
+fn warn_unsafe_paths(path: &Path, outcomes: &[EntryOutcome], options: &FixOptions) {
+    let unsafe_paths = outcomes
+        .iter()
+        .filter(|outcome| sanitize::is_unsafe_path(&outcome.new_name))
+        .count();
+    if unsafe_paths > 0 {
+        options.warn(
+            path,
+            &format!(
+                "{} has {unsafe_paths} entr{} with absolute or \"..\" paths",
+                path.display(),
+                if unsafe_paths == 1 { "y" } else { "ies" }
+            ),
+        );
+    }
+}
+
+/// The converted name of a failed entry, if it failed because the target
+/// encoding cannot represent it
+fn unencodable_name(outcome: &EntryOutcome, options: &FixOptions) -> Option<String> {
+    if outcome.action != Action::Failed {
+        return None;
+    }
+    let name = match (&outcome.rule, outcome.encoding) {
+        (Some(rule), _) => rule.clone(),
+        (None, Some(encoding)) => encoding
+            .decode_without_bom_handling_and_without_replacement(&outcome.new_name)?
+            .into_owned(),
+        (None, None) => return None,
+    };
+    encode_text(&name, options.target.encoding(), Unencodable::Error)
+        .is_err()
+        .then_some(name)
+}
+
+/// List every name the target encoding cannot represent, before anything is
+/// written, with the names the fallback policies would give instead
+fn report_unencodable(path: &Path, outcomes: &[EntryOutcome], options: &FixOptions) {
+    let names: Vec<String> = outcomes
+        .iter()
+        .filter_map(|outcome| unencodable_name(outcome, options))
+        .collect();
+    if names.is_empty() {
+        return;
+    }
+    let target = options.target.encoding();
+    let mut message = format!(
+        "{} has {} name{} {} cannot represent, left unchanged:",
+        path.display(),
+        names.len(),
+        if names.len() == 1 { "" } else { "s" },
+        target.name()
+    );
+    let mut buffer = [0; 4];
+    for name in &names {
+        let missing: String = name
+            .nfc()
+            .filter(|c| target.encode(c.encode_utf8(&mut buffer)).1)
+            .collect();
+        let fallback = |policy| {
+            encode_text(name, target, policy)
+                .map(|fixed| display_name(&fixed, target))
+                .unwrap_or_default()
+        };
+        let _ = write!(
+            message,
+            "\n  \"{}\" (\"{}\" missing): replace -> \"{}\", translit -> \"{}\"",
+            printable(name),
+            printable(&missing),
+            printable(&fallback(Unencodable::Replace)),
+            printable(&fallback(Unencodable::Translit))
+        );
+    }
+    message.push_str("\n(use --on-unencodable replace or translit to rename them this way)");
+    options.warn(path, &message);
+}
+
+/// With `strict`, fail if any name of the archive at `path` could not be
+/// converted, or was converted from an encoding detection was unsure of
+/// and `choose_encoding` did not confirm, before anything is written
+fn check_strict(path: &Path, outcomes: &[EntryOutcome], options: &FixOptions) -> Result<()> {
+    if !options.strict {
+        return Ok(());
+    }
+    if let Some(name) = outcomes
+        .iter()
+        .find_map(|outcome| unencodable_name(outcome, options))
+    {
+        return Err(Error::UnencodableName {
+            name,
+            encoding: options.target.encoding(),
+        });
+    }
+    let failed = outcomes
+        .iter()
+        .filter(|outcome| outcome.action == Action::Failed)
+        .count();
+    if failed > 0 {
+        return Err(Error::Unconverted(format!(
+            "{} left unchanged: {failed} name{} cannot be converted (--strict)",
+            path.display(),
+            if failed == 1 { "" } else { "s" }
+        )));
+    }
+    if options.choose_encoding.is_some() {
+        return Ok(());
+    }
+    let ambiguous = outcomes.iter().find(|outcome| {
+        outcome.action == Action::Fixed
+            && outcome.misread_as.is_none()
+            && outcome
+                .confidence
+                .is_some_and(|confidence| confidence < AMBIGUITY_MARGIN)
+    });
+    if let Some(outcome) = ambiguous
+        && let Some(encoding) = outcome.encoding
+    {
+        return Err(Error::DetectionAmbiguous {
+            name: outcome.display_name(),
+            encoding,
+            confidence: outcome.confidence.unwrap_or_default(),
+        });
+    }
+    Ok(())
+}
+
+/// New contents of the entries of an archive that change besides their names
+#[derive(Default)]
+struct ChangedData {
+    /// Fixed nested archives
+    nested: HashMap<usize, Vec<u8>>,
+    /// Text entries converted to UTF-8
+    texts: HashMap<usize, Vec<u8>>,
+}
+
+impl ChangedData {
+    fn is_empty(&self) -> bool {
+        self.nested.is_empty() && self.texts.is_empty()
+    }
+}
+
+/// Convert the text entries of `archive` with the extensions given in
+/// `--fix-contents` to UTF-8, returning the new contents of those that
+/// were in a legacy encoding
+fn fix_text_contents<R: Read + Seek>(
+    archive: &mut RawArchive<R>,
+    zipfile: &Path,
+    options: &FixOptions,
+) -> Result<HashMap<usize, Vec<u8>>> {
+    let mut texts = HashMap::new();
+    if options.fix_contents.is_empty() {
+        return Ok(texts);
+    }
+    for index in 0..archive.entries().len() {
+        let name = &archive.entries()[index].name;
+        if !contents::is_text_entry(name, &options.fix_contents) {
+            continue;
+        }
+        let name = zipfile.join(String::from_utf8_lossy(name).as_ref());
+        let Some(text) = archive.entry_data(index)? else {
+            options.outcome(format_args!(
+                "  Warning: {} is encrypted or compressed with an unsupported method, \
+                 leaving its contents as they are",
+                name.display()
+            ));
+            continue;
+        };
+        let Some(encoding) =
+            contents::text_encoding(&text, options.source_encoding, &options.candidates)
+        else {
+            continue;
+        };
+        let Some(converted) = contents::to_utf8(&text, encoding) else {
+            options.outcome(format_args!(
+                "  Warning: {} is not valid {} text, leaving its contents as they are",
+                name.display(),
+                encoding.name()
+            ));
+            continue;
+        };
+        options.outcome(format_args!(
+            "  {}: contents {} ({} -> UTF-8)",
+            name.display(),
+            if options.dry_run {
+                "WOULD CONVERT"
+            } else {
+                "CONVERTED"
+            },
+            encoding.name()
+        ));
+        texts.insert(index, converted);
+    }
+    Ok(texts)
+}
+
+/// Fix the ZIP archives stored inside `archive`, returning the new contents
+/// of the entries that changed
+///
+/// Nested archives are named like `outer.zip/inner.zip` in the output.
+fn fix_nested_archives<R: Read + Seek>(
+    archive: &mut RawArchive<R>,
+    zipfile: &Path,
+    options: &FixOptions,
+    report: &mut FixReport,
+    depth: usize,
+) -> Result<HashMap<usize, Vec<u8>>> {
+    let mut fixed = HashMap::new();
+    for index in 0..archive.entries().len() {
+        let name = &archive.entries()[index].name;
+        if !name.to_ascii_lowercase().ends_with(b".zip") {
+            continue;
+        }
+        let nested = zipfile.join(String::from_utf8_lossy(name).as_ref());
+        if depth >= MAX_NESTING_DEPTH {
+            options.outcome(format_args!(
+                "  Warning: {} is nested too deeply, leaving it as is",
+                nested.display()
+            ));
+            continue;
+        }
+        let Some(data) = archive.entry_data(index)? else {
+            options.outcome(format_args!(
+                "  Warning: {} is encrypted or compressed with an unsupported method, \
+                 leaving it as is",
+                nested.display()
+            ));
+            continue;
+        };
+        match fix_archive_data(data, &nested, options, report, depth + 1) {
+            Ok(Some(new_data)) => {
+                fixed.insert(index, new_data);
+            }
+            Err(Error::Cancelled) => return Err(Error::Cancelled),
+            Err(e) => options.outcome(format_args!(
+                "  Warning: leaving {} as is: {}",
+                nested.display(),
+                e.chain()
+            )),
+            Ok(None) => {}
+        }
+    }
+    Ok(fixed)
+}
+
+/// Fix an archive held in memory, returning its new contents if anything changed
+fn fix_archive_data(
+    data: Vec<u8>,
+    zipfile: &Path,
+    options: &FixOptions,
+    report: &mut FixReport,
+    depth: usize,
+) -> Result<Option<Vec<u8>>> {
+    let archive = RawArchive::new(Cursor::new(data)).context("Failed to read ZIP archive")?;
+    fix_zip_to_memory(archive, zipfile, options, report, depth)
+}
+
+/// Fix an opened archive, returning its new contents if anything changed
+fn fix_zip_to_memory<R: Read + Seek>(
+    archive: RawArchive<R>,
+    zipfile: &Path,
+    options: &FixOptions,
+    report: &mut FixReport,
+    depth: usize,
+) -> Result<Option<Vec<u8>>> {
+    let mut data = Cursor::new(Vec::new());
+    let written = fix_zip_into(archive, zipfile, options, report, depth, &mut data)?;
+    Ok(written.then(|| data.into_inner()))
+}
+
+/// Fix an opened archive into a temporary file in `options.tmpdir` or the
+/// system's temporary directory, returning the file rewound to the new
+/// archive if anything changed
+fn fix_zip_to_tempfile<R: Read + Seek>(
+    archive: RawArchive<R>,
+    zipfile: &Path,
+    options: &FixOptions,
+    report: &mut FixReport,
+) -> Result<Option<File>> {
+    let mut file = match options.tmpdir {
+        Some(dir) => tempfile::tempfile_in(dir),
+        None => tempfile::tempfile(),
+    }
+    .context("Failed to create temporary file")?;
+    if !fix_zip_into(archive, zipfile, options, report, 0, &mut file)? {
+        return Ok(None);
+    }
+    file.rewind().context("Failed to reread the new archive")?;
+    Ok(Some(file))
+}
+
+/// Fix an opened archive, writing the new one to `out` from its current
+/// position and reading it back to verify it; returns whether anything
+/// changed, as nothing is written otherwise
+fn fix_zip_into<R: Read + Seek, W: Read + Write + Seek>(
+    mut archive: RawArchive<R>,
+    zipfile: &Path,
+    options: &FixOptions,
+    report: &mut FixReport,
+    depth: usize,
+    out: &mut W,
+) -> Result<bool> {
+    let Some(plan) = plan_names(&mut archive, zipfile, options, report)? else {
+        return Ok(false);
+    };
+    let changed = ChangedData {
+        nested: if options.recurse_archives {
+            fix_nested_archives(&mut archive, zipfile, options, report, depth)?
+        } else {
+            HashMap::new()
+        },
+        texts: fix_text_contents(&mut archive, zipfile, options)?,
+    };
+
+    if options.dry_run || (changed.is_empty() && plan.is_noop(&archive, options)) {
+        return Ok(false);
+    }
+    let order = entry_order(&plan.outcomes, &plan.removed, options.sort);
+
+    let start = out
+        .stream_position()
+        .context("Failed to write new archive")?;
+    // Nested archives are not the new archive whose progress is followed
+    let events = if depth == 0 { options.events } else { None };
+    let mut writer = new_writer(
+        BufWriter::with_capacity(
+            options.buffer_size,
+            ProgressWriter::new(&mut *out, events, options.cancel),
+        ),
+        options,
+    );
+    write_entries(
+        &mut archive,
+        &plan.outcomes,
+        &order,
+        &changed,
+        &mut writer,
+        options,
+    )?;
+    let written = writer.entries().to_vec();
+    let comment = archive.comment().to_vec();
+    writer
+        .finish(&comment)
+        .context("Failed to finalize new archive")?
+        .flush()
+        .context("Failed to write new archive")?;
+    out.seek(SeekFrom::Start(start))
+        .context("Failed to reread the new archive")?;
+    verify::verify_archive(out, &written)?;
+    Ok(true)
+}
+
+/// Atomically replace `path` with `temp_file`
+///
+/// A temporary file on another file system cannot be renamed over `path`,
+/// so it is copied next to `path` first and that copy is renamed instead.
+fn replace_file(temp_file: NamedTempFile, path: &Path, backup: Backup) -> Result<()> {
+    // A full disk may only show when the data reaches it, which has to be
+    // before the original is gone
+    temp_file
+        .as_file()
+        .sync_all()
+        .context("Failed to write new archive")?;
+    back_up(path, backup)?;
+    let context = "Failed to replace original file with modified version";
+    let temp_file = match temp_file.persist(path) {
+        Ok(_) => return Ok(()),
+        Err(e) if e.error.kind() == std::io::ErrorKind::CrossesDevices => e.file,
+        Err(e) => return Err(e.error).context(context),
+    };
+    let mut local = NamedTempFile::new_in(path.parent().unwrap_or_else(|| Path::new(".")))
+        .context("Failed to create temporary file")?;
+    std::io::copy(&mut temp_file.reopen()?, local.as_file_mut())
+        .and_then(|_| local.as_file().sync_all())
+        .context("Failed to copy the new archive")?;
+    local.persist(path).map_err(|e| e.error).context(context)?;
+    Ok(())
+}
+
+/// Keep the original at `path` as `backup` asks, as a hard link where the
+/// file system allows it and a copy otherwise
+fn back_up(path: &Path, backup: Backup) -> Result<()> {
+    let with_suffix = |suffix: &str| {
+        let mut name = path.as_os_str().to_owned();
+        name.push(suffix);
+        PathBuf::from(name)
+    };
+    let backup_path = match backup {
+        Backup::None => return Ok(()),
+        Backup::Simple => {
+            let backup_path = with_suffix("~");
+            match std::fs::remove_file(&backup_path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                    return Err(e).context(format!(
+                        "Failed to remove old backup {}",
+                        backup_path.display()
+                    ));
+                }
+                _ => backup_path,
+            }
+        }
+        Backup::Numbered => (1..=u32::MAX)
+            .map(|number| with_suffix(&format!(".~{number}~")))
+            .find(|backup_path| !backup_path.exists())
+            .context("No backup number left")?,
+    };
+    std::fs::hard_link(path, &backup_path)
+        .or_else(|_| std::fs::copy(path, &backup_path).map(drop))
+        .with_context(|| format!("Failed to back up original as {}", backup_path.display()))
+}
+
+fn new_writer<W: Write>(writer: W, options: &FixOptions) -> RawWriter<W> {
+    let mut writer = RawWriter::new(writer);
+    if options.zipalign {
+        writer = writer.align_stored(4);
+    }
+    if options.dos_host {
+        writer = writer.dos_host();
+    }
+    writer
+}
+
+/// Indices of the entries planned as `outcomes` to write, leaving out the
+/// `removed` ones, in the order `sort` puts their new names in
+fn entry_order(outcomes: &[EntryOutcome], removed: &HashSet<usize>, sort: SortOrder) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..outcomes.len())
+        .filter(|i| !removed.contains(i))
+        .collect();
+    if sort == SortOrder::None {
+        return order;
+    }
+    let names: Vec<String> = outcomes.iter().map(EntryOutcome::display_name).collect();
+    let file_name = |i: usize| names[i].trim_end_matches('/').rsplit('/').next();
+    // Comparing components keeps "dir/" and "dir/file" ahead of "dir-2/"
+    let path = |a: usize, b: usize| names[a].split('/').cmp(names[b].split('/'));
+    match sort {
+        SortOrder::None => {}
+        SortOrder::Name => order.sort_by(|&a, &b| file_name(a).cmp(&file_name(b)).then(path(a, b))),
+        SortOrder::Path => order.sort_by(|&a, &b| path(a, b)),
+    }
+    order
+}
+
+/// Write every entry under its planned name, replacing the contents of the
+/// fixed nested archives and converted text entries
+fn write_entries<R: Read + Seek, W: Write>(
+    archive: &mut RawArchive<R>,
+    outcomes: &[EntryOutcome],
+    order: &[usize],
+    changed: &ChangedData,
+    writer: &mut RawWriter<W>,
+    options: &FixOptions,
+) -> Result<()> {
+    for &i in order {
+        if options.cancelled() {
+            return Err(Error::Cancelled);
+        }
+        let outcome = &outcomes[i];
+        let entry = &archive.entries()[i];
+        let utf8 = outcome.utf8_flag(entry);
+        let unicode_name = outcome.unicode_name(entry, options);
+        let unicode_name = unicode_name.as_deref();
+        let name = &outcome.new_name;
+        match (changed.nested.get(&i), changed.texts.get(&i)) {
+            (Some(data), _) => writer.add_stored_entry(entry, name, utf8, unicode_name, data),
+            (None, Some(text)) => writer.add_entry(entry, name, utf8, unicode_name, text, true),
+            // Entries are copied verbatim, only their names and EFS flags change
+            (None, None) => writer.copy_entry(archive, i, name, utf8, unicode_name),
+        }
+        .with_context(|| format!("Failed to copy entry {i}"))?;
+    }
+    Ok(())
+}
+
+fn fix_cyrillic_filenames(
+    zipfile: &Path,
+    options: &FixOptions,
+    report: &mut FixReport,
+) -> Result<()> {
+    if options.mmap {
+        rewrite_archive(map_archive(zipfile, options)?, zipfile, options, report)
+    } else {
+        rewrite_archive(
+            open_archive(zipfile, Some(options))?,
+            zipfile,
+            options,
+            report,
+        )
+    }
+}
+
+/// Rewrite the opened `archive` at `zipfile` with the fixed names
+fn rewrite_archive<R: Read + Seek>(
+    mut archive: RawArchive<R>,
+    zipfile: &Path,
+    options: &FixOptions,
+    report: &mut FixReport,
+) -> Result<()> {
+    let Some(plan) = plan_names(&mut archive, zipfile, options, report)? else {
+        return Ok(());
+    };
+    let changed = ChangedData {
+        nested: if options.recurse_archives {
+            fix_nested_archives(&mut archive, zipfile, options, report, 0)?
+        } else {
+            HashMap::new()
+        },
+        texts: fix_text_contents(&mut archive, zipfile, options)?,
+    };
+
+    // Skip the rewrite, temporary file and verification when nothing changes
+    if changed.is_empty() && plan.is_noop(&archive, options) {
+        options.outcome(format_args!("{}: nothing to do", zipfile.display()));
+        return Ok(());
+    }
+    let order = entry_order(&plan.outcomes, &plan.removed, options.sort);
+    let outcomes = plan.outcomes;
+
+    if !zip_preflight(&archive, zipfile, &outcomes, &plan.removed, &changed)?
+        .confirm(zipfile, options)
+    {
+        return Ok(());
+    }
+
+    let temp_file = options.temp_file_for(zipfile)?;
+    let mut writer = new_writer(options.archive_writer(temp_file.as_file()), options);
+    write_entries(
+        &mut archive,
+        &outcomes,
+        &order,
+        &changed,
+        &mut writer,
+        options,
+    )?;
+    let written = writer.entries().to_vec();
+    let comment = archive.comment().to_vec();
+    writer
+        .finish(&comment)
+        .context("Failed to finalize new archive")?;
+
+    // The original is kept if the new archive does not read back the same
+    let new_file = File::open(temp_file.path()).context("Failed to reopen the new archive")?;
+    verify::verify_archive(BufReader::new(new_file), &written)?;
+
+    // The map records entries by their index in the new archive
+    let mut rename_map = RenameMap::new(zipfile);
+    for (index, &i) in order.iter().enumerate() {
+        let entry = &archive.entries()[i];
+        rename_map.add_entry(index, &entry.name, entry.is_utf8(), &outcomes[i]);
+    }
+    drop(archive); // Close the original file
+
+    // The map is written first, so a failure to write it keeps the original
+    let renamed_any = outcomes
+        .iter()
+        .any(|outcome| outcome.action == Action::Fixed);
+    let sidecar = renamemap::sidecar_path(zipfile);
+    let staged_map = (options.write_rename_map && renamed_any)
+        .then(|| rename_map.stage(&sidecar))
+        .transpose()?;
+
+    // Atomically replace original with modified version
+    replace_file(temp_file, zipfile, options.backup)?;
+
+    if let Some(staged_map) = staged_map {
+        staged_map
+            .persist(&sidecar)
+            .map_err(|e| e.error)
+            .context(format!("Failed to create rename map {}", sidecar.display()))?;
+    }
+
+    Ok(())
+}
+
+/// What rewriting the ZIP archive at `zipfile` with `outcomes` and the
+/// `changed` entry data, leaving out the `removed` entries, involves
+fn zip_preflight<R: Read + Seek>(
+    archive: &RawArchive<R>,
+    zipfile: &Path,
+    outcomes: &[EntryOutcome],
+    removed: &HashSet<usize>,
+    changed: &ChangedData,
+) -> Result<Preflight> {
+    let size = std::fs::metadata(zipfile)
+        .context(format!("Failed to open {}", zipfile.display()))?
+        .len();
+    let mut temp_space = i128::from(size);
+    let mut bytes_to_copy = 0;
+    for (i, (outcome, entry)) in outcomes.iter().zip(archive.entries()).enumerate() {
+        if removed.contains(&i) {
+            // The central directory record is left out too, so this is an
+            // underestimate of the space saved
+            temp_space -= (entry.name.len() + 30) as i128 + i128::from(entry.compressed_size);
+            continue;
+        }
+        // Names are in both the local header and the central directory
+        temp_space += 2 * (outcome.new_name.len() as i128 - entry.name.len() as i128);
+        match changed.nested.get(&i).or_else(|| changed.texts.get(&i)) {
+            Some(data) => {
+                temp_space += data.len() as i128 - i128::from(entry.compressed_size);
+                bytes_to_copy += data.len() as u64;
+            }
+            None => bytes_to_copy += entry.compressed_size,
+        }
+    }
+    Ok(Preflight {
+        entries: outcomes.len(),
+        renamed: outcomes
+            .iter()
+            .zip(archive.entries())
+            .filter(|(outcome, entry)| outcome.new_name != entry.name)
+            .count(),
+        removed: removed.len(),
+        bytes_to_copy,
+        raw_copy: changed.is_empty(),
+        temp_space: u64::try_from(temp_space).unwrap_or(0),
+    })
+}
+
+/// Fix the original file name stored in a gzip header
+fn fix_gzip(path: &Path, options: &FixOptions, report: &mut FixReport) -> Result<()> {
+    let Some(name) = gzip::read_name(path)? else {
+        options.outcome(format_args!(
+            "{}: no original file name stored",
+            path.display()
+        ));
+        return Ok(());
+    };
+    options.outcome(format_args!(
+        "{} stores the original file name",
+        path.display()
+    ));
+
+    // The name is Latin-1 by the specification, but in practice whatever the
+    // system encoding of the compressing machine was
+    let mut outcome = process_entry_name(&name, false, Hints::default(), options);
+    apply_name_fixups(&mut outcome, options, &HashSet::new());
+    report.add(path, 0, &name, &outcome, false);
+    check_strict(path, std::slice::from_ref(&outcome), options)?;
+
+    if outcome.action == Action::Fixed && !options.dry_run {
+        let temp_file = options.temp_file_for(path)?;
+        let writer = options.archive_writer(temp_file.as_file());
+        gzip::copy_with_name(path, &outcome.new_name, writer)?;
+        replace_file(temp_file, path, options.backup)?;
+    }
+    Ok(())
+}
+
+/// New link target of a tar member: hard links follow the renamed member,
+/// symbolic links are recoded like the member name
+fn fixed_link_name(
+    member: &tarball::Member,
+    link_name: &[u8],
+    outcome: &EntryOutcome,
+    new_names: &HashMap<&[u8], &[u8]>,
+    options: &FixOptions,
+) -> Vec<u8> {
+    if member.hard_link {
+        return new_names
+            .get(link_name)
+            .map_or_else(|| link_name.to_vec(), |new_name| new_name.to_vec());
+    }
+    let Some(encoding) = outcome.encoding else {
+        return link_name.to_vec();
+    };
+    let Ok(mut fixed) = convert_encoding(
+        link_name,
+        encoding,
+        options.target.encoding(),
+        options.on_unencodable,
+    ) else {
+        return link_name.to_vec();
+    };
+    if options.target == Target::Translit {
+        fixed = translit::transliterate(&String::from_utf8_lossy(&fixed)).into_bytes();
+    }
+    fixed
+}
+
+/// Fix the member names of a tar archive, which may be compressed
+fn fix_tar(path: &Path, options: &FixOptions, report: &mut FixReport) -> Result<()> {
+    let members = tarball::list_members(compression::open(path)?)?;
+    options.outcome(format_args!(
+        "{} contains {} file{}",
+        path.display(),
+        members.len(),
+        if members.len() == 1 { "" } else { "s" }
+    ));
+
+    let mut used_names = HashSet::new();
+    let mut outcomes = Vec::with_capacity(members.len());
+    for (i, member) in members.iter().enumerate() {
+        let outcome = plan_entry(
+            path,
+            i,
+            &member.name,
+            member.name_is_utf8,
+            Hints::default(),
+            options,
+            &used_names,
+        );
+        used_names.insert(outcome.new_name.clone());
+        report.add(path, i, &member.name, &outcome, false);
+        outcomes.push(outcome);
+    }
+    warn_unsafe_paths(path, &outcomes, options);
+    report_unencodable(path, &outcomes, options);
+    check_strict(path, &outcomes, options)?;
+
+    let new_names: HashMap<&[u8], &[u8]> = members
+        .iter()
+        .zip(&outcomes)
+        .map(|(member, outcome)| (member.name.as_slice(), outcome.new_name.as_slice()))
+        .collect();
+    let renames: Vec<_> = members
+        .iter()
+        .zip(&outcomes)
+        .map(|(member, outcome)| tarball::Rename {
+            name: outcome.new_name.clone(),
+            link_name: member
+                .link_name
+                .as_ref()
+                .map(|link_name| fixed_link_name(member, link_name, outcome, &new_names, options)),
+        })
+        .collect();
+
+    let changed = members
+        .iter()
+        .zip(&renames)
+        .any(|(member, rename)| member.name != rename.name || member.link_name != rename.link_name);
+    if !changed {
+        return Ok(());
+    }
+    let size = std::fs::metadata(path)
+        .context(format!("Failed to open {}", path.display()))?
+        .len();
+    let preflight = Preflight {
+        entries: members.len(),
+        renamed: members
+            .iter()
+            .zip(&renames)
+            .filter(|(member, rename)| member.name != rename.name)
+            .count(),
+        removed: 0,
+        bytes_to_copy: size,
+        raw_copy: compression::detect(path)?.is_none(),
+        temp_space: size,
+    };
+    if !preflight.confirm(path, options) {
+        return Ok(());
+    }
+
+    let temp_file = options.temp_file_for(path)?;
+    // Compressed archives are recompressed the way they were compressed
+    let encoder = Encoder::like(path, options.archive_writer(temp_file.as_file()))?;
+    tarball::rewrite(compression::open(path)?, encoder, &renames)?
+        .finish()
+        .and_then(|mut writer| writer.flush())
+        .context("Failed to write new archive")?;
+
+    // The original is kept if the new archive does not read back as planned
+    let written = tarball::list_members(compression::open(temp_file.path())?)?;
+    let matches = written.len() == renames.len()
+        && written.iter().zip(&renames).all(|(member, rename)| {
+            member.name == rename.name && member.link_name == rename.link_name
+        });
+    if !matches {
+        return Err(Error::other(
+            "The new archive does not list the planned names",
+        ));
+    }
+
+    replace_file(temp_file, path, options.backup)?;
+    Ok(())
+}
+
+/// Fix the entry names of a 7z archive
+#[cfg(feature = "sevenz")]
+fn fix_7z(path: &Path, options: &FixOptions, report: &mut FixReport) -> Result<()> {
+    if options.target.encoding() != UTF_8 {
+        return Err(Error::other(
+            "7z archives always store Unicode names, only the utf-8 and translit targets apply",
+        ));
+    }
+    let names = sevenz::list_names(path)?;
+    options.outcome(format_args!(
+        "{} contains {} file{}",
+        path.display(),
+        names.len(),
+        if names.len() == 1 { "" } else { "s" }
+    ));
+
+    let mut used_names = HashSet::new();
+    let mut outcomes = Vec::with_capacity(names.len());
+    let mut new_names = HashMap::new();
+    for (i, name) in names.iter().enumerate() {
+        if let Some(events) = options.events {
+            events.on_entry_start(path, i, name.as_bytes());
+        }
+        if !options.selects(i, name.as_bytes()) {
+            let outcome = unselected_entry(name.as_bytes(), options);
+            report.add(path, i, name.as_bytes(), &outcome, false);
+            outcomes.push(outcome);
+            continue;
+        }
+        // Only names that decode to Cyrillic are taken for mojibake
+        let legacy_bytes = sevenz::misdecoded_bytes(name).filter(|bytes| {
+            options.source_encoding.is_some()
+                || detect_name_encoding(
+                    bytes,
+                    &unexplained,
+                    &options.candidates,
+                    Hints::default(),
+                    options.lang,
+                ) != UTF_8
+        });
+        let mut outcome = match (options.rename_rules, legacy_bytes) {
+            (Some(rules), bytes) => apply_rename_rules(
+                name.as_bytes(),
+                bytes.as_deref(),
+                Hints::default(),
+                rules,
+                options,
+            ),
+            (None, Some(bytes)) => process_entry_name(&bytes, false, Hints::default(), options),
+            (None, None) => process_entry_name(name.as_bytes(), true, Hints::default(), options),
+        };
+        apply_name_fixups(&mut outcome, options, &used_names);
+        outcome.announce_rename(path, i, name.as_bytes(), options);
+        used_names.insert(outcome.new_name.clone());
+        report.add(path, i, name.as_bytes(), &outcome, false);
+        if outcome.action == Action::Fixed {
+            new_names.insert(name.clone(), String::from_utf8(outcome.new_name.clone())?);
+        }
+        outcomes.push(outcome);
+    }
+    warn_unsafe_paths(path, &outcomes, options);
+    report_unencodable(path, &outcomes, options);
+    check_strict(path, &outcomes, options)?;
+
+    if options.dry_run || new_names.is_empty() {
+        return Ok(());
+    }
+
+    let temp_file = options.temp_file_for(path)?;
+    let writer = options.archive_writer(temp_file.as_file());
+    sevenz::rewrite(path, writer, &new_names)?
+        .flush()
+        .context("Failed to write new archive")?;
+
+    // The original is kept if the new archive does not read back as planned
+    let mut expected: Vec<_> = names
+        .iter()
+        .map(|name| new_names.get(name).unwrap_or(name).clone())
+        .collect();
+    let mut written = sevenz::list_names(temp_file.path())?;
+    expected.sort();
+    written.sort();
+    if written != expected {
+        return Err(Error::other(
+            "The new archive does not list the planned names",
+        ));
+    }
+
+    replace_file(temp_file, path, options.backup)?;
+    Ok(())
+}
+
+#[cfg(not(feature = "sevenz"))]
+#[allow(clippy::unnecessary_wraps)]
+fn fix_7z(path: &Path, _options: &FixOptions, _report: &mut FixReport) -> Result<()> {
+    Err(Error::other(format!(
+        "{} is a 7z archive, runzip was built without 7z support (the sevenz feature)",
+        path.display()
+    )))
+}
+
+/// Fix the names in a ZIP, tar or 7z archive or a gzip file, depending on
+/// its contents, replacing the file at `path`
+///
+/// # Errors
+///
+/// Fails if the file cannot be read, is not a supported archive, is being
+/// fixed by another process, cannot be replaced, or `cancel` is set. Nothing
+/// is changed then. The [`Error`] tells which kind of failure it was.
+pub fn fix_archive(path: &Path, options: &FixOptions) -> Result<FixReport, Error> {
+    let mut report = FixReport::default();
+    if options.cancelled() {
+        return Err(Error::Cancelled);
+    }
+    fix_file(path, options, &mut report)?;
+    Ok(report)
+}
+
+/// Fix the names in a ZIP archive held in memory, returning the new
+/// archive, or a copy of `data` if no name changes or in a dry run
+///
+/// The archives in the report are named after their path inside `data`,
+/// the outermost one with an empty path.
+///
+/// # Errors
+///
+/// Fails if `data` is not a ZIP archive, the new archive does not verify or
+/// `cancel` is set.
+pub fn fix_archive_bytes(data: &[u8], options: &FixOptions) -> Result<(Vec<u8>, FixReport), Error> {
+    let mut report = FixReport::default();
+    let archive = RawArchive::new(Cursor::new(data)).context("Failed to read ZIP archive")?;
+    let fixed = fix_zip_to_memory(archive, Path::new(""), options, &mut report, 0)?;
+    Ok((fixed.unwrap_or_else(|| data.to_vec()), report))
+}
+
+/// Fix the names of the ZIP archive read from `reader`, writing the new
+/// archive to `writer`
+///
+/// Nothing is written when no name changes or `options.dry_run` is set; the
+/// archive read is the one to keep then. The new archive is written to a
+/// temporary file in `tmpdir` (or the system's temporary directory) and
+/// verified there, like every fixed archive, then copied to `writer` from
+/// its current position, so `writer` is never read. `name` is the archive
+/// name used in the report.
+///
+/// # Errors
+///
+/// Fails if `reader` is not a ZIP archive, the new archive cannot be written
+/// or does not verify, or `cancel` is set. `writer` may hold part of the new
+/// archive then.
+pub fn fix_zip<R: Read + Seek, W: Write + Seek>(
+    reader: R,
+    writer: &mut W,
+    name: &Path,
+    options: &FixOptions,
+) -> Result<FixReport, Error> {
+    let mut report = FixReport::default();
+    let archive = RawArchive::new(reader).context("Failed to read ZIP archive")?;
+    if let Some(mut file) = fix_zip_to_tempfile(archive, name, options, &mut report)? {
+        std::io::copy(&mut file, writer).context("Failed to write new archive")?;
+        writer.flush().context("Failed to write new archive")?;
+    }
+    Ok(report)
+}
+
+fn fix_file(path: &Path, options: &FixOptions, report: &mut FixReport) -> Result<()> {
+    if options.dry_run {
+        return fix_any_format(path, options, report);
+    }
+    let _lock = ArchiveLock::acquire(path)?;
+    let permissions = std::fs::metadata(path)
+        .context(format!("Failed to open {}", path.display()))?
+        .permissions();
+    if permissions.readonly() && !options.force_readonly {
+        return Err(Error::other(format!(
+            "{} is read-only (use --force-readonly to fix it anyway)",
+            path.display()
+        )));
+    }
+    let modified = if options.preserve_mtime {
+        Some(modification_time(path)?)
+    } else {
+        None
+    };
+
+    // Windows refuses to replace read-only files
+    #[cfg(windows)]
+    if permissions.readonly() {
+        let mut writable = permissions.clone();
+        #[allow(clippy::permissions_set_readonly_false)] // Only the attribute on Windows
+        writable.set_readonly(false);
+        std::fs::set_permissions(path, writable)
+            .context(format!("Failed to make {} writable", path.display()))?;
+    }
+
+    let result = fix_any_format(path, options, report).and_then(|()| match modified {
+        Some(modified) if modification_time(path)? != modified => File::options()
+            .write(true)
+            .open(path)
+            .and_then(|file| file.set_modified(modified))
+            .context(format!(
+                "Failed to restore the modification time of {}",
+                path.display()
+            )),
+        _ => Ok(()),
+    });
+
+    // The new archive gets the permissions of the original, and a read-only
+    // original made writable gets them back even if fixing failed
+    let restored = match std::fs::metadata(path) {
+        Ok(metadata) if metadata.permissions() != permissions => {
+            std::fs::set_permissions(path, permissions).context(format!(
+                "Failed to restore the permissions of {}",
+                path.display()
+            ))
+        }
+        _ => Ok(()),
+    };
+    result.and(restored)
+}
+
+fn modification_time(path: &Path) -> Result<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .context(format!(
+            "Failed to read the modification time of {}",
+            path.display()
+        ))
+}
+
+/// Fix the file at `path` the way its format is fixed
+fn fix_any_format(path: &Path, options: &FixOptions, report: &mut FixReport) -> Result<()> {
+    #[cfg(feature = "cli")]
+    if rar::is_rar(path)? || iso::is_iso(path)? || arj::is_arj(path)? || lha::is_lha(path)? {
+        return Err(Error::other(
+            "RAR, ARJ and LHA archives and ISO images cannot be rewritten, \
+             see runzip detect and runzip extract",
+        ));
+    }
+    if tarball::is_tar(path)? {
+        fix_tar(path, options, report)
+    } else if sevenz::is_7z(path)? {
+        fix_7z(path, options, report)
+    } else if gzip::is_gzip(path)? {
+        fix_gzip(path, options, report)
+    } else {
+        fix_cyrillic_filenames(path, options, report)
+    }
+}
@@ -0,0 +1,91 @@
+//! Limits guarding against decompression bombs
+
+use crate::error::{Error, Result};
+use crate::rawzip::Entry;
+
+/// Optional caps on the sizes declared in the central directory
+#[derive(Clone, Copy, Default)]
+pub struct Limits {
+    /// Largest uncompressed size of a single entry
+    pub entry_size: Option<u64>,
+    /// Largest uncompressed size of all entries together
+    pub total_size: Option<u64>,
+    /// Largest ratio of uncompressed to compressed size
+    pub ratio: Option<u64>,
+}
+
+impl Limits {
+    /// Fail on the first entry that exceeds a limit
+    pub fn check(&self, entries: &[Entry]) -> Result<()> {
+        let mut total: u64 = 0;
+        for (index, entry) in entries.iter().enumerate() {
+            self.check_entry(
+                index,
+                &entry.name,
+                entry.uncompressed_size,
+                entry.compressed_size,
+                &mut total,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Fail if entry `index` named `name`, compressed `compressed_size` ->
+    /// `size` bytes, exceeds a limit; `total` is the size unpacked before
+    /// it, to which its own size is added
+    pub fn check_entry(
+        &self,
+        index: usize,
+        name: &[u8],
+        size: u64,
+        compressed_size: u64,
+        total: &mut u64,
+    ) -> Result<()> {
+        let describe = || format!("Entry {index} \"{}\"", String::from_utf8_lossy(name));
+
+        if let Some(max) = self.entry_size
+            && size > max
+        {
+            return Err(Error::other(format!(
+                "{} unpacks to {size} bytes, more than --max-entry-size {max}",
+                describe()
+            )));
+        }
+
+        if let Some(max) = self.ratio
+            && size > compressed_size.saturating_mul(max)
+        {
+            return Err(Error::other(format!(
+                "{} is compressed {compressed_size} -> {size} bytes, more than --max-ratio {max}:1",
+                describe()
+            )));
+        }
+
+        *total = total.saturating_add(size);
+        if let Some(max) = self.total_size
+            && *total > max
+        {
+            return Err(Error::other(format!(
+                "{} brings the unpacked size to {total} bytes, more than --max-total-size {max}",
+                describe()
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Parse a size in bytes with an optional K, M or G (binary) suffix
+#[cfg(feature = "cli")]
+pub fn parse_size(text: &str) -> Result<u64, String> {
+    let (digits, multiplier) = match text.char_indices().last() {
+        Some((i, 'k' | 'K')) => (&text[..i], 1 << 10),
+        Some((i, 'm' | 'M')) => (&text[..i], 1 << 20),
+        Some((i, 'g' | 'G')) => (&text[..i], 1 << 30),
+        _ => (text, 1),
+    };
+    digits
+        .parse::<u64>()
+        .ok()
+        .and_then(|value| value.checked_mul(multiplier))
+        .ok_or_else(|| format!("invalid size \"{text}\""))
+}
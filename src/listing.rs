@@ -0,0 +1,252 @@
+//! Tables printed by `runzip list`, and its `zipinfo` compatible output
+
+use std::fmt::Write as _;
+use unicode_normalization::char::is_combining_mark;
+
+use crate::rawzip::{self, Entry};
+
+/// Name of a ZIP compression method
+pub fn method_name(method: u16) -> String {
+    match method {
+        0 => "stored".to_string(),
+        1 => "shrunk".to_string(),
+        6 => "imploded".to_string(),
+        8 => "deflated".to_string(),
+        9 => "deflate64".to_string(),
+        12 => "bzip2".to_string(),
+        14 => "lzma".to_string(),
+        93 => "zstd".to_string(),
+        95 => "xz".to_string(),
+        99 => "aes".to_string(),
+        _ => format!("method {method}"),
+    }
+}
+
+/// Printable form of a raw name: UTF-8 names as is, otherwise printable
+/// ASCII as is and other bytes as `\xNN`
+pub fn escape_name(name: &[u8]) -> String {
+    if let Ok(text) = std::str::from_utf8(name)
+        && !text.chars().any(char::is_control)
+    {
+        return text.to_string();
+    }
+    name.iter().fold(String::new(), |mut out, &byte| {
+        if byte.is_ascii_graphic() || byte == b' ' {
+            out.push(char::from(byte));
+        } else {
+            let _ = write!(out, "\\x{byte:02x}");
+        }
+        out
+    })
+}
+
+/// Bytes of a raw name in hex, separated by spaces (`--show-raw`)
+pub fn hex_name(name: &[u8]) -> String {
+    let bytes: Vec<String> = name.iter().map(|byte| format!("{byte:02x}")).collect();
+    bytes.join(" ")
+}
+
+/// A raw name read as Latin-1, with control characters, backslashes and
+/// the invisible no-break space and soft hyphen as `\xNN` (`--show-raw`)
+pub fn latin1_name(name: &[u8]) -> String {
+    name.iter().fold(String::new(), |mut out, &byte| {
+        let c = char::from(byte);
+        if c.is_control() || matches!(c, '\\' | '\u{a0}' | '\u{ad}') {
+            let _ = write!(out, "\\x{byte:02x}");
+        } else {
+            out.push(c);
+        }
+        out
+    })
+}
+
+/// Print `rows` under `header` in aligned columns, right-aligning the
+/// columns flagged in `numeric`
+pub fn print_table(header: &[&str], numeric: &[bool], rows: &[Vec<String>]) {
+    let mut widths: Vec<usize> = header.iter().map(|title| display_width(title)).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(display_width(cell));
+        }
+    }
+
+    let header: Vec<String> = header.iter().map(ToString::to_string).collect();
+    for row in std::iter::once(&header).chain(rows) {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .zip(numeric)
+            .map(|((cell, &width), &numeric)| {
+                let padding = " ".repeat(width - display_width(cell));
+                if numeric {
+                    format!("{padding}{cell}")
+                } else {
+                    format!("{cell}{padding}")
+                }
+            })
+            .collect();
+        println!("{}", cells.join("  ").trim_end());
+    }
+}
+
+/// Columns `text` takes in a terminal, not counting combining marks
+fn display_width(text: &str) -> usize {
+    text.chars().filter(|&c| !is_combining_mark(c)).count()
+}
+
+/// Abbreviation `zipinfo` uses for the host system of `version_made_by`
+fn host_name(version_made_by: u16) -> &'static str {
+    match version_made_by >> 8 {
+        0 => "fat",
+        1 => "ami",
+        2 => "vms",
+        3 => "unx",
+        4 => "v/cm",
+        5 => "atr",
+        6 => "hpf",
+        7 => "mac",
+        10 => "t20",
+        11 => "ntf",
+        13 => "acn",
+        14 => "vft",
+        15 => "mvs",
+        18 => "os/400",
+        19 => "osx",
+        _ => "???",
+    }
+}
+
+/// Unix permissions in `ls -l` form
+fn unix_permissions(mode: u32) -> String {
+    let kind = match mode & 0o170_000 {
+        0o040_000 => 'd',
+        0o120_000 => 'l',
+        0o010_000 => 'p',
+        0o140_000 => 's',
+        0o060_000 => 'b',
+        0o020_000 => 'c',
+        _ => '-',
+    };
+    let mut text = String::from(kind);
+    for shift in [6, 3, 0] {
+        let bits = mode >> shift;
+        text.push(if bits & 4 != 0 { 'r' } else { '-' });
+        text.push(if bits & 2 != 0 { 'w' } else { '-' });
+        text.push(if bits & 1 != 0 { 'x' } else { '-' });
+    }
+    text
+}
+
+/// MS-DOS attributes in the form `zipinfo` prints them (`drwxahs`)
+fn dos_attributes(attributes: u32, name: &str) -> String {
+    let is_dir = attributes & 0x10 != 0;
+    let lower = name.to_ascii_lowercase();
+    let executable = [".exe", ".com", ".bat", ".cmd", ".btm"]
+        .iter()
+        .any(|extension| lower.ends_with(extension));
+    [
+        if is_dir { 'd' } else { '-' },
+        'r',
+        if attributes & 0x01 == 0 { 'w' } else { '-' },
+        if is_dir || executable { 'x' } else { '-' },
+        if attributes & 0x20 != 0 { 'a' } else { '-' },
+        if attributes & 0x02 != 0 { 'h' } else { '-' },
+        if attributes & 0x04 != 0 { 's' } else { '-' },
+    ]
+    .iter()
+    .collect()
+}
+
+/// Abbreviated compression method as `zipinfo` prints it
+fn method_abbreviation(entry: &Entry) -> String {
+    match entry.compression_method {
+        0 => "stor".to_string(),
+        1 => "shrk".to_string(),
+        method @ 2..=5 => format!("re:{}", method - 1),
+        6 => "i#:#".to_string(),
+        8 => format!(
+            "def{}",
+            ['N', 'X', 'F', 'S'][usize::from(entry.flags >> 1 & 3)]
+        ),
+        9 => "d64N".to_string(),
+        12 => "bzp2".to_string(),
+        14 => "lzma".to_string(),
+        93 => "zstd".to_string(),
+        95 => "xz".to_string(),
+        method => format!("u{method:03}"),
+    }
+}
+
+/// Modification time of an entry as `yy-Mon-dd hh:mm`
+fn dos_timestamp(date: u16, time: u16) -> String {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let month = MONTHS
+        .get(usize::from(date >> 5 & 0xF).wrapping_sub(1))
+        .unwrap_or(&"???");
+    format!(
+        "{:02}-{month}-{:02} {:02}:{:02}",
+        (1980 + (date >> 9)) % 100,
+        date & 0x1F,
+        time >> 11,
+        time >> 5 & 0x3F
+    )
+}
+
+/// A line of `zipinfo` output for `entry`, with `name` in place of the raw
+/// name
+pub fn zipinfo_line(entry: &Entry, name: &str) -> String {
+    let unix_mode = entry.external_attributes >> 16;
+    let permissions = if entry.version_made_by >> 8 == 3 && unix_mode != 0 {
+        unix_permissions(unix_mode)
+    } else {
+        dos_attributes(entry.external_attributes, name)
+    };
+    let mut kind = if entry.internal_attributes & 1 != 0 {
+        't'
+    } else {
+        'b'
+    };
+    if entry.flags & 1 != 0 {
+        kind = kind.to_ascii_uppercase();
+    }
+    let extra = match (
+        !entry.extra.is_empty(),
+        entry.flags & rawzip::FLAG_DATA_DESCRIPTOR != 0,
+    ) {
+        (true, true) => 'X',
+        (false, true) => 'l',
+        (true, false) => 'x',
+        (false, false) => '-',
+    };
+    format!(
+        "{permissions:<10}  {}.{} {:<3} {:>8} {kind}{extra} {} {} {name}",
+        entry.version_made_by % 256 / 10,
+        entry.version_made_by % 256 % 10,
+        host_name(entry.version_made_by),
+        entry.uncompressed_size,
+        method_abbreviation(entry),
+        dos_timestamp(entry.last_mod_date, entry.last_mod_time),
+    )
+}
+
+/// The closing line of `zipinfo` output
+pub fn zipinfo_summary(entries: &[Entry]) -> String {
+    let uncompressed: u64 = entries.iter().map(|entry| entry.uncompressed_size).sum();
+    let compressed: u64 = entries.iter().map(|entry| entry.compressed_size).sum();
+    // Saved space in tenths of a percent, negative if the data grew
+    let (sign, tenths) = if uncompressed == 0 {
+        ("", 0)
+    } else if compressed <= uncompressed {
+        ("", (uncompressed - compressed) * 1000 / uncompressed)
+    } else {
+        ("-", (compressed - uncompressed) * 1000 / uncompressed)
+    };
+    let ratio = format!("{sign}{}.{}", tenths / 10, tenths % 10);
+    format!(
+        "{} file{}, {uncompressed} bytes uncompressed, {compressed} bytes compressed:  {ratio}%",
+        entries.len(),
+        if entries.len() == 1 { "" } else { "s" }
+    )
+}
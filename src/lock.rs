@@ -0,0 +1,86 @@
+//! Advisory locks keeping concurrent runs away from the same archive
+//!
+//! The lock is taken on a `<file>.runzip.lock` file next to the archive, not
+//! on the archive itself: Windows locks are mandatory, so a locked archive
+//! could not be read through another handle. The lock file is removed when
+//! the lock is released; one left behind by a run that is killed is simply
+//! locked again. A run that opened the lock file just before it was removed
+//! locks a file no longer there and has to open it again.
+
+use std::fs::{File, OpenOptions, TryLockError};
+use std::path::{Path, PathBuf};
+
+use crate::error::{Context, Error, Result};
+
+/// Exclusive lock on an archive, released when dropped
+pub struct ArchiveLock {
+    path: PathBuf,
+    _file: File,
+}
+
+impl ArchiveLock {
+    /// Lock `path`, failing at once if another run holds the lock
+    pub fn acquire(path: &Path) -> Result<Self> {
+        // Failing on a missing archive before leaving a lock file for it
+        std::fs::metadata(path).context(format!("Failed to open {}", path.display()))?;
+        let lock_path = lock_path(path);
+        loop {
+            let file = OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(&lock_path)
+                .context(format!("Failed to create {}", lock_path.display()))?;
+            match file.try_lock() {
+                Ok(()) => {}
+                Err(TryLockError::WouldBlock) => {
+                    return Err(Error::other(format!(
+                        "{} is being modified by another runzip process",
+                        path.display()
+                    )));
+                }
+                Err(TryLockError::Error(e)) => {
+                    return Err(e).context(format!("Failed to lock {}", path.display()));
+                }
+            }
+            if is_same_file(&file, &lock_path) {
+                return Ok(Self {
+                    path: lock_path,
+                    _file: file,
+                });
+            }
+        }
+    }
+}
+
+impl Drop for ArchiveLock {
+    fn drop(&mut self) {
+        // Removed while still locked, so no other run can have locked it
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Location of the lock file of the archive at `path`
+fn lock_path(path: &Path) -> PathBuf {
+    let mut lock_path = path.as_os_str().to_owned();
+    lock_path.push(".runzip.lock");
+    PathBuf::from(lock_path)
+}
+
+/// Whether `path` still names the opened `file`
+#[cfg(unix)]
+fn is_same_file(file: &File, path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    match (file.metadata(), std::fs::metadata(path)) {
+        (Ok(opened), Ok(current)) => opened.dev() == current.dev() && opened.ino() == current.ino(),
+        _ => false,
+    }
+}
+
+/// Whether `path` still names the opened `file`, assumed where files have
+/// no portable identity
+#[cfg(not(unix))]
+fn is_same_file(_file: &File, _path: &Path) -> bool {
+    true
+}
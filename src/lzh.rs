@@ -0,0 +1,268 @@
+//! Decompressors of the LHA and ARJ formats
+//!
+//! LHA `-lh5-` to `-lh7-` and ARJ methods 1 to 3 share the same scheme: LZ77
+//! matches coded with static Huffman tables sent at the start of each block.
+//! They only differ in the window size and the number of distance codes. ARJ
+//! method 4 uses simple variable length codes instead.
+
+use std::io::Read;
+
+use crate::error::{Error, Result};
+
+const THRESHOLD: usize = 3;
+/// Literals, and match lengths from THRESHOLD to 256
+const NC: usize = 256 + 256 + 2 - THRESHOLD;
+const NT: usize = 19;
+const CBIT: u32 = 9;
+const TBIT: u32 = 5;
+
+/// Parameters of the block-Huffman method variants
+#[derive(Clone, Copy)]
+pub struct Method {
+    /// Number of distance codes (dictionary bits + 1)
+    np: usize,
+    /// Bits used to send the number of distance code lengths
+    pbit: u32,
+}
+
+pub const LH5: Method = Method { np: 14, pbit: 4 };
+pub const LH6: Method = Method { np: 16, pbit: 5 };
+pub const LH7: Method = Method { np: 17, pbit: 5 };
+/// ARJ methods 1 to 3 differ only in the compressor effort
+pub const ARJ: Method = Method { np: 17, pbit: 5 };
+
+/// Most significant bit first reader, reading zeros past the end like the
+/// original decoders
+struct BitReader<R: Read> {
+    reader: R,
+    buffer: u32,
+    count: u32,
+}
+
+impl<R: Read> BitReader<R> {
+    fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buffer: 0,
+            count: 0,
+        }
+    }
+
+    fn bits(&mut self, n: u32) -> Result<u32> {
+        if n == 0 {
+            return Ok(0);
+        }
+        while self.count < n {
+            let mut byte = [0; 1];
+            if self.reader.read(&mut byte)? == 0 {
+                byte[0] = 0;
+            }
+            self.buffer = self.buffer << 8 | u32::from(byte[0]);
+            self.count += 8;
+        }
+        self.count -= n;
+        let value = self.buffer >> self.count & ((1 << n) - 1);
+        self.buffer &= (1 << self.count) - 1;
+        Ok(value)
+    }
+
+    fn bit(&mut self) -> Result<bool> {
+        Ok(self.bits(1)? == 1)
+    }
+}
+
+/// Canonical Huffman code: shorter codes first, symbols in order within a length
+enum Huffman {
+    /// A table with a single symbol, coded with no bits at all
+    Single(usize),
+    Codes {
+        counts: [u16; 17],
+        symbols: Vec<usize>,
+    },
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0; 17];
+        for &length in lengths {
+            counts[usize::from(length)] += 1;
+        }
+        counts[0] = 0;
+        let mut symbols: Vec<usize> = (0..lengths.len()).filter(|&i| lengths[i] > 0).collect();
+        symbols.sort_by_key(|&i| lengths[i]);
+        Huffman::Codes { counts, symbols }
+    }
+
+    fn decode<R: Read>(&self, bits: &mut BitReader<R>) -> Result<usize> {
+        let (counts, symbols) = match self {
+            Huffman::Single(symbol) => return Ok(*symbol),
+            Huffman::Codes { counts, symbols } => (counts, symbols),
+        };
+        let (mut code, mut first, mut index) = (0, 0, 0);
+        for &count in &counts[1..] {
+            code |= usize::from(bits.bit()?);
+            let count = usize::from(count);
+            if code - first < count {
+                return Ok(symbols[index + code - first]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(Error::other("Invalid Huffman code in compressed data"))
+    }
+}
+
+/// Block state of the block-Huffman decoder
+struct Tables {
+    remaining: u32,
+    literals: Huffman,
+    distances: Huffman,
+}
+
+fn read_pt_len<R: Read>(
+    bits: &mut BitReader<R>,
+    count: usize,
+    nbit: u32,
+    special: Option<usize>,
+) -> Result<Huffman> {
+    let n = usize::try_from(bits.bits(nbit)?)?;
+    if n == 0 {
+        return Ok(Huffman::Single(usize::try_from(bits.bits(nbit)?)?));
+    }
+    if n > count {
+        return Err(Error::other("Invalid code lengths in compressed data"));
+    }
+    let mut lengths = vec![0; count];
+    let mut i = 0;
+    while i < n {
+        let mut length = bits.bits(3)?;
+        if length == 7 {
+            while bits.bit()? {
+                length += 1;
+                if length > 16 {
+                    return Err(Error::other("Invalid code lengths in compressed data"));
+                }
+            }
+        }
+        lengths[i] = u8::try_from(length)?;
+        i += 1;
+        if Some(i) == special {
+            let zeros = usize::try_from(bits.bits(2)?)?;
+            i = (i + zeros).min(count);
+        }
+    }
+    Ok(Huffman::new(&lengths))
+}
+
+fn read_c_len<R: Read>(bits: &mut BitReader<R>, lengths_code: &Huffman) -> Result<Huffman> {
+    let n = usize::try_from(bits.bits(CBIT)?)?;
+    if n == 0 {
+        return Ok(Huffman::Single(usize::try_from(bits.bits(CBIT)?)?));
+    }
+    if n > NC {
+        return Err(Error::other("Invalid code lengths in compressed data"));
+    }
+    let mut lengths = vec![0; NC];
+    let mut i = 0;
+    while i < n {
+        match lengths_code.decode(bits)? {
+            // Runs of zero lengths
+            0 => i += 1,
+            1 => i += usize::try_from(bits.bits(4)?)? + 3,
+            2 => i += usize::try_from(bits.bits(CBIT)?)? + 20,
+            length => {
+                lengths[i] = u8::try_from(length - 2)?;
+                i += 1;
+            }
+        }
+    }
+    if i > NC {
+        return Err(Error::other("Invalid code lengths in compressed data"));
+    }
+    Ok(Huffman::new(&lengths))
+}
+
+fn read_tables<R: Read>(bits: &mut BitReader<R>, method: Method) -> Result<Tables> {
+    let remaining = bits.bits(16)?;
+    let lengths_code = read_pt_len(bits, NT, TBIT, Some(3))?;
+    let literals = read_c_len(bits, &lengths_code)?;
+    let distances = read_pt_len(bits, method.np, method.pbit, None)?;
+    Ok(Tables {
+        remaining,
+        literals,
+        distances,
+    })
+}
+
+/// Copy a match of `length` bytes from `distance` bytes back
+fn copy_match(output: &mut Vec<u8>, distance: usize, length: usize) -> Result<()> {
+    if distance > output.len() {
+        return Err(Error::other("Invalid match distance in compressed data"));
+    }
+    let start = output.len() - distance;
+    for i in 0..length {
+        output.push(output[start + i]);
+    }
+    Ok(())
+}
+
+/// Decompress `size` bytes compressed with a block-Huffman method
+pub fn decode<R: Read>(reader: R, method: Method, size: usize) -> Result<Vec<u8>> {
+    let mut bits = BitReader::new(reader);
+    let mut output = Vec::with_capacity(size);
+    let mut tables: Option<Tables> = None;
+    while output.len() < size {
+        let block = match &mut tables {
+            Some(block) if block.remaining > 0 => block,
+            _ => tables.insert(read_tables(&mut bits, method)?),
+        };
+        block.remaining -= 1;
+        let code = block.literals.decode(&mut bits)?;
+        if code < 256 {
+            output.push(u8::try_from(code)?);
+            continue;
+        }
+        let length = code - 256 + THRESHOLD;
+        let mut distance = block.distances.decode(&mut bits)?;
+        if distance > 1 {
+            distance =
+                (1 << (distance - 1)) + usize::try_from(bits.bits(u32::try_from(distance - 1)?)?)?;
+        }
+        copy_match(&mut output, distance + 1, length)?;
+    }
+    output.truncate(size);
+    Ok(output)
+}
+
+/// Read a value coded as a run of ones choosing the width of the bits that follow
+fn decode_arj_value<R: Read>(bits: &mut BitReader<R>, start: u32, stop: u32) -> Result<usize> {
+    let (mut plus, mut power) = (0, 1 << start);
+    let mut width = start;
+    while width < stop {
+        if !bits.bit()? {
+            break;
+        }
+        plus += power;
+        power <<= 1;
+        width += 1;
+    }
+    Ok(usize::try_from(bits.bits(width)?)? + plus)
+}
+
+/// Decompress `size` bytes compressed with ARJ method 4
+pub fn decode_arj_fastest<R: Read>(reader: R, size: usize) -> Result<Vec<u8>> {
+    let mut bits = BitReader::new(reader);
+    let mut output = Vec::with_capacity(size);
+    while output.len() < size {
+        let length = decode_arj_value(&mut bits, 0, 7)?;
+        if length == 0 {
+            output.push(u8::try_from(bits.bits(8)?)?);
+        } else {
+            let distance = decode_arj_value(&mut bits, 9, 13)?;
+            copy_match(&mut output, distance + 1, length - 1 + THRESHOLD)?;
+        }
+    }
+    output.truncate(size);
+    Ok(output)
+}
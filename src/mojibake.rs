@@ -0,0 +1,58 @@
+//! Repair of UTF-8 names that were decoded in a single-byte code page and
+//! encoded again, such as `Ð¿Ñ€Ð¸Ð²ÐµÑ‚` (Windows-1252) or `РїСЂРёРІРµС‚`
+//! (Windows-1251) for `привет`
+//!
+//! Encoding such a name back to the code page it was misread in gives the
+//! original UTF-8 bytes. A name counts as misread when that gives Cyrillic
+//! UTF-8 and the lead bytes of Cyrillic characters in UTF-8, 0xD0 and 0xD1,
+//! make up half of its non-ASCII characters as read in the code page, at
+//! least two of them, the signature of every Cyrillic letter having become
+//! two characters. In Windows-1251 those are "Р" and "С", so names misread
+//! there are still Cyrillic, and real words do alternate them with `і`, `ї`
+//! or `ў` now and then (`Сі`, `СІРІ`), which read as UTF-8 are rare letters.
+//! So the original must also fit a language better than the name does.
+
+use crate::encoding::{Encoding, IBM437, UTF_8, WINDOWS_1251, WINDOWS_1252};
+use crate::lang::Lang;
+
+/// Code pages UTF-8 names are misread in, tried in this order
+static MISREADINGS: [&Encoding; 3] = [WINDOWS_1252, IBM437, WINDOWS_1251];
+
+/// The name `name` was before it was misread, with the code page it was
+/// misread in, or `None` if it does not look misread
+pub fn repair(name: &[u8]) -> Option<(String, &'static Encoding)> {
+    let text = std::str::from_utf8(name).ok()?;
+    if text.is_ascii() {
+        return None;
+    }
+    MISREADINGS.into_iter().find_map(|encoding| {
+        let (bytes, had_errors) = encoding.encode(text);
+        if had_errors || !has_lead_pairs(text, encoding) {
+            return None;
+        }
+        let original = String::from_utf8(bytes.into_owned()).ok()?;
+        (original
+            .chars()
+            .any(|c| matches!(c, '\u{0400}'..='\u{04FF}'))
+            && score(&original) > score(text))
+        .then_some((original, encoding))
+    })
+}
+
+/// Whether at least two, and at least half, of the non-ASCII characters of
+/// `text` are 0xD0 or 0xD1 read in `encoding`
+fn has_lead_pairs(text: &str, encoding: &'static Encoding) -> bool {
+    let leads = encoding.decode_without_bom_handling(&[0xD0, 0xD1]).0;
+    let non_ascii = text.chars().filter(|c| !c.is_ascii()).count();
+    let pairs = text.chars().filter(|&c| leads.contains(c)).count();
+    pairs >= 2 && pairs * 2 >= non_ascii
+}
+
+/// How well `text` fits the language it fits best, `None` for none
+fn score(text: &str) -> Option<f64> {
+    Lang::Auto
+        .profiles()
+        .iter()
+        .filter_map(|profile| profile.score(text.as_bytes(), UTF_8))
+        .max_by(f64::total_cmp)
+}
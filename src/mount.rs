@@ -0,0 +1,321 @@
+//! A read-only filesystem of the entries of an archive under their fixed
+//! names (`runzip mount`)
+
+use fuser::{
+    FileAttr, FileType, Filesystem, MountOption, ReplyAttr, ReplyData, ReplyDirectory, ReplyEntry,
+    ReplyOpen, Request,
+};
+use libc::c_int;
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::fs::{File, Metadata};
+use std::os::unix::fs::{FileExt, MetadataExt};
+use std::path::Path;
+use std::sync::mpsc;
+use std::time::{Duration, SystemTime};
+
+use crate::console;
+use crate::error::{Context, Result};
+use crate::extract::ReadOnlyArchive;
+use crate::limits::Limits;
+
+/// How long the kernel may keep names and attributes, which never change
+const TTL: Duration = Duration::from_hours(1);
+
+/// Inode number of the root directory
+const ROOT: u64 = fuser::FUSE_ROOT_ID;
+
+/// A file or directory, whose inode number is its index in `View::nodes`
+/// plus one
+struct Node {
+    /// Path from the root, for messages
+    path: String,
+    parent: u64,
+    /// Index of the entry of a file, `None` for a directory
+    entry: Option<usize>,
+    /// Inode numbers of the children of a directory by name
+    children: BTreeMap<String, u64>,
+    /// Contents of a file, unpacked to a temporary file when first opened
+    contents: Option<File>,
+}
+
+/// The entries of an archive as a tree of directories and files
+pub struct View {
+    archive: Box<dyn ReadOnlyArchive>,
+    nodes: Vec<Node>,
+    /// Limits on the files unpacked, checked when each is first opened
+    limits: Limits,
+    /// Size of the files unpacked so far
+    unpacked: u64,
+    /// Modification time of the archive, which every node has
+    time: SystemTime,
+    uid: u32,
+    gid: u32,
+}
+
+impl View {
+    /// Tree of the entries of `archive` under `names`, relative paths with
+    /// `/` separators, owned by the owner of the archive with its
+    /// `metadata`; an entry whose path is a file already is left out
+    pub fn new(
+        archive: Box<dyn ReadOnlyArchive>,
+        names: &[String],
+        metadata: &Metadata,
+        limits: Limits,
+    ) -> Self {
+        let mut view = View {
+            archive,
+            nodes: Vec::new(),
+            limits,
+            unpacked: 0,
+            time: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+            uid: metadata.uid(),
+            gid: metadata.gid(),
+        };
+        view.add(ROOT, "", None);
+        for (index, name) in names.iter().enumerate() {
+            let is_dir = view.archive.entries()[index].is_dir;
+            let mut parent = ROOT;
+            let mut components = name.split('/').filter(|component| !component.is_empty());
+            let mut component = components.next();
+            while let Some(name) = component {
+                component = components.next();
+                let entry = (component.is_none() && !is_dir).then_some(index);
+                parent = match view.nodes[index_of(parent)].children.get(name) {
+                    Some(&node)
+                        if entry.is_none() && view.nodes[index_of(node)].entry.is_none() =>
+                    {
+                        node
+                    }
+                    Some(_) => break,
+                    None => view.add(parent, name, entry),
+                };
+            }
+        }
+        view
+    }
+
+    /// Add a file with entry `entry`, or a directory, named `name` to
+    /// directory `parent`, returning its inode number
+    fn add(&mut self, parent: u64, name: &str, entry: Option<usize>) -> u64 {
+        let ino = self.nodes.len() as u64 + 1;
+        let path = match self.nodes.get(index_of(parent)) {
+            Some(parent) if !parent.path.is_empty() => format!("{}/{name}", parent.path),
+            _ => name.to_string(),
+        };
+        if let Some(parent) = self.nodes.get_mut(index_of(parent)) {
+            parent.children.insert(name.to_string(), ino);
+        }
+        self.nodes.push(Node {
+            path,
+            parent,
+            entry,
+            children: BTreeMap::new(),
+            contents: None,
+        });
+        ino
+    }
+
+    fn node(&self, ino: u64) -> Option<&Node> {
+        self.nodes.get(index_of(ino))
+    }
+
+    fn kind(&self, ino: u64) -> FileType {
+        match self.node(ino).and_then(|node| node.entry) {
+            Some(_) => FileType::RegularFile,
+            None => FileType::Directory,
+        }
+    }
+
+    /// Contents of file `ino`, unpacking them the first time unless that
+    /// exceeds the limits; prints why they cannot be unpacked
+    fn contents(&mut self, ino: u64) -> Result<&File, c_int> {
+        let node = self.nodes.get_mut(index_of(ino)).ok_or(libc::ENOENT)?;
+        let index = node.entry.ok_or(libc::EISDIR)?;
+        if node.contents.is_none() {
+            let entry = &self.archive.entries()[index];
+            let unpacked = self
+                .limits
+                .check_entry(
+                    index,
+                    &entry.name,
+                    entry.size,
+                    entry.compressed_size,
+                    &mut self.unpacked,
+                )
+                .and_then(|()| {
+                    let mut file = tempfile::tempfile()?;
+                    self.archive.extract_entry(index, &mut file)?;
+                    Ok(file)
+                });
+            match unpacked {
+                Ok(file) => node.contents = Some(file),
+                Err(e) => {
+                    eprintln!(
+                        "{}: Failed to unpack {}: {}",
+                        console::error(),
+                        node.path,
+                        e.chain()
+                    );
+                    return Err(libc::EIO);
+                }
+            }
+        }
+        node.contents.as_ref().ok_or(libc::EIO)
+    }
+
+    /// Attributes of `ino`, with the size of a file as the archive declares
+    /// it, which every format checks when unpacking
+    fn attr(&self, ino: u64) -> Result<FileAttr, c_int> {
+        let node = self.node(ino).ok_or(libc::ENOENT)?;
+        let kind = self.kind(ino);
+        let size = node
+            .entry
+            .map_or(0, |index| self.archive.entries()[index].size);
+        Ok(FileAttr {
+            ino,
+            size,
+            blocks: size.div_ceil(512),
+            atime: self.time,
+            mtime: self.time,
+            ctime: self.time,
+            crtime: self.time,
+            kind,
+            perm: if kind == FileType::Directory {
+                0o555
+            } else {
+                0o444
+            },
+            nlink: if kind == FileType::Directory { 2 } else { 1 },
+            uid: self.uid,
+            gid: self.gid,
+            rdev: 0,
+            blksize: 4096,
+            flags: 0,
+        })
+    }
+}
+
+/// Index in `View::nodes` of the node with inode number `ino`
+fn index_of(ino: u64) -> usize {
+    usize::try_from(ino.wrapping_sub(1)).unwrap_or(usize::MAX)
+}
+
+impl Filesystem for View {
+    fn lookup(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        let child = name
+            .to_str()
+            .and_then(|name| self.node(parent)?.children.get(name).copied());
+        match child.ok_or(libc::ENOENT).and_then(|ino| self.attr(ino)) {
+            Ok(attr) => reply.entry(&TTL, &attr, 0),
+            Err(error) => reply.error(error),
+        }
+    }
+
+    fn getattr(&mut self, _req: &Request<'_>, ino: u64, _fh: Option<u64>, reply: ReplyAttr) {
+        match self.attr(ino) {
+            Ok(attr) => reply.attr(&TTL, &attr),
+            Err(error) => reply.error(error),
+        }
+    }
+
+    fn open(&mut self, _req: &Request<'_>, ino: u64, _flags: i32, reply: ReplyOpen) {
+        match self.contents(ino) {
+            Ok(_) => reply.opened(0, 0),
+            Err(error) => reply.error(error),
+        }
+    }
+
+    fn read(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        size: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyData,
+    ) {
+        let Ok(offset) = u64::try_from(offset) else {
+            return reply.error(libc::EINVAL);
+        };
+        let file = match self.contents(ino) {
+            Ok(file) => file,
+            Err(error) => return reply.error(error),
+        };
+        let mut buffer = vec![0; size as usize];
+        let mut length = 0;
+        while length < buffer.len() {
+            match file.read_at(&mut buffer[length..], offset + length as u64) {
+                Ok(0) => break,
+                Ok(read) => length += read,
+                Err(_) => return reply.error(libc::EIO),
+            }
+        }
+        reply.data(&buffer[..length]);
+    }
+
+    fn readdir(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        mut reply: ReplyDirectory,
+    ) {
+        let Some(node) = self.node(ino) else {
+            return reply.error(libc::ENOENT);
+        };
+        if node.entry.is_some() {
+            return reply.error(libc::ENOTDIR);
+        }
+        let entries = [(ino, "."), (node.parent, "..")].into_iter().chain(
+            node.children
+                .iter()
+                .map(|(name, &child)| (child, name.as_str())),
+        );
+        let skip = usize::try_from(offset).unwrap_or(0);
+        for (next, (child, name)) in (1..).zip(entries).skip(skip) {
+            if reply.add(child, next, self.kind(child), name) {
+                break;
+            }
+        }
+        reply.ok();
+    }
+}
+
+/// Mount `view` of `archive` read-only on the directory `mountpoint` until
+/// it is unmounted or Ctrl-C is pressed
+pub fn mount(view: View, archive: &Path, mountpoint: &Path) -> Result<()> {
+    let options = [
+        MountOption::RO,
+        MountOption::FSName(archive.display().to_string()),
+        MountOption::Subtype("runzip".to_string()),
+    ];
+    let session = fuser::spawn_mount2(view, mountpoint, &options).context(format!(
+        "Failed to mount {} on {}",
+        archive.display(),
+        mountpoint.display()
+    ))?;
+    let (sender, receiver) = mpsc::channel();
+    ctrlc::set_handler(move || {
+        let _ = sender.send(());
+    })
+    .context("Failed to handle Ctrl-C")?;
+    println!(
+        "{} mounted on {}; press Ctrl-C to unmount it",
+        archive.display(),
+        mountpoint.display()
+    );
+
+    // The session ends by itself when the filesystem is unmounted elsewhere,
+    // and unmounts it when dropped otherwise
+    while !session.guard.is_finished() {
+        if receiver.recv_timeout(Duration::from_millis(100)).is_ok() {
+            break;
+        }
+    }
+    drop(session);
+    Ok(())
+}
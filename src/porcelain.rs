@@ -0,0 +1,50 @@
+//! Stable, line-oriented output for scripts (`--porcelain`)
+//!
+//! Version 1 prints one line per entry, fields separated by tabs:
+//!
+//! ```text
+//! <action> <archive> <index> <old name> <new name>
+//! ```
+//!
+//! The action is `ok`, `already-utf8`, `fixed`, `would-fix` or `failed`
+//! (the name could not be converted and is kept). An archive that could
+//! not be processed gets a single `error` line with empty index and names
+//! instead of its entries. Names and archives are escaped like in `--diff`
+//! listings, so they contain no tabs or line breaks. This format does not
+//! change; an incompatible one would be version 2.
+
+use std::io::Write;
+use std::path::Path;
+
+use crate::diff::escape;
+use crate::error::{Context, Result};
+use crate::{Action, EntryResult};
+
+/// Latest version of the format
+pub const VERSION: u32 = 1;
+
+/// Print the line of `entry`
+pub fn write_entry(entry: &EntryResult, dry_run: bool) -> Result<()> {
+    let action = match entry.action {
+        Action::AlreadyUtf8 => "already-utf8",
+        Action::Unchanged => "ok",
+        Action::Fixed if dry_run => "would-fix",
+        Action::Fixed => "fixed",
+        Action::Failed => "failed",
+    };
+    writeln!(
+        std::io::stdout(),
+        "{action}\t{}\t{}\t{}\t{}",
+        escape(entry.archive.as_os_str().as_encoded_bytes()),
+        entry.index,
+        escape(&entry.raw_name),
+        escape(entry.new_name.as_bytes())
+    )
+    .context("Failed to write output")
+}
+
+/// Print the `error` line of an archive that could not be processed
+pub fn write_failure(archive: &Path) -> Result<()> {
+    let archive = escape(archive.as_os_str().as_encoded_bytes());
+    writeln!(std::io::stdout(), "error\t{archive}\t\t\t").context("Failed to write output")
+}
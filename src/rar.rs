@@ -0,0 +1,283 @@
+//! Reading entry names from RAR archive headers
+//!
+//! RAR archives cannot be rewritten without the proprietary compressor, so
+//! this only lists names. RAR 5 names are always UTF-8; RAR 1.5-4 names are
+//! in the OEM code page of the archiving system unless the header also holds
+//! a Unicode copy.
+
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::error::{Context, Error, Result};
+
+const SIGNATURE_V4: &[u8] = b"Rar!\x1a\x07\x00";
+const SIGNATURE_V5: &[u8] = b"Rar!\x1a\x07\x01\x00";
+
+const V4_MAIN_HEADER: u8 = 0x73;
+const V4_FILE_HEADER: u8 = 0x74;
+const V4_END_HEADER: u8 = 0x7b;
+const V4_MAIN_ENCRYPTED: u16 = 0x0080;
+const V4_FILE_LARGE: u16 = 0x0100;
+const V4_FILE_UNICODE: u16 = 0x0200;
+const V4_LONG_BLOCK: u16 = 0x8000;
+/// Size of a file header up to the name, after the common block header
+const V4_FILE_FIELDS_SIZE: usize = 25;
+
+const V5_FILE_HEADER: u64 = 2;
+const V5_ENCRYPTION_HEADER: u64 = 4;
+const V5_END_HEADER: u64 = 5;
+const V5_EXTRA_AREA: u64 = 0x0001;
+const V5_DATA_AREA: u64 = 0x0002;
+const V5_FILE_MTIME: u64 = 0x0002;
+const V5_FILE_CRC: u64 = 0x0004;
+
+/// An entry as listed in a RAR archive
+pub struct Entry {
+    pub name: Vec<u8>,
+    /// Whether the name is known to be UTF-8
+    pub unicode: bool,
+}
+
+/// Whether `path` starts with a RAR signature
+pub fn is_rar(path: &Path) -> Result<bool> {
+    let file = File::open(path).context(format!("Failed to open {}", path.display()))?;
+    let mut signature = Vec::with_capacity(SIGNATURE_V5.len());
+    file.take(SIGNATURE_V5.len() as u64)
+        .read_to_end(&mut signature)?;
+    Ok(signature.starts_with(SIGNATURE_V4) || signature.starts_with(SIGNATURE_V5))
+}
+
+/// List the entries of the RAR archive at `path`
+pub fn list_entries(path: &Path) -> Result<Vec<Entry>> {
+    let file = File::open(path).context(format!("Failed to open {}", path.display()))?;
+    let mut reader = BufReader::new(file);
+    let mut signature = [0; SIGNATURE_V4.len()];
+    reader
+        .read_exact(&mut signature)
+        .context("Not a RAR archive")?;
+    if signature == SIGNATURE_V4 {
+        list_v4(&mut reader)
+    } else if signature[..] == SIGNATURE_V5[..SIGNATURE_V4.len()] {
+        reader.read_exact(&mut [0; 1])?;
+        list_v5(&mut reader)
+    } else {
+        Err(Error::other("Not a RAR archive"))
+    }
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap_or_default())
+}
+
+fn list_v4<R: Read + Seek>(reader: &mut R) -> Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    loop {
+        let mut block = [0; 7];
+        match reader.read_exact(&mut block) {
+            Ok(()) => {}
+            // Archives without an end block just stop
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        }
+        let block_type = block[2];
+        let flags = read_u16(&block, 3);
+        let header_size = usize::from(read_u16(&block, 5));
+        let mut header = vec![0; header_size.checked_sub(7).ok_or_else(truncated)?];
+        reader.read_exact(&mut header).map_err(|_| truncated())?;
+
+        if block_type == V4_MAIN_HEADER && flags & V4_MAIN_ENCRYPTED != 0 {
+            return Err(Error::other("The archive headers are encrypted"));
+        }
+        if block_type == V4_END_HEADER {
+            break;
+        }
+
+        let mut data_size = 0;
+        if flags & V4_LONG_BLOCK != 0 {
+            if header.len() < 4 {
+                return Err(truncated());
+            }
+            data_size = u64::from(read_u32(&header, 0));
+        }
+        if block_type == V4_FILE_HEADER {
+            if header.len() < V4_FILE_FIELDS_SIZE {
+                return Err(truncated());
+            }
+            let name_size = usize::from(read_u16(&header, 19));
+            let mut name_start = V4_FILE_FIELDS_SIZE;
+            if flags & V4_FILE_LARGE != 0 {
+                if header.len() < name_start + 8 {
+                    return Err(truncated());
+                }
+                data_size |= u64::from(read_u32(&header, name_start)) << 32;
+                name_start += 8;
+            }
+            let name = header
+                .get(name_start..name_start + name_size)
+                .ok_or_else(truncated)?;
+            entries.push(v4_entry_name(name, flags & V4_FILE_UNICODE != 0));
+        }
+        reader.seek(SeekFrom::Current(i64::try_from(data_size)?))?;
+    }
+    Ok(entries)
+}
+
+fn truncated() -> Error {
+    Error::other("Truncated RAR header")
+}
+
+/// Split a RAR 4 name field into the name to report
+fn v4_entry_name(field: &[u8], unicode: bool) -> Entry {
+    if !unicode {
+        return Entry {
+            name: field.to_vec(),
+            unicode: false,
+        };
+    }
+    // Unicode names are either plain UTF-8, or an OEM name followed by a NUL
+    // and the Unicode name compressed against it
+    match field.iter().position(|&byte| byte == 0) {
+        None => Entry {
+            name: field.to_vec(),
+            unicode: true,
+        },
+        Some(nul) => match decode_unicode_name(&field[..nul], &field[nul + 1..]) {
+            Some(name) => Entry {
+                name: name.into_bytes(),
+                unicode: true,
+            },
+            None => Entry {
+                name: field[..nul].to_vec(),
+                unicode: false,
+            },
+        },
+    }
+}
+
+/// Decode the compressed Unicode name of RAR 4 headers, which stores each
+/// character as a low byte, a low byte with a shared high byte, a full
+/// UTF-16 unit, or a run copied from the OEM name
+fn decode_unicode_name(oem: &[u8], encoded: &[u8]) -> Option<String> {
+    let mut bytes = encoded.iter().copied();
+    let high_byte = u16::from(bytes.next()?);
+    let mut units = Vec::new();
+    let (mut flags, mut flag_bits) = (0u8, 0);
+    while bytes.len() > 0 {
+        if flag_bits == 0 {
+            flags = bytes.next()?;
+            flag_bits = 8;
+        }
+        match flags >> 6 {
+            0 => units.push(u16::from(bytes.next()?)),
+            1 => units.push(u16::from(bytes.next()?) | high_byte << 8),
+            2 => {
+                let low = bytes.next()?;
+                let high = bytes.next()?;
+                units.push(u16::from_le_bytes([low, high]));
+            }
+            _ => {
+                let length = bytes.next()?;
+                if length & 0x80 == 0 {
+                    for _ in 0..length + 2 {
+                        units.push(u16::from(*oem.get(units.len())?));
+                    }
+                } else {
+                    let correction = bytes.next()?;
+                    for _ in 0..(length & 0x7f) + 2 {
+                        let low = oem.get(units.len())?.wrapping_add(correction);
+                        units.push(u16::from(low) | high_byte << 8);
+                    }
+                }
+            }
+        }
+        flags <<= 2;
+        flag_bits -= 2;
+    }
+    String::from_utf16(&units).ok()
+}
+
+/// Read a RAR 5 variable length integer, 7 bits per byte
+fn read_vint(bytes: &mut impl Iterator<Item = u8>) -> Result<u64> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let byte = bytes.next().ok_or_else(truncated)?;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(Error::other("Invalid RAR header"))
+}
+
+fn list_v5<R: Read + Seek>(reader: &mut R) -> Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    loop {
+        let mut crc = [0; 4];
+        match reader.read_exact(&mut crc) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        }
+        let mut size_bytes = Vec::new();
+        let mut byte = [0; 1];
+        loop {
+            reader.read_exact(&mut byte).map_err(|_| truncated())?;
+            size_bytes.push(byte[0]);
+            if byte[0] & 0x80 == 0 || size_bytes.len() == 3 {
+                break;
+            }
+        }
+        let header_size = read_vint(&mut size_bytes.into_iter())?;
+        let mut header = vec![0; usize::try_from(header_size)?];
+        reader.read_exact(&mut header).map_err(|_| truncated())?;
+
+        let mut fields = header.iter().copied();
+        let header_type = read_vint(&mut fields)?;
+        let flags = read_vint(&mut fields)?;
+        if flags & V5_EXTRA_AREA != 0 {
+            read_vint(&mut fields)?;
+        }
+        let data_size = if flags & V5_DATA_AREA == 0 {
+            0
+        } else {
+            read_vint(&mut fields)?
+        };
+
+        match header_type {
+            V5_ENCRYPTION_HEADER => return Err(Error::other("The archive headers are encrypted")),
+            V5_END_HEADER => break,
+            V5_FILE_HEADER => {
+                let file_flags = read_vint(&mut fields)?;
+                read_vint(&mut fields)?; // Unpacked size
+                read_vint(&mut fields)?; // Attributes
+                let skip = if file_flags & V5_FILE_MTIME == 0 {
+                    0
+                } else {
+                    4
+                } + if file_flags & V5_FILE_CRC == 0 { 0 } else { 4 };
+                if skip > 0 && fields.nth(skip - 1).is_none() {
+                    return Err(truncated());
+                }
+                read_vint(&mut fields)?; // Compression information
+                read_vint(&mut fields)?; // Host OS
+                let name_length = usize::try_from(read_vint(&mut fields)?)?;
+                let name: Vec<u8> = fields.take(name_length).collect();
+                if name.len() != name_length {
+                    return Err(truncated());
+                }
+                entries.push(Entry {
+                    name,
+                    unicode: true,
+                });
+            }
+            _ => {}
+        }
+        reader.seek(SeekFrom::Current(i64::try_from(data_size)?))?;
+    }
+    Ok(entries)
+}
@@ -0,0 +1,972 @@
+//! Low-level ZIP rewriting that copies entries verbatim
+//!
+//! `zip::ZipWriter` only accepts UTF-8 names and regenerates most header
+//! fields. Changing an entry name only requires replacing the name bytes
+//! (and the EFS flag), so this module copies everything else byte for byte,
+//! including the host system and external attributes that hold Unix modes
+//! and mark symlinks.
+
+use flate2::CrcWriter;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use crate::error::{Context, Error, Result};
+
+/// General purpose flag: the entry is encrypted
+const FLAG_ENCRYPTED: u16 = 1;
+/// General purpose flags holding compression options
+const FLAG_COMPRESSION_OPTIONS: u16 = 0b110;
+/// General purpose flag: sizes and CRC follow the data in a descriptor
+pub const FLAG_DATA_DESCRIPTOR: u16 = 1 << 3;
+/// General purpose flag (EFS): name and comment are UTF-8
+pub const FLAG_UTF8: u16 = 1 << 11;
+
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATED: u16 = 8;
+
+const LOCAL_HEADER_SIGNATURE: u32 = 0x0403_4b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x0201_4b50;
+const DATA_DESCRIPTOR_SIGNATURE: u32 = 0x0807_4b50;
+const EOCD_SIGNATURE: u32 = 0x0605_4b50;
+const ZIP64_EOCD_SIGNATURE: u32 = 0x0606_4b50;
+const ZIP64_LOCATOR_SIGNATURE: u32 = 0x0706_4b50;
+
+const LOCAL_HEADER_SIZE: usize = 30;
+const CENTRAL_HEADER_SIZE: usize = 46;
+const EOCD_SIZE: usize = 22;
+const ZIP64_EOCD_SIZE: usize = 56;
+const ZIP64_LOCATOR_SIZE: usize = 20;
+
+/// Extra field holding 64-bit sizes and offsets
+const ZIP64_EXTRA_ID: u16 = 0x0001;
+/// Info-ZIP Unicode Path extra field, only valid for the name it was made for
+const UNICODE_PATH_EXTRA_ID: u16 = 0x7075;
+/// Android zipalign padding extra field: alignment followed by zero bytes
+const ALIGNMENT_EXTRA_ID: u16 = 0xD935;
+
+const U32_MAX: u64 = 0xFFFF_FFFF;
+
+/// "Version made by" host systems
+const HOST_MSDOS: u16 = 0;
+const HOST_UNIX: u16 = 3;
+/// MS-DOS attributes in the low byte of the external attributes
+const DOS_READ_ONLY: u32 = 0x01;
+const DOS_DIRECTORY: u32 = 0x10;
+
+/// A central directory record
+#[derive(Clone)]
+pub struct Entry {
+    pub version_made_by: u16,
+    pub version_needed: u16,
+    pub flags: u16,
+    pub compression_method: u16,
+    pub last_mod_time: u16,
+    pub last_mod_date: u16,
+    pub crc32: u32,
+    pub compressed_size: u64,
+    pub uncompressed_size: u64,
+    pub internal_attributes: u16,
+    pub external_attributes: u32,
+    pub header_offset: u64,
+    pub name: Vec<u8>,
+    pub extra: Vec<u8>,
+    pub comment: Vec<u8>,
+}
+
+impl Entry {
+    pub fn is_utf8(&self) -> bool {
+        self.flags & FLAG_UTF8 != 0
+    }
+
+    /// Whether the entry is marked as made on MS-DOS
+    pub fn is_dos_host(&self) -> bool {
+        self.version_made_by >> 8 == HOST_MSDOS
+    }
+
+    /// Whether the data can be unpacked: it is not encrypted, and stored or
+    /// deflated
+    fn is_readable(&self) -> bool {
+        self.flags & FLAG_ENCRYPTED == 0
+            && matches!(self.compression_method, METHOD_STORED | METHOD_DEFLATED)
+    }
+}
+
+/// An archive opened for verbatim copying of its entries
+pub struct RawArchive<R> {
+    reader: R,
+    entries: Vec<Entry>,
+    comment: Vec<u8>,
+    /// Bytes prepended to the archive (e.g. a self-extractor stub)
+    prefix_len: u64,
+    /// Whether the entries were recovered from the local headers
+    salvaged: bool,
+}
+
+impl<R: Read + Seek> RawArchive<R> {
+    pub fn new(mut reader: R) -> Result<Self> {
+        let file_len = reader.seek(SeekFrom::End(0))?;
+        let tail_len = file_len.min((EOCD_SIZE + usize::from(u16::MAX)) as u64);
+        let tail_start = file_len - tail_len;
+        let mut tail = vec![0; usize::try_from(tail_len)?];
+        reader.seek(SeekFrom::Start(tail_start))?;
+        reader.read_exact(&mut tail)?;
+
+        let not_found = || Error::format("End of central directory not found");
+        let eocd_pos = (0..=tail.len().checked_sub(EOCD_SIZE).ok_or_else(not_found)?)
+            .rev()
+            .find(|&pos| {
+                let mut fields = Fields::new(&tail[pos..]);
+                fields.u32() == EOCD_SIGNATURE
+                    && pos + EOCD_SIZE + usize::from(Fields::new(&tail[pos + 20..]).u16())
+                        <= tail.len()
+            })
+            .ok_or_else(not_found)?;
+
+        let mut eocd = Fields::new(&tail[eocd_pos..]);
+        eocd.skip(4);
+        let disk = eocd.u16();
+        let cd_disk = eocd.u16();
+        eocd.skip(2);
+        let mut entry_count = u64::from(eocd.u16());
+        let mut cd_size = u64::from(eocd.u32());
+        let mut cd_offset = u64::from(eocd.u32());
+        let comment_len = usize::from(eocd.u16());
+        let comment = tail[eocd_pos + EOCD_SIZE..eocd_pos + EOCD_SIZE + comment_len].to_vec();
+        let mut cd_end = tail_start + eocd_pos as u64;
+
+        if disk != 0 || cd_disk != 0 {
+            return Err(Error::other("Multi-disk archives are not supported"));
+        }
+
+        if eocd_pos >= ZIP64_LOCATOR_SIZE {
+            let mut locator = Fields::new(&tail[eocd_pos - ZIP64_LOCATOR_SIZE..]);
+            if locator.u32() == ZIP64_LOCATOR_SIGNATURE {
+                locator.skip(4);
+                let stated_offset = locator.u64();
+                // Archives with a prefix have the record further than stated,
+                // right before the locator
+                let locator_pos = cd_end - ZIP64_LOCATOR_SIZE as u64;
+                let candidates = [
+                    stated_offset,
+                    locator_pos.saturating_sub(ZIP64_EOCD_SIZE as u64),
+                ];
+                let mut record = [0; ZIP64_EOCD_SIZE];
+                let mut found = None;
+                for pos in candidates {
+                    reader.seek(SeekFrom::Start(pos))?;
+                    if reader.read_exact(&mut record).is_ok()
+                        && Fields::new(&record).u32() == ZIP64_EOCD_SIGNATURE
+                    {
+                        found = Some(pos);
+                        break;
+                    }
+                }
+                let zip64_pos = found
+                    .ok_or_else(|| Error::format("ZIP64 end of central directory not found"))?;
+                let mut zip64 = Fields::new(&record);
+                zip64.skip(24);
+                entry_count = zip64.u64();
+                zip64.skip(8);
+                cd_size = zip64.u64();
+                cd_offset = zip64.u64();
+                cd_end = zip64_pos;
+            }
+        }
+
+        let prefix_len = cd_offset
+            .checked_add(cd_size)
+            .and_then(|cd_stated_end| cd_end.checked_sub(cd_stated_end))
+            .ok_or_else(|| Error::format("Central directory is out of bounds"))?;
+        reader.seek(SeekFrom::Start(cd_offset + prefix_len))?;
+        let mut central = vec![0; usize::try_from(cd_size)?];
+        reader
+            .read_exact(&mut central)
+            .context("Failed to read central directory")?;
+
+        let mut entries = Vec::new();
+        let mut pos = 0;
+        while pos < central.len() {
+            let (entry, len) = parse_central_header(&central[pos..])?;
+            entries.push(entry);
+            pos += len;
+        }
+        if entries.len() as u64 != entry_count {
+            return Err(Error::format(format!(
+                "Central directory lists {} entries, expected {entry_count}",
+                entries.len()
+            )));
+        }
+
+        Ok(Self {
+            reader,
+            entries,
+            comment,
+            prefix_len,
+            salvaged: false,
+        })
+    }
+
+    /// Rebuild the entry list from local file headers, for archives whose
+    /// central directory is damaged or missing
+    ///
+    /// The whole archive is scanned in memory. Entries whose data runs past
+    /// the end of the file are dropped; everything before them is kept.
+    pub fn salvage(mut reader: R) -> Result<Self> {
+        let mut data = Vec::new();
+        reader.seek(SeekFrom::Start(0))?;
+        reader.read_to_end(&mut data)?;
+
+        let mut entries = Vec::new();
+        let mut pos = 0;
+        while let Some(offset) = find_signature(&data, pos, LOCAL_HEADER_SIGNATURE) {
+            match parse_local_header(&data, offset) {
+                Some((entry, end)) => {
+                    entries.push(entry);
+                    pos = end;
+                }
+                None => pos = offset + 4,
+            }
+        }
+        if entries.is_empty() {
+            return Err(Error::format("No entries found"));
+        }
+
+        Ok(Self {
+            reader,
+            entries,
+            comment: Vec::new(),
+            prefix_len: 0,
+            salvaged: true,
+        })
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// Whether the archive was opened with `salvage`, so its central
+    /// directory needs rebuilding
+    pub fn is_salvaged(&self) -> bool {
+        self.salvaged
+    }
+
+    pub fn entries_mut(&mut self) -> &mut [Entry] {
+        &mut self.entries
+    }
+
+    /// Name stored in the local header of entry `index`, which broken
+    /// archivers sometimes write differently from the central directory
+    pub fn local_name(&mut self, index: usize) -> Result<Vec<u8>> {
+        let (_, name, _) = self.read_local_header(index)?;
+        Ok(name)
+    }
+
+    /// Uncompressed data of entry `index`, or `None` for encrypted entries
+    /// and compression methods other than store and deflate
+    pub fn entry_data(&mut self, index: usize) -> Result<Option<Vec<u8>>> {
+        let mut data = Vec::new();
+        Ok(self.write_entry_data(index, &mut data)?.then_some(data))
+    }
+
+    /// Stream the uncompressed data of entry `index` to `writer`, checking
+    /// its CRC-32 and size at the end
+    ///
+    /// Returns `false` without writing anything for encrypted entries and
+    /// compression methods other than store and deflate.
+    pub fn write_entry_data(&mut self, index: usize, writer: &mut dyn Write) -> Result<bool> {
+        let entry = self.entries[index].clone();
+        if !entry.is_readable() {
+            return Ok(false);
+        }
+        self.read_local_header(index)?;
+
+        let compressed = self.reader.by_ref().take(entry.compressed_size);
+        // One byte more than recorded to detect entries that unpack larger
+        let limit = entry.uncompressed_size.saturating_add(1);
+        let mut checked = CrcWriter::new(writer);
+        let written = if entry.compression_method == METHOD_STORED {
+            io::copy(&mut compressed.take(limit), &mut checked)?
+        } else {
+            io::copy(
+                &mut DeflateDecoder::new(compressed).take(limit),
+                &mut checked,
+            )
+            .context("Failed to inflate entry data")?
+        };
+        if written != entry.uncompressed_size || checked.crc().sum() != entry.crc32 {
+            return Err(Error::format(
+                "Entry data does not match its CRC-32 and size",
+            ));
+        }
+        Ok(true)
+    }
+
+    /// Up to `limit` bytes from the start of the uncompressed data of entry
+    /// `index`, which cannot be checked against its CRC-32, or `None` like
+    /// [`Self::entry_data`]
+    pub fn entry_prefix(&mut self, index: usize, limit: u64) -> Result<Option<Vec<u8>>> {
+        let entry = self.entries[index].clone();
+        if !entry.is_readable() {
+            return Ok(None);
+        }
+        self.read_local_header(index)?;
+
+        let compressed = self.reader.by_ref().take(entry.compressed_size);
+        let mut data = Vec::new();
+        if entry.compression_method == METHOD_STORED {
+            compressed.take(limit).read_to_end(&mut data)?;
+        } else {
+            DeflateDecoder::new(compressed)
+                .take(limit)
+                .read_to_end(&mut data)
+                .context("Failed to inflate entry data")?;
+        }
+        Ok(Some(data))
+    }
+
+    /// Read the local header of entry `index`, returning its fixed part,
+    /// name and extra field, and leave the reader at the entry data
+    fn read_local_header(
+        &mut self,
+        index: usize,
+    ) -> Result<([u8; LOCAL_HEADER_SIZE], Vec<u8>, Vec<u8>)> {
+        let entry = &self.entries[index];
+        self.reader
+            .seek(SeekFrom::Start(entry.header_offset + self.prefix_len))?;
+        let mut header = [0; LOCAL_HEADER_SIZE];
+        self.reader
+            .read_exact(&mut header)
+            .context("Failed to read local file header")?;
+        let mut fields = Fields::new(&header);
+        if fields.u32() != LOCAL_HEADER_SIGNATURE {
+            return Err(Error::format("Invalid local file header"));
+        }
+        fields.skip(22);
+        let mut name = vec![0; usize::from(fields.u16())];
+        let mut extra = vec![0; usize::from(fields.u16())];
+        self.reader
+            .read_exact(&mut name)
+            .context("Failed to read local file header")?;
+        self.reader
+            .read_exact(&mut extra)
+            .context("Failed to read local file header")?;
+        Ok((header, name, extra))
+    }
+
+    pub fn comment(&self) -> &[u8] {
+        &self.comment
+    }
+}
+
+/// Parse one central directory record, returning it and its length
+fn parse_central_header(data: &[u8]) -> Result<(Entry, usize)> {
+    if data.len() < CENTRAL_HEADER_SIZE {
+        return Err(Error::format("Truncated central directory"));
+    }
+    let mut fields = Fields::new(data);
+    if fields.u32() != CENTRAL_HEADER_SIGNATURE {
+        return Err(Error::format("Invalid central directory record"));
+    }
+    let version_made_by = fields.u16();
+    let version_needed = fields.u16();
+    let flags = fields.u16();
+    let compression_method = fields.u16();
+    let last_mod_time = fields.u16();
+    let last_mod_date = fields.u16();
+    let crc32 = fields.u32();
+    let compressed_size = fields.u32();
+    let uncompressed_size = fields.u32();
+    let name_len = usize::from(fields.u16());
+    let extra_len = usize::from(fields.u16());
+    let comment_len = usize::from(fields.u16());
+    let disk_start = fields.u16();
+    let internal_attributes = fields.u16();
+    let external_attributes = fields.u32();
+    let header_offset = fields.u32();
+
+    let len = CENTRAL_HEADER_SIZE + name_len + extra_len + comment_len;
+    if data.len() < len {
+        return Err(Error::format("Truncated central directory record"));
+    }
+    let name = data[CENTRAL_HEADER_SIZE..CENTRAL_HEADER_SIZE + name_len].to_vec();
+    let extra =
+        data[CENTRAL_HEADER_SIZE + name_len..CENTRAL_HEADER_SIZE + name_len + extra_len].to_vec();
+    let comment = data[CENTRAL_HEADER_SIZE + name_len + extra_len..len].to_vec();
+
+    let mut entry = Entry {
+        version_made_by,
+        version_needed,
+        flags,
+        compression_method,
+        last_mod_time,
+        last_mod_date,
+        crc32,
+        compressed_size: u64::from(compressed_size),
+        uncompressed_size: u64::from(uncompressed_size),
+        internal_attributes,
+        external_attributes,
+        header_offset: u64::from(header_offset),
+        name,
+        extra,
+        comment,
+    };
+
+    // 32-bit fields set to all ones are stored in the ZIP64 extra field
+    if let Some(zip64) = find_extra_field(&entry.extra, ZIP64_EXTRA_ID) {
+        let mut zip64 = Fields::new(zip64);
+        if u64::from(uncompressed_size) == U32_MAX {
+            entry.uncompressed_size = zip64.try_u64()?;
+        }
+        if u64::from(compressed_size) == U32_MAX {
+            entry.compressed_size = zip64.try_u64()?;
+        }
+        if u64::from(header_offset) == U32_MAX {
+            entry.header_offset = zip64.try_u64()?;
+        }
+    }
+    if disk_start != 0 && disk_start != 0xFFFF {
+        return Err(Error::other("Multi-disk archives are not supported"));
+    }
+
+    Ok((entry, len))
+}
+
+/// Position of the first `signature` in `data` at or after `start`
+fn find_signature(data: &[u8], start: usize, signature: u32) -> Option<usize> {
+    let signature = signature.to_le_bytes();
+    data.get(start..)?
+        .windows(4)
+        .position(|window| window == signature)
+        .map(|pos| start + pos)
+}
+
+/// Reconstruct an entry from the local file header at `offset`, returning it
+/// and the position right after its data (and data descriptor)
+fn parse_local_header(data: &[u8], offset: usize) -> Option<(Entry, usize)> {
+    let header = data.get(offset..offset + LOCAL_HEADER_SIZE)?;
+    let mut fields = Fields::new(header);
+    fields.skip(4);
+    let version_needed = fields.u16();
+    let flags = fields.u16();
+    let compression_method = fields.u16();
+    let last_mod_time = fields.u16();
+    let last_mod_date = fields.u16();
+    let mut crc32 = fields.u32();
+    let mut compressed_size = u64::from(fields.u32());
+    let mut uncompressed_size = u64::from(fields.u32());
+    let name_len = usize::from(fields.u16());
+    let extra_len = usize::from(fields.u16());
+
+    let name_start = offset + LOCAL_HEADER_SIZE;
+    let data_start = name_start + name_len + extra_len;
+    let name = data.get(name_start..name_start + name_len)?.to_vec();
+    let extra = data.get(name_start + name_len..data_start)?.to_vec();
+
+    // Local ZIP64 extra fields hold both sizes, uncompressed first
+    let zip64 = find_extra_field(&extra, ZIP64_EXTRA_ID);
+    if let Some(zip64) = zip64
+        && (compressed_size == U32_MAX || uncompressed_size == U32_MAX)
+    {
+        let mut zip64 = Fields::new(zip64);
+        uncompressed_size = zip64.try_u64().ok()?;
+        compressed_size = zip64.try_u64().ok()?;
+    }
+
+    let mut end = data_start;
+    if flags & FLAG_DATA_DESCRIPTOR == 0 {
+        end += usize::try_from(compressed_size).ok()?;
+    } else {
+        // The sizes follow the data; find the descriptor whose compressed
+        // size matches its distance from the data
+        let sizes_len = if zip64.is_some() { 16 } else { 8 };
+        let mut pos = data_start;
+        loop {
+            let candidate = data.get(pos..)?;
+            let (signed, rest) = match candidate.get(..4) {
+                Some(signature) if signature == DATA_DESCRIPTOR_SIGNATURE.to_le_bytes() => {
+                    (true, &candidate[4..])
+                }
+                _ => (false, candidate),
+            };
+            if rest.len() >= 4 + sizes_len {
+                let mut fields = Fields::new(rest);
+                let descriptor_crc = fields.u32();
+                let (compressed, uncompressed) = if zip64.is_some() {
+                    (fields.u64(), fields.u64())
+                } else {
+                    (u64::from(fields.u32()), u64::from(fields.u32()))
+                };
+                if compressed == (pos - data_start) as u64 {
+                    crc32 = descriptor_crc;
+                    compressed_size = compressed;
+                    uncompressed_size = uncompressed;
+                    end = pos + usize::from(signed) * 4 + 4 + sizes_len;
+                    break;
+                }
+            }
+            pos += 1;
+        }
+    }
+    if end > data.len() {
+        return None;
+    }
+
+    let entry = Entry {
+        version_made_by: version_needed,
+        version_needed,
+        flags,
+        compression_method,
+        last_mod_time,
+        last_mod_date,
+        crc32,
+        compressed_size,
+        uncompressed_size,
+        internal_attributes: 0,
+        // The attributes only live in the central directory; mark
+        // directories so extractors create them
+        external_attributes: if name.ends_with(b"/") { 0x10 } else { 0 },
+        header_offset: offset as u64,
+        name,
+        extra,
+        comment: Vec::new(),
+    };
+    Some((entry, end))
+}
+
+/// Writes a new archive from entries copied out of a [`RawArchive`]
+pub struct RawWriter<W> {
+    writer: W,
+    position: u64,
+    central: Vec<Entry>,
+    /// Alignment of the data of stored (uncompressed) entries
+    alignment: u16,
+    /// Whether entries are marked as made on MS-DOS
+    dos_host: bool,
+}
+
+impl<W: Write> RawWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            position: 0,
+            central: Vec::new(),
+            alignment: 1,
+            dos_host: false,
+        }
+    }
+
+    /// Pad local headers so that stored entry data starts at a multiple of
+    /// `alignment` bytes, the way Android's zipalign does
+    pub fn align_stored(mut self, alignment: u16) -> Self {
+        self.alignment = alignment;
+        self
+    }
+
+    /// Mark every entry as made on MS-DOS, so extractors read legacy names in
+    /// the OEM code page; Unix modes become the matching DOS attributes
+    pub fn dos_host(mut self) -> Self {
+        self.dos_host = true;
+        self
+    }
+
+    /// Add the central directory record of an entry that was just written
+    fn push_central(&mut self, mut entry: Entry) {
+        if self.dos_host && !entry.is_dos_host() {
+            let mut attributes = entry.external_attributes & 0xFF;
+            if entry.version_made_by >> 8 == HOST_UNIX {
+                let mode = entry.external_attributes >> 16;
+                if mode & 0o200 == 0 && mode != 0 {
+                    attributes |= DOS_READ_ONLY;
+                }
+            }
+            if entry.name.ends_with(b"/") {
+                attributes |= DOS_DIRECTORY;
+            }
+            entry.external_attributes = attributes;
+            entry.version_made_by = HOST_MSDOS << 8 | entry.version_made_by & 0xFF;
+        }
+        self.central.push(entry);
+    }
+
+    /// Copy entry `index` of `archive`, giving it a new name and EFS flag
+    ///
+    /// Everything else, down to the "version made by" host and the internal
+    /// and external attributes with their DOS hidden, system and read-only
+    /// flags, is kept. A legacy name given with its `unicode_name` gets an
+    /// Info-ZIP Unicode Path extra field with it.
+    pub fn copy_entry<R: Read + Seek>(
+        &mut self,
+        archive: &mut RawArchive<R>,
+        index: usize,
+        name: &[u8],
+        utf8: bool,
+        unicode_name: Option<&str>,
+    ) -> Result<()> {
+        let mut entry = archive.entries[index].clone();
+        let renamed = entry.name != name;
+
+        let (header, _, mut local_extra) = archive.read_local_header(index)?;
+        let reader = &mut archive.reader;
+
+        let flags = if utf8 {
+            entry.flags | FLAG_UTF8
+        } else {
+            entry.flags & !FLAG_UTF8
+        };
+        if renamed || unicode_name.is_some() {
+            local_extra = remove_extra_field(&local_extra, UNICODE_PATH_EXTRA_ID);
+            entry.extra = remove_extra_field(&entry.extra, UNICODE_PATH_EXTRA_ID);
+        }
+        if let Some(unicode_name) = unicode_name {
+            let field = unicode_path_extra(name, unicode_name)?;
+            local_extra.extend_from_slice(&field);
+            entry.extra.extend_from_slice(&field);
+        }
+
+        if entry.compression_method == METHOD_STORED && self.alignment > 1 {
+            local_extra = self.alignment_padding(&local_extra, name.len());
+        }
+
+        // Local header: everything but the flags, name and extra field is kept as is
+        let header_offset = self.position;
+        let mut local = header.to_vec();
+        local[6..8].copy_from_slice(&flags.to_le_bytes());
+        local[26..28].copy_from_slice(&u16::try_from(name.len())?.to_le_bytes());
+        local[28..30].copy_from_slice(&u16::try_from(local_extra.len())?.to_le_bytes());
+        local.extend_from_slice(name);
+        local.extend_from_slice(&local_extra);
+        self.write_all(&local)?;
+
+        let mut data = reader.by_ref().take(entry.compressed_size);
+        let copied = io::copy(&mut data, &mut self.writer).context("Failed to copy entry data")?;
+        if copied != entry.compressed_size {
+            return Err(Error::format("Entry data is truncated"));
+        }
+        self.position += copied;
+
+        if entry.flags & FLAG_DATA_DESCRIPTOR != 0 {
+            let mut descriptor = [0; 24];
+            reader.read_exact(&mut descriptor[..4])?;
+            let signed = Fields::new(&descriptor).u32() == DATA_DESCRIPTOR_SIGNATURE;
+            let sizes_len = if find_extra_field(&local_extra, ZIP64_EXTRA_ID).is_some() {
+                16
+            } else {
+                8
+            };
+            let len = if signed { 8 + sizes_len } else { 4 + sizes_len };
+            reader.read_exact(&mut descriptor[4..len])?;
+            self.write_all(&descriptor[..len])?;
+        }
+
+        entry.flags = flags;
+        entry.name = name.to_vec();
+        entry.header_offset = header_offset;
+        self.push_central(entry);
+        Ok(())
+    }
+
+    /// Write `data` uncompressed as a new entry named `name`, keeping the
+    /// timestamps, attributes and extra fields of `template`
+    pub fn add_stored_entry(
+        &mut self,
+        template: &Entry,
+        name: &[u8],
+        utf8: bool,
+        unicode_name: Option<&str>,
+        data: &[u8],
+    ) -> Result<()> {
+        self.add_entry(template, name, utf8, unicode_name, data, false)
+    }
+
+    /// Write `data` as a new entry named `name`, deflated if `compress` is set
+    /// and that makes it smaller, keeping the timestamps, attributes and extra
+    /// fields of `template`, and a Unicode Path extra field for a legacy
+    /// name given with its `unicode_name`
+    pub fn add_entry(
+        &mut self,
+        template: &Entry,
+        name: &[u8],
+        utf8: bool,
+        unicode_name: Option<&str>,
+        data: &[u8],
+        compress: bool,
+    ) -> Result<()> {
+        let size = data.len() as u64;
+        let deflated = if compress {
+            let mut encoder = DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(data)?;
+            Some(encoder.finish()?).filter(|deflated| deflated.len() < data.len())
+        } else {
+            None
+        };
+        let stored = deflated.as_deref().unwrap_or(data);
+        if size >= U32_MAX || stored.len() as u64 >= U32_MAX {
+            return Err(Error::other("Entry data is too large to store"));
+        }
+        let mut entry = template.clone();
+        entry.flags &=
+            !(FLAG_ENCRYPTED | FLAG_COMPRESSION_OPTIONS | FLAG_DATA_DESCRIPTOR | FLAG_UTF8);
+        if utf8 {
+            entry.flags |= FLAG_UTF8;
+        }
+        if deflated.is_some() {
+            entry.compression_method = METHOD_DEFLATED;
+            entry.version_needed = entry.version_needed.max(20);
+        } else {
+            entry.compression_method = METHOD_STORED;
+        }
+        entry.crc32 = crc32fast::hash(data);
+        entry.compressed_size = stored.len() as u64;
+        entry.uncompressed_size = size;
+        entry.extra = remove_extra_field(&template.extra, ZIP64_EXTRA_ID);
+        // A Unicode Path field only holds for the name it was made for
+        if template.name != name || unicode_name.is_some() {
+            entry.extra = remove_extra_field(&entry.extra, UNICODE_PATH_EXTRA_ID);
+        }
+        if let Some(unicode_name) = unicode_name {
+            entry
+                .extra
+                .extend_from_slice(&unicode_path_extra(name, unicode_name)?);
+        }
+        entry.name = name.to_vec();
+        let mut local_extra = entry.extra.clone();
+        if entry.compression_method == METHOD_STORED && self.alignment > 1 {
+            local_extra = self.alignment_padding(&local_extra, name.len());
+        }
+
+        let mut local = Vec::with_capacity(LOCAL_HEADER_SIZE + name.len() + local_extra.len());
+        local.extend_from_slice(&LOCAL_HEADER_SIGNATURE.to_le_bytes());
+        local.extend_from_slice(&entry.version_needed.to_le_bytes());
+        local.extend_from_slice(&entry.flags.to_le_bytes());
+        local.extend_from_slice(&entry.compression_method.to_le_bytes());
+        local.extend_from_slice(&entry.last_mod_time.to_le_bytes());
+        local.extend_from_slice(&entry.last_mod_date.to_le_bytes());
+        local.extend_from_slice(&entry.crc32.to_le_bytes());
+        local.extend_from_slice(&clamp_u32(entry.compressed_size).to_le_bytes());
+        local.extend_from_slice(&clamp_u32(size).to_le_bytes());
+        local.extend_from_slice(&u16::try_from(name.len())?.to_le_bytes());
+        local.extend_from_slice(&u16::try_from(local_extra.len())?.to_le_bytes());
+        local.extend_from_slice(name);
+        local.extend_from_slice(&local_extra);
+
+        entry.header_offset = self.position;
+        self.write_all(&local)?;
+        self.write_all(stored)?;
+        self.push_central(entry);
+        Ok(())
+    }
+
+    /// Entries written so far, as they will appear in the central directory
+    pub fn entries(&self) -> &[Entry] {
+        &self.central
+    }
+
+    /// Replace any alignment field in `extra` with one that makes the data
+    /// following a local header with a `name_len` byte name aligned
+    fn alignment_padding(&self, extra: &[u8], name_len: usize) -> Vec<u8> {
+        const FIELD_HEADER_LEN: u64 = 6;
+        let mut extra = remove_extra_field(extra, ALIGNMENT_EXTRA_ID);
+        let unpadded =
+            self.position + (LOCAL_HEADER_SIZE + name_len + extra.len()) as u64 + FIELD_HEADER_LEN;
+        // The remainder is smaller than the u16 alignment
+        let misalignment = u16::try_from(unpadded % u64::from(self.alignment)).unwrap_or(0);
+        let padding = (self.alignment - misalignment) % self.alignment;
+        extra.extend_from_slice(&ALIGNMENT_EXTRA_ID.to_le_bytes());
+        extra.extend_from_slice(&(2 + padding).to_le_bytes());
+        extra.extend_from_slice(&self.alignment.to_le_bytes());
+        extra.resize(extra.len() + usize::from(padding), 0);
+        extra
+    }
+
+    /// Write the central directory and return the underlying writer
+    pub fn finish(mut self, comment: &[u8]) -> Result<W> {
+        let cd_offset = self.position;
+        let entry_count = self.central.len() as u64;
+        for entry in std::mem::take(&mut self.central) {
+            let record = central_header(&entry)?;
+            self.write_all(&record)?;
+        }
+        let cd_size = self.position - cd_offset;
+
+        let needs_zip64 = entry_count >= 0xFFFF || cd_size >= U32_MAX || cd_offset >= U32_MAX;
+        if needs_zip64 {
+            let zip64_offset = self.position;
+            let mut record = Vec::with_capacity(ZIP64_EOCD_SIZE + ZIP64_LOCATOR_SIZE);
+            record.extend_from_slice(&ZIP64_EOCD_SIGNATURE.to_le_bytes());
+            record.extend_from_slice(&((ZIP64_EOCD_SIZE - 12) as u64).to_le_bytes());
+            record.extend_from_slice(&45u16.to_le_bytes()); // version made by
+            record.extend_from_slice(&45u16.to_le_bytes()); // version needed
+            record.extend_from_slice(&0u32.to_le_bytes()); // this disk
+            record.extend_from_slice(&0u32.to_le_bytes()); // central directory disk
+            record.extend_from_slice(&entry_count.to_le_bytes());
+            record.extend_from_slice(&entry_count.to_le_bytes());
+            record.extend_from_slice(&cd_size.to_le_bytes());
+            record.extend_from_slice(&cd_offset.to_le_bytes());
+            record.extend_from_slice(&ZIP64_LOCATOR_SIGNATURE.to_le_bytes());
+            record.extend_from_slice(&0u32.to_le_bytes());
+            record.extend_from_slice(&zip64_offset.to_le_bytes());
+            record.extend_from_slice(&1u32.to_le_bytes());
+            self.write_all(&record)?;
+        }
+
+        let mut eocd = Vec::with_capacity(EOCD_SIZE + comment.len());
+        eocd.extend_from_slice(&EOCD_SIGNATURE.to_le_bytes());
+        eocd.extend_from_slice(&0u16.to_le_bytes());
+        eocd.extend_from_slice(&0u16.to_le_bytes());
+        let short_count = u16::try_from(entry_count).unwrap_or(0xFFFF);
+        eocd.extend_from_slice(&short_count.to_le_bytes());
+        eocd.extend_from_slice(&short_count.to_le_bytes());
+        eocd.extend_from_slice(&clamp_u32(cd_size).to_le_bytes());
+        eocd.extend_from_slice(&clamp_u32(cd_offset).to_le_bytes());
+        eocd.extend_from_slice(&u16::try_from(comment.len())?.to_le_bytes());
+        eocd.extend_from_slice(comment);
+        self.write_all(&eocd)?;
+
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn write_all(&mut self, data: &[u8]) -> Result<()> {
+        self.writer.write_all(data)?;
+        self.position += data.len() as u64;
+        Ok(())
+    }
+}
+
+/// Serialize a central directory record, moving large values to ZIP64
+fn central_header(entry: &Entry) -> Result<Vec<u8>> {
+    let mut zip64 = Vec::new();
+    for value in [
+        entry.uncompressed_size,
+        entry.compressed_size,
+        entry.header_offset,
+    ] {
+        if value >= U32_MAX {
+            zip64.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+    let mut extra = remove_extra_field(&entry.extra, ZIP64_EXTRA_ID);
+    if !zip64.is_empty() {
+        let mut field = Vec::with_capacity(4 + zip64.len());
+        field.extend_from_slice(&ZIP64_EXTRA_ID.to_le_bytes());
+        field.extend_from_slice(&u16::try_from(zip64.len())?.to_le_bytes());
+        field.extend_from_slice(&zip64);
+        extra.splice(0..0, field);
+    }
+
+    let mut record = Vec::with_capacity(
+        CENTRAL_HEADER_SIZE + entry.name.len() + extra.len() + entry.comment.len(),
+    );
+    record.extend_from_slice(&CENTRAL_HEADER_SIGNATURE.to_le_bytes());
+    record.extend_from_slice(&entry.version_made_by.to_le_bytes());
+    record.extend_from_slice(&entry.version_needed.to_le_bytes());
+    record.extend_from_slice(&entry.flags.to_le_bytes());
+    record.extend_from_slice(&entry.compression_method.to_le_bytes());
+    record.extend_from_slice(&entry.last_mod_time.to_le_bytes());
+    record.extend_from_slice(&entry.last_mod_date.to_le_bytes());
+    record.extend_from_slice(&entry.crc32.to_le_bytes());
+    record.extend_from_slice(&clamp_u32(entry.compressed_size).to_le_bytes());
+    record.extend_from_slice(&clamp_u32(entry.uncompressed_size).to_le_bytes());
+    record.extend_from_slice(&u16::try_from(entry.name.len())?.to_le_bytes());
+    record.extend_from_slice(&u16::try_from(extra.len())?.to_le_bytes());
+    record.extend_from_slice(&u16::try_from(entry.comment.len())?.to_le_bytes());
+    record.extend_from_slice(&0u16.to_le_bytes()); // disk number start
+    record.extend_from_slice(&entry.internal_attributes.to_le_bytes());
+    record.extend_from_slice(&entry.external_attributes.to_le_bytes());
+    record.extend_from_slice(&clamp_u32(entry.header_offset).to_le_bytes());
+    record.extend_from_slice(&entry.name);
+    record.extend_from_slice(&extra);
+    record.extend_from_slice(&entry.comment);
+    Ok(record)
+}
+
+fn clamp_u32(value: u64) -> u32 {
+    u32::try_from(value).unwrap_or(u32::MAX)
+}
+
+/// Iterate over `(id, data)` pairs of an extra field block
+fn extra_fields(extra: &[u8]) -> impl Iterator<Item = (u16, &[u8])> {
+    let mut rest = extra;
+    std::iter::from_fn(move || {
+        if rest.len() < 4 {
+            return None;
+        }
+        let mut fields = Fields::new(rest);
+        let id = fields.u16();
+        let len = usize::from(fields.u16());
+        let data = rest.get(4..4 + len)?;
+        rest = &rest[4 + len..];
+        Some((id, data))
+    })
+}
+
+/// Info-ZIP Unicode Path extra field giving `unicode_name` as the UTF-8
+/// form of the raw `name`
+fn unicode_path_extra(name: &[u8], unicode_name: &str) -> Result<Vec<u8>> {
+    let mut field = Vec::with_capacity(9 + unicode_name.len());
+    field.extend_from_slice(&UNICODE_PATH_EXTRA_ID.to_le_bytes());
+    field.extend_from_slice(&u16::try_from(5 + unicode_name.len())?.to_le_bytes());
+    field.push(1); // Version
+    field.extend_from_slice(&crc32fast::hash(name).to_le_bytes());
+    field.extend_from_slice(unicode_name.as_bytes());
+    Ok(field)
+}
+
+fn find_extra_field(extra: &[u8], id: u16) -> Option<&[u8]> {
+    extra_fields(extra).find_map(|(field_id, data)| (field_id == id).then_some(data))
+}
+
+fn remove_extra_field(extra: &[u8], id: u16) -> Vec<u8> {
+    let mut result = Vec::with_capacity(extra.len());
+    for (field_id, data) in extra_fields(extra) {
+        if field_id != id {
+            result.extend_from_slice(&field_id.to_le_bytes());
+            result.extend_from_slice(&u16::try_from(data.len()).unwrap_or(0).to_le_bytes());
+            result.extend_from_slice(data);
+        }
+    }
+    result
+}
+
+/// Sequential little-endian reader over a byte slice
+struct Fields<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Fields<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn skip(&mut self, len: usize) {
+        self.data = &self.data[len.min(self.data.len())..];
+    }
+
+    fn take<const N: usize>(&mut self) -> [u8; N] {
+        let mut bytes = [0; N];
+        let len = N.min(self.data.len());
+        bytes[..len].copy_from_slice(&self.data[..len]);
+        self.skip(N);
+        bytes
+    }
+
+    fn u16(&mut self) -> u16 {
+        u16::from_le_bytes(self.take())
+    }
+
+    fn u32(&mut self) -> u32 {
+        u32::from_le_bytes(self.take())
+    }
+
+    fn u64(&mut self) -> u64 {
+        u64::from_le_bytes(self.take())
+    }
+
+    fn try_u64(&mut self) -> Result<u64> {
+        if self.data.len() < 8 {
+            return Err(Error::format("Truncated ZIP64 extra field"));
+        }
+        Ok(self.u64())
+    }
+}
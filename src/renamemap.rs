@@ -0,0 +1,101 @@
+//! Sidecar rename map documenting every change made to an archive
+
+use serde::{Deserialize, Serialize};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use tempfile::NamedTempFile;
+
+use crate::EntryOutcome;
+use crate::error::{Context, Result};
+use crate::hex;
+
+const FORMAT_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+pub struct RenameMap {
+    pub version: u32,
+    pub archive: String,
+    pub entries: Vec<MapEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MapEntry {
+    pub index: usize,
+    /// Raw name bytes as stored in the original archive
+    pub original_name_hex: String,
+    /// Whether the EFS (UTF-8) flag was set in the original archive
+    pub original_utf8_flag: bool,
+    pub new_name: String,
+    /// Raw name bytes written to the fixed archive
+    pub new_name_hex: String,
+    /// Detected source encoding, `None` for names taken from an explicit map
+    pub encoding: Option<String>,
+    pub action: String,
+}
+
+/// Location of the rename map written next to `zipfile`
+pub fn sidecar_path(zipfile: &Path) -> PathBuf {
+    let mut path = zipfile.as_os_str().to_owned();
+    path.push(".runzip.json");
+    PathBuf::from(path)
+}
+
+impl RenameMap {
+    pub fn new(zipfile: &Path) -> Self {
+        Self {
+            version: FORMAT_VERSION,
+            archive: zipfile
+                .file_name()
+                .map_or_else(String::new, |name| name.to_string_lossy().into_owned()),
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn add_entry(
+        &mut self,
+        index: usize,
+        raw_name: &[u8],
+        utf8_flag: bool,
+        outcome: &EntryOutcome,
+    ) {
+        self.entries.push(MapEntry {
+            index,
+            original_name_hex: hex::encode(raw_name),
+            original_utf8_flag: utf8_flag,
+            new_name: outcome.display_name(),
+            new_name_hex: hex::encode(&outcome.new_name),
+            encoding: outcome.encoding.map(|encoding| encoding.name().to_string()),
+            action: outcome.action.label(false).to_string(),
+        });
+    }
+
+    #[cfg(feature = "cli")]
+    pub fn read(path: &Path) -> Result<Self> {
+        let file = std::fs::File::open(path)
+            .with_context(|| format!("Failed to open rename map {}", path.display()))?;
+        let map: Self = serde_json::from_reader(std::io::BufReader::new(file))
+            .with_context(|| format!("Failed to parse rename map {}", path.display()))?;
+        if map.version != FORMAT_VERSION {
+            return Err(crate::Error::other(format!(
+                "Unsupported rename map version {} in {}",
+                map.version,
+                path.display()
+            )));
+        }
+        Ok(map)
+    }
+
+    /// Write the map to a temporary file next to `path`, to be persisted
+    /// there once the archive it describes is in place
+    pub fn stage(&self, path: &Path) -> Result<NamedTempFile> {
+        let dir = path.parent().unwrap_or_else(|| Path::new("."));
+        let file = NamedTempFile::new_in(dir)
+            .with_context(|| format!("Failed to create rename map {}", path.display()))?;
+        let mut writer = BufWriter::new(file.as_file());
+        serde_json::to_writer_pretty(&mut writer, self).context("Failed to write rename map")?;
+        writeln!(writer).context("Failed to write rename map")?;
+        writer.flush().context("Failed to write rename map")?;
+        drop(writer);
+        Ok(file)
+    }
+}
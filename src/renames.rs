@@ -0,0 +1,117 @@
+//! Explicit rename rules loaded from a user-supplied CSV file
+//!
+//! Each record is `old,new`. The old name is either `hex:` followed by the
+//! raw name bytes, or the name as it reads: stored as UTF-8 (e.g. a name
+//! that an earlier run decoded incorrectly), or in a legacy encoding and
+//! decoded in the source or detected encoding.
+//! `runzip detect --emit-map` writes files in the same format.
+
+use std::collections::HashMap;
+#[cfg(feature = "cli")]
+use std::fmt::Write;
+#[cfg(feature = "cli")]
+use std::path::Path;
+
+#[cfg(feature = "cli")]
+use crate::error::{Context, Error, Result};
+#[cfg(feature = "cli")]
+use crate::{csv, hex};
+
+pub struct RenameRules {
+    by_raw_name: HashMap<Vec<u8>, String>,
+    /// Rules given by the old name as it reads rather than as `hex:`
+    by_name: HashMap<String, String>,
+}
+
+impl RenameRules {
+    #[cfg(feature = "cli")]
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read rename map {}", path.display()))?;
+        let records = csv::parse_records(&text)
+            .with_context(|| format!("Failed to parse rename map {}", path.display()))?;
+
+        let mut by_raw_name = HashMap::new();
+        let mut by_name = HashMap::new();
+        for (line, record) in records.iter().enumerate() {
+            let [old, new] = record.as_slice() else {
+                return Err(Error::other(format!(
+                    "Rename map record {} must have exactly two fields: old,new",
+                    line + 1
+                )));
+            };
+            if line == 0 && old == "old" && new == "new" {
+                continue; // Header row
+            }
+            let raw_name = match old.strip_prefix("hex:") {
+                Some(digits) => hex::decode(digits)
+                    .with_context(|| format!("Invalid raw name in record {}", line + 1))?,
+                None => old.as_bytes().to_vec(),
+            };
+            if !old.starts_with("hex:") {
+                by_name.insert(old.clone(), new.clone());
+            }
+            if by_raw_name.insert(raw_name, new.clone()).is_some() {
+                return Err(Error::other(format!(
+                    "Duplicate old name \"{old}\" in rename map"
+                )));
+            }
+        }
+
+        Ok(Self {
+            by_raw_name,
+            by_name,
+        })
+    }
+
+    /// Rules renaming each raw name to its new name
+    #[cfg(feature = "cli")]
+    pub fn from_renames(renames: impl IntoIterator<Item = (Vec<u8>, String)>) -> Result<Self> {
+        let mut by_raw_name = HashMap::new();
+        for (raw_name, new) in renames {
+            if let Some(other) = by_raw_name.get(&raw_name)
+                && *other != new
+            {
+                return Err(Error::other(format!(
+                    "Conflicting new names \"{other}\" and \"{new}\" for \"{}\"",
+                    String::from_utf8_lossy(&raw_name)
+                )));
+            }
+            by_raw_name.insert(raw_name, new);
+        }
+        Ok(Self {
+            by_raw_name,
+            by_name: HashMap::new(),
+        })
+    }
+
+    /// New name for an entry with the given raw name, if a rule matches it
+    pub fn lookup(&self, raw_name: &[u8]) -> Option<&str> {
+        self.by_raw_name.get(raw_name).map(String::as_str)
+    }
+
+    /// Whether any rule gives the old name as it reads, so that legacy names
+    /// have to be decoded to be matched
+    pub fn has_names(&self) -> bool {
+        !self.by_name.is_empty()
+    }
+
+    /// New name for an entry whose name decodes to `name`, if a rule given
+    /// by the old name as it reads matches it
+    pub fn lookup_decoded(&self, name: &str) -> Option<&str> {
+        self.by_name.get(name).map(String::as_str)
+    }
+}
+
+/// Write rename rules for the given raw and new names, old names as hex
+#[cfg(feature = "cli")]
+pub fn write_rules(path: &Path, renames: &[(Vec<u8>, String)]) -> Result<()> {
+    let mut text = String::new();
+    writeln!(text, "{}", csv::format_record(&["old", "new"]))?;
+    for (raw_name, new_name) in renames {
+        let old = format!("hex:{}", hex::encode(raw_name));
+        writeln!(text, "{}", csv::format_record(&[&old, new_name]))?;
+    }
+    std::fs::write(path, text)
+        .with_context(|| format!("Failed to write rename map {}", path.display()))
+}
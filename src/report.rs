@@ -0,0 +1,105 @@
+//! Reports with one record per processed archive entry
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::diff::DiffWriter;
+use crate::error::{Context, Result};
+use crate::porcelain;
+use crate::{EntryResult, FixReport, detection_name};
+use crate::{csv, hex};
+
+/// The reports requested on the command line, fed the same entries
+#[derive(Default)]
+pub struct Reports {
+    pub csv: Option<CsvReport>,
+    pub diff: Option<DiffWriter>,
+    /// Print `--porcelain` lines
+    pub porcelain: bool,
+}
+
+impl Reports {
+    /// Add the entries of an archive fixed successfully
+    pub fn add(&mut self, report: &FixReport, dry_run: bool) -> Result<()> {
+        for entry in &report.entries {
+            if let Some(csv) = &mut self.csv {
+                csv.add_entry(entry, dry_run)?;
+            }
+            if let Some(diff) = &mut self.diff {
+                diff.add_entry(entry)?;
+            }
+            if self.porcelain {
+                porcelain::write_entry(entry, dry_run)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Note an archive that could not be fixed
+    pub fn add_failure(&self, archive: &Path) -> Result<()> {
+        if self.porcelain {
+            porcelain::write_failure(archive)?;
+        }
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<()> {
+        if let Some(csv) = self.csv {
+            csv.finish()?;
+        }
+        if let Some(diff) = self.diff {
+            diff.finish()?;
+        }
+        Ok(())
+    }
+}
+
+pub struct CsvReport {
+    writer: BufWriter<File>,
+}
+
+impl CsvReport {
+    /// Create the report file and write the header row
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create report {}", path.display()))?;
+        let mut report = Self {
+            writer: BufWriter::new(file),
+        };
+        report.write_row(&[
+            "archive",
+            "index",
+            "raw_name_hex",
+            "detected_encoding",
+            "confidence",
+            "new_name",
+            "action",
+        ])?;
+        Ok(report)
+    }
+
+    pub fn add_entry(&mut self, entry: &EntryResult, dry_run: bool) -> Result<()> {
+        self.write_row(&[
+            &entry.archive.display().to_string(),
+            &entry.index.to_string(),
+            &hex::encode(&entry.raw_name),
+            &detection_name(entry.encoding, entry.misread_as).unwrap_or_default(),
+            &entry
+                .confidence
+                .map(|confidence| format!("{confidence:.2}"))
+                .unwrap_or_default(),
+            &entry.new_name,
+            entry.action.label(dry_run),
+        ])
+    }
+
+    /// Flush buffered rows to disk
+    pub fn finish(mut self) -> Result<()> {
+        self.writer.flush().context("Failed to write report")
+    }
+
+    fn write_row(&mut self, fields: &[&str]) -> Result<()> {
+        writeln!(self.writer, "{}", csv::format_record(fields)).context("Failed to write report")
+    }
+}
@@ -0,0 +1,352 @@
+//! Cleanup of converted entry names for the platforms they are extracted on
+
+use crate::encoding::{Encoding, UTF_8};
+
+/// Longest path component most filesystems accept, in bytes
+pub const MAX_COMPONENT_BYTES: usize = 255;
+/// Windows `MAX_PATH`, in UTF-16 code units
+pub const WINDOWS_MAX_PATH: usize = 260;
+
+/// Longest extension kept intact when truncating a component
+const MAX_KEPT_EXTENSION: usize = 16;
+
+/// Characters that are not allowed in Windows file names
+const WINDOWS_FORBIDDEN: &[u8] = b":?*\"<>|";
+
+/// Device names that Windows reserves in every directory, with any extension
+const WINDOWS_RESERVED: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Length of the part of `component` that Windows matches against device names
+fn device_stem_len(component: &[u8]) -> usize {
+    let stem = component
+        .iter()
+        .position(|&byte| byte == b'.')
+        .map_or(component, |dot| &component[..dot]);
+    stem.trim_ascii_end().len()
+}
+
+fn is_windows_reserved(component: &[u8]) -> bool {
+    let stem = &component[..device_stem_len(component)];
+    WINDOWS_RESERVED
+        .iter()
+        .any(|reserved| stem.eq_ignore_ascii_case(reserved.as_bytes()))
+}
+
+/// The first path component of `name` that is a reserved Windows device name
+pub fn windows_reserved_component(name: &[u8]) -> Option<String> {
+    name.split(|&byte| byte == b'/')
+        .find(|component| is_windows_reserved(component))
+        .map(|component| String::from_utf8_lossy(component).into_owned())
+}
+
+/// Whether `name` starts with a Windows drive letter (`C:`)
+fn has_drive_prefix(name: &[u8]) -> bool {
+    name.len() >= 2 && name[0].is_ascii_alphabetic() && name[1] == b':'
+}
+
+/// Whether extracting `name` naively could write outside the target
+/// directory: absolute paths and `..` components, with either separator
+pub fn is_unsafe_path(name: &[u8]) -> bool {
+    name.starts_with(b"/")
+        || name.starts_with(b"\\")
+        || has_drive_prefix(name)
+        || name
+            .split(|&byte| byte == b'/' || byte == b'\\')
+            .any(|component| component == b"..")
+}
+
+/// Make `name` relative and drop its `.`, `..` and empty components,
+/// joining the rest with `/`
+pub fn safe_path(name: &[u8]) -> Vec<u8> {
+    let trailing_slash = name.ends_with(b"/") || name.ends_with(b"\\");
+    let name = if has_drive_prefix(name) {
+        &name[2..]
+    } else {
+        name
+    };
+    let mut result = name
+        .split(|&byte| byte == b'/' || byte == b'\\')
+        .filter(|component| !matches!(*component, b"" | b"." | b".."))
+        .collect::<Vec<_>>()
+        .join(&b'/');
+    if result.is_empty() {
+        result.push(b'_');
+    }
+    if trailing_slash {
+        result.push(b'/');
+    }
+    result
+}
+
+fn is_separator(byte: u8) -> bool {
+    byte == b'/' || byte == b'\\'
+}
+
+/// `name` without what makes it absolute: the `\\?\` and `\\.\` prefixes
+/// of Windows paths, the server and share of UNC paths (`\\server\share\`), a
+/// drive letter and leading separators
+pub fn strip_absolute(name: &[u8]) -> Vec<u8> {
+    let mut rest = name;
+    if let Some(path) = [&b"\\\\?\\"[..], b"\\\\.\\", b"//?/", b"//./"]
+        .iter()
+        .find_map(|prefix| rest.strip_prefix(*prefix))
+    {
+        rest = match path.strip_prefix(b"UNC") {
+            Some(unc) if unc.first().copied().is_some_and(is_separator) => {
+                skip_components(&unc[1..], 2)
+            }
+            _ => path,
+        };
+    } else if rest.len() > 2 && is_separator(rest[0]) && is_separator(rest[1]) {
+        rest = skip_components(&rest[2..], 2);
+    }
+    if has_drive_prefix(rest) {
+        rest = &rest[2..];
+    }
+    while let Some((&first, path)) = rest.split_first()
+        && is_separator(first)
+    {
+        rest = path;
+    }
+    if rest.is_empty() && !name.is_empty() {
+        return if name.last().copied().is_some_and(is_separator) {
+            b"_/".to_vec()
+        } else {
+            b"_".to_vec()
+        };
+    }
+    rest.to_vec()
+}
+
+/// `path` after its first `count` components
+fn skip_components(path: &[u8], count: usize) -> &[u8] {
+    let mut rest = path;
+    for _ in 0..count {
+        rest = match rest.iter().position(|&byte| is_separator(byte)) {
+            Some(separator) => &rest[separator + 1..],
+            None => &[],
+        };
+    }
+    rest
+}
+
+/// `name` without its first `count` directories, an empty name for one of
+/// those directories, or `None` for a file not inside `count` directories
+pub fn strip_components(name: &[u8], count: usize) -> Option<Vec<u8>> {
+    let mut rest = name;
+    for _ in 0..count {
+        let Some(separator) = rest.iter().position(|&byte| byte == b'/') else {
+            return name.ends_with(b"/").then(Vec::new);
+        };
+        rest = &rest[separator + 1..];
+    }
+    Some(rest.to_vec())
+}
+
+/// `name` without the directory `prefix`, an empty name for that
+/// directory, or `None` if `name` is not in it
+pub fn strip_path_prefix(name: &[u8], prefix: &[u8]) -> Option<Vec<u8>> {
+    let rest = name.strip_prefix(prefix)?.strip_prefix(b"/")?;
+    Some(rest.to_vec())
+}
+
+/// Whether `name`, in `encoding`, has control characters (newlines, bell,
+/// ...), which break shells and some extractors
+pub fn has_control_chars(name: &[u8], encoding: &'static Encoding) -> bool {
+    replace_control_chars(name, encoding, false) != name
+}
+
+/// `name`, in `encoding`, with each control character replaced by `_`, or
+/// by the `%XX` escapes of its bytes if `escape` is set
+///
+/// Unicode names also have their C1 controls replaced.
+pub fn replace_control_chars(name: &[u8], encoding: &'static Encoding, escape: bool) -> Vec<u8> {
+    let mut fixed = Vec::with_capacity(name.len());
+    let mut push = |bytes: &[u8], control: bool| {
+        if !control {
+            fixed.extend_from_slice(bytes);
+        } else if escape {
+            for byte in bytes {
+                fixed.extend_from_slice(format!("%{byte:02X}").as_bytes());
+            }
+        } else {
+            fixed.push(b'_');
+        }
+    };
+    match std::str::from_utf8(name) {
+        Ok(text) if encoding == UTF_8 => {
+            let mut buffer = [0; 4];
+            for c in text.chars() {
+                push(c.encode_utf8(&mut buffer).as_bytes(), c.is_control());
+            }
+        }
+        // The C0 controls and DEL are the same in every ASCII-compatible
+        // code page, and never part of a multi-byte character
+        _ => {
+            for &byte in name {
+                push(&[byte], byte.is_ascii_control());
+            }
+        }
+    }
+    fixed
+}
+
+/// `name`, in `encoding`, with the backslashes some Windows tools separate
+/// directories with replaced by `/`
+///
+/// Multi-byte code pages such as Shift-JIS can have a backslash byte inside
+/// a character, so their names are decoded first.
+pub fn forward_slashes(name: &[u8], encoding: &'static Encoding) -> Vec<u8> {
+    if encoding == UTF_8 || encoding.is_single_byte() {
+        return name
+            .iter()
+            .map(|&byte| if byte == b'\\' { b'/' } else { byte })
+            .collect();
+    }
+    match encoding.decode_without_bom_handling_and_without_replacement(name) {
+        Some(text) if text.contains('\\') => {
+            let text = text.replace('\\', "/");
+            let (fixed, had_errors) = encoding.encode(&text);
+            if had_errors {
+                name.to_vec()
+            } else {
+                fixed.into_owned()
+            }
+        }
+        _ => name.to_vec(),
+    }
+}
+
+/// Apply `fix` to every path component of `name`, keeping the separators
+fn map_components(name: &[u8], fix: impl Fn(&[u8]) -> Vec<u8>) -> Vec<u8> {
+    let (path, trailing_slash) = match name.strip_suffix(b"/") {
+        Some(path) => (path, true),
+        None => (name, false),
+    };
+    let mut result = path
+        .split(|&byte| byte == b'/')
+        .map(fix)
+        .collect::<Vec<_>>()
+        .join(&b'/');
+    if trailing_slash {
+        result.push(b'/');
+    }
+    result
+}
+
+/// Replace characters Windows does not allow in names with `_`, strip
+/// trailing dots and spaces, which Windows silently drops on extraction,
+/// and append `_` to reserved device names (`CON` -> `CON_`)
+pub fn windows_names(name: &[u8]) -> Vec<u8> {
+    map_components(name, |component| {
+        if component == b"." || component == b".." {
+            return component.to_vec();
+        }
+        let mut fixed: Vec<u8> = component
+            .iter()
+            .map(|&byte| {
+                if WINDOWS_FORBIDDEN.contains(&byte) {
+                    b'_'
+                } else {
+                    byte
+                }
+            })
+            .collect();
+        while matches!(fixed.last(), Some(b'.' | b' ')) {
+            fixed.pop();
+        }
+        if fixed.is_empty() && !component.is_empty() {
+            fixed.push(b'_');
+        }
+        if is_windows_reserved(&fixed) {
+            fixed.insert(device_stem_len(&fixed), b'_');
+        }
+        fixed
+    })
+}
+
+/// Length of the longest path component of `name`, in bytes
+pub fn longest_component(name: &[u8]) -> usize {
+    name.split(|&byte| byte == b'/')
+        .map(<[u8]>::len)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Length of `name` as a Windows path, in UTF-16 code units
+pub fn windows_path_len(name: &[u8]) -> usize {
+    String::from_utf8_lossy(name).encode_utf16().count()
+}
+
+/// Longest prefix of `bytes` no longer than `max` that does not split a
+/// UTF-8 character
+fn char_boundary_prefix(bytes: &[u8], max: usize) -> &[u8] {
+    if bytes.len() <= max {
+        return bytes;
+    }
+    match std::str::from_utf8(bytes) {
+        Ok(text) => {
+            let end = (0..=max)
+                .rev()
+                .find(|&end| text.is_char_boundary(end))
+                .unwrap_or(0);
+            &bytes[..end]
+        }
+        Err(_) => &bytes[..max],
+    }
+}
+
+/// Split a component into stem and extension (including the dot), keeping
+/// only short extensions
+fn split_extension(component: &[u8]) -> (&[u8], &[u8]) {
+    match component.iter().rposition(|&byte| byte == b'.') {
+        Some(dot) if dot > 0 && component.len() - dot <= MAX_KEPT_EXTENSION => {
+            component.split_at(dot)
+        }
+        _ => (component, &[]),
+    }
+}
+
+/// Shorten a component to at most `max` bytes, keeping its extension
+fn truncate_component(component: &[u8], max: usize) -> Vec<u8> {
+    if component.len() <= max {
+        return component.to_vec();
+    }
+    let (stem, extension) = split_extension(component);
+    let mut truncated = char_boundary_prefix(stem, max - extension.len()).to_vec();
+    truncated.extend_from_slice(extension);
+    truncated
+}
+
+/// Shorten every path component of `name` to at most `max` bytes
+pub fn truncate_components(name: &[u8], max: usize) -> Vec<u8> {
+    map_components(name, |component| truncate_component(component, max))
+}
+
+/// Add a `~N` suffix to the last component of `name`, before its extension,
+/// staying within `max` bytes per component
+pub fn with_unique_suffix(name: &[u8], n: usize, max: usize) -> Vec<u8> {
+    let (path, trailing_slash) = match name.strip_suffix(b"/") {
+        Some(path) => (path, true),
+        None => (name, false),
+    };
+    let (parent, leaf) = match path.iter().rposition(|&byte| byte == b'/') {
+        Some(slash) => path.split_at(slash + 1),
+        None => (&[][..], path),
+    };
+    let suffix = format!("~{n}");
+    let (stem, extension) = split_extension(leaf);
+    let stem = char_boundary_prefix(stem, max.saturating_sub(extension.len() + suffix.len()));
+
+    let mut result = parent.to_vec();
+    result.extend_from_slice(stem);
+    result.extend_from_slice(suffix.as_bytes());
+    result.extend_from_slice(extension);
+    if trailing_slash {
+        result.push(b'/');
+    }
+    result
+}
@@ -0,0 +1,81 @@
+//! Fixing entry names in 7z archives
+//!
+//! 7z stores names as UTF-16, so mojibake there comes from archivers that
+//! decoded the original bytes with the wrong code page, usually Windows-1252
+//! on Western systems. Such names are encoded back to recover the bytes.
+//!
+//! The entries are decompressed and recompressed with LZMA2, one stream per
+//! entry; timestamps and attributes are kept.
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+#[cfg(feature = "sevenz")]
+use {
+    encoding_rs::WINDOWS_1252,
+    sevenz_rust::{Password, SevenZReader, SevenZWriter},
+    std::collections::HashMap,
+    std::io::{Seek, Write},
+};
+
+use crate::error::{Context, Result};
+
+const MAGIC: &[u8] = b"7z\xbc\xaf\x27\x1c";
+
+/// Whether `path` starts with the 7z signature
+pub fn is_7z(path: &Path) -> Result<bool> {
+    let file = File::open(path).context(format!("Failed to open {}", path.display()))?;
+    let mut magic = Vec::with_capacity(MAGIC.len());
+    file.take(MAGIC.len() as u64).read_to_end(&mut magic)?;
+    Ok(magic == MAGIC)
+}
+
+/// The bytes a name was decoded from, if it looks like legacy bytes decoded
+/// as Windows-1252 rather than real text
+#[cfg(feature = "sevenz")]
+pub fn misdecoded_bytes(name: &str) -> Option<Vec<u8>> {
+    // Cyrillic names are fine already
+    if name.is_ascii() || name.chars().any(|c| ('\u{400}'..='\u{4ff}').contains(&c)) {
+        return None;
+    }
+    let (bytes, _, had_errors) = WINDOWS_1252.encode(name);
+    (!had_errors).then(|| bytes.into_owned())
+}
+
+/// Names of the entries of the 7z archive at `path`
+#[cfg(feature = "sevenz")]
+pub fn list_names(path: &Path) -> Result<Vec<String>> {
+    let reader =
+        SevenZReader::open(path, Password::empty()).context("Failed to read 7z archive")?;
+    Ok(reader
+        .archive()
+        .files
+        .iter()
+        .map(|entry| entry.name.clone())
+        .collect())
+}
+
+/// Copy the 7z archive at `path` to `writer`, renaming entries as listed in
+/// `new_names` (old name to new name)
+#[cfg(feature = "sevenz")]
+pub fn rewrite<W: Write + Seek>(
+    path: &Path,
+    writer: W,
+    new_names: &HashMap<String, String>,
+) -> Result<W> {
+    let mut reader =
+        SevenZReader::open(path, Password::empty()).context("Failed to read 7z archive")?;
+    let mut writer = SevenZWriter::new(writer).context("Failed to create 7z archive")?;
+    reader
+        .for_each_entries(|entry, data| {
+            let mut entry = entry.clone();
+            if let Some(new_name) = new_names.get(&entry.name) {
+                entry.name.clone_from(new_name);
+            }
+            let data = entry.has_stream.then_some(data);
+            writer.push_archive_entry(entry, data)?;
+            Ok(true)
+        })
+        .context("Failed to copy 7z entries")?;
+    writer.finish().context("Failed to finalize new archive")
+}
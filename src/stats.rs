@@ -0,0 +1,183 @@
+//! Encoding statistics across a corpus of archives (`runzip stats`)
+//!
+//! Every archive is counted once per table: under the encoding most of its
+//! non-ASCII names are detected in, the system most of its entries were made
+//! on, and the decade of its newest entry. Nothing is rewritten, so a
+//! collection can be surveyed before it is migrated.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::error::{Context, Result};
+use crate::{
+    Action, FixOptions, Hints, Host, apply_name_fixups, hints, listing, open_archive,
+    process_entry_name,
+};
+
+/// Counts of archives by encoding, creator system and decade
+#[derive(Default)]
+pub struct Survey {
+    archives: usize,
+    encodings: BTreeMap<String, usize>,
+    hosts: BTreeMap<String, usize>,
+    decades: BTreeMap<String, usize>,
+}
+
+impl Survey {
+    /// Count the archive at `path`
+    pub fn add_archive(&mut self, path: &Path, options: &FixOptions) -> Result<()> {
+        let mut archive = open_archive(path, None)?;
+        let content = if options.content_hints && options.source_encoding.is_none() {
+            hints::content_encoding(&mut archive, &options.candidates)
+        } else {
+            None
+        };
+
+        let mut names: HashMap<&str, usize> = HashMap::new();
+        let mut hosts: HashMap<u16, usize> = HashMap::new();
+        let mut newest = None;
+        let mut used_names = HashSet::new();
+        for entry in archive.entries() {
+            *hosts.entry(entry.version_made_by >> 8).or_default() += 1;
+            if let Some(year) = dos_year(entry.last_mod_date) {
+                newest = newest.max(Some(year));
+            }
+            if entry.name.is_ascii() {
+                continue;
+            }
+            let mut outcome = process_entry_name(
+                &entry.name,
+                entry.is_utf8(),
+                Hints {
+                    host: Host::from_version_made_by(entry.version_made_by),
+                    content,
+                },
+                options,
+            );
+            apply_name_fixups(&mut outcome, options, &used_names);
+            used_names.insert(outcome.new_name.clone());
+            let encoding = match (outcome.action, outcome.encoding) {
+                (Action::AlreadyUtf8, _) => "UTF-8",
+                (Action::Failed, _) => "undetected",
+                (_, encoding) => encoding.map_or("UTF-8", |encoding| encoding.name()),
+            };
+            *names.entry(encoding).or_default() += 1;
+        }
+
+        self.archives += 1;
+        let encoding = most_common(names).unwrap_or("ASCII");
+        *self.encodings.entry(encoding.to_string()).or_default() += 1;
+        let host = most_common(hosts).map_or("none", host_system);
+        *self.hosts.entry(host.to_string()).or_default() += 1;
+        let decade = newest.map_or_else(
+            || "unknown".to_string(),
+            |year| format!("{}s", year / 10 * 10),
+        );
+        *self.decades.entry(decade).or_default() += 1;
+        Ok(())
+    }
+
+    /// Print a table per statistic, with the share of the archives counted
+    pub fn print(&self) {
+        println!(
+            "{} archive{}",
+            self.archives,
+            if self.archives == 1 { "" } else { "s" }
+        );
+        for (title, counts) in [
+            ("Encoding", &self.encodings),
+            ("Made on", &self.hosts),
+            ("Decade", &self.decades),
+        ] {
+            println!();
+            let mut rows: Vec<(&String, &usize)> = counts.iter().collect();
+            // Most common first; the map keeps ties in alphabetical order
+            // and decades in chronological order
+            if title != "Decade" {
+                rows.sort_by(|a, b| b.1.cmp(a.1));
+            }
+            let rows: Vec<Vec<String>> = rows
+                .into_iter()
+                .map(|(key, &count)| vec![key.clone(), count.to_string(), self.percentage(count)])
+                .collect();
+            listing::print_table(&[title, "Archives", "Share"], &[false, true, true], &rows);
+        }
+    }
+
+    fn percentage(&self, count: usize) -> String {
+        // Counts of archives are far below 2^52, so the conversion is exact
+        #[allow(clippy::cast_precision_loss)]
+        let share = count as f64 * 100.0 / self.archives as f64;
+        format!("{share:.1}%")
+    }
+}
+
+/// ZIP archives among `paths`, searching directories recursively; other
+/// files are taken as given
+pub fn collect_archives(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut archives = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            collect_from_dir(path, &mut archives)?;
+        } else {
+            archives.push(path.clone());
+        }
+    }
+    Ok(archives)
+}
+
+fn collect_from_dir(dir: &Path, archives: &mut Vec<PathBuf>) -> Result<()> {
+    let mut entries = std::fs::read_dir(dir)
+        .and_then(Iterator::collect::<std::io::Result<Vec<_>>>)
+        .with_context(|| format!("Failed to read directory {}", dir.display()))?;
+    entries.sort_by_key(std::fs::DirEntry::file_name);
+    for entry in entries {
+        let path = entry.path();
+        // Symbolic links are not followed, so no archive is counted twice
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            collect_from_dir(&path, archives)?;
+        } else if file_type.is_file()
+            && path
+                .extension()
+                .is_some_and(|extension| extension.eq_ignore_ascii_case("zip"))
+        {
+            archives.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// The key counted most often, the smallest of those on a tie
+fn most_common<K: Ord + Copy>(counts: HashMap<K, usize>) -> Option<K> {
+    counts
+        .into_iter()
+        .max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0)))
+        .map(|(key, _)| key)
+}
+
+/// Year of an MS-DOS date, `None` if the date is unset or invalid
+fn dos_year(date: u16) -> Option<u16> {
+    let month = date >> 5 & 0xF;
+    let day = date & 0x1F;
+    ((1..=12).contains(&month) && day != 0).then_some(1980 + (date >> 9))
+}
+
+/// Name of the system in the upper byte of "version made by"
+fn host_system(host: u16) -> &'static str {
+    match host {
+        0 => "MS-DOS",
+        1 => "Amiga",
+        2 => "OpenVMS",
+        3 => "Unix",
+        5 => "Atari ST",
+        6 => "OS/2",
+        7 => "Macintosh",
+        10 => "TOPS-20",
+        11 => "Windows NTFS",
+        14 => "Windows VFAT",
+        18 => "OS/400",
+        19 => "OS X",
+        _ => "other",
+    }
+}
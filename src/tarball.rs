@@ -0,0 +1,307 @@
+//! Rewriting member names of tar archives
+//!
+//! Headers and data are copied as is. A new name goes where the old one was
+//! stored (the header, a GNU long name member or a PAX `path` record); names
+//! that no longer fit the header get a GNU long name member.
+
+use crate::compression;
+use std::io::{Read, Write};
+use std::path::Path;
+use tar::{Archive, Builder, EntryType, Header};
+
+use crate::error::{Context, Error, Result};
+
+const BLOCK_SIZE: usize = 512;
+const NAME_FIELD_LEN: usize = 100;
+const PREFIX_FIELD_LEN: usize = 155;
+const MAGIC_OFFSET: usize = 257;
+
+const TAR_EXTENSIONS: &[&str] = &[
+    ".tar", ".tar.gz", ".tgz", ".tar.xz", ".txz", ".tar.zst", ".tzst",
+];
+
+/// A member as the tar crate presents it, with extension records applied
+pub struct Member {
+    pub name: Vec<u8>,
+    /// Names from PAX records are UTF-8 by definition
+    pub name_is_utf8: bool,
+    pub link_name: Option<Vec<u8>>,
+    pub hard_link: bool,
+}
+
+/// New names for a member
+pub struct Rename {
+    pub name: Vec<u8>,
+    pub link_name: Option<Vec<u8>>,
+}
+
+/// Whether `path` looks like a tar archive, possibly compressed: the ustar
+/// magic, or a tar extension for old V7 archives without it
+pub fn is_tar(path: &Path) -> Result<bool> {
+    let mut block = Vec::with_capacity(BLOCK_SIZE);
+    // Data that fails to decompress is left to the other formats
+    let has_magic = compression::open(path)?
+        .take(BLOCK_SIZE as u64)
+        .read_to_end(&mut block)
+        .is_ok()
+        && block.get(MAGIC_OFFSET..MAGIC_OFFSET + 5) == Some(b"ustar".as_slice());
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let has_extension = TAR_EXTENSIONS
+        .iter()
+        .any(|extension| file_name.ends_with(extension));
+    Ok(has_magic || has_extension)
+}
+
+/// List the members of the tar archive read from `reader`
+pub fn list_members<R: Read>(reader: R) -> Result<Vec<Member>> {
+    let mut archive = Archive::new(reader);
+    let mut members = Vec::new();
+    for entry in archive.entries().context("Failed to read tar archive")? {
+        let mut entry = entry.context("Failed to read tar archive")?;
+        let entry_type = entry.header().entry_type();
+        if entry_type.is_gnu_sparse() {
+            return Err(Error::other("GNU sparse files are not supported"));
+        }
+        let name_is_utf8 = match entry.pax_extensions()? {
+            Some(extensions) => pax_path_is_utf8(extensions.filter_map(Result::ok)),
+            None => false,
+        };
+        members.push(Member {
+            name: entry.path_bytes().into_owned(),
+            name_is_utf8,
+            link_name: entry.link_name_bytes().map(std::borrow::Cow::into_owned),
+            hard_link: entry_type.is_hard_link(),
+        });
+    }
+    Ok(members)
+}
+
+/// Copy the tar archive read from `reader` to `writer`, renaming the members
+/// listed by [`list_members`] in order
+pub fn rewrite<R: Read, W: Write>(reader: R, writer: W, renames: &[Rename]) -> Result<W> {
+    let mut archive = Archive::new(reader);
+    let mut builder = Builder::new(writer);
+    let mut renames = renames.iter();
+    let mut extensions = Vec::new();
+
+    for entry in archive
+        .entries()
+        .context("Failed to read tar archive")?
+        .raw(true)
+    {
+        let mut entry = entry.context("Failed to read tar archive")?;
+        let header = entry.header().clone();
+        let entry_type = header.entry_type();
+        let recognized = header.as_gnu().is_some() || header.as_ustar().is_some();
+        if recognized
+            && (entry_type.is_gnu_longname()
+                || entry_type.is_gnu_longlink()
+                || entry_type.is_pax_local_extensions())
+        {
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data)?;
+            extensions.push((header, data));
+            continue;
+        }
+
+        let rename = renames
+            .next()
+            .ok_or_else(|| Error::other("The tar archive changed while fixing it"))?;
+        write_member(
+            &mut builder,
+            std::mem::take(&mut extensions),
+            header,
+            &mut entry,
+            rename,
+        )?;
+    }
+
+    builder
+        .into_inner()
+        .context("Failed to finalize new archive")
+}
+
+fn write_member<W: Write>(
+    builder: &mut Builder<W>,
+    extensions: Vec<(Header, Vec<u8>)>,
+    mut header: Header,
+    data: &mut impl Read,
+    rename: &Rename,
+) -> Result<()> {
+    let mut name_stored = false;
+    let mut link_stored = rename.link_name.is_none();
+
+    for (mut extension, mut contents) in extensions {
+        let entry_type = extension.entry_type();
+        if entry_type.is_gnu_longname() {
+            contents = zero_terminated(&rename.name);
+            name_stored = true;
+        } else if entry_type.is_gnu_longlink()
+            && let Some(link_name) = &rename.link_name
+        {
+            contents = zero_terminated(link_name);
+            link_stored = true;
+        } else if entry_type.is_pax_local_extensions() {
+            let (records, has_path, has_link) = replace_pax_paths(&contents, rename)?;
+            contents = records;
+            name_stored |= has_path;
+            link_stored |= has_link;
+        }
+        extension.set_size(contents.len() as u64);
+        append(builder, &mut extension, &contents[..])?;
+    }
+
+    // The header fields are what readers without extension support see, so
+    // they get the new name too, shortened if it does not fit
+    if !set_header_name(&mut header, &rename.name) && !name_stored {
+        append_long_name(builder, EntryType::GNULongName, &rename.name)?;
+    }
+    if let Some(link_name) = &rename.link_name {
+        let fits = link_name.len() <= NAME_FIELD_LEN;
+        set_field(&mut header.as_old_mut().linkname, link_name);
+        if !fits && !link_stored {
+            append_long_name(builder, EntryType::GNULongLink, link_name)?;
+        }
+    }
+    append(builder, &mut header, data)
+}
+
+fn append<W: Write>(builder: &mut Builder<W>, header: &mut Header, data: impl Read) -> Result<()> {
+    header.set_cksum();
+    builder
+        .append(header, data)
+        .context("Failed to write tar member")
+}
+
+/// Write a GNU long name (or long link name) member for the next member
+fn append_long_name<W: Write>(
+    builder: &mut Builder<W>,
+    entry_type: EntryType,
+    name: &[u8],
+) -> Result<()> {
+    let contents = zero_terminated(name);
+    let mut header = Header::new_gnu();
+    set_field(&mut header.as_old_mut().name, b"././@LongLink");
+    header.set_mode(0o644);
+    header.set_entry_type(entry_type);
+    header.set_size(contents.len() as u64);
+    append(builder, &mut header, &contents[..])
+}
+
+/// Whether a member with PAX records `extensions` has a UTF-8 `path`: one
+/// that is valid UTF-8 and not marked as raw bytes by `hdrcharset=BINARY`,
+/// as Python's tarfile writes names it cannot decode
+fn pax_path_is_utf8<'a>(extensions: impl Iterator<Item = tar::PaxExtension<'a>>) -> bool {
+    let mut path = None;
+    let mut binary = false;
+    for extension in extensions {
+        match extension.key_bytes() {
+            b"path" => path = Some(extension.value_bytes()),
+            b"hdrcharset" => binary = extension.value_bytes() == b"BINARY",
+            _ => {}
+        }
+    }
+    !binary && path.is_some_and(|path| std::str::from_utf8(path).is_ok())
+}
+
+/// Store `name` in the name (and ustar prefix) fields, returning whether it
+/// fit without shortening
+fn set_header_name(header: &mut Header, name: &[u8]) -> bool {
+    if let Some(ustar) = header.as_ustar_mut() {
+        // A ustar name can be split into a prefix and a name at a slash
+        let split = (0..name.len()).rev().find(|&slash| {
+            name[slash] == b'/'
+                && slash <= PREFIX_FIELD_LEN
+                && name.len() - slash - 1 <= NAME_FIELD_LEN
+                && slash + 1 < name.len()
+        });
+        if name.len() > NAME_FIELD_LEN
+            && let Some(slash) = split
+        {
+            set_field(&mut ustar.prefix, &name[..slash]);
+            set_field(&mut ustar.name, &name[slash + 1..]);
+            return true;
+        }
+        set_field(&mut ustar.prefix, b"");
+    }
+    set_field(&mut header.as_old_mut().name, name);
+    name.len() <= NAME_FIELD_LEN
+}
+
+/// Fill a NUL-padded header field, cutting `value` if it is too long
+fn set_field(field: &mut [u8], value: &[u8]) {
+    let len = value.len().min(field.len());
+    field[..len].copy_from_slice(&value[..len]);
+    field[len..].fill(0);
+}
+
+fn zero_terminated(name: &[u8]) -> Vec<u8> {
+    let mut contents = name.to_vec();
+    contents.push(0);
+    contents
+}
+
+/// Replace the `path` and `linkpath` records of a PAX extended header,
+/// returning the new records and which of the two were present; an
+/// `hdrcharset=BINARY` record stays, as UTF-8 names need no conversion either
+fn replace_pax_paths(records: &[u8], rename: &Rename) -> Result<(Vec<u8>, bool, bool)> {
+    let mut result = Vec::with_capacity(records.len());
+    let (mut has_path, mut has_link) = (false, false);
+    let mut rest = records;
+    while !rest.is_empty() {
+        let invalid = || Error::other("Invalid PAX extended header");
+        let space = rest
+            .iter()
+            .position(|&byte| byte == b' ')
+            .ok_or_else(invalid)?;
+        let len: usize = std::str::from_utf8(&rest[..space])
+            .ok()
+            .and_then(|len| len.parse().ok())
+            .filter(|&len| len > space && len <= rest.len())
+            .ok_or_else(invalid)?;
+        let record = &rest[space + 1..len];
+        rest = &rest[len..];
+
+        let equals = record
+            .iter()
+            .position(|&byte| byte == b'=')
+            .ok_or_else(invalid)?;
+        let key = &record[..equals];
+        match key {
+            b"path" => {
+                result.extend_from_slice(&pax_record(key, &rename.name));
+                has_path = true;
+            }
+            b"linkpath" if rename.link_name.is_some() => {
+                let link_name = rename.link_name.as_deref().unwrap_or_default();
+                result.extend_from_slice(&pax_record(key, link_name));
+                has_link = true;
+            }
+            _ => {
+                result.extend_from_slice(&len.to_string().into_bytes());
+                result.push(b' ');
+                result.extend_from_slice(record);
+            }
+        }
+    }
+    Ok((result, has_path, has_link))
+}
+
+/// Format a PAX record, whose length prefix counts its own digits
+fn pax_record(key: &[u8], value: &[u8]) -> Vec<u8> {
+    let body_len = 1 + key.len() + 1 + value.len() + 1;
+    let mut len = body_len + 1;
+    while len.to_string().len() + body_len != len {
+        len = len.to_string().len() + body_len;
+    }
+    let mut record = len.to_string().into_bytes();
+    record.push(b' ');
+    record.extend_from_slice(key);
+    record.push(b'=');
+    record.extend_from_slice(value);
+    record.push(b'\n');
+    record
+}
@@ -0,0 +1,88 @@
+//! Transliteration of Cyrillic names into plain ASCII
+
+use unicode_normalization::UnicodeNormalization;
+use unicode_normalization::char::is_combining_mark;
+
+/// Latin spelling of a lowercase Cyrillic letter (Russian, Ukrainian,
+/// Belarusian and Kazakh alphabets), following common passport-style
+/// romanization
+fn latin(c: char) -> Option<&'static str> {
+    Some(match c {
+        'а' | 'ә' => "a",
+        'б' => "b",
+        'в' => "v",
+        'г' | 'ґ' => "g",
+        'ғ' => "gh",
+        'д' => "d",
+        'е' | 'э' => "e",
+        'ё' => "yo",
+        'є' => "ye",
+        'ж' => "zh",
+        'з' => "z",
+        'и' | 'і' => "i",
+        'ї' => "yi",
+        'й' | 'ы' => "y",
+        'к' => "k",
+        'қ' => "q",
+        'л' => "l",
+        'м' => "m",
+        'н' => "n",
+        'ң' => "ng",
+        'о' | 'ө' => "o",
+        'п' => "p",
+        'р' => "r",
+        'с' => "s",
+        'т' => "t",
+        'у' | 'ў' | 'ұ' | 'ү' => "u",
+        'ф' => "f",
+        'х' => "kh",
+        'һ' => "h",
+        'ц' => "ts",
+        'ч' => "ch",
+        'ш' => "sh",
+        'щ' => "shch",
+        'ъ' | 'ь' => "",
+        'ю' => "yu",
+        'я' => "ya",
+        _ => return None,
+    })
+}
+
+/// Render `text` in ASCII: Cyrillic letters are transliterated, other
+/// non-ASCII characters are replaced with `_`
+pub fn transliterate(text: &str) -> String {
+    // macOS stores decomposed names (и + U+0306 for й); compose them first
+    // and drop accents that have no precomposed form
+    let chars: Vec<char> = text.nfc().filter(|&c| !is_combining_mark(c)).collect();
+    let mut result = String::with_capacity(text.len());
+
+    for (i, &c) in chars.iter().enumerate() {
+        if c.is_ascii() {
+            result.push(c);
+            continue;
+        }
+        let lower = c.to_lowercase().next().unwrap_or(c);
+        let Some(spelling) = latin(lower) else {
+            result.push('_');
+            continue;
+        };
+        if lower == c {
+            result.push_str(spelling);
+            continue;
+        }
+        // Capitals become "Sh" inside a word and "SH" in all-caps words
+        let next_is_upper = chars.get(i + 1).is_some_and(|next| next.is_uppercase());
+        let prev_is_upper = i > 0 && chars[i - 1].is_uppercase();
+        if next_is_upper || (prev_is_upper && chars.get(i + 1).is_none_or(|n| !n.is_alphabetic())) {
+            result.push_str(&spelling.to_ascii_uppercase());
+        } else {
+            let mut letters = spelling.chars();
+            if let Some(first) = letters.next() {
+                result.push(first.to_ascii_uppercase());
+                result.extend(letters);
+            }
+        }
+    }
+
+    result
+}
@@ -0,0 +1,106 @@
+//! Restoring original entry names from a rename map
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+use tempfile::NamedTempFile;
+
+use crate::error::{Context, Error, Result};
+use crate::hex;
+use crate::lock::ArchiveLock;
+use crate::rawzip::{RawArchive, RawWriter};
+use crate::renamemap::RenameMap;
+
+/// Restore the names and EFS flags recorded in `map_path`
+///
+/// The rename map is removed after a successful undo when `remove_map` is set.
+pub fn undo_archive(
+    zipfile: &Path,
+    map_path: &Path,
+    dry_run: bool,
+    remove_map: bool,
+) -> Result<()> {
+    let map = RenameMap::read(map_path)?;
+    let _lock = if dry_run {
+        None
+    } else {
+        Some(ArchiveLock::acquire(zipfile)?)
+    };
+    let file = File::open(zipfile).context(format!("Failed to open {}", zipfile.display()))?;
+    let mut archive =
+        RawArchive::new(BufReader::new(file)).context("Failed to read ZIP archive")?;
+
+    let file_count = archive.entries().len();
+    println!(
+        "{} contains {} file{}",
+        zipfile.display(),
+        file_count,
+        if file_count == 1 { "" } else { "s" }
+    );
+    if map.entries.len() != file_count {
+        return Err(Error::other(format!(
+            "Rename map {} lists {} entries, but the archive has {file_count}",
+            map_path.display(),
+            map.entries.len()
+        )));
+    }
+
+    let mut originals = Vec::with_capacity(file_count);
+    for (index, entry) in archive.entries().iter().enumerate() {
+        let mapped = map
+            .entries
+            .iter()
+            .find(|mapped| mapped.index == index)
+            .ok_or_else(|| Error::other(format!("Rename map has no record for entry {index}")))?;
+        let original_name = hex::decode(&mapped.original_name_hex)?;
+        let new_name = hex::decode(&mapped.new_name_hex)?;
+        let current_display = String::from_utf8_lossy(&entry.name);
+
+        if entry.name != new_name && entry.name != original_name {
+            return Err(Error::other(format!(
+                "Entry {index} is named \"{current_display}\", but the rename map expects \"{}\"",
+                mapped.new_name
+            )));
+        }
+
+        if entry.name == original_name && entry.is_utf8() == mapped.original_utf8_flag {
+            println!("  {current_display}: OK (unchanged)");
+        } else {
+            println!(
+                "  {}: {} (from \"{current_display}\")",
+                String::from_utf8_lossy(&original_name),
+                if dry_run { "WOULD RESTORE" } else { "RESTORED" }
+            );
+        }
+        originals.push((original_name, mapped.original_utf8_flag));
+    }
+
+    if dry_run {
+        return Ok(());
+    }
+
+    let temp_file = NamedTempFile::new_in(zipfile.parent().unwrap_or_else(|| Path::new(".")))
+        .context("Failed to create temporary file")?;
+    let mut writer = RawWriter::new(BufWriter::new(temp_file.as_file()));
+    for (index, (name, utf8)) in originals.iter().enumerate() {
+        writer
+            .copy_entry(&mut archive, index, name, *utf8, None)
+            .with_context(|| format!("Failed to copy entry {index}"))?;
+    }
+    let comment = archive.comment().to_vec();
+    writer
+        .finish(&comment)
+        .context("Failed to finalize new archive")?;
+    drop(archive); // Close the original file
+
+    temp_file
+        .persist(zipfile)
+        .context("Failed to replace original file with restored version")?;
+
+    if remove_map {
+        std::fs::remove_file(map_path)
+            .with_context(|| format!("Failed to remove rename map {}", map_path.display()))?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,65 @@
+//! Verification of a rewritten archive against the original entries
+
+use std::io::{self, Read, Seek};
+use zip::ZipArchive;
+use zip::result::ZipError;
+
+use crate::error::{Context, Error, Result};
+use crate::rawzip::Entry;
+
+/// Decompress every entry of the archive read from `reader` and check that
+/// its CRC-32 and size match `original`
+///
+/// Encrypted entries and unsupported compression methods cannot be
+/// decompressed, so only their recorded CRC-32 and size are compared.
+pub fn verify_archive<R: Read + Seek>(reader: R, original: &[Entry]) -> Result<()> {
+    verify(reader, original).map_err(|error| match error {
+        // A new archive that cannot be read back is one that failed to
+        // verify, not a damaged archive to fix
+        Error::ZipFormat { message, source } => Error::Other { message, source },
+        error => error,
+    })
+}
+
+fn verify<R: Read + Seek>(reader: R, original: &[Entry]) -> Result<()> {
+    let mut archive = ZipArchive::new(reader).context("Failed to read the new archive")?;
+    if archive.len() != original.len() {
+        return Err(Error::other(format!(
+            "The new archive has {} entries instead of {}",
+            archive.len(),
+            original.len()
+        )));
+    }
+
+    for (index, expected) in original.iter().enumerate() {
+        let file_entry = archive.by_index_raw(index)?;
+        let (crc32, size) = (file_entry.crc32(), file_entry.size());
+        drop(file_entry);
+        if crc32 != expected.crc32 || size != expected.uncompressed_size {
+            return Err(Error::other(format!(
+                "Entry {index} does not match the original: CRC-32 {crc32:08x}, {size} bytes \
+                 instead of {:08x}, {} bytes",
+                expected.crc32, expected.uncompressed_size
+            )));
+        }
+
+        match archive.by_index(index) {
+            Ok(mut file_entry) => {
+                // The reader itself fails if the data does not match its CRC-32
+                let unpacked = io::copy(&mut file_entry, &mut io::sink())
+                    .with_context(|| format!("Entry {index} is corrupted in the new archive"))?;
+                if unpacked != size {
+                    return Err(Error::other(format!(
+                        "Entry {index} unpacks to {unpacked} bytes instead of {size}"
+                    )));
+                }
+            }
+            Err(ZipError::UnsupportedArchive(_)) => {}
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("Failed to read entry {index} of the new archive"));
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,239 @@
+//! Archives with names in legacy Cyrillic encodings, written byte by byte
+//! as `zip::ZipWriter` only writes UTF-8 names
+//!
+//! Shared by the integration tests and the `gen_corpus` helper, which keeps
+//! the fixture archives in `tests/corpus` in sync with [`corpus`].
+
+// Each user of the module needs only some of it
+#![allow(dead_code)]
+
+use encoding_rs::{Encoding, IBM866, KOI8_R, KOI8_U, WINDOWS_1251};
+
+/// Words Russian file names are made of
+pub const RUSSIAN_WORDS: &[&str] = &[
+    "документы",
+    "отчёт",
+    "квартал",
+    "фотографии",
+    "море",
+    "лето",
+    "договор",
+    "счёт",
+    "презентация",
+    "проект",
+    "письмо",
+    "бухгалтерия",
+    "зарплата",
+    "сотрудники",
+    "музыка",
+    "книга",
+    "черновик",
+    "архив",
+    "новый",
+    "старый",
+    "годовой",
+    "итоговый",
+    "план",
+    "работа",
+    "семья",
+    "дача",
+    "поездка",
+    "справка",
+    "заявление",
+    "приказ",
+    "таблица",
+    "расписание",
+];
+
+/// Words Ukrainian file names are made of, with the letters Russian lacks
+pub const UKRAINIAN_WORDS: &[&str] = &[
+    "документи",
+    "звіт",
+    "квартал",
+    "фотографії",
+    "море",
+    "літо",
+    "договір",
+    "рахунок",
+    "презентація",
+    "проєкт",
+    "лист",
+    "бухгалтерія",
+    "зарплата",
+    "співробітники",
+    "музика",
+    "книжка",
+    "чернетка",
+    "архів",
+    "новий",
+    "старий",
+    "річний",
+    "підсумковий",
+    "план",
+    "ґанок",
+    "родина",
+    "їжа",
+    "подорож",
+    "довідка",
+    "заява",
+    "наказ",
+    "таблиця",
+    "розклад",
+];
+
+/// System an archive was made on, as recorded in "version made by"
+#[derive(Clone, Copy, Debug)]
+pub enum Host {
+    Dos = 0,
+    Unix = 3,
+}
+
+/// Host of the archivers that write names in `encoding`
+pub fn host_for(encoding: &'static Encoding) -> Host {
+    if encoding == KOI8_R || encoding == KOI8_U {
+        Host::Unix
+    } else {
+        Host::Dos
+    }
+}
+
+/// CRC-32 of a byte string
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// ZIP archive of stored `entries` with raw names, made on `host`
+pub fn raw_zip(entries: &[(&[u8], &[u8])], host: Host) -> Vec<u8> {
+    let mut data = Vec::new();
+    let mut central = Vec::new();
+
+    for (name, contents) in entries {
+        let offset = u32::try_from(data.len()).unwrap();
+        let crc = crc32(contents);
+        let size = u32::try_from(contents.len()).unwrap();
+        let name_len = u16::try_from(name.len()).unwrap();
+
+        data.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        data.extend_from_slice(&[20, 0, 0, 0, 0, 0, 0, 0, 0x21, 0x5b]); // version, flags, method, time, date
+        data.extend_from_slice(&crc.to_le_bytes());
+        data.extend_from_slice(&size.to_le_bytes());
+        data.extend_from_slice(&size.to_le_bytes());
+        data.extend_from_slice(&name_len.to_le_bytes());
+        data.extend_from_slice(&0u16.to_le_bytes());
+        data.extend_from_slice(name);
+        data.extend_from_slice(contents);
+
+        central.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+        central.extend_from_slice(&[20, host as u8, 20, 0, 0, 0, 0, 0, 0, 0, 0x21, 0x5b]); // versions, flags, method, time, date
+        central.extend_from_slice(&crc.to_le_bytes());
+        central.extend_from_slice(&size.to_le_bytes());
+        central.extend_from_slice(&size.to_le_bytes());
+        central.extend_from_slice(&name_len.to_le_bytes());
+        central.extend_from_slice(&[0; 8]); // extra and comment lengths, disk, internal attributes
+        let external: u32 = if name.ends_with(b"/") { 0x10 } else { 0 };
+        central.extend_from_slice(&external.to_le_bytes());
+        central.extend_from_slice(&offset.to_le_bytes());
+        central.extend_from_slice(name);
+    }
+
+    let count = u16::try_from(entries.len()).unwrap();
+    let central_offset = u32::try_from(data.len()).unwrap();
+    let central_size = u32::try_from(central.len()).unwrap();
+    data.extend_from_slice(&central);
+    data.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+    data.extend_from_slice(&[0; 4]);
+    data.extend_from_slice(&count.to_le_bytes());
+    data.extend_from_slice(&count.to_le_bytes());
+    data.extend_from_slice(&central_size.to_le_bytes());
+    data.extend_from_slice(&central_offset.to_le_bytes());
+    data.extend_from_slice(&0u16.to_le_bytes());
+    data
+}
+
+/// ZIP archive made on the usual host for `encoding`, with `names` encoded
+/// in it and each file holding its own name
+pub fn encoded_zip(names: &[String], encoding: &'static Encoding) -> Vec<u8> {
+    let encoded: Vec<Vec<u8>> = names
+        .iter()
+        .map(|name| {
+            let (bytes, _, had_errors) = encoding.encode(name);
+            assert!(
+                !had_errors,
+                "{name} is not representable in {}",
+                encoding.name()
+            );
+            bytes.into_owned()
+        })
+        .collect();
+    let entries: Vec<(&[u8], &[u8])> = encoded
+        .iter()
+        .zip(names)
+        .map(|(raw, name)| {
+            (
+                raw.as_slice(),
+                if name.ends_with('/') {
+                    &b""[..]
+                } else {
+                    name.as_bytes()
+                },
+            )
+        })
+        .collect();
+    raw_zip(&entries, host_for(encoding))
+}
+
+/// A fixture archive: its file name in `tests/corpus`, the encoding of its
+/// names and the names in UTF-8
+pub struct Fixture {
+    pub file_name: &'static str,
+    pub encoding: &'static Encoding,
+    pub names: Vec<String>,
+}
+
+impl Fixture {
+    pub fn data(&self) -> Vec<u8> {
+        encoded_zip(&self.names, self.encoding)
+    }
+}
+
+/// Every fixture archive: typical names from DOS and Windows archivers and
+/// Unix `zip`, in Russian and, where the encoding has the letters, Ukrainian
+pub fn corpus() -> Vec<Fixture> {
+    let russian = [
+        "Документы/",
+        "Документы/Отчёт за квартал.txt",
+        "Документы/Договор 15-А.doc",
+        "Фотографии/Море, лето 2003/",
+        "Фотографии/Море, лето 2003/ПЛЯЖ.JPG",
+        "Бухгалтерия/Зарплата сотрудников (итоговая).xls",
+    ];
+    let ukrainian = [
+        "Документи/",
+        "Документи/Звіт за квартал.txt",
+        "Документи/Проєкт договору.doc",
+        "Фотографії/Ґанок і подвір'я.jpg",
+        "Їжа та подорожі.txt",
+    ];
+    let fixture = |file_name, encoding, names: &[&[&str]]| Fixture {
+        file_name,
+        encoding,
+        names: names.concat().iter().map(ToString::to_string).collect(),
+    };
+    vec![
+        fixture("cp866.zip", IBM866, &[&russian]),
+        fixture("windows-1251.zip", WINDOWS_1251, &[&russian, &ukrainian]),
+        fixture("koi8-r.zip", KOI8_R, &[&russian]),
+        fixture("koi8-u.zip", KOI8_U, &[&russian, &ukrainian]),
+    ]
+}
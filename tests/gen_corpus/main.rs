@@ -0,0 +1,33 @@
+//! Regenerate the fixture archives in `tests/corpus` from [`corpus::corpus`]
+//!
+//! `cargo test --test gen_corpus -- --write` writes them; without `--write`
+//! it checks that they are up to date.
+
+mod corpus;
+
+use std::fs;
+use std::path::Path;
+
+fn main() {
+    let write = std::env::args().any(|arg| arg == "--write");
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/corpus");
+    let mut stale = Vec::new();
+    for fixture in corpus::corpus() {
+        let path = dir.join(fixture.file_name);
+        let data = fixture.data();
+        if write {
+            fs::create_dir_all(&dir).expect("Failed to create tests/corpus");
+            fs::write(&path, &data).expect("Failed to write fixture");
+            println!("Wrote {}", path.display());
+        } else if fs::read(&path).ok().as_deref() != Some(data.as_slice()) {
+            stale.push(path);
+        }
+    }
+    if !stale.is_empty() {
+        for path in &stale {
+            eprintln!("{} is out of date", path.display());
+        }
+        eprintln!("Regenerate the fixtures with: cargo test --test gen_corpus -- --write");
+        std::process::exit(1);
+    }
+}
@@ -1,9 +1,15 @@
+#[path = "gen_corpus/corpus.rs"]
+mod corpus;
+
 use anyhow::Result;
-use std::fs;
+use proptest::prelude::*;
+use runzip::{Action, FixOptions, fix_archive};
+use std::ffi::OsStr;
+use std::fs::{self, File};
 use std::path::{Path, PathBuf};
 use std::process::Command;
 use tempfile::TempDir;
-use zip::ZipArchive;
+use zip::{HasZipMetadata, ZipArchive};
 
 /// Test helper to get the path to the runzip binary
 fn get_runzip_binary() -> PathBuf {
@@ -34,6 +40,40 @@ fn setup_test_archives(temp_dir: &Path) -> Result<(PathBuf, PathBuf, PathBuf)> {
     Ok((windows_dst, mac_dst, linux_dst))
 }
 
+/// Test helper to create a ZIP archive with the given entry names and contents
+fn create_test_zip(zip_path: &Path, entries: &[(&str, &[u8])]) -> Result<()> {
+    let file = fs::File::create(zip_path)?;
+    let mut writer = zip::ZipWriter::new(file);
+    let options =
+        zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+
+    for (name, contents) in entries {
+        if name.ends_with('/') {
+            writer.add_directory(*name, options)?;
+        } else {
+            writer.start_file(*name, options)?;
+            std::io::Write::write_all(&mut writer, contents)?;
+        }
+    }
+
+    writer.finish()?;
+    Ok(())
+}
+
+/// Test helper to create a ZIP archive with raw (legacy-encoded) entry names,
+/// which `zip::ZipWriter` cannot produce
+fn create_raw_zip(zip_path: &Path, entries: &[(&[u8], &[u8])]) -> Result<()> {
+    fs::write(zip_path, corpus::raw_zip(entries, corpus::Host::Dos))?;
+    Ok(())
+}
+
+/// Test helper to encode text with a legacy encoding
+fn encode_legacy(text: &str, encoding: &'static encoding_rs::Encoding) -> Vec<u8> {
+    let (bytes, _, had_errors) = encoding.encode(text);
+    assert!(!had_errors, "{text} should be representable");
+    bytes.into_owned()
+}
+
 /// Test helper to extract filenames from a ZIP archive
 fn extract_filenames_from_zip(zip_path: &Path) -> Result<Vec<Vec<u8>>> {
     let file = std::fs::File::open(zip_path)?;
@@ -48,6 +88,21 @@ fn extract_filenames_from_zip(zip_path: &Path) -> Result<Vec<Vec<u8>>> {
     Ok(filenames)
 }
 
+/// Test helper to extract the Unix mode and contents of every entry
+fn extract_modes_and_contents(zip_path: &Path) -> Result<Vec<(Option<u32>, Vec<u8>)>> {
+    let mut archive = ZipArchive::new(fs::File::open(zip_path)?)?;
+    let mut entries = Vec::new();
+
+    for i in 0..archive.len() {
+        let mut file_entry = archive.by_index(i)?;
+        let mut contents = Vec::new();
+        std::io::Read::read_to_end(&mut file_entry, &mut contents)?;
+        entries.push((file_entry.unix_mode(), contents));
+    }
+
+    Ok(entries)
+}
+
 /// Test helper to check if bytes contain valid UTF-8 Russian text
 fn is_valid_utf8_russian(bytes: &[u8]) -> bool {
     if let Ok(utf8_str) = std::str::from_utf8(bytes) {
@@ -107,7 +162,7 @@ fn test_windows_archive_has_encoding_issues() -> Result<()> {
     let has_corrupted_filename = filenames.iter().any(|filename| {
         looks_like_encoding_corruption(filename)
             || (!is_valid_utf8_russian(filename)
-                && !std::str::from_utf8(filename).map_or(false, |s| s.is_ascii()))
+                && !std::str::from_utf8(filename).is_ok_and(|s| s.is_ascii()))
     });
 
     assert!(
@@ -261,7 +316,7 @@ fn test_fixing_windows_archive() -> Result<()> {
     let had_encoding_issues = original_filenames.iter().any(|filename| {
         looks_like_encoding_corruption(filename)
             || (!is_valid_utf8_russian(filename)
-                && !std::str::from_utf8(filename).map_or(false, |s| s.is_ascii()))
+                && !std::str::from_utf8(filename).is_ok_and(|s| s.is_ascii()))
     });
 
     if had_encoding_issues {
@@ -471,3 +526,5119 @@ fn test_nonexistent_file_handling() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_csv_report() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (windows_zip, mac_zip, _) = setup_test_archives(temp_dir.path())?;
+    let binary_path = get_runzip_binary();
+    let report_path = temp_dir.path().join("report.csv");
+
+    let output = Command::new(&binary_path)
+        .arg("--dry-run")
+        .arg("--report")
+        .arg(&report_path)
+        .arg(&windows_zip)
+        .arg(&mac_zip)
+        .output()?;
+
+    assert!(
+        output.status.success(),
+        "Dry run with report should succeed. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let report = fs::read_to_string(&report_path)?;
+    let lines: Vec<&str> = report.lines().collect();
+
+    assert_eq!(
+        lines[0],
+        "archive,index,raw_name_hex,detected_encoding,confidence,new_name,action"
+    );
+    // One header row plus one row per entry of both archives
+    let expected_rows = extract_filenames_from_zip(&windows_zip)?.len()
+        + extract_filenames_from_zip(&mac_zip)?.len();
+    assert_eq!(lines.len(), expected_rows + 1, "Report: {report}");
+    assert!(
+        lines.iter().any(|line| line.ends_with(",would-fix")),
+        "Windows archive entries should be reported as would-fix. Report: {report}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_keep_going_after_failure() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (windows_zip, _, _) = setup_test_archives(temp_dir.path())?;
+    let binary_path = get_runzip_binary();
+    let missing_zip = temp_dir.path().join("missing.zip");
+
+    let original_filenames = extract_filenames_from_zip(&windows_zip)?;
+
+    let output = run_runzip(&binary_path, &[&missing_zip, &windows_zip])?;
+
+    assert!(
+        !output.status.success(),
+        "Should fail when one of the archives doesn't exist"
+    );
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Failed to process 1 of 2 archives") && stderr.contains("missing.zip"),
+        "Should list the failed archive. stderr: {stderr}"
+    );
+
+    // The archive after the failing one should still be fixed
+    let new_filenames = extract_filenames_from_zip(&windows_zip)?;
+    assert_ne!(
+        original_filenames, new_filenames,
+        "Remaining archives should still be processed"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_fail_fast_stops_batch() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (windows_zip, _, _) = setup_test_archives(temp_dir.path())?;
+    let binary_path = get_runzip_binary();
+    let missing_zip = temp_dir.path().join("missing.zip");
+
+    let original_filenames = extract_filenames_from_zip(&windows_zip)?;
+
+    let output = Command::new(&binary_path)
+        .arg("--fail-fast")
+        .arg(&missing_zip)
+        .arg(&windows_zip)
+        .output()?;
+
+    assert!(
+        !output.status.success(),
+        "Should fail on the missing archive"
+    );
+    assert_eq!(
+        original_filenames,
+        extract_filenames_from_zip(&windows_zip)?,
+        "Archives after the failing one should be left alone"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_rename_map_sidecar() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (windows_zip, mac_zip, _) = setup_test_archives(temp_dir.path())?;
+    let binary_path = get_runzip_binary();
+
+    let original_filenames = extract_filenames_from_zip(&windows_zip)?;
+
+    let output = run_runzip(&binary_path, &[&windows_zip, &mac_zip])?;
+    assert!(
+        output.status.success(),
+        "runzip should succeed. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let map_path = temp_dir.path().join("windows-archive.zip.runzip.json");
+    let map: serde_json::Value = serde_json::from_str(&fs::read_to_string(&map_path)?)?;
+    let entries = map["entries"]
+        .as_array()
+        .expect("entries should be an array");
+
+    assert_eq!(entries.len(), original_filenames.len());
+    let new_filenames = extract_filenames_from_zip(&windows_zip)?;
+    for ((entry, original), new) in entries.iter().zip(&original_filenames).zip(&new_filenames) {
+        let hex: String = original.iter().map(|b| format!("{b:02x}")).collect();
+        assert_eq!(entry["original_name_hex"], hex.as_str());
+        assert_eq!(entry["new_name"], String::from_utf8_lossy(new).as_ref());
+    }
+
+    // Nothing was renamed in the Mac archive, so no map is written for it
+    assert!(!temp_dir.path().join("mac-archive.zip.runzip.json").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_explicit_rename_map() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (windows_zip, mac_zip, _) = setup_test_archives(temp_dir.path())?;
+    let binary_path = get_runzip_binary();
+
+    let windows_names = extract_filenames_from_zip(&windows_zip)?;
+    let mac_names = extract_filenames_from_zip(&mac_zip)?;
+    let windows_hex: String = windows_names[0]
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect();
+
+    // One rule by raw bytes, one by the name as stored in the archive
+    let map_path = temp_dir.path().join("renames.csv");
+    fs::write(
+        &map_path,
+        format!(
+            "old,new\nhex:{windows_hex},\"Папка, новая/\"\n{},папка/\n",
+            String::from_utf8_lossy(&mac_names[0])
+        ),
+    )?;
+
+    let output = Command::new(&binary_path)
+        .arg("--map")
+        .arg(&map_path)
+        .arg(&windows_zip)
+        .arg(&mac_zip)
+        .output()?;
+    assert!(
+        output.status.success(),
+        "runzip with --map should succeed. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let new_windows_names = extract_filenames_from_zip(&windows_zip)?;
+    assert_eq!(new_windows_names[0], "Папка, новая/".as_bytes());
+    assert_eq!(new_windows_names[1..], windows_names[1..]);
+
+    let new_mac_names = extract_filenames_from_zip(&mac_zip)?;
+    assert_eq!(new_mac_names[0], "папка/".as_bytes());
+    // Entries not listed in the map keep their names
+    assert_eq!(new_mac_names[1], mac_names[1]);
+
+    // A rule by the name as it reads matches legacy names decoded in the
+    // detected or the given encoding
+    let legacy_zip = temp_dir.path().join("legacy.zip");
+    let report_name = encode_legacy("Отчёт за квартал.txt", encoding_rs::IBM866);
+    create_raw_zip(
+        &legacy_zip,
+        &[(&report_name, b"report"), (b"notes.txt", b"notes")],
+    )?;
+    fs::write(
+        &map_path,
+        "Отчёт за квартал.txt,Отчёт за 3 квартал.txt
+",
+    )?;
+    for source in [None, Some("cp866")] {
+        let legacy_copy = temp_dir.path().join("legacy-copy.zip");
+        fs::copy(&legacy_zip, &legacy_copy)?;
+        let mut command = Command::new(&binary_path);
+        command.arg("--map").arg(&map_path).arg(&legacy_copy);
+        if let Some(source) = source {
+            command.arg("--source").arg(source);
+        }
+        let output = command.output()?;
+        assert!(
+            output.status.success(),
+            "runzip with --map should succeed. stderr: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let names = extract_filenames_from_zip(&legacy_copy)?;
+        assert_eq!(names[0], "Отчёт за 3 квартал.txt".as_bytes());
+        assert_eq!(names[1], b"notes.txt");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_undo_restores_original_names() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (windows_zip, _, _) = setup_test_archives(temp_dir.path())?;
+    let binary_path = get_runzip_binary();
+    let map_path = temp_dir.path().join("windows-archive.zip.runzip.json");
+
+    let original_filenames = extract_filenames_from_zip(&windows_zip)?;
+
+    let output = run_runzip(&binary_path, &[&windows_zip])?;
+    assert!(output.status.success(), "runzip should succeed");
+    assert_ne!(
+        original_filenames,
+        extract_filenames_from_zip(&windows_zip)?
+    );
+
+    let output = Command::new(&binary_path)
+        .arg("undo")
+        .arg(&windows_zip)
+        .output()?;
+    assert!(
+        output.status.success(),
+        "runzip undo should succeed. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    assert_eq!(
+        original_filenames,
+        extract_filenames_from_zip(&windows_zip)?,
+        "Undo should restore the original raw names"
+    );
+    let file = fs::File::open(&windows_zip)?;
+    let mut archive = ZipArchive::new(file)?;
+    for i in 0..archive.len() {
+        assert!(
+            !archive.by_index_raw(i)?.get_metadata().is_utf8,
+            "Undo should clear the EFS flag again"
+        );
+    }
+    assert!(!map_path.exists(), "The used rename map should be removed");
+
+    Ok(())
+}
+
+#[test]
+fn test_sanitize_windows_names() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let zip_path = temp_dir.path().join("names.zip");
+    let binary_path = get_runzip_binary();
+
+    create_test_zip(
+        &zip_path,
+        &[
+            ("notes: draft?/", b""),
+            ("notes: draft?/a<b>|c*.txt", b"data"),
+            ("trailing. /file.txt ", b"data"),
+            ("plain.txt", b"data"),
+        ],
+    )?;
+
+    let output = Command::new(&binary_path)
+        .arg("--sanitize-windows")
+        .arg(&zip_path)
+        .output()?;
+    assert!(
+        output.status.success(),
+        "runzip --sanitize-windows should succeed. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("Sanitized for Windows"),
+        "Changes should be reported. Output: {stdout}"
+    );
+
+    let names = extract_filenames_from_zip(&zip_path)?;
+    assert_eq!(
+        names,
+        vec![
+            b"notes_ draft_/".to_vec(),
+            b"notes_ draft_/a_b__c_.txt".to_vec(),
+            b"trailing/file.txt".to_vec(),
+            b"plain.txt".to_vec(),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_reserved_windows_device_names() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let zip_path = temp_dir.path().join("devices.zip");
+    let binary_path = get_runzip_binary();
+
+    create_test_zip(
+        &zip_path,
+        &[
+            ("con.txt", b"data"),
+            ("dir/NUL", b"data"),
+            ("console.txt", b"data"),
+        ],
+    )?;
+
+    // Without --sanitize-windows reserved names are only reported
+    let output = run_runzip_dry_run(&binary_path, &[&zip_path])?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("\"con.txt\" is a reserved device name") && stdout.contains("\"NUL\""),
+        "Reserved names should be reported. Output: {stdout}"
+    );
+    assert!(!stdout.contains("\"console.txt\" is a reserved"));
+
+    let output = Command::new(&binary_path)
+        .arg("--sanitize-windows")
+        .arg(&zip_path)
+        .output()?;
+    assert!(
+        output.status.success(),
+        "runzip --sanitize-windows should succeed"
+    );
+
+    let names = extract_filenames_from_zip(&zip_path)?;
+    assert_eq!(
+        names,
+        vec![
+            b"con_.txt".to_vec(),
+            b"dir/NUL_".to_vec(),
+            b"console.txt".to_vec()
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_truncate_long_names() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let zip_path = temp_dir.path().join("long.zip");
+    let binary_path = get_runzip_binary();
+
+    // 200 single-byte cp866 characters turn into 400 bytes of UTF-8
+    let long_stem = "Длинное имя файла ".repeat(12);
+    let first = encode_legacy(&format!("{long_stem}1.txt"), encoding_rs::IBM866);
+    let second = encode_legacy(&format!("{long_stem}2.txt"), encoding_rs::IBM866);
+    create_raw_zip(&zip_path, &[(&first, b"one"), (&second, b"two")])?;
+
+    let output = Command::new(&binary_path)
+        .args(["--dry-run", "--source", "cp866"])
+        .arg(&zip_path)
+        .output()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("longer than 255 bytes"),
+        "Long names should be reported. Output: {stdout}"
+    );
+
+    let output = Command::new(&binary_path)
+        .args(["--truncate-long-names", "--source", "cp866"])
+        .arg(&zip_path)
+        .output()?;
+    assert!(
+        output.status.success(),
+        "runzip --truncate-long-names should succeed. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let names = extract_filenames_from_zip(&zip_path)?;
+    for name in &names {
+        assert!(
+            name.len() <= 255,
+            "Name should be truncated: {}",
+            name.len()
+        );
+        let name = std::str::from_utf8(name)?;
+        assert!(name.starts_with("Длинное имя") && name.ends_with(".txt"));
+    }
+    assert_ne!(names[0], names[1], "Truncated names should stay unique");
+
+    Ok(())
+}
+
+#[test]
+fn test_translit_target() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (windows_zip, mac_zip, _) = setup_test_archives(temp_dir.path())?;
+    let binary_path = get_runzip_binary();
+
+    let output = Command::new(&binary_path)
+        .args(["--target", "translit"])
+        .arg(&windows_zip)
+        .arg(&mac_zip)
+        .output()?;
+    assert!(
+        output.status.success(),
+        "runzip --target translit should succeed. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    assert_eq!(
+        extract_filenames_from_zip(&windows_zip)?,
+        vec![
+            b"test/Eshchyo odin pustoy katalog/".to_vec(),
+            b"test/test.txt".to_vec()
+        ]
+    );
+    // Names that were already UTF-8 are transliterated as well
+    assert_eq!(
+        extract_filenames_from_zip(&mac_zip)?,
+        vec![b"test/".to_vec(), b"fayl.html".to_vec()]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_windows_target() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (windows_zip, mac_zip, _) = setup_test_archives(temp_dir.path())?;
+    let binary_path = get_runzip_binary();
+    let original_windows_names = extract_filenames_from_zip(&windows_zip)?;
+
+    let output = Command::new(&binary_path)
+        .arg("-w")
+        .arg(&windows_zip)
+        .arg(&mac_zip)
+        .output()?;
+    assert!(
+        output.status.success(),
+        "runzip -w should succeed. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // Names already in CP866 are left alone
+    assert_eq!(
+        extract_filenames_from_zip(&windows_zip)?,
+        original_windows_names
+    );
+    // Decomposed macOS names are composed and written in CP866 without the EFS flag
+    assert_eq!(
+        extract_filenames_from_zip(&mac_zip)?,
+        vec![
+            encode_legacy("тест/", encoding_rs::IBM866),
+            encode_legacy("файл.html", encoding_rs::IBM866)
+        ]
+    );
+    let mut archive = ZipArchive::new(fs::File::open(&mac_zip)?)?;
+    for i in 0..archive.len() {
+        assert!(!archive.by_index_raw(i)?.get_metadata().is_utf8);
+    }
+
+    // The Unix host becomes MS-DOS, with the directory attribute kept
+    let data = fs::read(&mac_zip)?;
+    let attributes: Vec<(u8, u32)> = data
+        .windows(4)
+        .enumerate()
+        .filter(|(_, window)| *window == 0x0201_4b50u32.to_le_bytes())
+        .map(|(offset, _)| {
+            let record = &data[offset..];
+            (
+                record[5],
+                u32::from_le_bytes([record[38], record[39], record[40], record[41]]),
+            )
+        })
+        .collect();
+    assert_eq!(attributes, vec![(0, 0x10), (0, 0)]);
+
+    Ok(())
+}
+
+#[test]
+fn test_windows_locale() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (windows_zip, _, _) = setup_test_archives(temp_dir.path())?;
+    let binary_path = get_runzip_binary();
+    let expected: Vec<Vec<u8>> = extract_filenames_from_zip(&windows_zip)?
+        .iter()
+        .map(|name| {
+            let (name, _) = encoding_rs::IBM866.decode_without_bom_handling(name);
+            encode_legacy(&name, encoding_rs::SHIFT_JIS)
+        })
+        .collect();
+
+    let output = Command::new(&binary_path)
+        .args(["-w", "--windows-locale", "ja"])
+        .arg(&windows_zip)
+        .output()?;
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(extract_filenames_from_zip(&windows_zip)?, expected);
+
+    // The locale only applies to -w
+    let output = Command::new(&binary_path)
+        .args(["--windows-locale", "ja"])
+        .arg(&windows_zip)
+        .output()?;
+    assert!(!output.status.success());
+
+    Ok(())
+}
+
+#[test]
+fn test_windows_locale_latin() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+
+    for (locale, source, name, expected) in [
+        (
+            "pl",
+            "cp852",
+            "Zażółć gęślą jaźń.txt",
+            &b"Za\xbe\xa2\x88\x86 g\xa9\x98l\xa5 ja\xab\xe4.txt"[..],
+        ),
+        (
+            "de",
+            "cp850",
+            "Größe der Übersicht.txt",
+            &b"Gr\x94\xe1e der \x9abersicht.txt"[..],
+        ),
+        (
+            "fr",
+            "cp850",
+            "Crème brûlée à l'øre.txt",
+            &b"Cr\x8ame br\x96l\x82e \x85 l'\x9bre.txt"[..],
+        ),
+    ] {
+        let zip_path = temp_dir.path().join(format!("{locale}.zip"));
+        create_test_zip(&zip_path, &[(name, b"hello")])?;
+
+        let output = Command::new(&binary_path)
+            .args(["-w", "--windows-locale", locale])
+            .arg(&zip_path)
+            .output()?;
+        assert!(
+            output.status.success(),
+            "stderr: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert_eq!(extract_filenames_from_zip(&zip_path)?, vec![expected]);
+
+        // And back to the original UTF-8 name
+        let output = Command::new(&binary_path)
+            .args(["-s", source])
+            .arg(&zip_path)
+            .output()?;
+        assert!(output.status.success());
+        assert_eq!(
+            extract_filenames_from_zip(&zip_path)?,
+            vec![name.as_bytes().to_vec()]
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_destination_presets() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+
+    // Total Commander gets cp866 names, with the Unicode names in extra
+    // fields
+    let zip_path = temp_dir.path().join("tc.zip");
+    create_test_zip(&zip_path, &[("Отчёт.txt", b"report")])?;
+    let output = Command::new(&binary_path)
+        .args(["--for", "total-commander"])
+        .arg(&zip_path)
+        .output()?;
+    assert!(
+        output.status.success(),
+        "runzip --for total-commander should succeed. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    // The zip crate reports the Unicode name, so look for the legacy one
+    let cp866_name = encode_legacy("Отчёт.txt", encoding_rs::IBM866);
+    assert!(
+        fs::read(&zip_path)?
+            .windows(cp866_name.len())
+            .any(|window| window == cp866_name)
+    );
+    let mut archive = ZipArchive::new(fs::File::open(&zip_path)?)?;
+    assert_eq!(archive.by_index(0)?.name(), "Отчёт.txt");
+
+    // WinRAR 5 reads UTF-8, but the names must be valid on Windows
+    let zip_path = temp_dir.path().join("winrar.zip");
+    create_test_zip(&zip_path, &[("Отчёт: итоги.txt", b"report")])?;
+    let output = Command::new(&binary_path)
+        .args(["--for", "winrar5"])
+        .arg(&zip_path)
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(
+        extract_filenames_from_zip(&zip_path)?,
+        vec!["Отчёт_ итоги.txt".as_bytes().to_vec()]
+    );
+
+    // A preset decides the target, so it cannot be combined with one
+    let output = Command::new(&binary_path)
+        .args(["--for", "windows-ru", "-t", "koi8-r"])
+        .arg(&zip_path)
+        .output()?;
+    assert!(!output.status.success());
+
+    Ok(())
+}
+
+#[test]
+fn test_on_unencodable_policies() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+
+    for (policy, expected) in [
+        ("error", "Україна.txt"),
+        ("replace", "Укра_на.txt"),
+        ("translit", "Украyiна.txt"),
+        ("skip", "Украна.txt"),
+    ] {
+        let zip_path = temp_dir.path().join(format!("{policy}.zip"));
+        create_test_zip(&zip_path, &[("Україна.txt", b"hello")])?;
+
+        let output = Command::new(&binary_path)
+            .args(["--target", "koi8-r", "--on-unencodable", policy])
+            .arg(&zip_path)
+            .output()?;
+        assert!(
+            output.status.success(),
+            "runzip --on-unencodable {policy} should succeed. stderr: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        // With "error" the entry keeps its original UTF-8 name
+        let expected = if policy == "error" {
+            expected.as_bytes().to_vec()
+        } else {
+            encode_legacy(expected, encoding_rs::KOI8_R)
+        };
+        assert_eq!(
+            extract_filenames_from_zip(&zip_path)?,
+            vec![expected],
+            "unexpected name with --on-unencodable {policy}"
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_strict() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+    let zip_path = temp_dir.path().join("test.zip");
+    create_test_zip(
+        &zip_path,
+        &[("привет.txt", b"hello"), ("Україна.txt", b"hello")],
+    )?;
+    let original = fs::read(&zip_path)?;
+
+    // KOI8-R has no "ї", so the archive is not rewritten at all
+    let output = Command::new(&binary_path)
+        .args(["--target", "koi8-r", "--strict"])
+        .arg(&zip_path)
+        .output()?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("test.zip: Cannot encode \"Україна.txt\" in KOI8-R"),
+        "stderr: {stderr}"
+    );
+    assert_eq!(fs::read(&zip_path)?, original);
+    assert_eq!(fs::read_dir(temp_dir.path())?.count(), 1);
+
+    let options = FixOptions::builder()
+        .target(runzip::Target::Koi8R)
+        .strict(true)
+        .build();
+    assert!(matches!(
+        fix_archive(&zip_path, &options),
+        Err(runzip::Error::UnencodableName { name, encoding })
+            if name == "Україна.txt" && encoding.name() == "KOI8-R"
+    ));
+    assert_eq!(fs::read(&zip_path)?, original);
+
+    // Nor is an archive with a name detection is unsure of
+    let ambiguous_path = temp_dir.path().join("ambiguous.zip");
+    create_raw_zip(
+        &ambiguous_path,
+        &[(&encode_legacy("ЯМА", encoding_rs::KOI8_R), b"pit")],
+    )?;
+    let ambiguous = fs::read(&ambiguous_path)?;
+    let options = FixOptions::builder().strict(true).build();
+    assert!(matches!(
+        fix_archive(&ambiguous_path, &options),
+        Err(runzip::Error::DetectionAmbiguous { name, confidence, .. })
+            if name == "ЯМА" && confidence < runzip::AMBIGUITY_MARGIN
+    ));
+    assert_eq!(fs::read(&ambiguous_path)?, ambiguous);
+    fs::remove_file(&ambiguous_path)?;
+
+    let output = Command::new(&binary_path)
+        .args(["--target", "koi8-r"])
+        .arg(&zip_path)
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(
+        extract_filenames_from_zip(&zip_path)?,
+        vec![
+            encode_legacy("привет.txt", encoding_rs::KOI8_R),
+            "Україна.txt".as_bytes().to_vec()
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_unencodable_names_reported_up_front() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let zip_path = temp_dir.path().join("test.zip");
+    create_test_zip(
+        &zip_path,
+        &[
+            ("Україна.txt", b"hello"),
+            ("їжак.txt", b"hello"),
+            ("ok.txt", b"hello"),
+        ],
+    )?;
+
+    let output = Command::new(get_runzip_binary())
+        .args(["--target", "koi8-r"])
+        .arg(&zip_path)
+        .output()?;
+    assert!(output.status.success());
+
+    // Both names are listed together, with what each fallback would give
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("has 2 names KOI8-R cannot represent"),
+        "stderr: {stderr}"
+    );
+    assert!(
+        stderr.contains(
+            "\"Україна.txt\" (\"ї\" missing): replace -> \"Укра_на.txt\", \
+             translit -> \"Украyiна.txt\""
+        ),
+        "stderr: {stderr}"
+    );
+    assert!(stderr.contains("\"їжак.txt\""), "stderr: {stderr}");
+
+    Ok(())
+}
+
+#[test]
+fn test_unsafe_paths() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let zip_path = temp_dir.path().join("slip.zip");
+    let binary_path = get_runzip_binary();
+    let entries: &[(&[u8], &[u8])] = &[
+        (b"../evil.txt", b"data"),
+        (b"/etc/passwd", b"data"),
+        (b"a/../../b.txt", b"data"),
+        (b"C:\\Windows\\x.dll", b"data"),
+        (b"safe/ok.txt", b"data"),
+    ];
+    create_raw_zip(&zip_path, entries)?;
+
+    // Without --sanitize-paths the names are only reported
+    let output = run_runzip(&binary_path, &[&zip_path])?;
+    assert!(output.status.success(), "runzip should succeed");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("has 4 entries with absolute or \"..\" paths"),
+        "Unsafe paths should be reported. stderr: {stderr}"
+    );
+    assert_eq!(
+        extract_filenames_from_zip(&zip_path)?,
+        entries
+            .iter()
+            .map(|(name, _)| name.to_vec())
+            .collect::<Vec<_>>()
+    );
+
+    let output = Command::new(&binary_path)
+        .arg("--sanitize-paths")
+        .arg(&zip_path)
+        .output()?;
+    assert!(
+        output.status.success(),
+        "runzip --sanitize-paths should succeed. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(output.stderr.is_empty());
+    assert_eq!(
+        extract_filenames_from_zip(&zip_path)?,
+        vec![
+            b"evil.txt".to_vec(),
+            b"etc/passwd".to_vec(),
+            b"a/b.txt".to_vec(),
+            b"Windows/x.dll".to_vec(),
+            b"safe/ok.txt".to_vec(),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_strip_absolute() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let zip_path = temp_dir.path().join("absolute.zip");
+    let binary_path = get_runzip_binary();
+    let report = encode_legacy("/home/user/Отчёт.txt", encoding_rs::IBM866);
+    create_raw_zip(
+        &zip_path,
+        &[
+            (&report, b"data"),
+            (b"C:\\Windows\\x.dll", b"data"),
+            (b"\\\\server\\share\\docs\\a.txt", b"data"),
+            (b"\\\\?\\UNC\\server\\share\\b.txt", b"data"),
+            (b"\\\\?\\D:\\c.txt", b"data"),
+            (b"/", b""),
+            (b"../up.txt", b"data"),
+        ],
+    )?;
+
+    let output = Command::new(&binary_path)
+        .arg("--strip-absolute")
+        .arg(&zip_path)
+        .output()?;
+    assert!(
+        output.status.success(),
+        "runzip --strip-absolute should succeed. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    // ".." is left to --sanitize-paths
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("has 1 entry with absolute or \"..\" paths"),
+        "stderr: {stderr}"
+    );
+    assert_eq!(
+        extract_filenames_from_zip(&zip_path)?,
+        vec![
+            "home/user/Отчёт.txt".as_bytes().to_vec(),
+            b"Windows\\x.dll".to_vec(),
+            b"docs\\a.txt".to_vec(),
+            b"b.txt".to_vec(),
+            b"c.txt".to_vec(),
+            b"_/".to_vec(),
+            b"../up.txt".to_vec(),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_control_chars() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+    let report = encode_legacy("Отчёт\t2003.txt", encoding_rs::IBM866);
+    let entries: &[(&[u8], &[u8])] = &[
+        (b"line\nbreak.txt", b"data"),
+        (b"bell\x07.txt", b"data"),
+        (&report, b"data"),
+        (b"plain.txt", b"data"),
+    ];
+
+    for (mode, expected) in [
+        (
+            "keep",
+            [
+                &b"line\nbreak.txt"[..],
+                b"bell\x07.txt",
+                "Отчёт\t2003.txt".as_bytes(),
+            ],
+        ),
+        (
+            "replace",
+            [
+                &b"line_break.txt"[..],
+                b"bell_.txt",
+                "Отчёт_2003.txt".as_bytes(),
+            ],
+        ),
+        (
+            "escape",
+            [
+                &b"line%0Abreak.txt"[..],
+                b"bell%07.txt",
+                "Отчёт%092003.txt".as_bytes(),
+            ],
+        ),
+    ] {
+        let zip_path = temp_dir.path().join(format!("{mode}.zip"));
+        create_raw_zip(&zip_path, entries)?;
+        let output = Command::new(&binary_path)
+            .args(["--control-chars", mode])
+            .arg(&zip_path)
+            .output()?;
+        assert!(
+            output.status.success(),
+            "runzip --control-chars {mode} should succeed. stderr: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        // Names are printed with their control characters escaped
+        if mode == "keep" {
+            assert!(
+                stdout.contains("\"line\\nbreak.txt\" contains control characters"),
+                "{stdout}"
+            );
+        } else {
+            assert!(
+                stdout.contains("Replaced control characters: \"bell\\u{7}.txt\""),
+                "{stdout}"
+            );
+        }
+        let mut names = extract_filenames_from_zip(&zip_path)?;
+        assert_eq!(names.pop(), Some(b"plain.txt".to_vec()));
+        assert_eq!(names, expected, "{mode}");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_fix_separators() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let zip_path = temp_dir.path().join("backslashes.zip");
+    let binary_path = get_runzip_binary();
+    let report = encode_legacy("Документы\\Отчёт за квартал.txt", encoding_rs::IBM866);
+    create_raw_zip(
+        &zip_path,
+        &[
+            (b"photos\\", b""),
+            (b"photos\\2003\\sea.jpg", b"data"),
+            (&report, b"data"),
+        ],
+    )?;
+
+    let output = run_runzip_dry_run(&binary_path, &[&zip_path])?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("separates directories with backslashes"),
+        "Backslashes should be reported. Output: {stdout}"
+    );
+
+    let output = Command::new(&binary_path)
+        .arg("--fix-separators")
+        .arg(&zip_path)
+        .output()?;
+    assert!(
+        output.status.success(),
+        "runzip --fix-separators should succeed. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(
+        extract_filenames_from_zip(&zip_path)?,
+        vec![
+            b"photos/".to_vec(),
+            b"photos/2003/sea.jpg".to_vec(),
+            "Документы/Отчёт за квартал.txt".as_bytes().to_vec(),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_symlinks_preserved() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let zip_path = temp_dir.path().join("links.zip");
+    let binary_path = get_runzip_binary();
+
+    let file = fs::File::create(&zip_path)?;
+    let mut writer = zip::ZipWriter::new(file);
+    let options =
+        zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    writer.start_file("файл.txt", options.unix_permissions(0o640))?;
+    std::io::Write::write_all(&mut writer, b"data")?;
+    writer.add_symlink("ссылка", "файл.txt", options)?;
+    writer.add_symlink("link", "../outside", options)?;
+    writer.finish()?;
+
+    let original = extract_modes_and_contents(&zip_path)?;
+    assert!(
+        original[1..]
+            .iter()
+            .all(|(mode, _)| mode.is_some_and(|mode| mode & 0o170_000 == 0o120_000)),
+        "test archive should contain symlinks"
+    );
+
+    // Force a rename of every entry
+    let output = Command::new(&binary_path)
+        .args(["--target", "cp866"])
+        .arg(&zip_path)
+        .output()?;
+    assert!(
+        output.status.success(),
+        "runzip should succeed. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    assert_eq!(
+        extract_filenames_from_zip(&zip_path)?,
+        vec![
+            encode_legacy("файл.txt", encoding_rs::IBM866),
+            encode_legacy("ссылка", encoding_rs::IBM866),
+            b"link".to_vec(),
+        ]
+    );
+    // Modes, symlink bits and link targets are untouched
+    assert_eq!(extract_modes_and_contents(&zip_path)?, original);
+
+    Ok(())
+}
+
+#[test]
+fn test_dos_attributes_preserved() -> Result<()> {
+    const READ_ONLY_HIDDEN_SYSTEM_ARCHIVE: u32 = 0x27;
+    const TEXT: u16 = 1;
+    let temp_dir = TempDir::new()?;
+    let zip_path = temp_dir.path().join("attributes.zip");
+    let binary_path = get_runzip_binary();
+    let names = [
+        encode_legacy("Скрытый.txt", encoding_rs::IBM866),
+        encode_legacy("Системный.sys", encoding_rs::IBM866),
+    ];
+    let text = encode_legacy(
+        "Только для чтения, не удалять этот файл!",
+        encoding_rs::IBM866,
+    );
+    create_raw_zip(&zip_path, &[(&names[0], &text), (&names[1], b"data")])?;
+
+    // Made on Windows NTFS, with every DOS attribute the entries can have
+    let mut data = fs::read(&zip_path)?;
+    let records: Vec<usize> = data
+        .windows(4)
+        .enumerate()
+        .filter(|(_, window)| *window == 0x0201_4b50u32.to_le_bytes())
+        .map(|(offset, _)| offset)
+        .collect();
+    for &record in &records {
+        data[record + 5] = 11;
+        data[record + 36..record + 38].copy_from_slice(&TEXT.to_le_bytes());
+        data[record + 38..record + 42]
+            .copy_from_slice(&READ_ONLY_HIDDEN_SYSTEM_ARCHIVE.to_le_bytes());
+    }
+    fs::write(&zip_path, data)?;
+
+    // Renamed entries are copied, converted text entries written anew
+    let output = Command::new(&binary_path)
+        .args(["--fix-contents", "txt"])
+        .arg(&zip_path)
+        .output()?;
+    assert!(
+        output.status.success(),
+        "runzip should succeed. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let data = fs::read(&zip_path)?;
+    let mut records = 0;
+    for (offset, _) in data
+        .windows(4)
+        .enumerate()
+        .filter(|(_, window)| *window == 0x0201_4b50u32.to_le_bytes())
+    {
+        let record = &data[offset..];
+        assert_eq!(record[5], 11, "The host stays NTFS");
+        assert_eq!(u16::from_le_bytes([record[36], record[37]]), TEXT);
+        assert_eq!(
+            u32::from_le_bytes([record[38], record[39], record[40], record[41]]),
+            READ_ONLY_HIDDEN_SYSTEM_ARCHIVE
+        );
+        records += 1;
+    }
+    assert_eq!(records, 2);
+    let names = extract_filenames_from_zip(&zip_path)?;
+    assert_eq!(names[0], "Скрытый.txt".as_bytes());
+    assert_eq!(
+        extract_modes_and_contents(&zip_path)?[0].1,
+        "Только для чтения, не удалять этот файл!".as_bytes()
+    );
+    assert_eq!(names[1], "Системный.sys".as_bytes());
+
+    Ok(())
+}
+
+#[test]
+fn test_decompression_bomb_limits() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let zip_path = temp_dir.path().join("bomb.zip");
+    let binary_path = get_runzip_binary();
+
+    let file = fs::File::create(&zip_path)?;
+    let mut writer = zip::ZipWriter::new(file);
+    writer.start_file("zeros.bin", zip::write::SimpleFileOptions::default())?;
+    std::io::Write::write_all(&mut writer, &vec![0; 1 << 20])?;
+    writer.finish()?;
+    let original = fs::read(&zip_path)?;
+
+    for limit in [
+        ["--max-entry-size", "512K"],
+        ["--max-total-size", "1023K"],
+        ["--max-ratio", "100"],
+    ] {
+        let output = Command::new(&binary_path)
+            .args(limit)
+            .arg(&zip_path)
+            .output()?;
+        assert!(
+            !output.status.success(),
+            "{limit:?} should refuse the archive"
+        );
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.contains("Entry 0 \"zeros.bin\"") && stderr.contains(limit[0]),
+            "The offending entry and limit should be named. stderr: {stderr}"
+        );
+        assert_eq!(
+            fs::read(&zip_path)?,
+            original,
+            "{limit:?} should leave the archive alone"
+        );
+    }
+
+    let output = Command::new(&binary_path)
+        .args(["--max-entry-size", "1M", "--max-total-size", "1M"])
+        .arg(&zip_path)
+        .output()?;
+    assert!(
+        output.status.success(),
+        "Archives within the limits should be processed. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_verification_keeps_original_on_mismatch() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let zip_path = temp_dir.path().join("corrupt.zip");
+    let binary_path = get_runzip_binary();
+
+    let name = encode_legacy("Привет.txt", encoding_rs::IBM866);
+    create_raw_zip(&zip_path, &[(&name, b"hello")])?;
+    // Damage the entry data so that it no longer matches its CRC-32
+    let mut data = fs::read(&zip_path)?;
+    data[30 + name.len()] ^= 0xFF;
+    fs::write(&zip_path, &data)?;
+
+    let output = run_runzip(&binary_path, &[&zip_path])?;
+    assert!(!output.status.success(), "runzip should fail verification");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Entry 0 is corrupted in the new archive"),
+        "The failing entry should be reported. stderr: {stderr}"
+    );
+    assert_eq!(fs::read(&zip_path)?, data, "The original should be kept");
+
+    Ok(())
+}
+
+#[test]
+fn test_salvage_damaged_archives() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+
+    // Central directory cut off and replaced with garbage
+    let (windows_zip, _, _) = setup_test_archives(temp_dir.path())?;
+    let mut data = fs::read(&windows_zip)?;
+    let cd_start = data
+        .windows(4)
+        .position(|window| window == b"PK\x01\x02")
+        .expect("central directory");
+    data.truncate(cd_start);
+    data.extend_from_slice(b"garbage");
+    fs::write(&windows_zip, &data)?;
+
+    // Streamed archive whose sizes live in data descriptors
+    let stream_zip = temp_dir.path().join("stream.zip");
+    let mut stream = Vec::new();
+    let mut writer = zip::ZipWriter::new_stream(&mut stream);
+    writer.start_file("Привет.txt", zip::write::SimpleFileOptions::default())?;
+    std::io::Write::write_all(&mut writer, &b"hello ".repeat(100))?;
+    writer.start_file("empty.txt", zip::write::SimpleFileOptions::default())?;
+    writer.finish()?;
+    let cd_start = stream
+        .windows(4)
+        .position(|window| window == b"PK\x01\x02")
+        .expect("central directory");
+    stream.truncate(cd_start);
+    fs::write(&stream_zip, &stream)?;
+
+    let output = run_runzip(&binary_path, &[&windows_zip])?;
+    assert!(!output.status.success(), "damaged archives need --salvage");
+
+    let output = Command::new(&binary_path)
+        .arg("--salvage")
+        .arg(&windows_zip)
+        .arg(&stream_zip)
+        .output()?;
+    assert!(
+        output.status.success(),
+        "runzip --salvage should succeed. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let names = extract_filenames_from_zip(&windows_zip)?;
+    assert_eq!(names.len(), 2);
+    assert!(names.iter().all(|name| is_valid_utf8_russian(name)));
+    assert_eq!(
+        extract_filenames_from_zip(&stream_zip)?,
+        vec!["Привет.txt".as_bytes().to_vec(), b"empty.txt".to_vec()]
+    );
+    let contents = extract_modes_and_contents(&stream_zip)?;
+    assert_eq!(contents[0].1, b"hello ".repeat(100));
+
+    Ok(())
+}
+
+#[test]
+fn test_local_central_name_mismatch() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+    let central_name = encode_legacy("Привет.txt", encoding_rs::IBM866);
+    let local_name = encode_legacy("Прощай.txt", encoding_rs::IBM866);
+
+    for (prefer, expected) in [("central", "Привет.txt"), ("local", "Прощай.txt")] {
+        let zip_path = temp_dir.path().join(format!("{prefer}.zip"));
+        create_raw_zip(&zip_path, &[(&central_name, b"hello")])?;
+        let mut data = fs::read(&zip_path)?;
+        data[30..30 + local_name.len()].copy_from_slice(&local_name);
+        fs::write(&zip_path, &data)?;
+
+        let output = Command::new(&binary_path)
+            .args(["--prefer-name", prefer])
+            .arg(&zip_path)
+            .output()?;
+        assert!(
+            output.status.success(),
+            "runzip --prefer-name {prefer} should succeed. stderr: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(
+            stdout.contains(&format!("using the {prefer} one")),
+            "The mismatch should be reported. Output: {stdout}"
+        );
+
+        // Both headers now carry the preferred name
+        assert_eq!(
+            extract_filenames_from_zip(&zip_path)?,
+            vec![expected.as_bytes().to_vec()]
+        );
+        let data = fs::read(&zip_path)?;
+        assert_eq!(&data[30..30 + expected.len()], expected.as_bytes());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_zipalign_keeps_order_and_alignment() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let zip_path = temp_dir.path().join("app.zip");
+    let binary_path = get_runzip_binary();
+
+    let names = [
+        encode_legacy("Привет.txt", encoding_rs::IBM866),
+        b"a".to_vec(),
+        encode_legacy("ресурс.bin", encoding_rs::IBM866),
+        b"abc/".to_vec(),
+        encode_legacy("ещё.dat", encoding_rs::IBM866),
+    ];
+    let entries: Vec<(&[u8], &[u8])> = names
+        .iter()
+        .map(|name| (name.as_slice(), &b"12345"[..]))
+        .collect();
+    create_raw_zip(&zip_path, &entries)?;
+
+    let output = Command::new(&binary_path)
+        .args(["--zipalign", "-s", "cp866"])
+        .arg(&zip_path)
+        .output()?;
+    assert!(
+        output.status.success(),
+        "runzip --zipalign should succeed. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    assert_eq!(
+        extract_filenames_from_zip(&zip_path)?,
+        vec![
+            "Привет.txt".as_bytes().to_vec(),
+            b"a".to_vec(),
+            "ресурс.bin".as_bytes().to_vec(),
+            b"abc/".to_vec(),
+            "ещё.dat".as_bytes().to_vec(),
+        ]
+    );
+    let mut archive = ZipArchive::new(fs::File::open(&zip_path)?)?;
+    for i in 0..archive.len() {
+        let file_entry = archive.by_index_raw(i)?;
+        assert_eq!(
+            file_entry.data_start() % 4,
+            0,
+            "{} should be aligned",
+            file_entry.name()
+        );
+    }
+    for (_, contents) in extract_modes_and_contents(&zip_path)? {
+        assert_eq!(contents, b"12345");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_structured_formats_skipped() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+    let name = encode_legacy("Привет.txt", encoding_rs::IBM866);
+
+    // Recognized by extension
+    let docx = temp_dir.path().join("report.docx");
+    create_raw_zip(&docx, &[(&name, b"hello")])?;
+    // Recognized by a marker entry
+    let jar = temp_dir.path().join("library.zip");
+    create_raw_zip(&jar, &[(b"META-INF/MANIFEST.MF", b""), (&name, b"hello")])?;
+    let originals = [fs::read(&docx)?, fs::read(&jar)?];
+
+    let output = Command::new(&binary_path)
+        .args(["-s", "cp866"])
+        .arg(&docx)
+        .arg(&jar)
+        .output()?;
+    assert!(output.status.success(), "Skipping is not a failure");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Office Open XML document") && stderr.contains("Java archive"),
+        "Skipped archives should be reported. stderr: {stderr}"
+    );
+    assert_eq!([fs::read(&docx)?, fs::read(&jar)?], originals);
+
+    let output = Command::new(&binary_path)
+        .args(["-s", "cp866", "--force"])
+        .arg(&docx)
+        .output()?;
+    assert!(output.status.success(), "runzip --force should succeed");
+    assert_eq!(
+        extract_filenames_from_zip(&docx)?,
+        vec!["Привет.txt".as_bytes().to_vec()]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_recurse_archives() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (windows_zip, _, _) = setup_test_archives(temp_dir.path())?;
+    let binary_path = get_runzip_binary();
+    let inner = fs::read(&windows_zip)?;
+
+    // One inner archive deflated, one stored inside another archive
+    let middle_path = temp_dir.path().join("middle.zip");
+    let file = fs::File::create(&middle_path)?;
+    let mut writer = zip::ZipWriter::new(file);
+    writer.start_file(
+        "inner.zip",
+        zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored),
+    )?;
+    std::io::Write::write_all(&mut writer, &inner)?;
+    writer.finish()?;
+
+    let outer_path = temp_dir.path().join("outer.zip");
+    let file = fs::File::create(&outer_path)?;
+    let mut writer = zip::ZipWriter::new(file);
+    let options = zip::write::SimpleFileOptions::default();
+    writer.start_file("backup.zip", options)?;
+    std::io::Write::write_all(&mut writer, &inner)?;
+    writer.start_file("middle.zip", options)?;
+    std::io::Write::write_all(&mut writer, &fs::read(&middle_path)?)?;
+    writer.start_file("broken.zip", options)?;
+    std::io::Write::write_all(&mut writer, b"not an archive")?;
+    writer.finish()?;
+
+    let read_entry = |data: &[u8], name: &str| -> Result<Vec<u8>> {
+        let mut archive = ZipArchive::new(std::io::Cursor::new(data))?;
+        let mut contents = Vec::new();
+        std::io::Read::read_to_end(&mut archive.by_name(name)?, &mut contents)?;
+        Ok(contents)
+    };
+    let names_of = |data: &[u8]| -> Result<Vec<Vec<u8>>> {
+        let mut archive = ZipArchive::new(std::io::Cursor::new(data))?;
+        (0..archive.len())
+            .map(|i| Ok(archive.by_index_raw(i)?.name_raw().to_vec()))
+            .collect()
+    };
+
+    // Without the option nested archives are left alone
+    let output = run_runzip(&binary_path, &[&outer_path])?;
+    assert!(output.status.success(), "runzip should succeed");
+    let outer = fs::read(&outer_path)?;
+    assert_eq!(read_entry(&outer, "backup.zip")?, inner);
+
+    let output = Command::new(&binary_path)
+        .arg("--recurse-archives")
+        .arg(&outer_path)
+        .output()?;
+    assert!(
+        output.status.success(),
+        "runzip --recurse-archives should succeed. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("middle.zip/inner.zip contains 2 files"),
+        "Nested archives should be listed. Output: {stdout}"
+    );
+
+    let outer = fs::read(&outer_path)?;
+    let backup = read_entry(&outer, "backup.zip")?;
+    let middle = read_entry(&outer, "middle.zip")?;
+    let nested = read_entry(&middle, "inner.zip")?;
+    for fixed in [&backup, &nested] {
+        let names = names_of(fixed)?;
+        assert_eq!(names.len(), 2);
+        assert!(names.iter().all(|name| is_valid_utf8_russian(name)));
+    }
+    assert_eq!(read_entry(&outer, "broken.zip")?, b"not an archive");
+
+    Ok(())
+}
+
+#[test]
+fn test_gzip_original_name() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let gz_path = temp_dir.path().join("report.txt.gz");
+    let binary_path = get_runzip_binary();
+    let contents = "Годовой отчёт\n".repeat(50);
+
+    let file = fs::File::create(&gz_path)?;
+    let mut encoder = flate2::GzBuilder::new()
+        .filename(encode_legacy("Отчёт за год.txt", encoding_rs::WINDOWS_1251))
+        .comment("comment")
+        .write(file, flate2::Compression::default());
+    std::io::Write::write_all(&mut encoder, contents.as_bytes())?;
+    encoder.finish()?;
+
+    let output = Command::new(&binary_path)
+        .args(["fix", "-s", "windows-1251"])
+        .arg(&gz_path)
+        .output()?;
+    assert!(
+        output.status.success(),
+        "runzip fix should succeed on gzip files. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let mut decoder = flate2::read::GzDecoder::new(fs::File::open(&gz_path)?);
+    let mut unpacked = String::new();
+    std::io::Read::read_to_string(&mut decoder, &mut unpacked)?;
+    assert_eq!(unpacked, contents);
+    let header = decoder.header().expect("gzip header");
+    assert_eq!(header.filename(), Some("Отчёт за год.txt".as_bytes()));
+    assert_eq!(header.comment(), Some(&b"comment"[..]));
+
+    Ok(())
+}
+
+/// Test helper to append a tar member with a raw name, optionally preceded by
+/// a GNU long name member
+fn append_tar_member(
+    builder: &mut tar::Builder<fs::File>,
+    entry_type: tar::EntryType,
+    name: &[u8],
+    link_name: Option<&[u8]>,
+    data: &[u8],
+) -> Result<()> {
+    if name.len() > 100 {
+        let mut long_name = tar::Header::new_gnu();
+        long_name.as_old_mut().name[..13].copy_from_slice(b"././@LongLink");
+        long_name.set_entry_type(tar::EntryType::GNULongName);
+        long_name.set_size(name.len() as u64 + 1);
+        long_name.set_cksum();
+        builder.append(&long_name, [name, b"\0"].concat().as_slice())?;
+    }
+    let mut header = tar::Header::new_gnu();
+    let stored = name.len().min(100);
+    header.as_old_mut().name[..stored].copy_from_slice(&name[..stored]);
+    if let Some(link_name) = link_name {
+        header.as_old_mut().linkname[..link_name.len()].copy_from_slice(link_name);
+    }
+    header.set_entry_type(entry_type);
+    header.set_mode(0o644);
+    header.set_size(data.len() as u64);
+    header.set_cksum();
+    builder.append(&header, data)?;
+    Ok(())
+}
+
+#[test]
+fn test_tar_archives() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let tar_path = temp_dir.path().join("backup.tar");
+    let binary_path = get_runzip_binary();
+    let koi8 = |name: &str| encode_legacy(name, encoding_rs::KOI8_R);
+    let long_name = format!("{}.txt", "Очень длинное имя ".repeat(4));
+    // Over 100 bytes even in KOI8-R, so stored in a GNU long name member
+    let longer_name = format!("{}.txt", "Очень длинное имя ".repeat(10));
+
+    let mut builder = tar::Builder::new(fs::File::create(&tar_path)?);
+    let regular = tar::EntryType::Regular;
+    append_tar_member(&mut builder, regular, &koi8("Отчёт.txt"), None, b"report")?;
+    append_tar_member(&mut builder, regular, &koi8(&long_name), None, b"long")?;
+    append_tar_member(&mut builder, regular, &koi8(&longer_name), None, b"longer")?;
+    let symlink = tar::EntryType::Symlink;
+    append_tar_member(
+        &mut builder,
+        symlink,
+        &koi8("Ссылка"),
+        Some(&koi8("Отчёт.txt")),
+        b"",
+    )?;
+    let hard_link = tar::EntryType::Link;
+    append_tar_member(
+        &mut builder,
+        hard_link,
+        &koi8("Копия"),
+        Some(&koi8("Отчёт.txt")),
+        b"",
+    )?;
+    // PAX names are UTF-8 already and stay as they are
+    builder.append_pax_extensions([("path", "Заметки.txt".as_bytes())])?;
+    append_tar_member(&mut builder, regular, b"notes.txt", None, b"notes")?;
+    // Python's tarfile marks names it cannot decode as raw bytes
+    builder.append_pax_extensions([
+        ("hdrcharset", &b"BINARY"[..]),
+        ("path", &koi8("Письмо.txt")),
+    ])?;
+    append_tar_member(&mut builder, regular, &koi8("Письмо.txt"), None, b"letter")?;
+    // A PAX path that is not UTF-8 is a legacy name all the same
+    builder.append_pax_extensions([("path", &koi8("Черновик.txt")[..])])?;
+    append_tar_member(&mut builder, regular, &koi8("Черновик.txt"), None, b"draft")?;
+    builder.into_inner()?;
+
+    let output = Command::new(&binary_path)
+        .args(["fix", "-s", "koi8-r"])
+        .arg(&tar_path)
+        .output()?;
+    assert!(
+        output.status.success(),
+        "runzip fix should succeed on tar archives. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let mut archive = tar::Archive::new(fs::File::open(&tar_path)?);
+    let mut members = Vec::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let name = String::from_utf8(entry.path_bytes().into_owned())?;
+        let link_name = entry
+            .link_name_bytes()
+            .map(|link| String::from_utf8(link.into_owned()))
+            .transpose()?;
+        let mut data = Vec::new();
+        std::io::Read::read_to_end(&mut entry, &mut data)?;
+        members.push((name, link_name, data));
+    }
+    let expected = [
+        ("Отчёт.txt", None, &b"report"[..]),
+        (long_name.as_str(), None, b"long"),
+        (longer_name.as_str(), None, b"longer"),
+        ("Ссылка", Some("Отчёт.txt"), b""),
+        ("Копия", Some("Отчёт.txt"), b""),
+        ("Заметки.txt", None, b"notes"),
+        ("Письмо.txt", None, b"letter"),
+        ("Черновик.txt", None, b"draft"),
+    ];
+    assert_eq!(members.len(), expected.len());
+    for ((name, link_name, data), (expected_name, expected_link, expected_data)) in
+        members.iter().zip(expected)
+    {
+        assert_eq!(name, expected_name);
+        assert_eq!(link_name.as_deref(), expected_link);
+        assert_eq!(data, expected_data);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_compressed_tar_archives() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+    let name = encode_legacy("Отчёт.txt", encoding_rs::KOI8_R);
+
+    let tar_data = {
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_ustar();
+        header.as_old_mut().name[..name.len()].copy_from_slice(&name);
+        header.set_mode(0o644);
+        header.set_size(6);
+        header.set_cksum();
+        builder.append(&header, &b"report"[..])?;
+        builder.into_inner()?
+    };
+
+    let gz_path = temp_dir.path().join("backup.tar.gz");
+    let mut encoder = flate2::GzBuilder::new()
+        .filename("backup.tar")
+        .write(fs::File::create(&gz_path)?, flate2::Compression::best());
+    std::io::Write::write_all(&mut encoder, &tar_data)?;
+    encoder.finish()?;
+
+    let xz_path = temp_dir.path().join("backup.txz");
+    let mut xz_data = Vec::new();
+    std::io::Read::read_to_end(
+        &mut xz2::read::XzEncoder::new(&tar_data[..], 9),
+        &mut xz_data,
+    )?;
+    fs::write(&xz_path, xz_data)?;
+
+    let zst_path = temp_dir.path().join("backup.tar.zst");
+    fs::write(&zst_path, zstd::encode_all(&tar_data[..], 19)?)?;
+
+    for path in [&gz_path, &xz_path, &zst_path] {
+        let output = Command::new(&binary_path)
+            .args(["fix", "-s", "koi8-r"])
+            .arg(path)
+            .output()?;
+        assert!(
+            output.status.success(),
+            "runzip fix should succeed on {}. stderr: {}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    let mut decoder = flate2::read::GzDecoder::new(fs::File::open(&gz_path)?);
+    let mut unpacked = Vec::new();
+    std::io::Read::read_to_end(&mut decoder, &mut unpacked)?;
+    let header = decoder.header().expect("gzip header");
+    assert_eq!(header.filename(), Some(&b"backup.tar"[..]));
+    // XFL still records the best compression level
+    assert_eq!(fs::read(&gz_path)?[8], 2);
+    let xz_unpacked = {
+        let mut data = Vec::new();
+        std::io::Read::read_to_end(
+            &mut xz2::read::XzDecoder::new(fs::File::open(&xz_path)?),
+            &mut data,
+        )?;
+        data
+    };
+    let zst_unpacked = zstd::decode_all(fs::File::open(&zst_path)?)?;
+
+    for unpacked in [unpacked, xz_unpacked, zst_unpacked] {
+        let mut archive = tar::Archive::new(&unpacked[..]);
+        let mut entries = archive.entries()?;
+        let mut entry = entries.next().expect("a member")?;
+        assert_eq!(&*entry.path_bytes(), "Отчёт.txt".as_bytes());
+        let mut data = Vec::new();
+        std::io::Read::read_to_end(&mut entry, &mut data)?;
+        assert_eq!(data, b"report");
+        drop(entry);
+        assert!(entries.next().is_none());
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "sevenz")]
+#[test]
+fn test_7z_archives() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let archive_path = temp_dir.path().join("photos.7z");
+    let binary_path = get_runzip_binary();
+    // What Windows-1252 systems made of Windows-1251 names
+    let cp1251_name = encode_legacy("Отчёт.txt", encoding_rs::WINDOWS_1251);
+    let (mojibake, _, _) = encoding_rs::WINDOWS_1252.decode(&cp1251_name);
+
+    let mut writer = sevenz_rust::SevenZWriter::create(&archive_path)?;
+    for (name, data) in [(mojibake.as_ref(), &b"report"[..]), ("notes.txt", b"notes")] {
+        let mut entry = sevenz_rust::SevenZArchiveEntry::new();
+        entry.name = name.to_string();
+        writer.push_archive_entry(entry, Some(data))?;
+    }
+    writer.finish()?;
+
+    let output = Command::new(&binary_path)
+        .arg("fix")
+        .arg(&archive_path)
+        .output()?;
+    assert!(
+        output.status.success(),
+        "runzip fix should succeed on 7z archives. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let mut reader =
+        sevenz_rust::SevenZReader::open(&archive_path, sevenz_rust::Password::empty())?;
+    let mut entries = Vec::new();
+    reader.for_each_entries(|entry, data| {
+        let mut contents = Vec::new();
+        data.read_to_end(&mut contents)?;
+        entries.push((entry.name.clone(), contents));
+        Ok(true)
+    })?;
+    entries.sort();
+    assert_eq!(
+        entries,
+        [
+            ("notes.txt".to_string(), b"notes".to_vec()),
+            ("Отчёт.txt".to_string(), b"report".to_vec()),
+        ]
+    );
+
+    Ok(())
+}
+
+/// Test helper to build a RAR 4 archive of stored entries, with raw names and
+/// the Unicode name flag
+fn create_test_rar4(rar_path: &Path, entries: &[(&[u8], bool, &[u8])]) -> Result<()> {
+    let mut rar = b"Rar!\x1a\x07\x00".to_vec();
+    // Main header: CRC, type, flags, size and reserved fields
+    rar.extend_from_slice(&[0, 0, 0x73, 0, 0, 13, 0, 0, 0, 0, 0, 0, 0]);
+    for (name, unicode, data) in entries {
+        let flags: u16 = if *unicode { 0x8200 } else { 0x8000 };
+        let size = u32::try_from(data.len())?;
+        rar.extend_from_slice(&[0, 0, 0x74]);
+        rar.extend_from_slice(&flags.to_le_bytes());
+        rar.extend_from_slice(&u16::try_from(7 + 25 + name.len())?.to_le_bytes());
+        rar.extend_from_slice(&size.to_le_bytes()); // Packed size
+        rar.extend_from_slice(&size.to_le_bytes()); // Unpacked size
+        rar.push(0); // MS-DOS
+        rar.extend_from_slice(&crc32fast::hash(data).to_le_bytes());
+        rar.extend_from_slice(&[0, 0, 0x21, 0x00]); // DOS time
+        rar.extend_from_slice(&[29, 0x30]); // Version, stored
+        rar.extend_from_slice(&u16::try_from(name.len())?.to_le_bytes());
+        rar.extend_from_slice(&[0x20, 0, 0, 0]); // Attributes
+        rar.extend_from_slice(name);
+        rar.extend_from_slice(data);
+    }
+    rar.extend_from_slice(&[0, 0, 0x7b, 0, 0x40, 7, 0]);
+    fs::write(rar_path, rar)?;
+    Ok(())
+}
+
+#[test]
+fn test_detect_rar() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+
+    let rar_path = temp_dir.path().join("old.rar");
+    let oem_name = encode_legacy("Документ.txt", encoding_rs::IBM866);
+    create_test_rar4(
+        &rar_path,
+        &[
+            (&oem_name, false, b"text"),
+            ("Письмо.txt".as_bytes(), true, b"letter"),
+            (b"readme.txt", false, b"readme"),
+        ],
+    )?;
+    let original = fs::read(&rar_path)?;
+    let map_path = temp_dir.path().join("renames.csv");
+
+    let output = Command::new(&binary_path)
+        .args(["detect", "-s", "cp866", "--emit-map"])
+        .arg(&map_path)
+        .arg(&rar_path)
+        .output()?;
+    assert!(
+        output.status.success(),
+        "runzip detect should succeed on RAR archives. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("contains 3 files"), "stdout: {stdout}");
+    assert!(
+        stdout.contains("Документ.txt: WOULD FIX"),
+        "stdout: {stdout}"
+    );
+    assert!(stdout.contains("Письмо.txt: OK"), "stdout: {stdout}");
+    assert_eq!(
+        fs::read(&rar_path)?,
+        original,
+        "RAR archives are never modified"
+    );
+
+    let hex: String = oem_name.iter().map(|byte| format!("{byte:02x}")).collect();
+    assert_eq!(
+        fs::read_to_string(&map_path)?,
+        format!("old,new\nhex:{hex},Документ.txt\n")
+    );
+
+    // RAR 5 names are always UTF-8
+    let rar5_path = temp_dir.path().join("new.rar");
+    let mut rar5 = b"Rar!\x1a\x07\x01\x00".to_vec();
+    let name = "Фото.jpg".as_bytes();
+    let header = [
+        &[2, 2, 4, 0, 4, 0, 0, 1, u8::try_from(name.len())?][..],
+        name,
+    ]
+    .concat();
+    rar5.extend_from_slice(&[0; 4]);
+    rar5.push(u8::try_from(header.len())?);
+    rar5.extend_from_slice(&header);
+    rar5.extend_from_slice(b"jpeg");
+    rar5.extend_from_slice(&[0, 0, 0, 0, 3, 5, 0, 0]);
+    fs::write(&rar5_path, rar5)?;
+
+    let output = Command::new(&binary_path)
+        .arg("detect")
+        .arg(&rar5_path)
+        .output()?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("Фото.jpg: OK (already UTF-8)"),
+        "stdout: {stdout}"
+    );
+
+    Ok(())
+}
+
+/// Test helper to build an ISO 9660 directory record
+fn iso_record(extent: u32, size: u32, is_dir: bool, name: &[u8], system_use: &[u8]) -> Vec<u8> {
+    let padding = usize::from(name.len().is_multiple_of(2));
+    let mut record = vec![0; 33];
+    record[0] = u8::try_from(33 + name.len() + padding + system_use.len()).unwrap();
+    record[2..6].copy_from_slice(&extent.to_le_bytes());
+    record[6..10].copy_from_slice(&extent.to_be_bytes());
+    record[10..14].copy_from_slice(&size.to_le_bytes());
+    record[14..18].copy_from_slice(&size.to_be_bytes());
+    record[25] = if is_dir { 0x02 } else { 0 };
+    record[32] = u8::try_from(name.len()).unwrap();
+    record.extend_from_slice(name);
+    record.resize(record.len() + padding, 0);
+    record.extend_from_slice(system_use);
+    record
+}
+
+/// A file of a test ISO image: primary name, optional Rock Ridge and Joliet
+/// names, and contents
+type IsoFile<'a> = (&'a [u8], Option<&'a [u8]>, Option<&'a str>, &'a [u8]);
+
+/// Test helper to build an ISO image with files in its root directory
+fn create_test_iso(iso_path: &Path, files: &[IsoFile]) -> Result<()> {
+    const SECTOR: usize = 2048;
+    let joliet = files.iter().any(|(_, _, joliet, _)| joliet.is_some());
+    let rock_ridge = files
+        .iter()
+        .any(|(_, rock_ridge, _, _)| rock_ridge.is_some());
+    let terminator = if joliet { 18 } else { 17 };
+    let (primary_root, joliet_root) = (terminator + 1, terminator + 2);
+    let first_file = terminator + 3;
+
+    let mut image = vec![0; SECTOR * (first_file + files.len())];
+    let descriptor = |image: &mut Vec<u8>, sector: usize, kind: u8, root: usize| {
+        let start = sector * SECTOR;
+        image[start] = kind;
+        image[start + 1..start + 6].copy_from_slice(b"CD001");
+        image[start + 6] = 1;
+        if kind != 255 {
+            let root = iso_record(u32::try_from(root).unwrap(), 2048, true, &[0], &[]);
+            image[start + 156..start + 156 + root.len()].copy_from_slice(&root);
+        }
+    };
+    descriptor(&mut image, 16, 1, primary_root);
+    if joliet {
+        descriptor(&mut image, 17, 2, joliet_root);
+        image[17 * SECTOR + 88..17 * SECTOR + 91].copy_from_slice(b"%/E");
+    }
+    descriptor(&mut image, terminator, 255, 0);
+
+    for (root, use_joliet) in [(primary_root, false), (joliet_root, true)] {
+        if use_joliet && !joliet {
+            continue;
+        }
+        let sharing_protocol: &[u8] = if rock_ridge && !use_joliet {
+            &[b'S', b'P', 7, 1, 0xbe, 0xef, 0]
+        } else {
+            &[]
+        };
+        let root_extent = u32::try_from(root)?;
+        let mut directory = iso_record(root_extent, 2048, true, &[0], sharing_protocol);
+        directory.extend(iso_record(root_extent, 2048, true, &[1], &[]));
+        for (i, (primary, rock_ridge, joliet_name, data)) in files.iter().enumerate() {
+            let extent = u32::try_from(first_file + i)?;
+            let size = u32::try_from(data.len())?;
+            if use_joliet {
+                let name: Vec<u8> = joliet_name
+                    .unwrap_or_default()
+                    .encode_utf16()
+                    .flat_map(u16::to_be_bytes)
+                    .collect();
+                directory.extend(iso_record(extent, size, false, &name, &[]));
+            } else {
+                let mut name = primary.to_vec();
+                name.extend_from_slice(b";1");
+                let system_use = rock_ridge.map_or_else(Vec::new, |rock_ridge| {
+                    [
+                        &[
+                            b'N',
+                            b'M',
+                            u8::try_from(5 + rock_ridge.len()).unwrap(),
+                            1,
+                            0,
+                        ][..],
+                        rock_ridge,
+                    ]
+                    .concat()
+                });
+                directory.extend(iso_record(extent, size, false, &name, &system_use));
+            }
+        }
+        image[root * SECTOR..root * SECTOR + directory.len()].copy_from_slice(&directory);
+    }
+
+    for (i, (_, _, _, data)) in files.iter().enumerate() {
+        let start = (first_file + i) * SECTOR;
+        image[start..start + data.len()].copy_from_slice(data);
+    }
+    fs::write(iso_path, image)?;
+    Ok(())
+}
+
+#[test]
+fn test_iso_images() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+
+    // Careless mastering tools put code page bytes into the primary names
+    let primary_path = temp_dir.path().join("primary.iso");
+    let cp866_name = encode_legacy("ОТЧЁТ.TXT", encoding_rs::IBM866);
+    create_test_iso(&primary_path, &[(&cp866_name, None, None, b"report")])?;
+
+    let rock_ridge_path = temp_dir.path().join("rock-ridge.iso");
+    let koi8_name = encode_legacy("Письмо.txt", encoding_rs::KOI8_R);
+    create_test_iso(
+        &rock_ridge_path,
+        &[(b"PISMO.TXT", Some(&koi8_name), None, b"letter")],
+    )?;
+
+    let joliet_path = temp_dir.path().join("joliet.iso");
+    create_test_iso(
+        &joliet_path,
+        &[(b"FOTO.JPG", None, Some("Фото.jpg"), b"jpeg")],
+    )?;
+
+    let output = Command::new(&binary_path)
+        .args(["detect", "-s", "cp866"])
+        .arg(&primary_path)
+        .output()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "stdout: {stdout}");
+    assert!(stdout.contains("using ISO 9660 names"), "stdout: {stdout}");
+    assert!(stdout.contains("ОТЧЁТ.TXT: WOULD FIX"), "stdout: {stdout}");
+
+    let output = Command::new(&binary_path)
+        .arg("detect")
+        .arg(&joliet_path)
+        .arg(&rock_ridge_path)
+        .output()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "stdout: {stdout}");
+    assert!(stdout.contains("using Joliet names"), "stdout: {stdout}");
+    assert!(
+        stdout.contains("using Rock Ridge names"),
+        "stdout: {stdout}"
+    );
+
+    let dest = temp_dir.path().join("extracted");
+    for (path, source) in [
+        (&primary_path, "cp866"),
+        (&rock_ridge_path, "koi8-r"),
+        (&joliet_path, "cp866"),
+    ] {
+        let output = Command::new(&binary_path)
+            .args(["extract", "-s", source, "-d"])
+            .arg(&dest)
+            .arg(path)
+            .output()?;
+        assert!(
+            output.status.success(),
+            "runzip extract should succeed on ISO images. stderr: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    assert_eq!(fs::read(dest.join("ОТЧЁТ.TXT"))?, b"report");
+    assert_eq!(fs::read(dest.join("Письмо.txt"))?, b"letter");
+    assert_eq!(fs::read(dest.join("Фото.jpg"))?, b"jpeg");
+
+    // ISO images are read-only
+    let output = Command::new(&binary_path)
+        .arg("fix")
+        .arg(&joliet_path)
+        .output()?;
+    assert!(!output.status.success());
+
+    Ok(())
+}
+
+/// Test helper to build an ARJ archive of stored entries
+fn create_test_arj(arj_path: &Path, entries: &[(&[u8], &[u8])]) -> Result<()> {
+    fn header(fields: &[u8], name: &[u8]) -> Result<Vec<u8>> {
+        // Fields, name and an empty comment, each string NUL-terminated
+        let basic = [fields, name, b"\0\0"].concat();
+        let mut header = vec![0x60, 0xea];
+        header.extend_from_slice(&u16::try_from(basic.len())?.to_le_bytes());
+        header.extend_from_slice(&basic);
+        header.extend_from_slice(&crc32fast::hash(&basic).to_le_bytes());
+        header.extend_from_slice(&[0, 0]); // No extended headers
+        Ok(header)
+    }
+
+    let mut fields = vec![30, 11, 1, 0, 0x10, 0, 2, 0];
+    fields.resize(30, 0);
+    let mut arj = header(&fields, b"TEST.ARJ")?;
+    for (name, data) in entries {
+        let size = u32::try_from(data.len())?;
+        let mut fields = vec![30, 11, 1, 0, 0x10, 0, 0, 0]; // Stored file
+        fields.extend_from_slice(&[0, 0, 0x21, 0]); // DOS time
+        fields.extend_from_slice(&size.to_le_bytes()); // Compressed size
+        fields.extend_from_slice(&size.to_le_bytes()); // Original size
+        fields.extend_from_slice(&crc32fast::hash(data).to_le_bytes());
+        fields.extend_from_slice(&[0; 6]); // Position, attributes, host data
+        arj.extend_from_slice(&header(&fields, name)?);
+        arj.extend_from_slice(data);
+    }
+    arj.extend_from_slice(&[0x60, 0xea, 0, 0]);
+    fs::write(arj_path, arj)?;
+    Ok(())
+}
+
+/// Test helper for the CRC-16 that LHA archives use
+fn lha_crc16(data: &[u8]) -> u16 {
+    let mut crc = 0u16;
+    for &byte in data {
+        crc ^= u16::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 0 {
+                crc >> 1
+            } else {
+                (crc >> 1) ^ 0xa001
+            };
+        }
+    }
+    crc
+}
+
+/// Test helper to build an LHA archive of stored entries: a level 0 header
+/// for names with DOS separators and a level 2 header for directory/name pairs
+fn create_test_lha(
+    lha_path: &Path,
+    level0: &[u8],
+    level2: (&[u8], &[u8]),
+    data: &[u8],
+) -> Result<()> {
+    let size = u32::try_from(data.len())?;
+    let crc = lha_crc16(data);
+
+    let mut fields = b"-lh0-".to_vec();
+    fields.extend_from_slice(&size.to_le_bytes()); // Compressed size
+    fields.extend_from_slice(&size.to_le_bytes()); // Original size
+    fields.extend_from_slice(&[0, 0, 0x21, 0, 0x20, 0]); // DOS time, attributes, level
+    fields.push(u8::try_from(level0.len())?);
+    fields.extend_from_slice(level0);
+    fields.extend_from_slice(&crc.to_le_bytes());
+    let checksum = fields.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte));
+    let mut lha = vec![u8::try_from(fields.len())?, checksum];
+    lha.extend_from_slice(&fields);
+    lha.extend_from_slice(data);
+
+    let (directory, name) = level2;
+    let mut extended = Vec::new();
+    for (kind, value) in [(1, name), (2, directory)] {
+        extended.extend_from_slice(&u16::try_from(value.len() + 3)?.to_le_bytes());
+        extended.push(kind);
+        extended.extend_from_slice(value);
+    }
+    extended.extend_from_slice(&[0, 0]);
+    let mut header = u16::try_from(24 + extended.len())?.to_le_bytes().to_vec();
+    header.extend_from_slice(b"-lh0-");
+    header.extend_from_slice(&size.to_le_bytes());
+    header.extend_from_slice(&size.to_le_bytes());
+    header.extend_from_slice(&[0, 0, 0, 0, 0x20, 2]); // Unix time, reserved, level
+    header.extend_from_slice(&crc.to_le_bytes());
+    header.push(b'M'); // MS-DOS
+    header.extend_from_slice(&extended);
+    lha.extend_from_slice(&header);
+    lha.extend_from_slice(data);
+
+    lha.push(0);
+    fs::write(lha_path, lha)?;
+    Ok(())
+}
+
+#[test]
+fn test_arj_and_lha_archives() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+
+    let arj_path = temp_dir.path().join("old.arj");
+    let arj_name = encode_legacy("Отчёт.txt", encoding_rs::IBM866);
+    create_test_arj(
+        &arj_path,
+        &[(&arj_name, b"report"), (b"README.TXT", b"readme")],
+    )?;
+
+    let lha_path = temp_dir.path().join("old.lzh");
+    let level0 = encode_legacy("Письма\\Бабушке.txt", encoding_rs::IBM866);
+    // Level 2 headers separate directories with 0xFF
+    let directory = [
+        encode_legacy("Фото", encoding_rs::IBM866),
+        encode_legacy("Лето", encoding_rs::IBM866),
+    ]
+    .join(&0xff);
+    let name = encode_legacy("Море.txt", encoding_rs::IBM866);
+    create_test_lha(&lha_path, &level0, (&directory, &name), b"letter")?;
+
+    let output = Command::new(&binary_path)
+        .args(["detect", "-s", "cp866"])
+        .arg(&arj_path)
+        .arg(&lha_path)
+        .output()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "stdout: {stdout}");
+    assert!(stdout.contains("Отчёт.txt: WOULD FIX"), "stdout: {stdout}");
+    assert!(stdout.contains("README.TXT: OK"), "stdout: {stdout}");
+    assert!(
+        stdout.contains("Письма/Бабушке.txt: WOULD FIX"),
+        "stdout: {stdout}"
+    );
+    assert!(
+        stdout.contains("Фото/Лето/Море.txt: WOULD FIX"),
+        "stdout: {stdout}"
+    );
+
+    let dest = temp_dir.path().join("extracted");
+    let output = Command::new(&binary_path)
+        .args(["extract", "-s", "cp866", "-d"])
+        .arg(&dest)
+        .arg(&arj_path)
+        .arg(&lha_path)
+        .output()?;
+    assert!(
+        output.status.success(),
+        "runzip extract should succeed on ARJ and LHA archives. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(fs::read(dest.join("Отчёт.txt"))?, b"report");
+    assert_eq!(fs::read(dest.join("README.TXT"))?, b"readme");
+    assert_eq!(fs::read(dest.join("Письма/Бабушке.txt"))?, b"letter");
+    assert_eq!(fs::read(dest.join("Фото/Лето/Море.txt"))?, b"letter");
+
+    // A damaged entry is reported instead of extracted
+    let mut damaged = fs::read(&arj_path)?;
+    let data_start = damaged.len() - b"readme".len() - 4;
+    damaged[data_start] ^= 0xff;
+    fs::write(&arj_path, damaged)?;
+    let output = Command::new(&binary_path)
+        .args(["extract", "-d"])
+        .arg(temp_dir.path().join("damaged"))
+        .arg(&arj_path)
+        .output()?;
+    assert!(!output.status.success());
+    assert!(!temp_dir.path().join("damaged/README.TXT").exists());
+
+    // ARJ and LHA archives are read-only
+    let output = Command::new(&binary_path).arg(&lha_path).output()?;
+    assert!(!output.status.success());
+
+    Ok(())
+}
+
+#[test]
+fn test_extract_zip() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+    let (windows_zip, _, _) = setup_test_archives(temp_dir.path())?;
+
+    let zip_path = temp_dir.path().join("cp1251.zip");
+    let report = encode_legacy("Отчёт.txt", encoding_rs::WINDOWS_1251);
+    let evil = encode_legacy("../Письмо соседу.txt", encoding_rs::WINDOWS_1251);
+    create_raw_zip(&zip_path, &[(&report, b"report"), (&evil, b"outside")])?;
+    let original = fs::read(&zip_path)?;
+
+    let dest = temp_dir.path().join("extracted");
+    let output = Command::new(&binary_path)
+        .args(["extract", "-d"])
+        .arg(&dest)
+        .arg(&windows_zip)
+        .arg(&zip_path)
+        .output()?;
+    assert!(
+        output.status.success(),
+        "runzip extract should succeed on ZIP archives. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    assert!(dest.join("тест/Ещё один пустой каталог").is_dir());
+    assert!(dest.join("тест/тест.txt").is_file());
+    assert_eq!(fs::read(dest.join("Отчёт.txt"))?, b"report");
+    // Names are kept inside the target directory
+    assert_eq!(fs::read(dest.join("Письмо соседу.txt"))?, b"outside");
+    assert!(!temp_dir.path().join("Письмо соседу.txt").exists());
+    assert_eq!(
+        fs::read(&zip_path)?,
+        original,
+        "the archive is not modified"
+    );
+
+    // Existing files are never overwritten
+    let output = Command::new(&binary_path)
+        .args(["extract", "-d"])
+        .arg(&dest)
+        .arg(&zip_path)
+        .output()?;
+    assert!(!output.status.success());
+    assert_eq!(fs::read(dest.join("Отчёт.txt"))?, b"report");
+
+    // Archives over the limits are refused before anything is extracted
+    let limited = temp_dir.path().join("limited");
+    let output = Command::new(&binary_path)
+        .args(["extract", "--max-entry-size", "6", "-d"])
+        .arg(&limited)
+        .arg(&zip_path)
+        .output()?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("--max-entry-size 6"), "stderr: {stderr}");
+    assert!(!limited.join("Отчёт.txt").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_create_archive() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+
+    let source = temp_dir.path().join("Документы");
+    fs::create_dir_all(source.join("Пусто"))?;
+    fs::write(source.join("Отчёт.txt"), b"report")?;
+    let numbers: String = (0..1000).map(|n| format!("{n}\n")).collect();
+    fs::write(source.join("Числа.txt"), &numbers)?;
+
+    let cp866_zip = temp_dir.path().join("cp866.zip");
+    let output = Command::new(&binary_path)
+        .arg("create")
+        .arg(&cp866_zip)
+        .arg(&source)
+        .output()?;
+    assert!(
+        output.status.success(),
+        "runzip create should succeed. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let expected: Vec<Vec<u8>> = [
+        "Документы/",
+        "Документы/Отчёт.txt",
+        "Документы/Пусто/",
+        "Документы/Числа.txt",
+    ]
+    .iter()
+    .map(|name| encode_legacy(name, encoding_rs::IBM866))
+    .collect();
+    assert_eq!(extract_filenames_from_zip(&cp866_zip)?, expected);
+    let contents: Vec<_> = extract_modes_and_contents(&cp866_zip)?
+        .into_iter()
+        .map(|(_, contents)| contents)
+        .collect();
+    assert_eq!(contents[1], b"report");
+    assert_eq!(contents[3], numbers.as_bytes());
+
+    // Legacy names can carry their Unicode form in an extra field
+    let cp1251_zip = temp_dir.path().join("cp1251.zip");
+    let output = Command::new(&binary_path)
+        .args(["create", "-t", "windows-1251", "--unicode-extra"])
+        .arg(&cp1251_zip)
+        .arg(source.join("Отчёт.txt"))
+        .output()?;
+    assert!(output.status.success());
+    let cp1251_name = encode_legacy("Отчёт.txt", encoding_rs::WINDOWS_1251);
+    assert!(
+        fs::read(&cp1251_zip)?
+            .windows(cp1251_name.len())
+            .any(|window| window == cp1251_name)
+    );
+    let mut archive = ZipArchive::new(fs::File::open(&cp1251_zip)?)?;
+    assert_eq!(archive.by_index(0)?.name(), "Отчёт.txt");
+
+    // Names the target cannot hold and existing archives are refused
+    fs::write(source.join("日本.txt"), b"")?;
+    let output = Command::new(&binary_path)
+        .arg("create")
+        .arg(temp_dir.path().join("refused.zip"))
+        .arg(&source)
+        .output()?;
+    assert!(!output.status.success());
+    assert!(!temp_dir.path().join("refused.zip").exists());
+    let output = Command::new(&binary_path)
+        .args(["create", "-t", "utf-8"])
+        .arg(&cp866_zip)
+        .arg(&source)
+        .output()?;
+    assert!(!output.status.success());
+    assert_eq!(extract_filenames_from_zip(&cp866_zip)?, expected);
+
+    // Archives created with UTF-8 names need no fixing
+    let utf8_zip = temp_dir.path().join("utf8.zip");
+    let output = Command::new(&binary_path)
+        .args(["create", "-t", "utf-8"])
+        .arg(&utf8_zip)
+        .arg(&source)
+        .output()?;
+    assert!(output.status.success());
+    let output = run_runzip_dry_run(&binary_path, &[&utf8_zip])?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!stdout.contains("WOULD FIX"), "stdout: {stdout}");
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_fix_names_on_disk() -> Result<()> {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+
+    let root = temp_dir.path().join("extracted");
+    let documents = root.join(OsStr::from_bytes(&encode_legacy(
+        "Документы",
+        encoding_rs::IBM866,
+    )));
+    fs::create_dir_all(documents.join(OsStr::from_bytes(&encode_legacy(
+        "Лето 2005",
+        encoding_rs::WINDOWS_1251,
+    ))))?;
+    fs::write(
+        documents.join(OsStr::from_bytes(&encode_legacy(
+            "Отчёт за год.txt",
+            encoding_rs::IBM866,
+        ))),
+        b"report",
+    )?;
+    fs::write(root.join("Письмо.txt"), b"letter")?;
+    // Already taken by a correctly named file
+    fs::write(
+        root.join(OsStr::from_bytes(&encode_legacy(
+            "Письмо.txt",
+            encoding_rs::KOI8_R,
+        ))),
+        b"duplicate",
+    )?;
+
+    let output = Command::new(&binary_path)
+        .args(["fs", "--dry-run"])
+        .arg(&root)
+        .output()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Документы: WOULD FIX"), "stdout: {stdout}");
+    assert!(stdout.contains("Лето 2005: WOULD FIX"), "stdout: {stdout}");
+    assert!(documents.exists(), "dry run renames nothing");
+
+    let output = Command::new(&binary_path).arg("fs").arg(&root).output()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!output.status.success());
+    assert!(
+        stdout.contains("Skipped: \"Письмо.txt\" already exists"),
+        "stdout: {stdout}"
+    );
+    assert!(root.join("Документы/Лето 2005").is_dir());
+    assert_eq!(
+        fs::read(root.join("Документы/Отчёт за год.txt"))?,
+        b"report"
+    );
+    assert_eq!(fs::read(root.join("Письмо.txt"))?, b"letter");
+
+    Ok(())
+}
+
+/// Without the feature, `mount` is refused instead of taken for archives to
+/// fix
+#[cfg(not(feature = "mount"))]
+#[test]
+fn test_mount_unavailable() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let zip_path = temp_dir.path().join("mount.zip");
+    let cp866 = encode_legacy("файл.txt", encoding_rs::IBM866);
+    create_raw_zip(&zip_path, &[(&cp866, b"hello")])?;
+    let original = fs::read(&zip_path)?;
+
+    let output = Command::new(get_runzip_binary())
+        .arg("mount")
+        .arg(&zip_path)
+        .arg(temp_dir.path())
+        .output()?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("needs the mount feature"),
+        "stderr: {stderr}"
+    );
+    assert_eq!(fs::read(&zip_path)?, original);
+
+    Ok(())
+}
+
+#[cfg(all(feature = "mount", target_os = "linux"))]
+#[test]
+fn test_mount() -> Result<()> {
+    use std::process::Stdio;
+    use std::time::Duration;
+
+    let temp_dir = TempDir::new()?;
+    let zip_path = temp_dir.path().join("mount.zip");
+    let cp866 = encode_legacy("Отчёт/файл.txt", encoding_rs::IBM866);
+    let big = vec![b'x'; 2048];
+    create_raw_zip(
+        &zip_path,
+        &[
+            (&cp866, b"hello"),
+            (b"readme.txt", b"readme"),
+            (b"big.bin", &big),
+        ],
+    )?;
+    let mountpoint = temp_dir.path().join("mnt");
+    fs::create_dir(&mountpoint)?;
+
+    let mut child = Command::new(get_runzip_binary())
+        .args(["mount", "--max-entry-size", "1K"])
+        .arg(&zip_path)
+        .arg(&mountpoint)
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let file = mountpoint.join("Отчёт").join("файл.txt");
+    for _ in 0..100 {
+        if file.exists() {
+            break;
+        }
+        if child.try_wait()?.is_some() {
+            // FUSE is not available everywhere tests run
+            let output = child.wait_with_output()?;
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            assert!(stderr.contains("Failed to mount"), "stderr: {stderr}");
+            return Ok(());
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    // Sizes come from the archive, files are unpacked when opened
+    assert_eq!(fs::metadata(&file)?.len(), 5);
+    assert_eq!(fs::read(&file)?, b"hello");
+    assert_eq!(fs::read(mountpoint.join("readme.txt"))?, b"readme");
+    assert_eq!(fs::metadata(mountpoint.join("big.bin"))?.len(), 2048);
+    assert!(fs::read(mountpoint.join("big.bin")).is_err());
+    assert!(fs::write(mountpoint.join("new.txt"), b"new").is_err());
+
+    // Ctrl-C unmounts it
+    Command::new("kill")
+        .arg("-INT")
+        .arg(child.id().to_string())
+        .status()?;
+    let output = child.wait_with_output()?;
+    assert!(output.status.success());
+    assert!(!file.exists());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("--max-entry-size"), "stderr: {stderr}");
+
+    Ok(())
+}
+
+#[test]
+fn test_list_table() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+    let (windows_zip, mac_zip, _) = setup_test_archives(temp_dir.path())?;
+    let original = fs::read(&windows_zip)?;
+
+    let output = Command::new(&binary_path)
+        .arg("list")
+        .arg(&windows_zip)
+        .arg(&mac_zip)
+        .output()?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert!(
+        lines[1].starts_with("#  Size  Method  UTF-8  Raw name"),
+        "stdout: {stdout}"
+    );
+    assert!(
+        lines[3].contains("\\xe2\\xa5\\xe1\\xe2/\\xe2\\xa5\\xe1\\xe2.txt")
+            && lines[3].contains("IBM866")
+            && lines[3].ends_with("тест/тест.txt"),
+        "stdout: {stdout}"
+    );
+    // Only the table is printed, not the progress of a fix
+    assert!(!stdout.contains("WOULD FIX"), "stdout: {stdout}");
+    assert!(
+        stdout.contains("0     0  stored  no     тест/"),
+        "stdout: {stdout}"
+    );
+
+    let output = Command::new(&binary_path)
+        .args(["list", "--show-raw"])
+        .arg(&windows_zip)
+        .output()?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert!(lines[1].contains("Raw name"), "stdout: {stdout}");
+    assert!(lines[1].contains("Hex"), "stdout: {stdout}");
+    assert!(lines[1].contains("Latin-1"), "stdout: {stdout}");
+    assert!(
+        lines[3].contains("e2 a5 e1 e2 2f e2 a5 e1 e2 2e 74 78 74")
+            && lines[3].contains("â¥áâ/â¥áâ.txt"),
+        "stdout: {stdout}"
+    );
+    assert_eq!(fs::read(&windows_zip)?, original);
+
+    Ok(())
+}
+
+#[test]
+fn test_list_zipinfo() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+    let (windows_zip, _, linux_zip) = setup_test_archives(temp_dir.path())?;
+
+    let output = Command::new(&binary_path)
+        .args(["list", "--zipinfo"])
+        .arg(&windows_zip)
+        .arg(&linux_zip)
+        .output()?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains(
+            "Zip file size: 258 bytes, number of entries: 2\n\
+             drwx---     2.0 ntf        0 b- stor 15-Dec-21 09:18 тест/Ещё один пустой каталог/\n\
+             -rw----     2.0 ntf        0 b- stor 15-Dec-21 08:53 тест/тест.txt\n\
+             2 files, 0 bytes uncompressed, 0 bytes compressed:  0.0%\n"
+        ),
+        "stdout: {stdout}"
+    );
+    assert!(
+        stdout.contains("-rw-r--r--  3.0 unx       13 tx stor"),
+        "stdout: {stdout}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_dedupe_entries() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+
+    // The same path stored twice, once in cp866 and once in UTF-8
+    let cp866 = encode_legacy("отчёт.txt", encoding_rs::IBM866);
+    let entries: [(&[u8], &[u8]); 3] = [
+        (&cp866, b"first"),
+        (b"readme.txt", b"readme"),
+        ("отчёт.txt".as_bytes(), b"second"),
+    ];
+    for (mode, kept) in [("keep-first", "first"), ("keep-last", "second")] {
+        let zip_path = temp_dir.path().join(format!("{mode}.zip"));
+        create_raw_zip(&zip_path, &entries)?;
+        let output = Command::new(&binary_path)
+            .args(["--dedupe", mode])
+            .arg(&zip_path)
+            .output()?;
+        assert!(output.status.success());
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(
+            stdout.contains("removing duplicate entries:"),
+            "stdout: {stdout}"
+        );
+
+        let mut archive = ZipArchive::new(File::open(&zip_path)?)?;
+        assert_eq!(archive.len(), 2);
+        let mut contents = String::new();
+        std::io::Read::read_to_string(&mut archive.by_name("отчёт.txt")?, &mut contents)?;
+        assert_eq!(contents, kept);
+    }
+
+    let zip_path = temp_dir.path().join("suffix.zip");
+    create_raw_zip(&zip_path, &entries)?;
+    let output = Command::new(&binary_path)
+        .args(["--dedupe", "suffix"])
+        .arg(&zip_path)
+        .output()?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("Renamed duplicate: \"отчёт.txt\" -> \"отчёт~1.txt\""),
+        "stdout: {stdout}"
+    );
+    let mut archive = ZipArchive::new(File::open(&zip_path)?)?;
+    assert_eq!(archive.len(), 3);
+    for (name, expected) in [("отчёт.txt", "first"), ("отчёт~1.txt", "second")] {
+        let mut contents = String::new();
+        std::io::Read::read_to_string(&mut archive.by_name(name)?, &mut contents)?;
+        assert_eq!(contents, expected);
+    }
+
+    let zip_path = temp_dir.path().join("error.zip");
+    create_raw_zip(&zip_path, &entries)?;
+    let original = fs::read(&zip_path)?;
+    let output = Command::new(&binary_path)
+        .args(["--dedupe", "error"])
+        .arg(&zip_path)
+        .output()?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Entries 0 and 2 are both named \"отчёт.txt\""),
+        "stderr: {stderr}"
+    );
+    assert_eq!(fs::read(&zip_path)?, original);
+
+    Ok(())
+}
+
+#[test]
+fn test_delete_entries() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+    let zip_path = temp_dir.path().join("junk.zip");
+    let cp866 = |name: &str| encode_legacy(name, encoding_rs::IBM866);
+    create_raw_zip(
+        &zip_path,
+        &[
+            (&cp866("фото/Thumbs.db"), b"junk"),
+            (&cp866("фото/море.jpg"), b"jpeg"),
+            (b"__MACOSX/", b""),
+            (&cp866("__MACOSX/фото/._море.jpg"), b"junk"),
+            (b".DS_Store", b"junk"),
+            (b"notes/__MACOSX.txt", b"text"),
+        ],
+    )?;
+
+    let output = Command::new(&binary_path)
+        .args(["--delete", "Thumbs.db", "--delete", "__MACOSX/"])
+        .args(["--delete", ".DS_*"])
+        .arg(&zip_path)
+        .output()?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("  0 \"фото/Thumbs.db\"\n"),
+        "stdout: {stdout}"
+    );
+    assert!(stdout.contains("remove 4 entries"), "stdout: {stdout}");
+    assert_eq!(
+        extract_filenames_from_zip(&zip_path)?,
+        ["фото/море.jpg".as_bytes(), b"notes/__MACOSX.txt"]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_selected_entries() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+    let zip_path = temp_dir.path().join("selected.zip");
+    let cp866 = |name: &str| encode_legacy(name, encoding_rs::IBM866);
+    create_raw_zip(
+        &zip_path,
+        &[
+            (&cp866("a/Альфа.txt"), b"a"),
+            (&cp866("b/Бета.txt"), b"b"),
+            (&cp866("c/Гамма.txt"), b"c"),
+        ],
+    )?;
+
+    let output = Command::new(&binary_path)
+        .args(["--entry", "0", "--entry-name", "c/*"])
+        .arg(&zip_path)
+        .output()?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("OK (not selected)"), "stdout: {stdout}");
+    assert_eq!(
+        extract_filenames_from_zip(&zip_path)?,
+        [
+            "a/Альфа.txt".as_bytes(),
+            &cp866("b/Бета.txt"),
+            "c/Гамма.txt".as_bytes()
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_prefix_operations() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+    let cp866 = |name: &str| encode_legacy(name, encoding_rs::IBM866);
+    let entries = [
+        (cp866("проект/"), b"".as_slice()),
+        (cp866("проект/док/"), b""),
+        (cp866("проект/док/отчёт.txt"), b"report"),
+        (cp866("проект/план.txt"), b"plan"),
+    ];
+    let entries: Vec<(&[u8], &[u8])> = entries
+        .iter()
+        .map(|(name, data)| (name.as_slice(), *data))
+        .collect();
+
+    // By count and by path, the wrapper directory itself is left out
+    for strip in ["1", "проект/"] {
+        let zip_path = temp_dir.path().join("wrapped.zip");
+        create_raw_zip(&zip_path, &entries)?;
+        let output = Command::new(&binary_path)
+            .args(["--strip-prefix", strip])
+            .arg(&zip_path)
+            .output()?;
+        assert!(output.status.success());
+        assert_eq!(
+            extract_filenames_from_zip(&zip_path)?,
+            [
+                "док/".as_bytes(),
+                "док/отчёт.txt".as_bytes(),
+                "план.txt".as_bytes()
+            ]
+        );
+    }
+
+    // The new prefix goes on after the old one is removed
+    let zip_path = temp_dir.path().join("moved.zip");
+    create_raw_zip(&zip_path, &entries)?;
+    let output = Command::new(&binary_path)
+        .args(["--strip-prefix", "1", "--add-prefix", "архив/2024/"])
+        .arg(&zip_path)
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(
+        extract_filenames_from_zip(&zip_path)?,
+        [
+            "архив/2024/док/".as_bytes(),
+            "архив/2024/док/отчёт.txt".as_bytes(),
+            "архив/2024/план.txt".as_bytes()
+        ]
+    );
+
+    // Files outside the directories stripped keep their names
+    let zip_path = temp_dir.path().join("shallow.zip");
+    create_raw_zip(&zip_path, &[(&cp866("файл.txt"), b"text")])?;
+    let output = Command::new(&binary_path)
+        .args(["--strip-prefix", "1"])
+        .arg(&zip_path)
+        .output()?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("not inside enough directories"),
+        "stdout: {stdout}"
+    );
+    assert_eq!(
+        extract_filenames_from_zip(&zip_path)?,
+        ["файл.txt".as_bytes()]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_lang_detection() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+    let zip_path = temp_dir.path().join("languages.zip");
+    let entries = [
+        (
+            encode_legacy("Ελληνικά έγγραφα.txt", encoding_rs::WINDOWS_1253),
+            b"el".as_slice(),
+        ),
+        (
+            encode_legacy("日本語のファイル.txt", encoding_rs::SHIFT_JIS),
+            b"ja",
+        ),
+        (
+            encode_legacy("Привет мир.txt", encoding_rs::WINDOWS_1251),
+            b"ru",
+        ),
+    ];
+    let entries: Vec<(&[u8], &[u8])> = entries
+        .iter()
+        .map(|(name, data)| (name.as_slice(), *data))
+        .collect();
+
+    // Only the names in the language given are converted
+    create_raw_zip(&zip_path, &entries)?;
+    let output = Command::new(&binary_path)
+        .args(["--lang", "ja"])
+        .arg(&zip_path)
+        .output()?;
+    assert!(output.status.success());
+    let names = extract_filenames_from_zip(&zip_path)?;
+    assert_eq!(names[0], entries[0].0);
+    assert_eq!(names[1], "日本語のファイル.txt".as_bytes());
+    assert_eq!(names[2], entries[2].0);
+
+    create_raw_zip(&zip_path, &entries)?;
+    let output = Command::new(&binary_path)
+        .args(["--lang", "auto"])
+        .arg(&zip_path)
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(
+        extract_filenames_from_zip(&zip_path)?,
+        [
+            "Ελληνικά έγγραφα.txt".as_bytes(),
+            "日本語のファイル.txt".as_bytes(),
+            "Привет мир.txt".as_bytes()
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_south_slavic_langs() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+    let zip_path = temp_dir.path().join("south_slavic.zip");
+    let serbian = encode_legacy("Његош/Горски вијенац.doc", encoding_rs::WINDOWS_1251);
+    let macedonian = encode_legacy("Ќерка на ѓаволот.txt", encoding_rs::WINDOWS_1251);
+    let dos = encode_legacy("Документы.txt", encoding_rs::IBM866);
+
+    // Serbian names are only found in Windows-1251, so CP866 is left alone
+    create_raw_zip(
+        &zip_path,
+        &[(&serbian, b"sr"), (&macedonian, b"mk"), (&dos, b"ru")],
+    )?;
+    let output = Command::new(&binary_path)
+        .args(["--lang", "sr"])
+        .arg(&zip_path)
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(
+        extract_filenames_from_zip(&zip_path)?,
+        [
+            "Његош/Горски вијенац.doc".as_bytes(),
+            "Ќерка на ѓаволот.txt".as_bytes(),
+            &dos
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_ukrainian_code_pages() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+    let zip_path = temp_dir.path().join("ukrainian.zip");
+    let cp1125 = |name: &str| -> Vec<u8> {
+        name.chars()
+            .map(|c| match c {
+                'Є' => 0xF4,
+                'і' => 0xF7,
+                'ї' => 0xF9,
+                c => encode_legacy(&c.to_string(), encoding_rs::IBM866)[0],
+            })
+            .collect()
+    };
+    let entries = [
+        cp1125("Київ/Фото.jpg"),
+        cp1125("Звіт за рік.doc"),
+        cp1125("Євген.txt"),
+        encode_legacy("Їжак і білка.txt", encoding_rs::KOI8_U),
+        encode_legacy("Документи.txt", encoding_rs::IBM866),
+    ];
+    let entries: Vec<(&[u8], &[u8])> = entries
+        .iter()
+        .map(|name| (name.as_slice(), b"uk".as_slice()))
+        .collect();
+
+    create_raw_zip(&zip_path, &entries)?;
+    let output = Command::new(&binary_path)
+        .args(["--lang", "uk"])
+        .arg(&zip_path)
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(
+        extract_filenames_from_zip(&zip_path)?,
+        [
+            "Київ/Фото.jpg".as_bytes(),
+            "Звіт за рік.doc".as_bytes(),
+            "Євген.txt".as_bytes(),
+            "Їжак і білка.txt".as_bytes(),
+            "Документи.txt".as_bytes()
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_belarusian_names() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+    let zip_path = temp_dir.path().join("belarusian.zip");
+    let minsk = encode_legacy("Беларусь/Мінск.jpg", encoding_rs::WINDOWS_1251);
+    let diary = encode_legacy("Ўсё пра ўсё.txt", encoding_rs::WINDOWS_1251);
+    let dos = encode_legacy("Дакументы.txt", encoding_rs::IBM866);
+
+    create_raw_zip(
+        &zip_path,
+        &[(&minsk, b"be"), (&diary, b"be"), (&dos, b"be")],
+    )?;
+    let output = Command::new(&binary_path)
+        .args(["--lang", "be"])
+        .arg(&zip_path)
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(
+        extract_filenames_from_zip(&zip_path)?,
+        [
+            "Беларусь/Мінск.jpg".as_bytes(),
+            "Ўсё пра ўсё.txt".as_bytes(),
+            "Дакументы.txt".as_bytes()
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_kazakh_code_pages() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+    let zip_path = temp_dir.path().join("kazakh.zip");
+    // Kazakh letters where KZ-1048 and PTCP154 have them, the rest as in
+    // Windows-1251
+    let encode = |name: &str, kazakh: &[(char, u8)]| -> Vec<u8> {
+        name.chars()
+            .map(|c| match kazakh.iter().find(|(letter, _)| *letter == c) {
+                Some(&(_, byte)) => byte,
+                None => encode_legacy(&c.to_string(), encoding_rs::WINDOWS_1251)[0],
+            })
+            .collect()
+    };
+    let kz_1048 = [('Қ', 0x8D), ('қ', 0x9D), ('ұ', 0xA2), ('ғ', 0xBA)];
+    let ptcp154 = [('Ә', 0xAA), ('Ө', 0xA4), ('ң', 0x9C)];
+    let entries = [
+        encode("Қазақстан/Алматы.jpg", &kz_1048),
+        encode("Мұғалім.doc", &kz_1048),
+        encode("Әлем.txt", &ptcp154),
+        encode("Өлең жолдары.txt", &ptcp154),
+    ];
+    let entries: Vec<(&[u8], &[u8])> = entries
+        .iter()
+        .map(|name| (name.as_slice(), b"kk".as_slice()))
+        .collect();
+
+    create_raw_zip(&zip_path, &entries)?;
+    let output = Command::new(&binary_path)
+        .args(["--lang", "kk"])
+        .arg(&zip_path)
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(
+        extract_filenames_from_zip(&zip_path)?,
+        [
+            "Қазақстан/Алматы.jpg".as_bytes(),
+            "Мұғалім.doc".as_bytes(),
+            "Әлем.txt".as_bytes(),
+            "Өлең жолдары.txt".as_bytes()
+        ]
+    );
+
+    // Kazakh letters are transliterated too
+    create_raw_zip(&zip_path, &entries[..2])?;
+    let output = Command::new(&binary_path)
+        .args(["-s", "kz-1048", "--target", "translit"])
+        .arg(&zip_path)
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(
+        extract_filenames_from_zip(&zip_path)?,
+        [b"Qazaqstan/Almaty.jpg".as_slice(), b"Mughalim.doc"]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_confidence() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+    let zip_path = temp_dir.path().join("confidence.zip");
+    let report_path = temp_dir.path().join("report.csv");
+    let dos = encode_legacy("Документы/отчёт.txt", encoding_rs::IBM866);
+    let windows = encode_legacy("Привет мир.txt", encoding_rs::WINDOWS_1251);
+    create_raw_zip(
+        &zip_path,
+        &[
+            (&dos, b"dos"),
+            (&windows, b"windows"),
+            (b"readme.txt", b"ascii"),
+        ],
+    )?;
+
+    let output = Command::new(&binary_path)
+        .args(["--dry-run", "--report"])
+        .arg(&report_path)
+        .arg(&zip_path)
+        .output()?;
+    assert!(output.status.success());
+    let report = fs::read_to_string(&report_path)?;
+    let confidences: Vec<f64> = report
+        .lines()
+        .skip(1)
+        .map(|line| line.split(',').nth(4).unwrap().parse().unwrap())
+        .collect();
+    assert_eq!(confidences.len(), 3, "report: {report}");
+    assert!(confidences[0] > 0.9, "report: {report}");
+    assert!(confidences[1] > 0.5, "report: {report}");
+    assert!(
+        (confidences[2] - 1.0).abs() < f64::EPSILON,
+        "report: {report}"
+    );
+
+    let output = Command::new(&binary_path)
+        .arg("list")
+        .arg(&zip_path)
+        .output()?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Confidence"), "stdout: {stdout}");
+
+    Ok(())
+}
+
+#[test]
+fn test_interactive_on_ambiguous() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+    let zip_path = temp_dir.path().join("ambiguous.zip");
+    let clear = encode_legacy("Документы.txt", encoding_rs::IBM866);
+    create_raw_zip(
+        &zip_path,
+        &[(&clear, b"clear"), (b"\xf9\xec\xe5\xed.txt", b"?")],
+    )?;
+
+    // Only the name that reads as badly in every encoding is asked about
+    let mut child = Command::new(&binary_path)
+        .args(["--yes", "--interactive-on-ambiguous"])
+        .arg(&zip_path)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .spawn()?;
+    std::io::Write::write_all(&mut child.stdin.take().unwrap(), b"4\n")?;
+    let output = child.wait_with_output()?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(
+        stdout.matches("is ambiguous").count(),
+        1,
+        "stdout: {stdout}"
+    );
+    assert!(stdout.contains("4. ЫЛЕМ.txt (KOI8-R"), "stdout: {stdout}");
+    assert_eq!(
+        extract_filenames_from_zip(&zip_path)?,
+        ["Документы.txt".as_bytes(), "ЫЛЕМ.txt".as_bytes()]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_repair_mojibake() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+    let zip_path = temp_dir.path().join("mojibake.zip");
+    let report_path = temp_dir.path().join("report.csv");
+    // UTF-8 names misread in Windows-1252 and in CP437
+    let entries: [(&[u8], &[u8]); 3] = [
+        ("Ð¿Ñ€Ð¸Ð²ÐµÑ‚.txt".as_bytes(), b"hello"),
+        ("╨╛╤é╤ç╤æ╤é.doc".as_bytes(), b"report"),
+        ("Ñandú.txt".as_bytes(), b"bird"),
+    ];
+
+    create_raw_zip(&zip_path, &entries)?;
+    let output = Command::new(&binary_path)
+        .arg("--keep-mojibake")
+        .arg(&zip_path)
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(extract_filenames_from_zip(&zip_path)?, entries.map(|e| e.0));
+
+    // That run flagged the names as UTF-8, which they are taken for then
+    create_raw_zip(&zip_path, &entries)?;
+    let output = Command::new(&binary_path)
+        .arg("--report")
+        .arg(&report_path)
+        .arg(&zip_path)
+        .output()?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("привет.txt: FIXED (UTF-8 misread as windows-1252 -> UTF-8)"),
+        "stdout: {stdout}"
+    );
+    // A Latin name that only looks like mojibake is kept
+    assert_eq!(
+        extract_filenames_from_zip(&zip_path)?,
+        [
+            "привет.txt".as_bytes(),
+            "отчёт.doc".as_bytes(),
+            "Ñandú.txt".as_bytes()
+        ]
+    );
+    let report = fs::read_to_string(&report_path)?;
+    assert!(
+        report.contains(",UTF-8 misread as IBM437,1.00,отчёт.doc,fixed"),
+        "report: {report}"
+    );
+
+    // Misread in Windows-1251 the names stay Cyrillic, while real words
+    // are kept
+    create_raw_zip(
+        &zip_path,
+        &[
+            ("РїСЂРёРІРµС‚/".as_bytes(), b""),
+            ("Сергей Рахманинов.txt".as_bytes(), b"bio"),
+        ],
+    )?;
+    let output = Command::new(&binary_path).arg(&zip_path).output()?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("(UTF-8 misread as windows-1251 -> UTF-8)"),
+        "stdout: {stdout}"
+    );
+    assert_eq!(
+        extract_filenames_from_zip(&zip_path)?,
+        ["привет/".as_bytes(), "Сергей Рахманинов.txt".as_bytes()]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_mojibake_real_names() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+    let zip_path = temp_dir.path().join("names.zip");
+    let options = FixOptions::default();
+
+    // Russian, Ukrainian and Belarusian names with "Р" or "С" before "і",
+    // "ї", "ў" or "ё", which read as UTF-8 are other Cyrillic letters
+    for name in [
+        "Сі.txt",
+        "Рі.txt",
+        "СІРІ.txt",
+        "СІРІ РЇ.doc",
+        "Сї Рє.txt",
+        "РЎ СЎ.txt",
+        "Сёння.txt",
+        "Сёмга и Рёва.txt",
+        "Сірий кіт.jpg",
+    ] {
+        assert_eq!(
+            runzip::convert_name(name.as_bytes(), &options),
+            (name.as_bytes().to_vec(), Action::Unchanged),
+            "{name}"
+        );
+    }
+
+    // Names flagged as UTF-8 are what the archiver was given
+    create_test_zip(&zip_path, &[("РїСЂРёРІРµС‚/", b"")])?;
+    let output = Command::new(&binary_path).arg(&zip_path).output()?;
+    assert!(output.status.success());
+    assert_eq!(
+        extract_filenames_from_zip(&zip_path)?,
+        ["РїСЂРёРІРµС‚/".as_bytes()]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_normalize_names() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+    let zip_path = temp_dir.path().join("names.zip");
+
+    create_raw_zip(
+        &zip_path,
+        &[(&encode_legacy("йод.txt", encoding_rs::IBM866), b"text")],
+    )?;
+    let output = Command::new(&binary_path)
+        .args(["--normalize", "nfd"])
+        .arg(&zip_path)
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(
+        extract_filenames_from_zip(&zip_path)?,
+        ["и\u{306}од.txt".as_bytes()]
+    );
+
+    // Names already in UTF-8 are normalized too
+    create_test_zip(&zip_path, &[("ﬁнал²", b"text")])?;
+    let output = Command::new(&binary_path)
+        .args(["--normalize", "nfkc"])
+        .arg(&zip_path)
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(
+        extract_filenames_from_zip(&zip_path)?,
+        ["fiнал2".as_bytes()]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_case_folding() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+    let zip_path = temp_dir.path().join("dos.zip");
+    let cp866 = |name: &str| encode_legacy(name, encoding_rs::IBM866);
+    let entries = [
+        (cp866("ДОКУМЕНТ/"), b"".as_slice()),
+        (cp866("ДОКУМЕНТ/ОТЧЁТ.TXT"), b"report"),
+        (b"README.TXT".to_vec(), b"readme"),
+    ];
+    let entries: Vec<(&[u8], &[u8])> = entries
+        .iter()
+        .map(|(name, data)| (name.as_slice(), *data))
+        .collect();
+
+    create_raw_zip(&zip_path, &entries)?;
+    let output = Command::new(&binary_path)
+        .args(["--case", "lower", "--add-prefix", "Архив"])
+        .arg(&zip_path)
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(
+        extract_filenames_from_zip(&zip_path)?,
+        [
+            "Архив/документ/".as_bytes(),
+            "Архив/документ/отчёт.txt".as_bytes(),
+            "Архив/readme.txt".as_bytes()
+        ]
+    );
+
+    // The legacy target encoding has both cases too
+    create_raw_zip(&zip_path, &entries)?;
+    let output = Command::new(&binary_path)
+        .args(["--case", "lower", "--target", "cp866"])
+        .arg(&zip_path)
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(
+        extract_filenames_from_zip(&zip_path)?,
+        [
+            cp866("документ/"),
+            cp866("документ/отчёт.txt"),
+            b"readme.txt".to_vec()
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_sort_entries() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+    let zip_path = temp_dir.path().join("unsorted.zip");
+    let names = |zip_path: &Path| -> Result<Vec<String>> {
+        Ok(extract_filenames_from_zip(zip_path)?
+            .into_iter()
+            .map(|name| String::from_utf8_lossy(&name).into_owned())
+            .collect())
+    };
+
+    // Names that need no fixing are still reordered
+    create_test_zip(
+        &zip_path,
+        &[
+            ("b/z.txt", b"1"),
+            ("a-b.txt", b"2"),
+            ("a/", b""),
+            ("b/", b""),
+            ("a/y.txt", b"3"),
+        ],
+    )?;
+    let output = Command::new(&binary_path)
+        .args(["--sort", "path"])
+        .arg(&zip_path)
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(
+        names(&zip_path)?,
+        ["a/", "a/y.txt", "a-b.txt", "b/", "b/z.txt"]
+    );
+
+    let output = Command::new(&binary_path)
+        .args(["--sort", "name"])
+        .arg(&zip_path)
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(
+        names(&zip_path)?,
+        ["a/", "a-b.txt", "b/", "a/y.txt", "b/z.txt"]
+    );
+
+    // Sorted archives are left alone
+    let output = Command::new(&binary_path)
+        .args(["--sort", "name"])
+        .arg(&zip_path)
+        .output()?;
+    assert!(String::from_utf8_lossy(&output.stdout).contains("nothing to do"));
+
+    Ok(())
+}
+
+#[test]
+fn test_print_hash() -> Result<()> {
+    use sha2::{Digest, Sha256};
+
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+    let (windows_zip, _, _) = setup_test_archives(temp_dir.path())?;
+    let sha256 = |path: &Path| -> Result<String> {
+        Ok(Sha256::digest(fs::read(path)?)
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect())
+    };
+    let original = sha256(&windows_zip)?;
+
+    let output = Command::new(&binary_path)
+        .args(["--print-hash", "sha256", "--print-hash-original"])
+        .arg(&windows_zip)
+        .output()?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let fixed = sha256(&windows_zip)?;
+    assert_ne!(original, fixed);
+    assert!(
+        stdout.ends_with(&format!(
+            "Original SHA256 ({path}) = {original}\nSHA256 ({path}) = {fixed}\n",
+            path = windows_zip.display()
+        )),
+        "stdout: {stdout}"
+    );
+
+    // A dry run writes no archive to hash
+    let output = Command::new(&binary_path)
+        .args(["-n", "--print-hash", "sha256"])
+        .arg(&windows_zip)
+        .output()?;
+    assert!(!output.status.success());
+
+    Ok(())
+}
+
+#[test]
+fn test_diff_archives() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+    let (windows_zip, _, _) = setup_test_archives(temp_dir.path())?;
+    let original = temp_dir.path().join("original.zip");
+    fs::copy(&windows_zip, &original)?;
+    assert!(run_runzip(&binary_path, &[&windows_zip])?.status.success());
+
+    // A fixed archive differs from the original only in the stored names
+    let output = Command::new(&binary_path)
+        .arg("diff")
+        .arg(&original)
+        .arg(&windows_zip)
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "re-encoded  тест/Ещё один пустой каталог/ (IBM866 -> UTF-8)\n\
+         re-encoded  тест/тест.txt (IBM866 -> UTF-8)\n\
+         0 added, 0 removed, 0 renamed, 0 modified, 2 re-encoded\n"
+    );
+
+    let old_zip = temp_dir.path().join("old.zip");
+    let new_zip = temp_dir.path().join("new.zip");
+    create_test_zip(
+        &old_zip,
+        &[("один.txt", b"1"), ("два.txt", b"2"), ("три.txt", b"3")],
+    )?;
+    create_test_zip(
+        &new_zip,
+        &[
+            ("один.txt", b"one"),
+            ("второй.txt", b"2"),
+            ("четыре.txt", b"4"),
+        ],
+    )?;
+    let output = Command::new(&binary_path)
+        .arg("diff")
+        .arg(&old_zip)
+        .arg(&new_zip)
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "modified    один.txt\n\
+         renamed     два.txt -> второй.txt\n\
+         removed     три.txt\n\
+         added       четыре.txt\n\
+         1 added, 1 removed, 1 renamed, 1 modified, 0 re-encoded\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_batch_manifest() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+    let dir = temp_dir.path();
+    fs::create_dir_all(dir.join("in/odd"))?;
+    let (windows_zip, _, _) = setup_test_archives(dir)?;
+    fs::copy(&windows_zip, dir.join("in/windows.zip"))?;
+    fs::copy(&windows_zip, dir.join("in/odd/windows.zip"))?;
+    let original = fs::read(&windows_zip)?;
+    fs::write(
+        dir.join("jobs.yaml"),
+        "report: report.csv\n\
+         jobs:\n\
+         \x20 - input: in\n\
+         \x20   output: out\n\
+         \x20   overrides:\n\
+         \x20     - archive: in/odd/windows.zip\n\
+         \x20       source: windows-1251\n\
+         \x20 - input: windows-archive.zip\n\
+         \x20   target: translit\n",
+    )?;
+
+    let output = Command::new(&binary_path)
+        .arg("batch")
+        .arg(dir.join("jobs.yaml"))
+        .output()?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.ends_with("2 jobs: 3 archives, 6 entries renamed, 0 failed\n"),
+        "stdout: {stdout}"
+    );
+
+    // The inputs of a job with an output directory are left alone
+    assert_eq!(fs::read(dir.join("in/windows.zip"))?, original);
+    let names = |path: &Path| -> Result<Vec<String>> {
+        Ok(extract_filenames_from_zip(path)?
+            .into_iter()
+            .map(|name| String::from_utf8_lossy(&name).into_owned())
+            .collect())
+    };
+    assert_eq!(
+        names(&dir.join("out/windows.zip"))?,
+        ["тест/Ещё один пустой каталог/", "тест/тест.txt"]
+    );
+    assert_ne!(names(&dir.join("out/odd/windows.zip"))?[1], "тест/тест.txt");
+    assert_eq!(
+        names(&windows_zip)?,
+        ["test/Eshchyo odin pustoy katalog/", "test/test.txt"]
+    );
+
+    let report = fs::read_to_string(dir.join("report.csv"))?;
+    assert_eq!(report.lines().count(), 7, "report: {report}");
+    assert!(report.contains(",windows-1251,"), "report: {report}");
+
+    // Fixed copies are never overwritten
+    let output = Command::new(&binary_path)
+        .arg("batch")
+        .arg(dir.join("jobs.yaml"))
+        .output()?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("already exists"), "stderr: {stderr}");
+
+    Ok(())
+}
+
+#[test]
+fn test_stats() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+    let corpus = temp_dir.path().join("corpus");
+    fs::create_dir_all(corpus.join("nested"))?;
+    setup_test_archives(&corpus)?;
+    fs::rename(
+        corpus.join("linux-archive.zip"),
+        corpus.join("nested/linux-archive.zip"),
+    )?;
+    // Only .zip files are counted when searching a directory
+    fs::write(corpus.join("readme.txt"), "not an archive")?;
+
+    let output = Command::new(&binary_path)
+        .arg("stats")
+        .arg(&corpus)
+        .output()?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with("3 archives\n"), "stdout: {stdout}");
+    assert!(
+        stdout.contains(
+            "Encoding  Archives  Share\n\
+             UTF-8            2  66.7%\n\
+             IBM866           1  33.3%\n"
+        ),
+        "stdout: {stdout}"
+    );
+    assert!(
+        stdout.contains(
+            "Made on       Archives  Share\n\
+             Unix                 2  66.7%\n\
+             Windows NTFS         1  33.3%\n"
+        ),
+        "stdout: {stdout}"
+    );
+    assert!(
+        stdout.contains(
+            "Decade  Archives  Share\n\
+             2010s          2  66.7%\n\
+             2020s          1  33.3%\n"
+        ),
+        "stdout: {stdout}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_shell_completions() -> Result<()> {
+    let binary_path = get_runzip_binary();
+
+    for shell in ["bash", "zsh", "fish", "powershell", "elvish"] {
+        let output = Command::new(&binary_path)
+            .args(["completions", shell])
+            .output()?;
+        assert!(
+            output.status.success(),
+            "{shell} completions should succeed"
+        );
+        let script = String::from_utf8_lossy(&output.stdout);
+        assert!(
+            script.contains("runzip") && script.contains("dry-run"),
+            "{shell}: {script}"
+        );
+    }
+
+    let output = Command::new(&binary_path)
+        .args(["completions", "tcsh"])
+        .output()?;
+    assert!(!output.status.success());
+
+    Ok(())
+}
+
+#[test]
+fn test_man_pages() -> Result<()> {
+    let binary_path = get_runzip_binary();
+
+    let output = Command::new(&binary_path).arg("man").output()?;
+    assert!(output.status.success());
+    let page = String::from_utf8_lossy(&output.stdout);
+    assert!(page.contains(".TH runzip 1"), "page: {page}");
+    assert!(page.contains("dry\\-run"), "page: {page}");
+
+    let output = Command::new(&binary_path)
+        .args(["man", "extract"])
+        .output()?;
+    assert!(output.status.success());
+    let page = String::from_utf8_lossy(&output.stdout);
+    assert!(page.contains(".TH runzip-extract 1"), "page: {page}");
+    assert!(page.contains("runzip extract"), "page: {page}");
+
+    let output = Command::new(&binary_path).args(["man", "bogus"]).output()?;
+    assert!(!output.status.success());
+
+    // The command is meant for packagers and stays out of the help
+    let output = Command::new(&binary_path).arg("--help").output()?;
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("  man "));
+
+    Ok(())
+}
+
+#[test]
+fn test_config_file() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+    let (windows_zip, _, _) = setup_test_archives(temp_dir.path())?;
+    let config_dir = temp_dir.path().join("config");
+    fs::create_dir_all(config_dir.join("runzip"))?;
+    let config_path = config_dir.join("runzip").join("config.toml");
+    let list = |args: &[&str]| {
+        Command::new(&binary_path)
+            .env_remove("RUNZIP_CONFIG")
+            .env("XDG_CONFIG_HOME", &config_dir)
+            .arg("list")
+            .args(args)
+            .arg(&windows_zip)
+            .output()
+    };
+
+    fs::write(&config_path, "target = \"translit\"\n")?;
+    let output = list(&[])?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("test/test.txt"), "stdout: {stdout}");
+
+    // Command line options take precedence
+    let output = list(&["--target", "utf-8"])?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("тест/тест.txt"), "stdout: {stdout}");
+
+    // Names detected as an encoding missing from the candidates are left alone
+    fs::write(&config_path, "candidates = [\"koi8-r\"]\n")?;
+    let output = list(&[])?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!stdout.contains("тест/тест.txt"), "stdout: {stdout}");
+    let output = list(&["--source", "cp866"])?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("тест/тест.txt"), "stdout: {stdout}");
+
+    for invalid in [
+        "colour = true\n",
+        "target = \"latin\"\n",
+        "backup = \"always\"\n",
+        "candidates = [\"x\"]\n",
+    ] {
+        fs::write(&config_path, invalid)?;
+        let output = list(&[])?;
+        assert!(!output.status.success(), "{invalid}");
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("config.toml"), "stderr: {stderr}");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_config_backup_and_color() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+    let (windows_zip, _, _) = setup_test_archives(temp_dir.path())?;
+    let original = fs::read(&windows_zip)?;
+    let config_dir = temp_dir.path().join("config");
+    fs::create_dir_all(config_dir.join("runzip"))?;
+    let config_path = config_dir.join("runzip").join("config.toml");
+    let runzip = |args: &[&OsStr]| {
+        Command::new(&binary_path)
+            .env_remove("RUNZIP_CONFIG")
+            .env_remove("NO_COLOR")
+            .env("XDG_CONFIG_HOME", &config_dir)
+            .args(args)
+            .output()
+    };
+    let fix = |args: &[&str]| -> Result<()> {
+        fs::write(&windows_zip, &original)?;
+        let mut all: Vec<&OsStr> = vec![OsStr::new("fix"), OsStr::new("--no-rename-map")];
+        all.extend(args.iter().map(OsStr::new));
+        all.push(windows_zip.as_os_str());
+        assert!(runzip(&all)?.status.success());
+        Ok(())
+    };
+    let backup = |suffix: &str| {
+        let mut name = windows_zip.clone().into_os_string();
+        name.push(suffix);
+        PathBuf::from(name)
+    };
+
+    fs::write(&config_path, "backup = \"simple\"\n")?;
+    fix(&[])?;
+    assert_eq!(fs::read(backup("~"))?, original);
+    fs::remove_file(backup("~"))?;
+
+    // Command line options take precedence
+    fix(&["--backup", "none"])?;
+    assert!(!backup("~").exists());
+    fix(&["--backup", "numbered"])?;
+    fix(&["--backup", "numbered"])?;
+    assert_eq!(fs::read(backup(".~1~"))?, original);
+    assert_eq!(fs::read(backup(".~2~"))?, original);
+    assert!(!backup("~").exists());
+
+    let missing = temp_dir.path().join("missing.zip");
+    fs::write(&config_path, "color = \"always\"\n")?;
+    let output = runzip(&[OsStr::new("list"), missing.as_os_str()])?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.starts_with("\x1b[1;31mError\x1b[0m processing"),
+        "stderr: {stderr}"
+    );
+    let output = runzip(&[
+        OsStr::new("list"),
+        OsStr::new("--color"),
+        OsStr::new("never"),
+        missing.as_os_str(),
+    ])?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.starts_with("Error processing"), "stderr: {stderr}");
+
+    // Not a terminal, so no colors by default
+    fs::remove_file(&config_path)?;
+    let output = runzip(&[OsStr::new("list"), missing.as_os_str()])?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.starts_with("Error processing"), "stderr: {stderr}");
+
+    Ok(())
+}
+
+#[test]
+fn test_host_system_hint() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+
+    // Too short for chardetng to tell apart, so the system the archive was
+    // made on decides
+    let zip_made_on = |name: &[u8], host: u8| -> Result<PathBuf> {
+        let zip_path = temp_dir.path().join(format!("{host}-{}.zip", name.len()));
+        create_raw_zip(&zip_path, &[(name, b"")])?;
+        let mut data = fs::read(&zip_path)?;
+        let central = data
+            .windows(4)
+            .position(|window| window == 0x0201_4b50u32.to_le_bytes())
+            .expect("central directory");
+        data[central + 5] = host;
+        fs::write(&zip_path, data)?;
+        Ok(zip_path)
+    };
+    let cp866 = encode_legacy("акт.txt", encoding_rs::IBM866);
+    let koi8 = encode_legacy("акт.txt", encoding_rs::KOI8_R);
+
+    for (name, host, expected) in [
+        (&cp866, 0, "IBM866"),
+        (&cp866, 11, "IBM866"),
+        (&koi8, 3, "KOI8-R"),
+    ] {
+        let zip_path = zip_made_on(name, host)?;
+        let output = Command::new(&binary_path)
+            .arg("list")
+            .arg(&zip_path)
+            .output()?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(
+            stdout.contains(expected) && stdout.contains("акт.txt"),
+            "host {host}: {stdout}"
+        );
+    }
+
+    // Without a recorded host the names are left alone
+    let zip_path = zip_made_on(&cp866, 10)?;
+    let output = Command::new(&binary_path)
+        .arg("list")
+        .arg(&zip_path)
+        .output()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!stdout.contains("акт.txt"), "stdout: {stdout}");
+
+    Ok(())
+}
+
+#[test]
+fn test_cp437_names() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let zip_path = temp_dir.path().join("western.zip");
+
+    // Western names from a DOS archiver, in CP437 as APPNOTE says, next to a
+    // Russian one; encoding_rs has no CP437, so the bytes are spelled out
+    let cp866 = encode_legacy("Отчёт.txt", encoding_rs::IBM866);
+    create_raw_zip(
+        &zip_path,
+        &[
+            (b"Caf\x82.txt", b""),
+            (b"M\x81ller Bericht.doc", b""),
+            (b"Se\xa4or Garc\xa1a.txt", b""),
+            (&cp866, b""),
+        ],
+    )?;
+
+    let output = Command::new(get_runzip_binary()).arg(&zip_path).output()?;
+    assert!(
+        output.status.success(),
+        "runzip should succeed. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(
+        extract_filenames_from_zip(&zip_path)?,
+        [
+            "Café.txt",
+            "Müller Bericht.doc",
+            "Señor García.txt",
+            "Отчёт.txt"
+        ]
+        .map(|name| name.as_bytes().to_vec())
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_content_hints() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+    let text = "Сыр и чай для дачи. Список покупок на неделю: хлеб, молоко, \
+                масло, яйца, картофель, капуста, морковь и яблоки.";
+
+    // Both code pages decode these names to Cyrillic, and chardetng picks
+    // the wrong one; the text file packed with them decides
+    for (name, encoding) in [
+        ("сыр", encoding_rs::IBM866),
+        ("чай", encoding_rs::WINDOWS_1251),
+    ] {
+        let zip_path = temp_dir.path().join(format!("{}.zip", encoding.name()));
+        let raw_name = encode_legacy(name, encoding);
+        let contents = encode_legacy(text, encoding);
+        create_raw_zip(&zip_path, &[(&raw_name, b""), (b"readme.txt", &contents)])?;
+
+        let fix = |content_hints: bool| -> Result<String> {
+            let mut command = Command::new(&binary_path);
+            command.arg("--dry-run").arg(&zip_path);
+            if content_hints {
+                command.arg("--content-hints");
+            }
+            Ok(String::from_utf8_lossy(&command.output()?.stdout).into_owned())
+        };
+        let stdout = fix(false)?;
+        assert!(!stdout.contains(name), "{name}: {stdout}");
+        let stdout = fix(true)?;
+        assert!(
+            stdout.contains(&format!("{name}: WOULD FIX")),
+            "{name}: {stdout}"
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_fix_contents() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let zip_path = temp_dir.path().join("contents.zip");
+    let binary_path = get_runzip_binary();
+    let text = "Годовой отчёт по продажам за прошлый квартал\r\n".repeat(20);
+    let table = "Фамилия;Имя;Отдел\r\nИванов;Пётр;Бухгалтерия\r\n";
+    let legacy_text = encode_legacy(&text, encoding_rs::WINDOWS_1251);
+    let legacy_table = encode_legacy(table, encoding_rs::WINDOWS_1251);
+    create_raw_zip(
+        &zip_path,
+        &[
+            (
+                &encode_legacy("Отчёт.txt", encoding_rs::WINDOWS_1251),
+                &legacy_text,
+            ),
+            (b"staff.CSV", &legacy_table),
+            (b"notes.txt", "Уже в UTF-8".as_bytes()),
+            (b"report.doc", &legacy_text),
+        ],
+    )?;
+
+    let output = Command::new(&binary_path)
+        .args(["--dry-run", "--fix-contents", "txt,.csv"])
+        .arg(&zip_path)
+        .output()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("staff.CSV: contents WOULD CONVERT"),
+        "{stdout}"
+    );
+
+    let output = Command::new(&binary_path)
+        .args(["--fix-contents", "txt,.csv"])
+        .arg(&zip_path)
+        .output()?;
+    assert!(
+        output.status.success(),
+        "runzip --fix-contents should succeed. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let contents: Vec<Vec<u8>> = extract_modes_and_contents(&zip_path)?
+        .into_iter()
+        .map(|(_, contents)| contents)
+        .collect();
+    assert_eq!(contents[0], text.as_bytes());
+    assert_eq!(contents[1], table.as_bytes());
+    assert_eq!(contents[2], "Уже в UTF-8".as_bytes());
+    assert_eq!(
+        contents[3], legacy_text,
+        "Other entries keep their contents"
+    );
+    let names = extract_filenames_from_zip(&zip_path)?;
+    assert_eq!(names[0], "Отчёт.txt".as_bytes());
+
+    Ok(())
+}
+
+#[test]
+fn test_unchanged_archive_not_rewritten() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+    let zip_path = temp_dir.path().join("ascii.zip");
+    create_test_zip(&zip_path, &[("docs/", b""), ("docs/readme.txt", b"hello")])?;
+    // A stub in front of the archive makes any rewrite show
+    let mut data = b"#!stub\n".to_vec();
+    data.extend(fs::read(&zip_path)?);
+    fs::write(&zip_path, &data)?;
+
+    let output = run_runzip(&binary_path, &[&zip_path])?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("ascii.zip: nothing to do"),
+        "stdout: {stdout}"
+    );
+    assert_eq!(fs::read(&zip_path)?, data);
+    assert_eq!(fs::read_dir(temp_dir.path())?.count(), 1);
+
+    // Archives with names to fix are still rewritten
+    let (windows_zip, _, _) = setup_test_archives(temp_dir.path())?;
+    let output = run_runzip(&binary_path, &[&windows_zip])?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!stdout.contains("nothing to do"), "stdout: {stdout}");
+
+    Ok(())
+}
+
+#[test]
+fn test_detection_shared_by_directory() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+    let zip_path = temp_dir.path().join("photos.zip");
+    let dir = encode_legacy("Фотографии с дачи/", encoding_rs::IBM866);
+    let mut names = vec![dir.clone()];
+    for i in 0..50 {
+        let mut name = dir.clone();
+        name.extend_from_slice(format!("2019/IMG_{i:04}.jpg").as_bytes());
+        names.push(name);
+    }
+    let entries: Vec<(&[u8], &[u8])> = names.iter().map(|name| (&name[..], &b""[..])).collect();
+    create_raw_zip(&zip_path, &entries)?;
+
+    let output = Command::new(&binary_path)
+        .args(["-v", "-n"])
+        .arg(&zip_path)
+        .output()?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.matches("chardetng detected").count(), 1, "{stdout}");
+    assert_eq!(stdout.matches("Same as").count(), 50, "{stdout}");
+
+    run_runzip(&binary_path, &[&zip_path])?;
+    let fixed = extract_filenames_from_zip(&zip_path)?;
+    assert_eq!(fixed[0], "Фотографии с дачи/".as_bytes());
+    assert_eq!(fixed[50], "Фотографии с дачи/2019/IMG_0049.jpg".as_bytes());
+
+    Ok(())
+}
+
+#[test]
+fn test_memory_mapped_reading() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+    let (windows_zip, mac_zip, _) = setup_test_archives(temp_dir.path())?;
+    let buffered_zip = temp_dir.path().join("buffered.zip");
+    fs::copy(&windows_zip, &buffered_zip)?;
+
+    let output = Command::new(&binary_path)
+        .arg("--mmap")
+        .arg(&windows_zip)
+        .arg(&mac_zip)
+        .output()?;
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    run_runzip(&binary_path, &[&buffered_zip])?;
+
+    // Reading through the map makes no difference to the result
+    assert_eq!(fs::read(&windows_zip)?, fs::read(&buffered_zip)?);
+    assert!(
+        extract_filenames_from_zip(&windows_zip)?.contains(&"тест/тест.txt".as_bytes().to_vec())
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_buffer_size() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+    let (windows_zip, _, _) = setup_test_archives(temp_dir.path())?;
+    let tiny_zip = temp_dir.path().join("tiny.zip");
+    fs::copy(&windows_zip, &tiny_zip)?;
+
+    run_runzip(&binary_path, &[&windows_zip])?;
+    let output = Command::new(&binary_path)
+        .args(["--buffer-size", "1"])
+        .arg(&tiny_zip)
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(fs::read(&tiny_zip)?, fs::read(&windows_zip)?);
+
+    for invalid in ["0", "lots"] {
+        let output = Command::new(&binary_path)
+            .args(["--buffer-size", invalid])
+            .arg(&tiny_zip)
+            .output()?;
+        assert!(!output.status.success(), "--buffer-size {invalid}");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_preflight_plan() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let zip_path = temp_dir.path().join("plan.zip");
+    let binary_path = get_runzip_binary();
+    let name = encode_legacy("Отчёт.txt", encoding_rs::IBM866);
+    let text = encode_legacy("Отчёт за год", encoding_rs::IBM866);
+    create_raw_zip(&zip_path, &[(&name, &text), (b"readme.txt", b"hello")])?;
+
+    // The plan is printed before anything is written, dry run or not
+    let output = Command::new(&binary_path)
+        .arg("--dry-run")
+        .arg(&zip_path)
+        .output()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("rename 1 of 2 entries, copy 17 bytes of entry data as stored"),
+        "stdout: {stdout}"
+    );
+
+    let output = Command::new(&binary_path)
+        .args(["--fix-contents", "txt", "--dry-run"])
+        .arg(&zip_path)
+        .output()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("recompressing it"), "stdout: {stdout}");
+
+    // Without a terminal there is no one to ask, with or without --yes
+    let output = Command::new(&binary_path)
+        .arg("--yes")
+        .arg(&zip_path)
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(
+        extract_filenames_from_zip(&zip_path)?,
+        vec!["Отчёт.txt".as_bytes().to_vec(), b"readme.txt".to_vec()]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_preserve_mtime() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+    let old = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000_000);
+
+    for preserve in [false, true] {
+        let (windows_zip, _, _) = setup_test_archives(temp_dir.path())?;
+        fs::File::options()
+            .write(true)
+            .open(&windows_zip)?
+            .set_modified(old)?;
+        let mut command = Command::new(&binary_path);
+        if preserve {
+            command.arg("--preserve-mtime");
+        }
+        let output = command.arg(&windows_zip).output()?;
+        assert!(
+            output.status.success(),
+            "runzip should succeed. stderr: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert!(
+            extract_filenames_from_zip(&windows_zip)?
+                .iter()
+                .all(|name| std::str::from_utf8(name).is_ok())
+        );
+        let modified = fs::metadata(&windows_zip)?.modified()?;
+        assert_eq!(modified == old, preserve, "--preserve-mtime {preserve}");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_read_only_archives() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+    let (windows_zip, _, _) = setup_test_archives(temp_dir.path())?;
+    let original = fs::read(&windows_zip)?;
+    let mut permissions = fs::metadata(&windows_zip)?.permissions();
+    permissions.set_readonly(true);
+    fs::set_permissions(&windows_zip, permissions.clone())?;
+
+    // Skipped up front with the reason
+    let output = run_runzip(&binary_path, &[&windows_zip])?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("is read-only"), "stderr: {stderr}");
+    assert_eq!(fs::read(&windows_zip)?, original);
+
+    let output = Command::new(&binary_path)
+        .arg("--force-readonly")
+        .arg(&windows_zip)
+        .output()?;
+    assert!(
+        output.status.success(),
+        "runzip --force-readonly should succeed. stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_ne!(fs::read(&windows_zip)?, original);
+    assert_eq!(fs::metadata(&windows_zip)?.permissions(), permissions);
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_permissions_kept() -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+    let (windows_zip, _, _) = setup_test_archives(temp_dir.path())?;
+    fs::set_permissions(&windows_zip, fs::Permissions::from_mode(0o654))?;
+
+    let output = run_runzip(&binary_path, &[&windows_zip])?;
+    assert!(output.status.success(), "runzip should succeed");
+    assert_eq!(
+        fs::metadata(&windows_zip)?.permissions().mode() & 0o777,
+        0o654
+    );
+
+    Ok(())
+}
+
+/// A file size limit makes writing the new archive fail halfway, like a
+/// full disk
+#[cfg(unix)]
+#[test]
+fn test_failed_write_rolled_back() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+    let zip_path = temp_dir.path().join("test.zip");
+    let contents = vec![b'x'; 64 * 1024];
+    create_raw_zip(&zip_path, &[(b"\xe2\xa5\xe1\xe2.txt", &contents)])?;
+    let original = fs::read(&zip_path)?;
+
+    // SIGXFSZ is ignored so that the write fails instead of killing runzip
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg("trap '' XFSZ; ulimit -f 32; exec \"$0\" \"$1\"")
+        .arg(&binary_path)
+        .arg(&zip_path)
+        .output()?;
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("File too large"));
+    assert_eq!(fs::read(&zip_path)?, original);
+    assert_eq!(fs::read_dir(temp_dir.path())?.count(), 1);
+
+    let output = run_runzip(&binary_path, &[&zip_path])?;
+    assert!(output.status.success());
+    assert_eq!(
+        extract_filenames_from_zip(&zip_path)?,
+        vec!["тест.txt".as_bytes().to_vec()]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_tmpdir() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let scratch = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+    let (windows_zip, _, _) = setup_test_archives(temp_dir.path())?;
+    let other_zip = temp_dir.path().join("other.zip");
+    fs::copy(&windows_zip, &other_zip)?;
+
+    run_runzip(&binary_path, &[&windows_zip])?;
+    let output = Command::new(&binary_path)
+        .arg("--tmpdir")
+        .arg(scratch.path())
+        .arg(&other_zip)
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(fs::read(&other_zip)?, fs::read(&windows_zip)?);
+    assert_eq!(fs::read_dir(scratch.path())?.count(), 0);
+
+    let output = Command::new(&binary_path)
+        .arg("--tmpdir")
+        .arg(temp_dir.path().join("missing"))
+        .arg(setup_test_archives(scratch.path())?.0)
+        .output()?;
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Failed to create temporary file"));
+
+    Ok(())
+}
+
+#[test]
+fn test_locked_archive_skipped() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+    let (windows_zip, _, _) = setup_test_archives(temp_dir.path())?;
+    let original = fs::read(&windows_zip)?;
+
+    let lock_path = temp_dir.path().join("windows-archive.zip.runzip.lock");
+    let lock = File::create(&lock_path)?;
+    lock.lock()?;
+    let output = Command::new(&binary_path).arg(&windows_zip).output()?;
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("another runzip process"));
+    assert_eq!(fs::read(&windows_zip)?, original);
+
+    let output = Command::new(&binary_path)
+        .arg("-n")
+        .arg(&windows_zip)
+        .output()?;
+    assert!(output.status.success());
+
+    drop(lock);
+    run_runzip(&binary_path, &[&windows_zip])?;
+    assert_ne!(fs::read(&windows_zip)?, original);
+    // The lock file is removed once the archive is fixed
+    assert!(!lock_path.exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_journal_resume() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+    let (windows_zip, _, _) = setup_test_archives(temp_dir.path())?;
+    let original = fs::read(&windows_zip)?;
+    let broken_zip = temp_dir.path().join("broken.zip");
+    fs::write(&broken_zip, b"not an archive")?;
+    let journal = temp_dir.path().join("state.json");
+
+    let output = Command::new(&binary_path)
+        .arg("--journal")
+        .arg(&journal)
+        .args([&windows_zip, &broken_zip])
+        .output()?;
+    assert!(!output.status.success());
+    let fixed = fs::read(&windows_zip)?;
+    assert_ne!(fixed, original);
+    let state = fs::read_to_string(&journal)?;
+    assert!(state.contains("windows-archive.zip"));
+    assert!(!state.contains("broken.zip"));
+
+    // The finished archive is skipped, even though it changed meanwhile
+    fs::write(&windows_zip, &original)?;
+    fs::write(&broken_zip, &original)?;
+    let output = Command::new(&binary_path)
+        .arg("--journal")
+        .arg(&journal)
+        .args([&windows_zip, &broken_zip])
+        .output()?;
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("already fixed"));
+    assert_eq!(fs::read(&windows_zip)?, original);
+    assert_eq!(fs::read(&broken_zip)?, fixed);
+    assert!(!journal.exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_diff_and_apply() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+    let (windows_zip, _, _) = setup_test_archives(temp_dir.path())?;
+    let original = fs::read(&windows_zip)?;
+    let fixed_zip = temp_dir.path().join("fixed.zip");
+    fs::copy(&windows_zip, &fixed_zip)?;
+    run_runzip(&binary_path, &[&fixed_zip])?;
+    let diff = temp_dir.path().join("diff.txt");
+
+    let output = Command::new(&binary_path)
+        .arg("-n")
+        .arg("--diff")
+        .arg(&diff)
+        .arg(&windows_zip)
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(fs::read(&windows_zip)?, original);
+    let text = fs::read_to_string(&diff)?;
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines[0], format!("--- {}", windows_zip.display()));
+    assert_eq!(lines[1], format!("+++ {}", windows_zip.display()));
+    assert!(lines[2].starts_with("- \\x"));
+    assert!(text.contains("+ тест/тест.txt\n"));
+
+    let output = Command::new(&binary_path)
+        .arg("--apply")
+        .arg(&diff)
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(fs::read(&windows_zip)?, fs::read(&fixed_zip)?);
+
+    // Edited listings are applied as they are
+    fs::write(&windows_zip, &original)?;
+    fs::write(
+        &diff,
+        text.replace("+ тест/тест.txt", "+ тест/проверка.txt"),
+    )?;
+    let output = Command::new(&binary_path)
+        .arg("--apply")
+        .arg(&diff)
+        .output()?;
+    assert!(output.status.success());
+    let names = extract_filenames_from_zip(&windows_zip)?;
+    assert!(names.contains(&"тест/проверка.txt".as_bytes().to_vec()));
+
+    let output = Command::new(&binary_path)
+        .arg("--diff")
+        .arg(&diff)
+        .arg(&windows_zip)
+        .output()?;
+    assert!(!output.status.success(), "--diff requires --dry-run");
+
+    Ok(())
+}
+
+#[test]
+fn test_porcelain_output() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+    let (windows_zip, _, _) = setup_test_archives(temp_dir.path())?;
+    let broken_zip = temp_dir.path().join("broken.zip");
+    fs::write(&broken_zip, b"not an archive")?;
+
+    let output = Command::new(&binary_path)
+        .args(["-n", "--porcelain"])
+        .args([&windows_zip, &broken_zip])
+        .output()?;
+    assert!(!output.status.success());
+    let stdout = String::from_utf8(output.stdout)?;
+    let lines: Vec<Vec<&str>> = stdout
+        .lines()
+        .map(|line| line.split('\t').collect())
+        .collect();
+    let archive = windows_zip.display().to_string();
+    assert_eq!(lines.len(), 3, "stdout: {stdout}");
+    assert_eq!(
+        lines[1],
+        [
+            "would-fix",
+            &archive,
+            "1",
+            "\\xe2\\xa5\\xe1\\xe2/\\xe2\\xa5\\xe1\\xe2.txt",
+            "тест/тест.txt"
+        ]
+    );
+    let broken = broken_zip.display().to_string();
+    assert_eq!(lines[2], ["error", &broken, "", "", ""]);
+
+    let output = Command::new(&binary_path)
+        .arg("--porcelain=1")
+        .arg(&windows_zip)
+        .output()?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout)?;
+    assert!(stdout.lines().all(|line| line.starts_with("fixed\t")));
+
+    let output = Command::new(&binary_path)
+        .arg("--porcelain=2")
+        .arg(&windows_zip)
+        .output()?;
+    assert!(!output.status.success());
+
+    Ok(())
+}
+
+#[test]
+fn test_library_fix_report() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (windows_zip, _, _) = setup_test_archives(temp_dir.path())?;
+    let original = fs::read(&windows_zip)?;
+
+    let options = FixOptions::builder().dry_run(true).build();
+    let report = fix_archive(&windows_zip, &options)?;
+    assert_eq!(fs::read(&windows_zip)?, original);
+    assert_eq!((report.renamed, report.skipped, report.errors), (2, 0, 0));
+    let entry = &report.entries[1];
+    assert_eq!(entry.archive, windows_zip);
+    assert_eq!(entry.index, 1);
+    assert_eq!(entry.raw_name, b"\xe2\xa5\xe1\xe2/\xe2\xa5\xe1\xe2.txt");
+    assert_eq!(entry.new_name, "тест/тест.txt");
+    assert_eq!(entry.encoding.map(runzip::Encoding::name), Some("IBM866"));
+    assert_eq!(entry.action, Action::Fixed);
+
+    let report = fix_archive(&windows_zip, &FixOptions::default())?;
+    assert_eq!(report.renamed, 2);
+    let report = fix_archive(&windows_zip, &FixOptions::default())?;
+    assert_eq!((report.renamed, report.skipped), (0, 2));
+
+    assert!(matches!(
+        fix_archive(&temp_dir.path().join("missing.zip"), &options),
+        Err(runzip::Error::Io {
+            kind: std::io::ErrorKind::NotFound,
+            ..
+        })
+    ));
+    // The error is what went wrong last, its sources what led to it
+    let error = runzip::fix_archive_bytes(b"not an archive", &options).unwrap_err();
+    assert!(matches!(error, runzip::Error::ZipFormat { .. }));
+    assert_eq!(error.to_string(), "Failed to read ZIP archive");
+    assert_eq!(
+        std::error::Error::source(&error).map(ToString::to_string),
+        Some("End of central directory not found".to_string())
+    );
+    assert_eq!(
+        format!("{:#}", anyhow::Error::from(error)),
+        "Failed to read ZIP archive: End of central directory not found"
+    );
+    // A source that cannot be read is no damaged archive
+    struct Unreadable;
+    impl std::io::Read for Unreadable {
+        fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+            Err(std::io::ErrorKind::PermissionDenied.into())
+        }
+    }
+    impl std::io::Seek for Unreadable {
+        fn seek(&mut self, _pos: std::io::SeekFrom) -> std::io::Result<u64> {
+            Ok(100)
+        }
+    }
+    let error = runzip::fix_zip(
+        Unreadable,
+        &mut std::io::Cursor::new(Vec::new()),
+        Path::new("upload.zip"),
+        &options,
+    )
+    .unwrap_err();
+    assert!(matches!(
+        error,
+        runzip::Error::Io {
+            kind: std::io::ErrorKind::PermissionDenied,
+            ..
+        }
+    ));
+    assert!(matches!(
+        runzip::string_to_encoding("ebcdic"),
+        Err(runzip::Error::UnsupportedEncoding(name)) if name == "ebcdic"
+    ));
+
+    Ok(())
+}
+
+/// Events of a fix, as recorded by [`Recorder`]
+#[derive(Default)]
+struct Recorder {
+    started: std::sync::Mutex<Vec<usize>>,
+    renamed: std::sync::Mutex<Vec<String>>,
+    progress: std::sync::Mutex<Vec<u64>>,
+    warnings: std::sync::Mutex<Vec<String>>,
+    messages: std::sync::Mutex<Vec<String>>,
+}
+
+impl runzip::Events for Recorder {
+    fn on_entry_start(&self, _path: &Path, index: usize, _name: &[u8]) {
+        self.started.lock().unwrap().push(index);
+    }
+
+    fn on_entry_renamed(&self, _path: &Path, _index: usize, _old_name: &[u8], new_name: &str) {
+        self.renamed.lock().unwrap().push(new_name.to_string());
+    }
+
+    fn on_progress(&self, bytes: u64) {
+        self.progress.lock().unwrap().push(bytes);
+    }
+
+    fn on_warning(&self, _path: &Path, message: &str) {
+        self.warnings.lock().unwrap().push(message.to_string());
+    }
+
+    fn on_message(&self, message: &str) {
+        self.messages.lock().unwrap().push(message.to_string());
+    }
+}
+
+#[test]
+fn test_library_events() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (windows_zip, _, _) = setup_test_archives(temp_dir.path())?;
+
+    let recorder = Recorder::default();
+    let options = FixOptions::builder()
+        .events(Some(&recorder))
+        .quiet(false)
+        .build();
+    fix_archive(&windows_zip, &options)?;
+    assert_eq!(*recorder.started.lock().unwrap(), [0, 1]);
+    // The outcome of every entry is told, not printed
+    assert!(
+        recorder
+            .messages
+            .lock()
+            .unwrap()
+            .contains(&"  тест/тест.txt: FIXED (IBM866 -> UTF-8)".to_string())
+    );
+    assert_eq!(
+        *recorder.renamed.lock().unwrap(),
+        ["тест/Ещё один пустой каталог/", "тест/тест.txt"]
+    );
+    let progress = recorder.progress.lock().unwrap();
+    assert!(progress.is_sorted());
+    assert_eq!(progress.last(), Some(&fs::metadata(&windows_zip)?.len()));
+
+    Ok(())
+}
+
+#[test]
+fn test_library_warnings() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let zip_path = temp_dir.path().join("slip.zip");
+    create_raw_zip(&zip_path, &[(b"../evil.txt", b"data")])?;
+
+    // Warnings go to the events instead of stderr
+    let recorder = Recorder::default();
+    let options = FixOptions::builder().events(Some(&recorder)).build();
+    fix_archive(&zip_path, &options)?;
+    assert_eq!(
+        *recorder.warnings.lock().unwrap(),
+        [format!(
+            "{} has 1 entry with absolute or \"..\" paths",
+            zip_path.display()
+        )]
+    );
+
+    Ok(())
+}
+
+/// Cancels a fix as soon as anything is written
+struct CancelOnProgress<'a>(&'a std::sync::atomic::AtomicBool);
+
+impl runzip::Events for CancelOnProgress<'_> {
+    fn on_progress(&self, _bytes: u64) {
+        self.0.store(true, std::sync::atomic::Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn test_library_cancel() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (windows_zip, _, _) = setup_test_archives(temp_dir.path())?;
+    let original = fs::read(&windows_zip)?;
+    let files = || fs::read_dir(temp_dir.path()).map(Iterator::count);
+    let file_count = files()?;
+
+    let cancel = std::sync::atomic::AtomicBool::new(false);
+    let events = CancelOnProgress(&cancel);
+    let options = FixOptions::builder()
+        .cancel(Some(&cancel))
+        .events(Some(&events))
+        .buffer_size(1)
+        .build();
+    let error = fix_archive(&windows_zip, &options).unwrap_err();
+    assert!(matches!(error, runzip::Error::Cancelled));
+    assert_eq!(error.to_string(), "Cancelled");
+    assert_eq!(fs::read(&windows_zip)?, original);
+    assert_eq!(files()?, file_count);
+
+    // Nothing is started once cancelled
+    let options = FixOptions::builder().cancel(Some(&cancel)).build();
+    assert!(fix_archive(&windows_zip, &options).is_err());
+    assert_eq!(fs::read(&windows_zip)?, original);
+
+    // Failures of other kinds are reported as they are, cancelled or not
+    let error = runzip::fix_zip(
+        std::io::Cursor::new(b"not an archive"),
+        &mut std::io::Cursor::new(Vec::new()),
+        Path::new("upload.zip"),
+        &options,
+    )
+    .unwrap_err();
+    assert!(matches!(error, runzip::Error::ZipFormat { .. }));
+
+    Ok(())
+}
+
+/// Storage that runs full after `capacity` bytes
+struct FullStorage {
+    inner: std::io::Cursor<Vec<u8>>,
+    capacity: u64,
+}
+
+impl std::io::Write for FullStorage {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.inner.position() + buf.len() as u64 > self.capacity {
+            return Err(std::io::ErrorKind::StorageFull.into());
+        }
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl std::io::Read for FullStorage {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl std::io::Seek for FullStorage {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+/// Gives up halfway through writing a new archive
+struct PanicOnProgress;
+
+impl runzip::Events for PanicOnProgress {
+    fn on_progress(&self, bytes: u64) {
+        assert!(bytes < 100, "writing failed");
+    }
+}
+
+#[test]
+fn test_library_rollback() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (windows_zip, _, _) = setup_test_archives(temp_dir.path())?;
+    let original = fs::read(&windows_zip)?;
+    let files = || fs::read_dir(temp_dir.path()).map(Iterator::count);
+    let file_count = files()?;
+
+    let mut storage = FullStorage {
+        inner: std::io::Cursor::new(Vec::new()),
+        capacity: 100,
+    };
+    let error = runzip::fix_zip(
+        std::io::Cursor::new(&original),
+        &mut storage,
+        Path::new("upload.zip"),
+        &FixOptions::default(),
+    )
+    .unwrap_err();
+    assert!(matches!(
+        error,
+        runzip::Error::Io {
+            kind: std::io::ErrorKind::StorageFull,
+            ..
+        }
+    ));
+
+    // Even a panic halfway leaves no temporary file and the original as it was
+    let options = FixOptions::builder()
+        .events(Some(&PanicOnProgress))
+        .buffer_size(1)
+        .build();
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        fix_archive(&windows_zip, &options)
+    }));
+    assert!(result.is_err());
+    assert_eq!(fs::read(&windows_zip)?, original);
+    assert_eq!(files()?, file_count);
+
+    Ok(())
+}
+
+#[test]
+fn test_shared_archive_fixer() -> Result<()> {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<runzip::ArchiveFixer>();
+    assert_send_sync::<FixOptions>();
+    assert_send_sync::<runzip::Candidate>();
+
+    let temp_dir = TempDir::new()?;
+    let (windows_zip, _, _) = setup_test_archives(temp_dir.path())?;
+    let original = fs::read(&windows_zip)?;
+    let fixer = runzip::ArchiveFixer::new(FixOptions::default());
+    let reports = std::thread::scope(|scope| {
+        let threads: Vec<_> = (0..4)
+            .map(|_| scope.spawn(|| fixer.fix_bytes(&original)))
+            .collect();
+        threads
+            .into_iter()
+            .map(|thread| thread.join().unwrap())
+            .collect::<Result<Vec<_>, _>>()
+    })?;
+    for (fixed, report) in &reports {
+        assert_eq!(report.renamed, 2);
+        assert_eq!(fixed, &reports[0].0);
+    }
+
+    let name = encode_legacy("Привет мир.txt", encoding_rs::WINDOWS_1251);
+    assert_eq!(fixer.detect(&name)[0].decoded, "Привет мир.txt");
+
+    Ok(())
+}
+
+#[test]
+fn test_library_detect_candidates() {
+    let name = encode_legacy("Привет мир.txt", encoding_rs::WINDOWS_1251);
+    let candidates = runzip::detect(&name);
+    assert_eq!(candidates[0].encoding.name(), "windows-1251");
+    assert_eq!(candidates[0].decoded, "Привет мир.txt");
+    assert!(candidates[0].score > 0.5);
+    assert!(candidates.iter().any(|c| c.encoding.name() == "KOI8-R"));
+    assert!(candidates[1..].windows(2).all(|w| w[0].score >= w[1].score));
+
+    let candidates = runzip::detect(b"readme.txt");
+    assert_eq!(candidates.len(), 1);
+    assert_eq!(candidates[0].encoding.name(), "UTF-8");
+    assert!((candidates[0].score - 1.0).abs() < f64::EPSILON);
+}
+
+#[test]
+fn test_fix_archive_bytes() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+    let (windows_zip, _, _) = setup_test_archives(temp_dir.path())?;
+    let original = fs::read(&windows_zip)?;
+    run_runzip(&binary_path, &[&windows_zip])?;
+
+    let (fixed, report) = runzip::fix_archive_bytes(&original, &FixOptions::default())?;
+    assert_eq!(report.renamed, 2);
+    assert_eq!(report.entries[0].archive, Path::new(""));
+    assert_eq!(fixed, fs::read(&windows_zip)?);
+
+    let options = FixOptions::builder().dry_run(true).build();
+    let (unchanged, report) = runzip::fix_archive_bytes(&original, &options)?;
+    assert_eq!(report.renamed, 2);
+    assert_eq!(unchanged, original);
+
+    assert!(runzip::fix_archive_bytes(b"not an archive", &options).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_library_fix_zip_streams() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let (windows_zip, _, _) = setup_test_archives(temp_dir.path())?;
+    let original = fs::read(&windows_zip)?;
+    let options = FixOptions::default();
+
+    let mut fixed = std::io::Cursor::new(Vec::new());
+    let report = runzip::fix_zip(
+        std::io::Cursor::new(&original),
+        &mut fixed,
+        Path::new("upload.zip"),
+        &options,
+    )?;
+    assert_eq!(report.renamed, 2);
+    assert_eq!(report.entries[0].archive, Path::new("upload.zip"));
+    assert_eq!(
+        fixed.get_ref(),
+        &runzip::fix_archive_bytes(&original, &options)?.0
+    );
+
+    // Any seekable storage will do, a file here
+    let mut file = tempfile::tempfile()?;
+    runzip::fix_zip(File::open(&windows_zip)?, &mut file, &windows_zip, &options)?;
+    let mut written = Vec::new();
+    std::io::Seek::rewind(&mut file)?;
+    std::io::Read::read_to_end(&mut file, &mut written)?;
+    assert_eq!(&written, fixed.get_ref());
+
+    // The destination is only written to, after whatever it holds already
+    struct WriteOnly(std::io::Cursor<Vec<u8>>);
+    impl std::io::Write for WriteOnly {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+    impl std::io::Seek for WriteOnly {
+        fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+            self.0.seek(pos)
+        }
+    }
+    let mut write_only = WriteOnly(std::io::Cursor::new(b"header".to_vec()));
+    std::io::Seek::seek(&mut write_only, std::io::SeekFrom::End(0))?;
+    runzip::fix_zip(
+        std::io::Cursor::new(&original),
+        &mut write_only,
+        Path::new("upload.zip"),
+        &options,
+    )?;
+    assert_eq!(&write_only.0.get_ref()[..6], b"header");
+    assert_eq!(&write_only.0.get_ref()[6..], fixed.get_ref());
+
+    let mut unchanged = std::io::Cursor::new(Vec::new());
+    runzip::fix_zip(
+        std::io::Cursor::new(fixed.get_ref()),
+        &mut unchanged,
+        Path::new("upload.zip"),
+        &options,
+    )?;
+    assert!(unchanged.get_ref().is_empty());
+
+    // Changes other than names rewrite the archive too
+    let mut rewritten = std::io::Cursor::new(Vec::new());
+    runzip::fix_zip(
+        std::io::Cursor::new(fixed.get_ref()),
+        &mut rewritten,
+        Path::new("upload.zip"),
+        &FixOptions::builder().dos_host(true).build(),
+    )?;
+    assert!(!rewritten.get_ref().is_empty());
+
+    // Nested archives are left alone without recurse_archives
+    let mut outer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    outer.start_file("inner.zip", zip::write::SimpleFileOptions::default())?;
+    std::io::Write::write_all(&mut outer, &original)?;
+    let outer = outer.finish()?.into_inner();
+    let mut unchanged = std::io::Cursor::new(Vec::new());
+    runzip::fix_zip(
+        std::io::Cursor::new(&outer),
+        &mut unchanged,
+        Path::new("outer.zip"),
+        &options,
+    )?;
+    assert!(unchanged.get_ref().is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_corpus() -> Result<()> {
+    for fixture in corpus::corpus() {
+        let data = fs::read(Path::new("tests/corpus").join(fixture.file_name))?;
+        let (_, report) = runzip::fix_archive_bytes(&data, &FixOptions::default())?;
+        let names: Vec<&str> = report.entries.iter().map(|e| e.new_name.as_str()).collect();
+        assert_eq!(names, fixture.names, "{}", fixture.file_name);
+        // Russian names in KOI8-R may well be detected as KOI8-U, which
+        // decodes them the same
+        assert!(
+            report.entries.iter().all(|e| e.action == Action::Fixed),
+            "{}",
+            fixture.file_name
+        );
+    }
+    Ok(())
+}
+
+/// Archive names of 1-3 words from a vocabulary, some capitalized, in a
+/// directory or not, with an extension or not, and an encoding that has
+/// all their letters
+fn encoded_names() -> impl Strategy<Value = (&'static encoding_rs::Encoding, Vec<String>)> {
+    use encoding_rs::{IBM866, KOI8_R, KOI8_U, WINDOWS_1251};
+
+    let vocabularies = prop_oneof![
+        Just((IBM866, corpus::RUSSIAN_WORDS)),
+        Just((WINDOWS_1251, corpus::RUSSIAN_WORDS)),
+        Just((WINDOWS_1251, corpus::UKRAINIAN_WORDS)),
+        Just((KOI8_R, corpus::RUSSIAN_WORDS)),
+        Just((KOI8_U, corpus::RUSSIAN_WORDS)),
+        Just((KOI8_U, corpus::UKRAINIAN_WORDS)),
+    ];
+    vocabularies.prop_flat_map(|(encoding, words)| {
+        let word = (prop::sample::select(words), any::<bool>()).prop_map(|(word, capital)| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) if capital => first.to_uppercase().chain(chars).collect(),
+                _ => word.to_string(),
+            }
+        });
+        let name = (
+            prop::option::of(word.clone()),
+            prop::collection::vec(word, 1..=3),
+            prop::sample::select(vec![" ", "_", "-"]),
+            prop::sample::select(vec!["", ".txt", ".doc", ".jpg"]),
+        )
+            .prop_map(|(dir, words, separator, extension)| {
+                let dir = dir.map(|dir| format!("{dir}/")).unwrap_or_default();
+                format!("{dir}{}{extension}", words.join(separator))
+            });
+        let names = prop::collection::btree_set(name, 1..8)
+            .prop_map(|names| names.into_iter().collect::<Vec<_>>());
+        (Just(encoding), names)
+    })
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    /// Names encoded in a legacy encoding come out of the fix as they were
+    #[test]
+    fn test_round_trip((encoding, names) in encoded_names()) {
+        let data = corpus::encoded_zip(&names, encoding);
+        let (fixed, report) = runzip::fix_archive_bytes(&data, &FixOptions::default()).unwrap();
+        let new_names: Vec<&str> = report.entries.iter().map(|e| e.new_name.as_str()).collect();
+        prop_assert_eq!(new_names, names.clone(), "from {}", encoding.name());
+
+        let mut archive = ZipArchive::new(std::io::Cursor::new(fixed)).unwrap();
+        for (index, name) in names.iter().enumerate() {
+            let entry = archive.by_index(index).unwrap();
+            prop_assert_eq!(entry.name(), name);
+            prop_assert!(entry.get_metadata().is_utf8);
+        }
+    }
+}
+
+/// Malformed archives found by fuzzing, which fail with an error instead of
+/// panicking
+#[test]
+fn test_malformed_archives() {
+    let options = FixOptions::default();
+
+    // End of central directory signature in a file too short for the record
+    assert!(runzip::fix_archive_bytes(b"PK\x05\x06KK\x03\x00\x00\x03", &options).is_err());
+
+    // ZIP64 central directory whose offset and size add up past u64::MAX
+    let mut zip64 = Vec::new();
+    zip64.extend(0x0606_4b50u32.to_le_bytes());
+    zip64.extend(44u64.to_le_bytes());
+    zip64.extend([0; 12]);
+    zip64.extend(1u64.to_le_bytes());
+    zip64.extend(1u64.to_le_bytes());
+    zip64.extend(u64::MAX.to_le_bytes());
+    zip64.extend(u64::MAX.to_le_bytes());
+    zip64.extend(0x0706_4b50u32.to_le_bytes());
+    zip64.extend([0; 4]);
+    zip64.extend(0u64.to_le_bytes());
+    zip64.extend(1u32.to_le_bytes());
+    zip64.extend(0x0605_4b50u32.to_le_bytes());
+    zip64.extend([0; 4]);
+    zip64.extend([0xff; 16]);
+    zip64.extend([0; 2]);
+    assert!(runzip::fix_archive_bytes(&zip64, &options).is_err());
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn test_async_fix() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+    let (windows_zip, _, _) = setup_test_archives(temp_dir.path())?;
+    let original = fs::read(&windows_zip)?;
+    run_runzip(&binary_path, &[&windows_zip])?;
+
+    let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+    let mut fixed = Vec::new();
+    let report = runtime.block_on(runzip::fix_archive_async(
+        std::io::Cursor::new(original.clone()),
+        &mut fixed,
+        Path::new("windows-archive.zip"),
+        FixOptions::default(),
+    ))?;
+    assert_eq!(report.renamed, 2);
+    assert_eq!(report.entries[0].archive, Path::new("windows-archive.zip"));
+    assert_eq!(fixed, fs::read(&windows_zip)?);
+
+    // Nothing is written for an archive that needs no changes
+    let mut unchanged = Vec::new();
+    let report = runtime.block_on(runzip::fix_archive_async(
+        std::io::Cursor::new(fixed),
+        &mut unchanged,
+        Path::new("windows-archive.zip"),
+        FixOptions::default(),
+    ))?;
+    assert_eq!(report.renamed, 0);
+    assert!(unchanged.is_empty());
+
+    Ok(())
+}
+
+/// Test helper writing a ZIP64 archive with one stored entry of `size` zero
+/// bytes, leaving the data as a hole in a sparse file
+fn create_zip64_zip(zip_path: &Path, name: &[u8], size: u64) -> Result<()> {
+    let mut hasher = crc32fast::Hasher::new();
+    let zeros = vec![0; 1 << 20];
+    let mut left = size;
+    while left > 0 {
+        let chunk = usize::try_from(left.min(1 << 20))?;
+        hasher.update(&zeros[..chunk]);
+        left -= chunk as u64;
+    }
+    let crc = hasher.finalize();
+    let name_len = u16::try_from(name.len())?;
+    let mut zip64 = vec![0x01, 0x00, 16, 0];
+    zip64.extend_from_slice(&size.to_le_bytes());
+    zip64.extend_from_slice(&size.to_le_bytes());
+
+    let mut local = 0x0403_4b50u32.to_le_bytes().to_vec();
+    local.extend_from_slice(&[45, 0, 0, 0, 0, 0, 0, 0, 0x21, 0x5b]); // version, flags, method, time, date
+    local.extend_from_slice(&crc.to_le_bytes());
+    local.extend_from_slice(&[0xff; 8]); // sizes in the ZIP64 field
+    local.extend_from_slice(&name_len.to_le_bytes());
+    local.extend_from_slice(&20u16.to_le_bytes());
+    local.extend_from_slice(name);
+    local.extend_from_slice(&zip64);
+
+    let mut central = 0x0201_4b50u32.to_le_bytes().to_vec();
+    central.extend_from_slice(&[45, 0, 45, 0, 0, 0, 0, 0, 0, 0, 0x21, 0x5b]); // versions, flags, method, time, date
+    central.extend_from_slice(&crc.to_le_bytes());
+    central.extend_from_slice(&[0xff; 8]);
+    central.extend_from_slice(&name_len.to_le_bytes());
+    central.extend_from_slice(&20u16.to_le_bytes());
+    central.extend_from_slice(&[0; 14]); // comment length, disk, attributes, offset
+    central.extend_from_slice(name);
+    central.extend_from_slice(&zip64);
+
+    let central_offset = local.len() as u64 + size;
+    let mut tail = central.clone();
+    tail.extend_from_slice(&0x0606_4b50u32.to_le_bytes());
+    tail.extend_from_slice(&44u64.to_le_bytes());
+    tail.extend_from_slice(&[45, 0, 45, 0, 0, 0, 0, 0, 0, 0, 0, 0]); // versions, disks
+    tail.extend_from_slice(&1u64.to_le_bytes());
+    tail.extend_from_slice(&1u64.to_le_bytes());
+    tail.extend_from_slice(&(central.len() as u64).to_le_bytes());
+    tail.extend_from_slice(&central_offset.to_le_bytes());
+    tail.extend_from_slice(&0x0706_4b50u32.to_le_bytes());
+    tail.extend_from_slice(&0u32.to_le_bytes());
+    tail.extend_from_slice(&(central_offset + central.len() as u64).to_le_bytes());
+    tail.extend_from_slice(&1u32.to_le_bytes());
+    tail.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+    tail.extend_from_slice(&[0, 0, 0, 0, 1, 0, 1, 0]);
+    tail.extend_from_slice(&u32::try_from(central.len())?.to_le_bytes());
+    tail.extend_from_slice(&[0xff, 0xff, 0xff, 0xff, 0, 0]); // offset in ZIP64 record, comment
+
+    let mut file = fs::File::create(zip_path)?;
+    std::io::Write::write_all(&mut file, &local)?;
+    file.set_len(central_offset)?;
+    std::io::Seek::seek(&mut file, std::io::SeekFrom::Start(central_offset))?;
+    std::io::Write::write_all(&mut file, &tail)?;
+    Ok(())
+}
+
+/// Fix and extract a ZIP64 archive with one entry of `size` bytes
+fn check_zip64_entry(size: u64) -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let binary_path = get_runzip_binary();
+    let zip_path = temp_dir.path().join("large.zip");
+    let name = encode_legacy("Резервная копия.img", encoding_rs::IBM866);
+    create_zip64_zip(&zip_path, &name, size)?;
+
+    let output = run_runzip(&binary_path, &[&zip_path])?;
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(
+        extract_filenames_from_zip(&zip_path)?,
+        vec!["Резервная копия.img".as_bytes().to_vec()]
+    );
+    let archive = ZipArchive::new(fs::File::open(&zip_path)?)?;
+    assert_eq!(archive.decompressed_size(), Some(u128::from(size)));
+
+    let dest = temp_dir.path().join("extracted");
+    let output = Command::new(&binary_path)
+        .args(["extract", "-d"])
+        .arg(&dest)
+        .arg(&zip_path)
+        .output()?;
+    assert!(
+        output.status.success(),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(fs::metadata(dest.join("Резервная копия.img"))?.len(), size);
+
+    Ok(())
+}
+
+#[test]
+fn test_zip64_archive() -> Result<()> {
+    check_zip64_entry(100_000)
+}
+
+#[test]
+#[ignore = "writes over 8 GiB to disk"]
+fn test_entry_larger_than_4_gib() -> Result<()> {
+    check_zip64_entry((1 << 32) + 10)
+}
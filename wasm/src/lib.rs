@@ -0,0 +1,127 @@
+//! runzip for web pages, fixing archives in the browser without uploading
+//! them anywhere
+//!
+//! Built with `wasm-pack build --target web` in this directory.
+
+#![warn(clippy::pedantic)]
+
+use anyhow::{Result, anyhow};
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use runzip::{FixOptions, Target};
+
+/// The new archive and what was done to its entries
+#[wasm_bindgen]
+pub struct FixResult {
+    data: Vec<u8>,
+    report: String,
+}
+
+#[wasm_bindgen]
+impl FixResult {
+    /// The new archive, a copy of the original one if no name changed
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn data(&self) -> Vec<u8> {
+        self.data.clone()
+    }
+
+    /// JSON report: `renamed`, `skipped` and `errors` counts and `entries`
+    /// with the `archive`, `index`, `rawName` bytes, `newName`, `encoding`,
+    /// `confidence` and `action` of every entry
+    #[wasm_bindgen(getter)]
+    #[must_use]
+    pub fn report(&self) -> String {
+        self.report.clone()
+    }
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    entries: Vec<Entry<'a>>,
+    renamed: usize,
+    skipped: usize,
+    errors: usize,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Entry<'a> {
+    archive: String,
+    index: usize,
+    raw_name: &'a [u8],
+    new_name: &'a str,
+    encoding: Option<&'static str>,
+    confidence: Option<f64>,
+    action: &'static str,
+}
+
+/// Fix the names in the ZIP archive `data`, detecting their encoding
+/// unless `source` is given, and converting them to `target` (UTF-8 by
+/// default)
+///
+/// # Errors
+///
+/// Fails for unsupported encodings and archives that cannot be read.
+// wasm-bindgen only takes optional strings by value
+#[allow(clippy::needless_pass_by_value)]
+#[wasm_bindgen]
+pub fn fix(
+    data: &[u8],
+    source: Option<String>,
+    target: Option<String>,
+    dry_run: bool,
+) -> Result<FixResult, JsError> {
+    fix_data(data, source.as_deref(), target.as_deref(), dry_run)
+        .map_err(|e| JsError::new(&format!("{e:#}")))
+}
+
+/// [`fix`] with a Rust error, for use outside of a browser
+///
+/// # Errors
+///
+/// Fails for unsupported encodings and archives that cannot be read.
+pub fn fix_data(
+    data: &[u8],
+    source: Option<&str>,
+    target: Option<&str>,
+    dry_run: bool,
+) -> Result<FixResult> {
+    let target = target
+        .map(|target| {
+            target
+                .parse::<Target>()
+                .map_err(|_| anyhow!("Unsupported target: {target}"))
+        })
+        .transpose()?;
+    let options = FixOptions::builder()
+        .dry_run(dry_run)
+        .write_rename_map(false)
+        .source(source.map(runzip::string_to_encoding).transpose()?)
+        .target(target.unwrap_or(Target::Utf8))
+        .build();
+    let (data, report) = runzip::fix_archive_bytes(data, &options)?;
+    let report = Report {
+        entries: report
+            .entries
+            .iter()
+            .map(|entry| Entry {
+                archive: entry.archive.display().to_string(),
+                index: entry.index,
+                raw_name: &entry.raw_name,
+                new_name: &entry.new_name,
+                encoding: entry.encoding.map(runzip::Encoding::name),
+                confidence: entry.confidence,
+                action: entry.action.label(dry_run),
+            })
+            .collect(),
+        renamed: report.renamed,
+        skipped: report.skipped,
+        errors: report.errors,
+    };
+    Ok(FixResult {
+        data,
+        report: serde_json::to_string(&report)?,
+    })
+}
@@ -0,0 +1,24 @@
+use serde_json::Value;
+use std::fs;
+
+#[test]
+fn test_fix_data() -> anyhow::Result<()> {
+    let original = fs::read("../tests/originals/windows-archive.zip")?;
+
+    let result = runzip_wasm::fix_data(&original, None, Some("translit"), false)?;
+    let report: Value = serde_json::from_str(&result.report())?;
+    assert_eq!(report["renamed"], 2);
+    assert_eq!(report["entries"][1]["newName"], "test/test.txt");
+    assert_eq!(report["entries"][1]["action"], "fixed");
+    assert_eq!(report["entries"][1]["encoding"], "IBM866");
+    assert_ne!(result.data(), original);
+
+    let result = runzip_wasm::fix_data(&original, Some("cp866"), None, true)?;
+    let report: Value = serde_json::from_str(&result.report())?;
+    assert_eq!(report["entries"][1]["newName"], "тест/тест.txt");
+    assert_eq!(report["entries"][1]["action"], "would-fix");
+    assert_eq!(result.data(), original);
+
+    assert!(runzip_wasm::fix_data(&original, Some("ebcdic"), None, true).is_err());
+    Ok(())
+}